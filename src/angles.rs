@@ -0,0 +1,159 @@
+use std::f32::consts::{ PI, TAU };
+use macroquad::math::Vec2;
+
+/// wraps an angle into (-PI, PI]
+pub fn wrap_pi(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(TAU) - PI;
+    if wrapped <= -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+/// wraps an angle into [0, TAU)
+pub fn wrap_tau(angle: f32) -> f32 {
+    let wrapped = angle.rem_euclid(TAU);
+    // rem_euclid rounds tiny negative angles up to exactly TAU
+    if wrapped >= TAU {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+/// signed shortest rotation from one heading to another, positive is counter clockwise in world space
+pub fn relative_angle(from: f32, to: f32) -> f32 {
+    wrap_pi(to - from)
+}
+
+/// heading of the direction from point_a to point_b
+pub fn angle_to(point_a: Vec2, point_b: Vec2) -> f32 {
+    let direction = point_b - point_a;
+    direction.y.atan2(direction.x)
+}
+
+/// the angle `steps` multiples of `step` away from the multiple closest to `angle`, computed from the step
+/// index so repeated steps never accumulate float drift, `step` has to divide TAU evenly
+pub fn snap_step(angle: f32, step: f32, steps: i32) -> f32 {
+    let steps_per_turn = (TAU / step).round() as i32;
+    let index = ((wrap_tau(angle) / step).round() as i32 + steps).rem_euclid(steps_per_turn);
+    (index as f32) * step
+}
+
+/// where a bearing lands on a strip centered on the view angle that shows `span` radians, -1 is the left end
+/// and 1 the right one, positive relative angles are on the left like the view draws them. Bearings beyond
+/// the ends, behind the player too, stick to the nearer end.
+pub fn strip_position(view_angle: f32, bearing: f32, span: f32) -> f32 {
+    (-relative_angle(view_angle, bearing) / (span / 2.0)).clamp(-1.0, 1.0)
+}
+
+pub fn within_fov(relative: f32, half_fov: f32) -> bool {
+    relative.abs() <= half_fov
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < EPSILON, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn wrap_pi_keeps_pi_and_turns_minus_pi_into_pi() {
+        assert_eq!(wrap_pi(PI), PI);
+        assert_eq!(wrap_pi(-PI), PI);
+        assert_close(wrap_pi(PI + 0.1), -PI + 0.1);
+        assert_close(wrap_pi(-PI - 0.1), PI - 0.1);
+    }
+
+    #[test]
+    fn wrap_pi_removes_whole_turns() {
+        assert_close(wrap_pi(TAU), 0.0);
+        assert_close(wrap_pi(3.0 * TAU + 1.0), 1.0);
+        assert_close(wrap_pi(-3.0 * TAU - 1.0), -1.0);
+    }
+
+    #[test]
+    fn wrap_tau_stays_below_tau() {
+        assert_eq!(wrap_tau(TAU), 0.0);
+        assert_eq!(wrap_tau(0.0), 0.0);
+        assert_close(wrap_tau(-0.5), TAU - 0.5);
+        assert_close(wrap_tau(TAU + 0.5), 0.5);
+        // rem_euclid alone rounds this one up to TAU
+        let tiny = wrap_tau(-1e-8);
+        assert!((0.0..TAU).contains(&tiny));
+    }
+
+    #[test]
+    fn relative_angle_takes_the_short_way_across_the_seam() {
+        assert_close(relative_angle(PI - 0.1, -PI + 0.1), 0.2);
+        assert_close(relative_angle(-PI + 0.1, PI - 0.1), -0.2);
+        assert_close(relative_angle(TAU - 0.1, 0.1), 0.2);
+        assert_close(relative_angle(0.1, TAU - 0.1), -0.2);
+    }
+
+    #[test]
+    fn relative_angle_of_opposite_headings_is_pi() {
+        assert_eq!(relative_angle(0.0, PI), PI);
+        assert_eq!(relative_angle(PI, 0.0), PI);
+    }
+
+    #[test]
+    fn snap_step_does_not_drift_over_many_steps() {
+        let step = std::f32::consts::FRAC_PI_4;
+        let mut angle = 0.0;
+        for turn in 1..=8000 {
+            angle = snap_step(angle, step, 1);
+            assert_eq!(angle, ((turn % 8) as f32) * step);
+        }
+        for _ in 0..8000 {
+            angle = snap_step(angle, step, -1);
+        }
+        assert_eq!(angle, 0.0);
+    }
+
+    #[test]
+    fn snap_step_starts_from_the_closest_multiple() {
+        let step = std::f32::consts::FRAC_PI_2;
+        assert_eq!(snap_step(0.3, step, 0), 0.0);
+        assert_eq!(snap_step(step - 0.3, step, 1), 2.0 * step);
+        // just below a full turn is closest to zero
+        assert_eq!(snap_step(-0.1, step, 0), 0.0);
+        assert_eq!(snap_step(-0.1, step, -1), 3.0 * step);
+    }
+
+    const SPAN: f32 = std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn strip_position_is_continuous_across_the_seam() {
+        // a quarter of the half span to either side of the view, with the view and the bearing on both sides
+        // of the seam
+        assert_close(strip_position(PI - 0.1, -PI + 0.1, SPAN), -0.2 / (SPAN / 2.0));
+        assert_close(strip_position(-PI + 0.1, PI - 0.1, SPAN), 0.2 / (SPAN / 2.0));
+        assert_close(strip_position(TAU - 0.1, 0.1, SPAN), -0.2 / (SPAN / 2.0));
+        assert_close(strip_position(PI, -PI, SPAN), 0.0);
+    }
+
+    #[test]
+    fn strip_position_puts_the_left_of_the_view_on_the_left_and_the_ends_at_the_half_span() {
+        assert_close(strip_position(1.0, 1.0 + SPAN / 2.0, SPAN), -1.0);
+        assert_close(strip_position(1.0, 1.0 - SPAN / 2.0, SPAN), 1.0);
+        assert_close(strip_position(1.0, 1.0 + SPAN / 4.0, SPAN), -0.5);
+    }
+
+    #[test]
+    fn markers_behind_the_player_stick_to_the_nearer_end() {
+        assert_eq!(strip_position(0.0, PI - 0.2, SPAN), -1.0);
+        assert_eq!(strip_position(0.0, -PI + 0.2, SPAN), 1.0);
+        // across the seam behind a player looking the other way
+        assert_eq!(strip_position(PI - 0.1, 0.2, SPAN), 1.0);
+        assert_eq!(strip_position(-PI + 0.1, -0.2, SPAN), -1.0);
+        // straight behind wraps to PI and so waits at the left end
+        assert_eq!(strip_position(0.0, PI, SPAN), -1.0);
+    }
+}
+
@@ -0,0 +1,123 @@
+use std::{ collections::BTreeMap, fs::{ self, File }, io::{ BufWriter, Write } };
+
+/// The state hash after every physics step of a run, see World::state_hash. --record-hashes=<file> writes them,
+/// --verify-hashes=<file> compares a later run of the same map and inputs against the file and reports the first
+/// step that hashed differently. Only the run from launch to the first restart is covered.
+pub enum HashCheckpoints {
+    Off,
+    Record(BufWriter<File>),
+    Verify {
+        expected: BTreeMap<u64, u64>, // hash by step
+        reported: bool, // only the first divergence is worth reporting, every later step differs as well
+    },
+}
+
+/// the first step of a verified run whose state hash differs from the recorded one
+#[derive(PartialEq, Debug)]
+pub struct Divergence {
+    pub step: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl HashCheckpoints {
+    pub fn from_args(args: &[String]) -> Self {
+        let value = |prefix: &str| args.iter().find_map(|arg| arg.strip_prefix(prefix));
+        if let Some(path) = value("--record-hashes=") {
+            match File::create(path) {
+                Ok(file) => {
+                    return HashCheckpoints::Record(BufWriter::new(file));
+                }
+                Err(err) => eprintln!("Warning: could not create {}: {}, hashes are not recorded", path, err),
+            }
+        } else if let Some(path) = value("--verify-hashes=") {
+            match fs::read_to_string(path).map_err(|err| err.to_string()).and_then(|text| Self::parse(&text)) {
+                Ok(expected) => {
+                    return HashCheckpoints::Verify { expected, reported: false };
+                }
+                Err(err) => eprintln!("Warning: could not read the hashes in {}: {}, nothing is verified", path, err),
+            }
+        }
+        HashCheckpoints::Off
+    }
+
+    /// lines of `<step> <hash in hex>`, as Record writes them
+    pub fn parse(text: &str) -> Result<BTreeMap<u64, u64>, String> {
+        let mut expected = BTreeMap::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let parsed = line
+                .split_once(' ')
+                .and_then(|(step, hash)| Some((step.parse().ok()?, u64::from_str_radix(hash, 16).ok()?)));
+            let Some((step, hash)) = parsed else {
+                return Err(format!("expected '<step> <hash>', found '{}'", line));
+            };
+            expected.insert(step, hash);
+        }
+        Ok(expected)
+    }
+
+    /// records or verifies the hash after `step`, Some on the first step that differs from the recording
+    pub fn check(&mut self, step: u64, hash: u64) -> Option<Divergence> {
+        match self {
+            HashCheckpoints::Off => None,
+            HashCheckpoints::Record(writer) => {
+                // flushed every step, the game exits without dropping the world
+                if let Err(err) = writeln!(writer, "{} {:016x}", step, hash).and_then(|()| writer.flush()) {
+                    eprintln!("Warning: could not record the state hash: {}, recording stops", err);
+                    *self = HashCheckpoints::Off;
+                }
+                None
+            }
+            HashCheckpoints::Verify { expected, reported } => {
+                let expected = *expected.get(&step)?;
+                if expected == hash || *reported {
+                    return None;
+                }
+                *reported = true;
+                Some(Divergence { step, expected, actual: hash })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verifying(text: &str) -> HashCheckpoints {
+        HashCheckpoints::Verify { expected: HashCheckpoints::parse(text).expect("valid hashes"), reported: false }
+    }
+
+    #[test]
+    fn recorded_hashes_read_back() {
+        let path = std::env::temp_dir().join(format!("doomr_hashes_{}.txt", std::process::id()));
+        let mut recording = HashCheckpoints::from_args(&[format!("--record-hashes={}", path.display())]);
+        for (step, hash) in [(1, 0xabc), (2, u64::MAX)] {
+            assert_eq!(recording.check(step, hash), None);
+        }
+        let text = fs::read_to_string(&path).expect("the recording was written");
+        assert_eq!(HashCheckpoints::parse(&text), Ok(BTreeMap::from([(1, 0xabc), (2, u64::MAX)])));
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn only_the_first_divergent_step_is_reported() {
+        let mut checkpoints = verifying("1 0a\n2 0b\n3 0c\n");
+        assert_eq!(checkpoints.check(1, 0xa), None);
+        assert_eq!(checkpoints.check(2, 0xf), Some(Divergence { step: 2, expected: 0xb, actual: 0xf }));
+        assert_eq!(checkpoints.check(3, 0xf), None);
+    }
+
+    #[test]
+    fn steps_past_the_recording_are_not_checked() {
+        let mut checkpoints = verifying("1 0a\n");
+        assert_eq!(checkpoints.check(2, 0xf), None);
+    }
+
+    #[test]
+    fn broken_lines_are_rejected() {
+        assert!(HashCheckpoints::parse("1 0a\n2\n").is_err());
+        assert!(HashCheckpoints::parse("x 0a\n").is_err());
+        assert!(HashCheckpoints::parse("1 zz\n").is_err());
+    }
+}
@@ -6,8 +6,9 @@ pub mod config {
         // 3 = Enemies
         // 4 = RIGHT OR DOWN < DOOR
         // 5 = LEFT OR UP < DOOR
+        // 7 = Skeleton archers, see the spawn table in content/base.toml
         [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-        [1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 1],
+        [1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 1],
         [1, 0, 0, 2, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
         [1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
         [1, 1, 1, 4, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1],
@@ -31,7 +32,7 @@ pub mod config {
         [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1],
         [1, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1, 1],
         [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-        [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1],
+        [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 7, 0, 1],
         [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
         [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
         [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
@@ -43,17 +44,91 @@ pub mod config {
     pub const HALF_SCREEN_WIDTH: f32 = (SCREEN_WIDTH as f32) / 2.0;
     pub const SCREEN_HEIGHT: usize = 1080;
     pub const HALF_SCREEN_HEIGHT: f32 = (SCREEN_HEIGHT as f32) / 2.0;
-    pub const WORLD_WIDTH: usize = WORLD_LAYOUT[0].len() as usize;
-    pub const WORLD_HEIGHT: usize = WORLD_LAYOUT.len() as usize;
     pub const PHYSICS_FRAME_TIME: f32 = 1.0 / 60.0;
-    pub const TILE_SIZE_X_PIXEL: usize = SCREEN_WIDTH / WORLD_WIDTH;
-    pub const TILE_SIZE_Y_PIXEL: usize = SCREEN_HEIGHT / WORLD_HEIGHT;
+    pub const MINIMAP_TILE_WIDTH: f32 = 9.5; // pixels per tile before ui_scale
+    pub const MINIMAP_TILE_HEIGHT: f32 = 9.0;
+    pub const COMPASS_WIDTH: f32 = 0.3; // fraction of the screen width
+    pub const COMPASS_SPAN: f32 = (2.0 * PI) / 3.0; // radians of heading the compass strip shows
     pub const PLAYER_FOV: f32 = PI / 2.0;
     pub const HALF_PLAYER_FOV: f32 = PLAYER_FOV / 2.0;
     pub const LEFT_MOST_RAY: f32 = PLAYER_FOV - HALF_PLAYER_FOV;
     pub const RIGHT_MOST_RAY: f32 = PLAYER_FOV + HALF_PLAYER_FOV;
-    pub const MAX_VIEW_DISTANCE: usize = WORLD_WIDTH;
+    pub const SHADE_DISTANCE: f32 = 30.0; // walls and sprites fade to black over this many tiles, the shader has a copy
     pub const MAP_X_OFFSET: f32 = (SCREEN_WIDTH as f32) * 0.75;
     pub const MAP_Y_OFFSET: f32 = (SCREEN_HEIGHT as f32) * 0.25;
+    pub const MINIMAP_PARALLAX_STRENGTH: f32 = 3.0; // pixels of minimap offset per unit of player velocity
     pub const ENEMY_VIEW_DISTANCE: f32 = 5.0;
+    pub const ENEMY_GROWL_INTERVAL: f32 = 4.0; // average seconds between growls of the aggressive enemies in view distance
+    pub const SOUND_FALLOFF_DISTANCE: f32 = 12.0; // tiles at which a sound from somewhere in the level goes silent
+    pub const ENEMY_SIGHT_MEMORY: f32 = 2.0; // seconds an enemy keeps chasing where it last saw the player
+    pub const ENEMY_ATTACK_WINDUP: f32 = 0.5; // seconds a ranged enemy stands still and aims before it fires
+    pub const ENEMY_PATH_INTERVAL: u32 = 20; // physics frames between path updates while the player stays on one tile
+    pub const ENEMY_LOD_HALF_DISTANCE: f32 = 20.0; // beyond this enemies are drawn from the half resolution impostor
+    pub const ENEMY_LOD_QUARTER_DISTANCE: f32 = 35.0;
+    pub const ENEMY_TURN_SPEED: f32 = 8.0; // smoothing rate of the facing angle used for the sprite choice
+    pub const MAX_ENEMIES_PER_LEVEL: usize = 256; // EnemyHandle is a u16 index
+    pub const CORPSE_LIFETIME: f32 = 30.0; // seconds
+    pub const CORPSE_FADE_TIME: f32 = 3.0; // seconds at the end of the lifetime
+    pub const SOLID_ENEMIES: bool = true; // false lets the player and enemies walk through each other
+    pub const SUBSTEP_THRESHOLD: f32 = 0.5; // tiles per physics frame above which movement is split into substeps
+    pub const MAX_SUBSTEP_LENGTH: f32 = 0.4; // tiles
+    pub const MAX_SUBSTEPS: u32 = 8; // bounds the cost, even faster movers get longer substeps
+    pub const PLAYER_HIT_COOLDOWN: f32 = 1.0; // seconds
+    pub const OUT_OF_COMBAT_TIME: f32 = 10.0; // seconds without damage or aggressive enemies before regeneration starts
+    pub const HEALTH_REGEN_INTERVAL: f32 = 30.0; // seconds per regenerated health point
+    pub const HEALTH_REGEN_MAX: u16 = 2;
+    pub const ADRENALINE_DURATION: f32 = 3.0; // seconds, granted by kills at 1 health
+    pub const ADRENALINE_SPEED_BONUS: f32 = 0.3;
+    pub const PLAYER_SPEED: f32 = 2.0; // before adrenaline, sprinting and walking
+    pub const WALK_SPEED_FACTOR: f32 = 0.5; // while walking is toggled on
+    pub const CROUCH_SPEED_FACTOR: f32 = 0.6; // while crouching, stacks with walking
+    pub const MOVE_ACCELERATION: f32 = 15.0; // per second, how fast the velocity catches up with the input
+    pub const MOVE_DECELERATION: f32 = 20.0; // per second, how fast the velocity runs out without input
+    pub const KNOCKBACK_SPEED: f32 = 5.0; // added to the player's velocity by a hit, about half a tile of push
+    pub const BOBBING_MIN_SPEED: f32 = 0.1; // the sway stops below this, the velocity only approaches zero
+    pub const STAMINA_DRAIN: f32 = 0.5; // stamina per second of sprinting, a full bar is 1.0
+    pub const STAMINA_REGEN: f32 = 0.2; // stamina per second while not sprinting
+    pub const QUICK_TURN_DURATION: f32 = 0.2; // seconds for a 180° turn
+    pub const TURN_STEP_DURATION: f32 = 0.08; // seconds per snapped turn step, see Settings::turn_step_degrees
+    pub const MOUSE_SENSITIVITY: f32 = 0.003; // radians per pixel of mouse movement, scaled by Settings::mouse_sensitivity
+    pub const TITLE_CARD_TIME: f32 = 3.0; // seconds a title of a scripted sequence stays on screen
+    pub const MAX_PITCH: f32 = PI / 6.0; // radians the view tilts up or down with the mouse
+    pub const MOUSE_LOOK_MAX_FRAME_TIME: f32 = 0.25; // a longer frame drops the mouse movement, see MouseLook
+    pub const WEAPON_LOWER_TIME: f32 = 0.25; // seconds, the weapon can't fire while it is switched
+    pub const WEAPON_RAISE_TIME: f32 = 0.25;
+    pub const BURST_SHOT_INTERVAL: f32 = 4.0 * PHYSICS_FRAME_TIME; // seconds between the shots of a burst
+    pub const PELLET_JITTER: f32 = 0.015; // radians each pellet of a fan strays from its place at random
+    pub const AUTO_FIRE_BLOOM_PER_SHOT: f32 = 0.01; // radians of random aim offset each automatic shot adds
+    pub const AUTO_FIRE_BLOOM_DECAY: f32 = 0.03; // radians per second the offset shrinks while the weapon rests
+    pub const AUTO_FIRE_MAX_BLOOM: f32 = 0.15;
+    pub const HIT_STOP_DURATION: f32 = 0.05; // seconds the simulation nearly freezes on a heavy impact, see Settings::hit_stop
+    pub const HIT_STOP_TIME_SCALE: f32 = 0.05;
+    pub const WEAPON_WHEEL_TIME_SCALE: f32 = 0.3; // while the weapon wheel is held open
+    pub const WEAPON_WHEEL_RADIUS: f32 = 120.0; // pixels from the screen center to the weapons, before the ui scale
+    pub const WEAPON_WHEEL_DEADZONE: f32 = 25.0; // pixels the mouse has to move before its direction picks a weapon
+    pub const HIT_STOP_KILL_DISTANCE: f32 = 2.0; // kills closer than this many tiles trigger a hit-stop
+    pub const TELEPORT_LOCKOUT: f32 = 0.5; // seconds before a teleported player or enemy can teleport again
+    pub const ELEVATOR_RIDE_TIME: f32 = 2.0; // seconds between the elevator doors closing and the next level
+    pub const DECORATION_REGION_SIZE: usize = 8; // tiles per side of a square of decorations that is culled as one
+    pub const DECORATION_SWAY_SPEED: f32 = 1.5; // frames per second of decorations with more than one frame
+    pub const AMMO_PICKUP_AMOUNT: u16 = 8; // pistol rounds in an ammo box placed by the map
+    pub const HEALTH_PICKUP_AMOUNT: u8 = 1; // health points in a medkit placed by the map
+    pub const PLAYER_MAX_HEALTH: u16 = 3;
+    pub const PICKUP_SCALE: f32 = 0.3; // height of a pickup sprite in wall heights
+    pub const PICKUP_LABEL_DISTANCE: f32 = 4.0; // tiles from which an ammo pickup in view is labeled
+    pub const PROJECTILE_RADIUS: f32 = 0.15; // tiles, for drawing and for hitting the player
+    pub const NEAR_MISS_DISTANCE: f32 = 0.5; // tiles between an enemy projectile and the player that still whizz
+    pub const NEAR_MISS_NUDGE: f32 = 3.0; // pixels the view flinches away from a near miss
+    pub const WATER_SPEED_MULTIPLIER: f32 = 0.5; // for the player and enemies standing in water
+    pub const WATER_VOLUME_MULTIPLIER: f32 = 0.5; // sounds are muffled while the player is in water
+    pub const GRID_SNAP_SPEED: f32 = 8.0; // velocity per unit of distance to the tile when grid snapping
+    pub const DEFAULT_MAP_ID: &str = "default"; // map id from content/base.toml or a mod, overridden by the first CLI argument
+    pub const MAP_RELOAD_POLL_INTERVAL: f32 = 2.0; // seconds, debug builds only
+    pub const STRESS_SEED: u64 = 1337; // --stress always generates the same map
+    pub const STRESS_ENEMIES: usize = 200; // before --stress-density, the level holds at most MAX_ENEMIES_PER_LEVEL
+    pub const REPULSION_DURATION: f32 = 2.0; // seconds enemies scatter from where something blew up
+    pub const REPULSION_RADIUS: f32 = 4.0; // tiles around the blast center that push enemies away
+    pub const REPULSION_STRENGTH: f32 = 4.0; // tiles per second at the center of a fresh field, fading to 0 at the edge
+    pub const SIMULATION_SEED: u64 = 0x5eed; // loot, pellet jitter and bloom, every level starts from it
+    pub const STATE_HASH_QUANTUM: f32 = 1024.0; // parts of a tile, radian or second a state hash tells apart
 }
@@ -0,0 +1,308 @@
+use std::{ collections::HashMap, fs, path::{ Path, PathBuf } };
+use serde::Deserialize;
+use crate::{ AttackType, DamageType, FireMode, LootItem, Sounds };
+
+const BASE_CONTENT: &str = include_str!("../content/base.toml");
+const MODS_DIRECTORY: &str = "mods";
+const MOD_MANIFEST: &str = "mod.toml";
+
+#[derive(Deserialize, Clone)]
+pub struct LootEntryDef {
+    pub item: LootItem,
+    pub weight: f32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EnemyKindDef {
+    pub id: String,
+    pub health: u8,
+    #[serde(default = "default_speed")]
+    pub speed: f32, // multiplier on the base movement speed
+    #[serde(default = "default_tint")]
+    pub tint: [u8; 3],
+    #[serde(default)]
+    pub loot: Vec<LootEntryDef>,
+    #[serde(default)]
+    pub resistances: HashMap<DamageType, f32>, // damage multiplier per type, missing types take full damage
+    #[serde(default)]
+    pub uses_teleporters: bool,
+    #[serde(default = "default_attack")]
+    pub attack: AttackType, // on top of the contact damage every enemy deals
+}
+fn default_speed() -> f32 {
+    1.0
+}
+fn default_attack() -> AttackType {
+    AttackType::Melee
+}
+fn default_tint() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+/// places an enemy kind on every map tile with this digit
+#[derive(Deserialize, Clone)]
+pub struct SpawnDef {
+    pub tile: u8,
+    pub enemy: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WeaponDef {
+    pub id: String,
+    pub damage: u8,
+    pub range: u8,
+    pub reload_frames: u8, // in physics frames
+    #[serde(default = "default_damage_type")]
+    pub damage_type: DamageType,
+    #[serde(default)]
+    pub penetration: u8, // extra enemies a shot passes into, each taking half the damage of the one before
+    #[serde(default = "default_fire_modes")]
+    pub fire_modes: Vec<FireMode>, // the cycle key goes through them in order, the first one is active at the start
+    pub fire_sound: Sounds,
+    pub reload_sound: Sounds,
+    #[serde(default = "default_empty_sound")]
+    pub empty_sound: Sounds, // played when the trigger is pulled with no ammo left at all
+    #[serde(default = "default_slot")]
+    pub slot: u8, // weapons are ordered by slot, the number keys select them in that order
+    #[serde(default)]
+    pub magazine_size: u8, // shots before a reload, 0 never runs dry
+    #[serde(default)]
+    pub reserve_ammo: u16, // rounds carried besides the full magazine at the start
+    #[serde(default = "default_pellets")]
+    pub pellets: u8, // rays fanned out per shot, every one deals the full damage to what it hits
+    #[serde(default)]
+    pub spread: f32, // degrees between the outermost pellets
+    #[serde(default)]
+    pub projectile_speed: f32, // tiles per second of the rocket a shot launches instead of its rays, 0 is hitscan
+    #[serde(default)]
+    pub blast_radius: f32, // tiles around the impact of a rocket that take its damage, 0 only hurts what it hits
+}
+
+fn default_damage_type() -> DamageType {
+    DamageType::Ballistic
+}
+
+fn default_fire_modes() -> Vec<FireMode> {
+    vec![FireMode::Single]
+}
+
+fn default_pellets() -> u8 {
+    1
+}
+
+fn default_empty_sound() -> Sounds {
+    Sounds::DryFire
+}
+
+/// weapons without a slot come after the ones that have one
+fn default_slot() -> u8 {
+    u8::MAX
+}
+
+/// a prop that is only drawn, maps place it with `decoration <id> <x> <y>`
+#[derive(Deserialize, Clone)]
+pub struct DecorationDef {
+    pub id: String,
+    pub sprite: String, // relative to the package directory, frames side by side
+    #[serde(default = "default_frames")]
+    pub frames: u8, // more than one sways through them slowly
+    pub scale: f32, // height as a fraction of a wall
+    #[serde(default)]
+    pub ground_offset: f32, // gap below the sprite as a fraction of a wall, hanging props use the upper part
+}
+
+fn default_frames() -> u8 {
+    1
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MapDef {
+    pub id: String,
+    pub file: String, // relative to the package directory
+}
+
+/// contents of content/base.toml or of a mods/<name>/mod.toml manifest
+#[derive(Deserialize, Default)]
+struct ContentPackage {
+    min_alive_enemies: Option<usize>,
+    #[serde(default)]
+    enemies: Vec<EnemyKindDef>,
+    #[serde(default)]
+    spawns: Vec<SpawnDef>,
+    #[serde(default)]
+    weapons: Vec<WeaponDef>,
+    #[serde(default)]
+    decorations: Vec<DecorationDef>,
+    #[serde(default)]
+    maps: Vec<MapDef>,
+}
+
+/// all definitions after merging the base content with the mods, later packages win on conflicts
+pub struct ContentRegistry {
+    pub enemies: HashMap<String, EnemyKindDef>,
+    pub spawn_table: HashMap<u8, String>,
+    pub weapons: HashMap<String, WeaponDef>,
+    pub decorations: HashMap<String, DecorationDef>,
+    pub maps: HashMap<String, MapDef>,
+    pub min_alive_enemies: Option<usize>, // see allows_spawn
+    pub report: Vec<String>,
+}
+
+impl ContentRegistry {
+    /// only the embedded base content, without any mods
+    pub fn base() -> Self {
+        let mut registry = ContentRegistry {
+            enemies: HashMap::new(),
+            spawn_table: HashMap::new(),
+            weapons: HashMap::new(),
+            decorations: HashMap::new(),
+            maps: HashMap::new(),
+            min_alive_enemies: None,
+            report: Vec::new(),
+        };
+        let base: ContentPackage = toml
+            ::from_str(BASE_CONTENT)
+            .expect("Embedded base content is invalid");
+        registry.merge("base", base, Path::new(""));
+        registry
+    }
+
+    /// loads the embedded base content, then every mod in mods/ in alphabetical order,
+    /// a broken mod is skipped and noted in the report instead of stopping the game
+    pub fn load() -> Self {
+        let mut registry = Self::base();
+
+        let mut mod_directories: Vec<PathBuf> = match fs::read_dir(MODS_DIRECTORY) {
+            Ok(entries) =>
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_dir())
+                    .collect(),
+            Err(_) => Vec::new(),
+        };
+        mod_directories.sort();
+        for directory in mod_directories {
+            let name = directory
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            match Self::read_package(&directory) {
+                Ok(package) => {
+                    registry.merge(&name, package, &directory);
+                    registry.report.push(format!("Loaded mod {}", name));
+                }
+                Err(err) => {
+                    registry.report.push(format!("Failed to load mod {}: {}", name, err));
+                }
+            }
+        }
+        registry.remove_dangling_spawns();
+        registry
+    }
+
+    /// whether a scripted spawn step adds its enemy while `alive_enemies` are alive, without a threshold
+    /// only the level capacity limits them
+    pub fn allows_spawn(&self, alive_enemies: usize) -> bool {
+        self.min_alive_enemies.is_none_or(|min_alive_enemies| alive_enemies < min_alive_enemies)
+    }
+
+    /// the map digits that spawn an enemy, see map::validate
+    pub fn spawn_tiles(&self) -> Vec<u8> {
+        self.spawn_table.keys().copied().collect()
+    }
+
+    fn read_package(directory: &Path) -> Result<ContentPackage, String> {
+        let manifest = directory.join(MOD_MANIFEST);
+        let text = fs
+            ::read_to_string(&manifest)
+            .map_err(|err| format!("could not read {}: {}", manifest.display(), err))?;
+        toml::from_str(&text).map_err(|err| err.to_string())
+    }
+
+    fn merge(&mut self, source: &str, package: ContentPackage, root: &Path) {
+        if let Some(min_alive_enemies) = package.min_alive_enemies {
+            if self.min_alive_enemies.is_some() {
+                self.report.push(format!("{} overrides min_alive_enemies with {}", source, min_alive_enemies));
+            }
+            self.min_alive_enemies = Some(min_alive_enemies);
+        }
+        for enemy in package.enemies {
+            if self.enemies.contains_key(&enemy.id) {
+                self.report.push(format!("{} overrides enemy '{}'", source, enemy.id));
+            }
+            self.enemies.insert(enemy.id.clone(), enemy);
+        }
+        for spawn in package.spawns {
+            if self.spawn_table.contains_key(&spawn.tile) {
+                self.report.push(format!("{} overrides spawn tile {}", source, spawn.tile));
+            }
+            self.spawn_table.insert(spawn.tile, spawn.enemy);
+        }
+        for weapon in package.weapons {
+            if self.weapons.contains_key(&weapon.id) {
+                self.report.push(format!("{} overrides weapon '{}'", source, weapon.id));
+            }
+            self.weapons.insert(weapon.id.clone(), weapon);
+        }
+        for mut decoration in package.decorations {
+            if self.decorations.contains_key(&decoration.id) {
+                self.report.push(format!("{} overrides decoration '{}'", source, decoration.id));
+            }
+            decoration.sprite = root.join(&decoration.sprite).to_string_lossy().to_string();
+            self.decorations.insert(decoration.id.clone(), decoration);
+        }
+        for mut map in package.maps {
+            if self.maps.contains_key(&map.id) {
+                self.report.push(format!("{} overrides map '{}'", source, map.id));
+            }
+            map.file = root.join(&map.file).to_string_lossy().to_string();
+            self.maps.insert(map.id.clone(), map);
+        }
+    }
+
+    fn remove_dangling_spawns(&mut self) {
+        let enemies = &self.enemies;
+        let report = &mut self.report;
+        self.spawn_table.retain(|tile, enemy| {
+            let known = enemies.contains_key(enemy);
+            if !known {
+                report.push(format!("Spawn tile {} uses unknown enemy '{}', ignoring it", tile, enemy));
+            }
+            known
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_content_limits_scripted_spawns() {
+        let registry = ContentRegistry::base();
+        assert_eq!(registry.min_alive_enemies, Some(8));
+        assert!(registry.allows_spawn(7));
+        assert!(!registry.allows_spawn(8));
+        assert!(!registry.allows_spawn(9));
+    }
+
+    #[test]
+    fn a_mod_overrides_min_alive_enemies() {
+        let mut registry = ContentRegistry::base();
+        let package: ContentPackage = toml::from_str("min_alive_enemies = 2").expect("valid package");
+        registry.merge("horde", package, Path::new(""));
+        assert!(registry.allows_spawn(1));
+        assert!(!registry.allows_spawn(2));
+        assert_eq!(registry.report, vec!["horde overrides min_alive_enemies with 2".to_string()]);
+    }
+
+    #[test]
+    fn a_mod_without_the_key_keeps_the_base_value() {
+        let mut registry = ContentRegistry::base();
+        let package: ContentPackage = toml::from_str("").expect("valid package");
+        registry.merge("empty", package, Path::new(""));
+        assert_eq!(registry.min_alive_enemies, Some(8));
+        assert!(registry.report.is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    fs,
+    panic::{ self, PanicHookInfo },
+    sync::Mutex,
+    time::{ SystemTime, UNIX_EPOCH },
+};
+use once_cell::sync::Lazy;
+use crate::settings::Settings;
+
+const CRASH_REPORTS_DIRECTORY: &str = "crash_reports";
+const RECENT_EVENTS_CAPACITY: usize = 300;
+
+/// what the game looked like the last time it told us, read by the panic hook
+#[derive(Default, Clone)]
+pub struct WorldSummary {
+    pub game_time: f64, // seconds since the window opened
+    pub player_pos: (f32, f32),
+    pub player_health: u16,
+    pub enemies_alive: usize,
+    pub enemies_total: usize,
+    pub state_hash: u64, // see World::state_hash
+}
+
+#[derive(Default)]
+struct CrashContext {
+    map_path: String,
+    summary: WorldSummary,
+    settings: Option<Settings>,
+    recent_events: VecDeque<String>,
+}
+
+static CONTEXT: Lazy<Mutex<CrashContext>> = Lazy::new(|| Mutex::new(CrashContext::default()));
+
+/// Writes crash_reports/<unix time>.txt on a panic before the default hook prints the message.
+/// The report only holds game state: the panic, the world summary, the settings and recent events.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(
+        Box::new(move |info| {
+            match write_report(info, CRASH_REPORTS_DIRECTORY) {
+                Ok(path) => eprintln!("The game crashed, a report was written to {}", path),
+                Err(err) => {
+                    eprintln!("The game crashed and the crash report could not be written: {}", err);
+                }
+            }
+            default_hook(info);
+        })
+    );
+}
+
+/// remembers one line for the report, only the last RECENT_EVENTS_CAPACITY are kept
+pub fn record_event(event: String) {
+    let mut context = lock();
+    if context.recent_events.len() == RECENT_EVENTS_CAPACITY {
+        context.recent_events.pop_front();
+    }
+    context.recent_events.push_back(event);
+}
+
+pub fn update_summary(summary: WorldSummary) {
+    lock().summary = summary;
+}
+
+pub fn set_map_path(path: &str) {
+    lock().map_path = path.to_string();
+}
+
+pub fn set_settings(settings: &Settings) {
+    lock().settings = Some(settings.clone());
+}
+
+/// a panic while the context was locked must not stop the report from being written
+fn lock() -> std::sync::MutexGuard<'static, CrashContext> {
+    CONTEXT.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write_report(info: &PanicHookInfo, directory: &str) -> Result<String, String> {
+    // try_lock, the panic might have happened on this thread while it held the lock
+    let context = match CONTEXT.try_lock() {
+        Ok(context) => context,
+        Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => {
+            return Err("the crash context is in use".to_string());
+        }
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let mut report = String::new();
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    let location = info
+        .location()
+        .map(|location| format!("{}:{}", location.file(), location.line()))
+        .unwrap_or_else(|| "unknown location".to_string());
+    let summary = &context.summary;
+    // writing into a String can't fail
+    let _ = writeln!(
+        report,
+        "DoomR {} crash report, unix time {}",
+        env!("CARGO_PKG_VERSION"),
+        timestamp
+    );
+    let _ = writeln!(report, "panic: {}", message);
+    let _ = writeln!(report, "at: {}", location);
+    let _ = writeln!(report, "\n[world]");
+    let _ = writeln!(report, "map: {}", context.map_path);
+    let _ = writeln!(report, "game time: {:.2}s", summary.game_time);
+    let _ = writeln!(
+        report,
+        "player: ({:.2}, {:.2}), health {}",
+        summary.player_pos.0,
+        summary.player_pos.1,
+        summary.player_health
+    );
+    let _ = writeln!(report, "enemies: {} alive of {}", summary.enemies_alive, summary.enemies_total);
+    let _ = writeln!(report, "state hash: {:016x}", summary.state_hash);
+    let _ = writeln!(report, "\n[settings]");
+    match &context.settings {
+        Some(settings) => {
+            let settings = toml::to_string(settings).unwrap_or_else(|err| err.to_string());
+            let _ = write!(report, "{}", settings);
+        }
+        None => {
+            let _ = writeln!(report, "not loaded yet");
+        }
+    }
+    let _ = writeln!(report, "\n[last {} events, oldest first]", context.recent_events.len());
+    for event in &context.recent_events {
+        let _ = writeln!(report, "{}", event);
+    }
+    fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+    let path = format!("{}/{}.txt", directory, timestamp);
+    fs::write(&path, report).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn deliberate_panic_writes_a_report_of_the_game_state() {
+        let directory = std::env::temp_dir().join(format!("doomr_crash_reports_{}", std::process::id()));
+        let directory = directory.to_string_lossy().to_string();
+        set_map_path("maps/crash_test.map");
+        update_summary(WorldSummary {
+            game_time: 12.5,
+            player_pos: (3.0, 4.5),
+            player_health: 2,
+            enemies_alive: 1,
+            enemies_total: 4,
+            state_hash: 0xabc,
+        });
+        record_event("12.40s shot hit with the center ray".to_string());
+        // the real hook would also run the default one, which only prints
+        let written = Arc::new(Mutex::new(None));
+        let previous_hook = panic::take_hook();
+        {
+            let (written, directory) = (Arc::clone(&written), directory.clone());
+            panic::set_hook(Box::new(move |info| *written.lock().unwrap() = Some(write_report(info, &directory))));
+        }
+        let crashed = panic::catch_unwind(|| panic!("deliberate crash"));
+        panic::set_hook(previous_hook);
+        assert!(crashed.is_err());
+        let path = written.lock().unwrap().take().expect("the hook ran").expect("the report was written");
+        let report = fs::read_to_string(&path).expect("the report can be read");
+        fs::remove_dir_all(&directory).ok();
+        for expected in [
+            "panic: deliberate crash",
+            "at: src/crash.rs:",
+            "map: maps/crash_test.map",
+            "game time: 12.50s",
+            "player: (3.00, 4.50), health 2",
+            "enemies: 1 alive of 4",
+            "state hash: 0000000000000abc",
+            "[settings]",
+            "12.40s shot hit with the center ray",
+        ] {
+            assert!(report.contains(expected), "'{}' is missing from the report:\n{}", expected, report);
+        }
+    }
+}
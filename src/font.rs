@@ -0,0 +1,145 @@
+use std::fs;
+use macroquad::prelude::*;
+use once_cell::sync::Lazy;
+
+const FONT_PATH: &str = "fonts/pixel.png";
+/// the glyph sheet holds the printable ascii characters in order, 16 per row
+const FIRST_CHARACTER: char = ' ';
+const LAST_CHARACTER: char = '~';
+const SHEET_COLUMNS: u32 = 16;
+const CELL_WIDTH: f32 = 6.0; // 5 pixels of glyph and one of spacing
+const CELL_HEIGHT: f32 = 10.0;
+const ASCENT: f32 = 7.0; // glyph rows above the baseline, g, j, p, q and y reach two below it
+
+/// None falls back to macroquad's default font, the game stays playable without the asset
+static PIXEL_FONT: Lazy<Option<Texture2D>> = Lazy::new(|| {
+    match fs::read(FONT_PATH) {
+        Ok(bytes) => {
+            let texture = Texture2D::from_file_with_format(&bytes, Some(ImageFormat::Png));
+            texture.set_filter(FilterMode::Nearest);
+            Some(texture)
+        }
+        Err(err) => {
+            eprintln!("Failed to load {}: {}, using the default font", FONT_PATH, err);
+            None
+        }
+    }
+});
+
+/// What a piece of text is for, the size follows the window height in physical pixels like the rest of the HUD
+/// does through ui_scale, so the layout holds at any resolution
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextStyle {
+    Title,
+    Prompt, // interaction prompts and notifications
+    Hud, // labels, the controls help and the timer
+    Debug, // timings and counters
+    Fixed(f32), // a size that follows something else, like the minimap tiles or the note panel
+}
+impl TextStyle {
+    pub fn size(self) -> f32 {
+        let fraction = match self {
+            TextStyle::Title => 0.046,
+            TextStyle::Prompt => 0.023,
+            TextStyle::Hud | TextStyle::Debug => 0.0185,
+            TextStyle::Fixed(size) => {
+                return size;
+            }
+        };
+        screen_height() * screen_dpi_scale() * fraction
+    }
+    /// a dark copy underneath keeps the text readable over bright walls and effects
+    fn backing(self) -> Backing {
+        match self {
+            TextStyle::Title | TextStyle::Prompt => Backing::Outline,
+            TextStyle::Hud => Backing::Shadow,
+            TextStyle::Debug | TextStyle::Fixed(_) => Backing::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Backing {
+    None,
+    Shadow, // one copy down and to the right
+    Outline, // copies on all four sides
+}
+
+/// pixel font texels per glyph pixel, whole numbers keep the glyphs sharp
+fn pixel_scale(size: f32) -> f32 {
+    (size / CELL_HEIGHT).round().max(1.0)
+}
+
+/// width and height above the baseline
+pub fn measure(text: &str, style: TextStyle) -> Vec2 {
+    let size = style.size();
+    match PIXEL_FONT.as_ref() {
+        Some(_) => {
+            let scale = pixel_scale(size);
+            let characters = text.chars().count() as f32;
+            Vec2::new((characters * CELL_WIDTH - 1.0).max(0.0) * scale, ASCENT * scale)
+        }
+        None => {
+            let dimensions = measure_text(text, None, size as u16, 1.0);
+            Vec2::new(dimensions.width, dimensions.offset_y)
+        }
+    }
+}
+
+/// like draw_text, y is the baseline
+pub fn draw(text: &str, x: f32, y: f32, style: TextStyle, color: Color) {
+    let size = style.size();
+    let offset = match PIXEL_FONT.as_ref() {
+        Some(_) => pixel_scale(size),
+        None => (size / 16.0).max(1.0),
+    };
+    let backing_color = Color::new(0.0, 0.0, 0.0, color.a * 0.8);
+    match style.backing() {
+        Backing::None => {}
+        Backing::Shadow => draw_plain(text, x + offset, y + offset, size, backing_color),
+        Backing::Outline => {
+            for (dx, dy) in [(-offset, 0.0), (offset, 0.0), (0.0, -offset), (0.0, offset)] {
+                draw_plain(text, x + dx, y + dy, size, backing_color);
+            }
+        }
+    }
+    draw_plain(text, x, y, size, color);
+}
+
+pub fn draw_centered(text: &str, center_x: f32, y: f32, style: TextStyle, color: Color) {
+    let width = measure(text, style).x;
+    draw(text, center_x - width / 2.0, y, style, color);
+}
+
+pub fn draw_right(text: &str, right_x: f32, y: f32, style: TextStyle, color: Color) {
+    let width = measure(text, style).x;
+    draw(text, right_x - width, y, style, color);
+}
+
+fn draw_plain(text: &str, x: f32, y: f32, size: f32, color: Color) {
+    let Some(texture) = PIXEL_FONT.as_ref() else {
+        draw_text(text, x, y, size, color);
+        return;
+    };
+    let scale = pixel_scale(size);
+    let top = y - ASCENT * scale;
+    for (i, character) in text.chars().enumerate() {
+        if character == ' ' {
+            continue;
+        }
+        // anything the sheet doesn't have shows up as a question mark
+        let character = if (FIRST_CHARACTER..=LAST_CHARACTER).contains(&character) { character } else { '?' };
+        let index = (character as u32) - (FIRST_CHARACTER as u32);
+        let source = Rect::new(
+            ((index % SHEET_COLUMNS) as f32) * CELL_WIDTH,
+            ((index / SHEET_COLUMNS) as f32) * CELL_HEIGHT,
+            CELL_WIDTH,
+            CELL_HEIGHT
+        );
+        draw_texture_ex(texture, x + (i as f32) * CELL_WIDTH * scale, top, color, DrawTextureParams {
+            dest_size: Some(Vec2::new(CELL_WIDTH, CELL_HEIGHT) * scale),
+            source: Some(source),
+            ..Default::default()
+        });
+    }
+}
@@ -0,0 +1,237 @@
+use std::{ f32::consts::FRAC_PI_2, fs, rc::Rc };
+use macroquad::prelude::*;
+use crate::{
+    config::config::HALF_PLAYER_FOV,
+    content::{ ContentRegistry, MapDef },
+    settings::Settings,
+    DoorHandle,
+    EntityType,
+    MinimapTransform,
+    Tile,
+    World,
+};
+
+const GOLDEN_DIRECTORY: &str = "goldens";
+const SCENARIO_MAP_ID: &str = "golden_scenario";
+const SCENARIO_MAP_FILE: &str = "goldens/scenario.map";
+const CHANNEL_TOLERANCE: u8 = 8; // per color channel, absorbs rounding differences between drivers
+const MAX_DIFFERING_PIXELS: usize = 64;
+/// the raycast shader samples textures per pixel instead of per stripe, so edges land a pixel off
+const GPU_MAX_DIFFERING_PIXELS: usize = 4000;
+
+struct Scenario {
+    name: &'static str,
+    setup: fn(&mut World),
+    minimap_only: bool,
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "empty_corridor",
+        setup: |world| place_player(world, Vec2::new(25.0, 2.0), 0.0),
+        minimap_only: false,
+    },
+    Scenario {
+        name: "enemy_at_fov_edge",
+        setup: |world| place_player(world, Vec2::new(20.0, 20.0), HALF_PLAYER_FOV * 0.9),
+        minimap_only: false,
+    },
+    Scenario {
+        name: "half_open_door",
+        setup: |world| {
+            place_player(world, Vec2::new(40.0, 2.0), FRAC_PI_2);
+            half_open_door(world, DoorHandle(1));
+        },
+        minimap_only: false,
+    },
+    Scenario {
+        name: "enemy_behind_door",
+        setup: |world| {
+            place_player(world, Vec2::new(10.0, 2.0), FRAC_PI_2);
+            half_open_door(world, DoorHandle(0));
+        },
+        minimap_only: false,
+    },
+    Scenario {
+        name: "minimap",
+        setup: |world| place_player(world, Vec2::new(25.0, 2.0), 0.0),
+        minimap_only: true,
+    },
+];
+
+/// same tile convention as MovementSystem::update_player
+fn place_player(world: &mut World, tile: Vec2, angle: f32) {
+    let old_tile = Tile::from_vec2(world.player.pos);
+    if world.world_layout[old_tile.y as usize][old_tile.x as usize] == EntityType::Player {
+        world.world_layout[old_tile.y as usize][old_tile.x as usize] = EntityType::None;
+    }
+    world.world_layout[tile.y as usize][tile.x as usize] = EntityType::Player;
+    world.player.pos = tile;
+    world.player.angle = angle;
+}
+
+fn half_open_door(world: &mut World, door: DoorHandle) {
+    world.doors.open_door(door);
+    world.doors.update_animation(world.doors.animation_duration * 0.5);
+}
+
+/// Renderer screenshot comparisons, they need a window and a GPU so they are run by hand:
+/// `cargo run --features golden-tests -- --golden` compares every scenario against goldens/<name>.png,
+/// `-- --golden --bless` overwrites the goldens after an intentional rendering change.
+/// Only goldens/scenario.map is checked in, the PNGs have to be blessed and committed once before the
+/// comparison can pass, see the readme.
+/// Goldens are blessed from the cpu renderer, the gpu renderer is compared against the same images.
+/// Goldens are only comparable between machines with the same window size and DPI scale.
+/// Returns whether every scenario matched (or was blessed).
+pub async fn run(bless: bool) -> bool {
+    // base content only, so installed mods can't change the scenes
+    let mut content = ContentRegistry::base();
+    content.maps.insert(SCENARIO_MAP_ID.to_string(), MapDef {
+        id: SCENARIO_MAP_ID.to_string(),
+        file: SCENARIO_MAP_FILE.to_string(),
+    });
+    let content = Rc::new(content);
+    // the first frame can still have the window at its initial size
+    next_frame().await;
+
+    let mut all_passed = true;
+    for (scenario, gpu_raycasting) in SCENARIOS.iter().flat_map(|scenario| [(scenario, false), (scenario, true)]) {
+        if bless && gpu_raycasting {
+            continue;
+        }
+        let settings = Settings {
+            gpu_raycasting,
+            ..Settings::default()
+        };
+        let mut world = World::default(content.clone(), SCENARIO_MAP_ID, &settings).await;
+        (scenario.setup)(&mut world);
+        world.draw();
+        let actual = flip_rows(&get_screen_data());
+        next_frame().await;
+
+        let golden_path = format!("{}/{}.png", GOLDEN_DIRECTORY, scenario.name);
+        let name = if gpu_raycasting { format!("{}.gpu", scenario.name) } else { scenario.name.to_string() };
+        if bless {
+            export_upright(&actual, &golden_path);
+            println!("blessed {}", golden_path);
+            continue;
+        }
+        let golden = match fs::read(&golden_path) {
+            Ok(bytes) => Image::from_file_with_format(&bytes, None).map_err(|err| err.to_string()),
+            Err(err) => Err(format!("{}, run with --bless to create it", err)),
+        };
+        let golden = match golden {
+            Ok(golden) => golden,
+            Err(err) => {
+                println!("FAIL {}: could not load {}: {}", name, golden_path, err);
+                all_passed = false;
+                continue;
+            }
+        };
+        let region = compared_region(&world, scenario.minimap_only, &actual);
+        let max_differing = if gpu_raycasting { GPU_MAX_DIFFERING_PIXELS } else { MAX_DIFFERING_PIXELS };
+        match compare(&name, &actual, &golden, region, max_differing) {
+            Ok(()) => println!("ok   {}", name),
+            Err(err) => {
+                println!("FAIL {}: {}", name, err);
+                all_passed = false;
+                export_upright(&actual, &format!("{}/{}.actual.png", GOLDEN_DIRECTORY, name));
+            }
+        }
+    }
+    all_passed
+}
+
+/// the part of the screenshot that is compared, in image pixels
+fn compared_region(world: &World, minimap_only: bool, image: &Image) -> Rect {
+    let pixel_scale = (image.width as f32) / screen_width();
+    let screen = if minimap_only {
+        MinimapTransform::new(world.ui_scale, Vec2::ZERO).background
+    } else {
+        Rect::new(0.0, 0.0, screen_width(), screen_height())
+    };
+    Rect::new(
+        screen.x * pixel_scale,
+        screen.y * pixel_scale,
+        screen.w * pixel_scale,
+        screen.h * pixel_scale
+    )
+}
+
+/// the raycasting timing in the top left corner changes every frame and is never compared
+fn is_timing_text(x: usize, y: usize, pixel_scale: f32, ui_scale: f32) -> bool {
+    (x as f32) < 400.0 * ui_scale * pixel_scale && (y as f32) < 40.0 * ui_scale * pixel_scale
+}
+
+/// writes a diff image next to the golden when more than max_differing pixels are off by more than CHANNEL_TOLERANCE
+fn compare(
+    name: &str,
+    actual: &Image,
+    golden: &Image,
+    region: Rect,
+    max_differing: usize
+) -> Result<(), String> {
+    if actual.width != golden.width || actual.height != golden.height {
+        return Err(
+            format!(
+                "screenshot is {}x{}, golden is {}x{}",
+                actual.width,
+                actual.height,
+                golden.width,
+                golden.height
+            )
+        );
+    }
+    let pixel_scale = (actual.width as f32) / screen_width();
+    let ui_scale = screen_dpi_scale();
+    let width = actual.width as usize;
+    let mut diff = Image::gen_image_color(actual.width, actual.height, BLACK);
+    let mut differing = 0;
+    for (i, (actual_pixel, golden_pixel)) in actual.bytes
+        .chunks_exact(4)
+        .zip(golden.bytes.chunks_exact(4))
+        .enumerate() {
+        let (x, y) = (i % width, i / width);
+        if
+            !region.contains(Vec2::new(x as f32, y as f32)) ||
+            is_timing_text(x, y, pixel_scale, ui_scale)
+        {
+            continue;
+        }
+        let off = actual_pixel
+            .iter()
+            .zip(golden_pixel)
+            .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE);
+        let pixel = if off {
+            differing += 1;
+            [255, 0, 0, 255]
+        } else {
+            // dimmed copy of the screenshot so the differences can be located
+            [actual_pixel[0] / 4, actual_pixel[1] / 4, actual_pixel[2] / 4, 255]
+        };
+        diff.bytes[i * 4..i * 4 + 4].copy_from_slice(&pixel);
+    }
+    if differing <= max_differing {
+        return Ok(());
+    }
+    let diff_path = format!("{}/{}.diff.png", GOLDEN_DIRECTORY, name);
+    export_upright(&diff, &diff_path);
+    Err(format!("{} pixels differ, see {}", differing, diff_path))
+}
+
+/// get_screen_data returns the rows bottom up, goldens are stored top down
+fn flip_rows(image: &Image) -> Image {
+    let row_length = (image.width as usize) * 4;
+    let mut flipped = image.clone();
+    for (target, source) in flipped.bytes
+        .chunks_exact_mut(row_length)
+        .zip(image.bytes.chunks_exact(row_length).rev()) {
+        target.copy_from_slice(source);
+    }
+    flipped
+}
+
+/// Image::export_png flips the rows itself, it expects them bottom up like get_screen_data
+fn export_upright(image: &Image, path: &str) {
+    flip_rows(image).export_png(path);
+}
@@ -1,7 +1,7 @@
 use macroquad::{
     color::Color,
     prelude::ImageFormat,
-    texture::{Image, Texture2D},
+    texture::{FilterMode, Image, Texture2D},
 };
 
 pub fn is_white(color: Color) -> bool {
@@ -22,4 +22,66 @@ pub fn load_and_convert_texture(data: &[u8], format: ImageFormat) -> Texture2D {
     convert_white_to_transparent(&mut texture_data);
     texture.update(&texture_data);
     texture
-}
\ No newline at end of file
+}
+
+/// Reads the `_{cols}x{rows}` suffix of a spritesheet file name, e.g. `explosion_8x6.png` -> (8, 6)
+pub fn parse_spritesheet_dimensions(filename: &str) -> Option<(u16, u16)> {
+    let file = filename.rsplit(['/', '\\']).next()?;
+    let stem = file.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(file);
+    let (_, dimensions) = stem.rsplit_once('_')?;
+    let (cols, rows) = dimensions.split_once('x')?;
+    let cols = cols.parse::<u16>().ok()?;
+    let rows = rows.parse::<u16>().ok()?;
+    if cols == 0 || rows == 0 {
+        return None;
+    }
+    Some((cols, rows))
+}
+
+/// Box-filters every frame of a single row spritesheet down by `factor`, each frame only averages its own
+/// pixels so neighbouring frames don't bleed into each other. Returns the texture and its frame width.
+pub fn downsample_spritesheet(texture: &Texture2D, frame_width: f32, factor: u16) -> (Texture2D, f32) {
+    let image = texture.get_texture_data();
+    let factor = factor.max(1) as usize;
+    let source_frame_width = frame_width as usize;
+    let frames = image.width() / source_frame_width;
+    let lod_frame_width = source_frame_width.div_ceil(factor);
+    let lod_height = image.height().div_ceil(factor);
+    let mut lod = Image::gen_image_color(
+        (lod_frame_width * frames) as u16,
+        lod_height as u16,
+        Color::new(0.0, 0.0, 0.0, 0.0),
+    );
+    for frame in 0..frames {
+        for y in 0..lod_height {
+            for x in 0..lod_frame_width {
+                let start_x = frame * source_frame_width + x * factor;
+                let end_x = (start_x + factor).min((frame + 1) * source_frame_width);
+                let end_y = (y * factor + factor).min(image.height());
+                let (mut r, mut g, mut b, mut a) = (0.0, 0.0, 0.0, 0.0);
+                let mut count = 0.0;
+                for source_y in y * factor..end_y {
+                    for source_x in start_x..end_x {
+                        let pixel = image.get_pixel(source_x as u32, source_y as u32);
+                        // weighted by alpha so transparent pixels don't darken the edges
+                        r += pixel.r * pixel.a;
+                        g += pixel.g * pixel.a;
+                        b += pixel.b * pixel.a;
+                        a += pixel.a;
+                        count += 1.0;
+                    }
+                }
+                if a > 0.0 {
+                    lod.set_pixel(
+                        (frame * lod_frame_width + x) as u32,
+                        y as u32,
+                        Color::new(r / a, g / a, b / a, a / count),
+                    );
+                }
+            }
+        }
+    }
+    let lod_texture = Texture2D::from_image(&lod);
+    lod_texture.set_filter(FilterMode::Nearest);
+    (lod_texture, lod_frame_width as f32)
+}
@@ -0,0 +1,340 @@
+use std::fs;
+use macroquad::input::{ KeyCode, MouseButton };
+use crate::{ InputButton, KeyBindings };
+
+const KEYS_PATH: &str = "keys.cfg";
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Space", KeyCode::Space),
+    ("Apostrophe", KeyCode::Apostrophe),
+    ("Comma", KeyCode::Comma),
+    ("Minus", KeyCode::Minus),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+    ("Key0", KeyCode::Key0),
+    ("Key1", KeyCode::Key1),
+    ("Key2", KeyCode::Key2),
+    ("Key3", KeyCode::Key3),
+    ("Key4", KeyCode::Key4),
+    ("Key5", KeyCode::Key5),
+    ("Key6", KeyCode::Key6),
+    ("Key7", KeyCode::Key7),
+    ("Key8", KeyCode::Key8),
+    ("Key9", KeyCode::Key9),
+    ("Semicolon", KeyCode::Semicolon),
+    ("Equal", KeyCode::Equal),
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("LeftBracket", KeyCode::LeftBracket),
+    ("Backslash", KeyCode::Backslash),
+    ("RightBracket", KeyCode::RightBracket),
+    ("GraveAccent", KeyCode::GraveAccent),
+    ("Escape", KeyCode::Escape),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Right", KeyCode::Right),
+    ("Left", KeyCode::Left),
+    ("Down", KeyCode::Down),
+    ("Up", KeyCode::Up),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("CapsLock", KeyCode::CapsLock),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Kp0", KeyCode::Kp0),
+    ("Kp1", KeyCode::Kp1),
+    ("Kp2", KeyCode::Kp2),
+    ("Kp3", KeyCode::Kp3),
+    ("Kp4", KeyCode::Kp4),
+    ("Kp5", KeyCode::Kp5),
+    ("Kp6", KeyCode::Kp6),
+    ("Kp7", KeyCode::Kp7),
+    ("Kp8", KeyCode::Kp8),
+    ("Kp9", KeyCode::Kp9),
+    ("KpDecimal", KeyCode::KpDecimal),
+    ("KpDivide", KeyCode::KpDivide),
+    ("KpMultiply", KeyCode::KpMultiply),
+    ("KpSubtract", KeyCode::KpSubtract),
+    ("KpAdd", KeyCode::KpAdd),
+    ("KpEnter", KeyCode::KpEnter),
+    ("LeftShift", KeyCode::LeftShift),
+    ("LeftControl", KeyCode::LeftControl),
+    ("LeftAlt", KeyCode::LeftAlt),
+    ("RightShift", KeyCode::RightShift),
+    ("RightControl", KeyCode::RightControl),
+    ("RightAlt", KeyCode::RightAlt),
+];
+
+/// Applies keys.cfg to `bindings` if there is one, the returned lines describe what was skipped.
+/// Every line is `action = button, button` and `#` starts a comment. Actions are named like the fields of
+/// KeyBindings, the weapon slots are weapon_1 to weapon_9. Buttons are named like the KeyCode variants of
+/// macroquad, `W`, `Key1`, `LeftShift`, `Up`, or MouseLeft, MouseRight and MouseMiddle. A listed action only
+/// answers to the buttons given for it, the others keep their defaults.
+pub fn load(bindings: &mut KeyBindings) -> Vec<String> {
+    let mut report = Vec::new();
+    // no file keeps the defaults, that is the usual case
+    if let Ok(text) = fs::read_to_string(KEYS_PATH) {
+        parse(&text, bindings, &mut report);
+    }
+    report
+}
+
+fn parse(text: &str, bindings: &mut KeyBindings, report: &mut Vec<String>) {
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((action, buttons)) = line.split_once('=') else {
+            report.push(format!("{} line {}: expected `action = button`", KEYS_PATH, index + 1));
+            continue;
+        };
+        let action = action.trim();
+        let mut parsed = Vec::new();
+        for name in buttons.split(',').map(str::trim) {
+            match button(name) {
+                Some(button) => parsed.push(button),
+                None => report.push(format!("{} line {}: unknown button '{}'", KEYS_PATH, index + 1, name)),
+            }
+        }
+        if parsed.is_empty() {
+            // an action nothing triggers would lock the player out of it
+            report.push(
+                format!("{} line {}: no buttons left for {}, it keeps its default", KEYS_PATH, index + 1, action)
+            );
+            continue;
+        }
+        match action_buttons(bindings, action) {
+            Some(buttons) => {
+                *buttons = parsed;
+            }
+            None => report.push(format!("{} line {}: unknown action '{}'", KEYS_PATH, index + 1, action)),
+        }
+    }
+    resolve_conflicts(bindings, report);
+}
+
+/// pause answers to a press and the weapon wheel to holding, so a button bound to both only pauses
+fn resolve_conflicts(bindings: &mut KeyBindings, report: &mut Vec<String>) {
+    let pause = bindings.pause.clone();
+    bindings.weapon_wheel.retain(|button| {
+        let shared = pause.contains(button);
+        if shared {
+            report.push(
+                format!("{}: {} is bound to pause and weapon_wheel, it only pauses", KEYS_PATH, display_name(*button))
+            );
+        }
+        !shared
+    });
+}
+
+fn action_buttons<'a>(bindings: &'a mut KeyBindings, action: &str) -> Option<&'a mut Vec<InputButton>> {
+    // weapon_wheel isn't a slot
+    if let Some(slot) = action.strip_prefix("weapon_").and_then(|slot| slot.parse::<usize>().ok()) {
+        return bindings.weapon_slots.get_mut(slot.checked_sub(1)?);
+    }
+    let buttons = match action {
+        "forward" => &mut bindings.forward,
+        "back" => &mut bindings.back,
+        "strafe_left" => &mut bindings.strafe_left,
+        "strafe_right" => &mut bindings.strafe_right,
+        "turn_left" => &mut bindings.turn_left,
+        "turn_right" => &mut bindings.turn_right,
+        "quick_turn" => &mut bindings.quick_turn,
+        "sprint" => &mut bindings.sprint,
+        "walk_toggle" => &mut bindings.walk_toggle,
+        "crouch" => &mut bindings.crouch,
+        "map_overview" => &mut bindings.map_overview,
+        "pause" => &mut bindings.pause,
+        "cycle_hud_theme" => &mut bindings.cycle_hud_theme,
+        "cycle_fire_mode" => &mut bindings.cycle_fire_mode,
+        "toggle_renderer" => &mut bindings.toggle_renderer,
+        "reload" => &mut bindings.reload,
+        "shoot" => &mut bindings.shoot,
+        "interact" => &mut bindings.interact,
+        "weapon_wheel" => &mut bindings.weapon_wheel,
+        _ => {
+            return None;
+        }
+    };
+    Some(buttons)
+}
+
+/// every button of an action the way the screen shows it, e.g. `Space / LMB`
+pub fn describe(buttons: &[InputButton]) -> String {
+    buttons
+        .iter()
+        .map(|button| display_name(*button))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// the name keys.cfg uses, shortened for the digits, Escape and the mouse
+fn display_name(button: InputButton) -> String {
+    let key = match button {
+        InputButton::Mouse(MouseButton::Left) => {
+            return "LMB".to_string();
+        }
+        InputButton::Mouse(MouseButton::Right) => {
+            return "RMB".to_string();
+        }
+        InputButton::Mouse(MouseButton::Middle) => {
+            return "MMB".to_string();
+        }
+        InputButton::Mouse(_) => {
+            return "Mouse".to_string();
+        }
+        InputButton::Key(key) => key,
+    };
+    let name = KEY_NAMES.iter()
+        .find(|(_, named_key)| *named_key == key)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("{:?}", key));
+    match name.as_str() {
+        "Escape" => "Esc".to_string(),
+        _ => name.strip_prefix("Key").map(str::to_string).unwrap_or(name),
+    }
+}
+
+fn button(name: &str) -> Option<InputButton> {
+    match name {
+        "MouseLeft" => Some(InputButton::Mouse(MouseButton::Left)),
+        "MouseRight" => Some(InputButton::Mouse(MouseButton::Right)),
+        "MouseMiddle" => Some(InputButton::Mouse(MouseButton::Middle)),
+        _ =>
+            KEY_NAMES.iter()
+                .find(|(key_name, _)| *key_name == name)
+                .map(|(_, key)| InputButton::Key(*key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(text: &str) -> (KeyBindings, Vec<String>) {
+        let mut bindings = KeyBindings::default();
+        let mut report = Vec::new();
+        parse(text, &mut bindings, &mut report);
+        (bindings, report)
+    }
+
+    #[test]
+    fn listed_action_answers_only_to_its_new_buttons() {
+        let (bindings, report) = parsed("interact = F\nshoot = MouseRight, Enter");
+        assert!(report.is_empty());
+        assert_eq!(bindings.interact, vec![InputButton::Key(KeyCode::F)]);
+        assert_eq!(bindings.shoot, vec![InputButton::Mouse(MouseButton::Right), InputButton::Key(KeyCode::Enter)]);
+        // not listed, so still the default
+        assert_eq!(bindings.reload, vec![InputButton::Key(KeyCode::R)]);
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let (bindings, report) = parsed("# my keys\n\n   \nsprint = RightShift # the other one\n");
+        assert!(report.is_empty());
+        assert_eq!(bindings.sprint, vec![InputButton::Key(KeyCode::RightShift)]);
+    }
+
+    #[test]
+    fn weapon_slots_count_from_one() {
+        let (bindings, report) = parsed("weapon_1 = Kp1\nweapon_9 = Kp9");
+        assert!(report.is_empty());
+        assert_eq!(bindings.weapon_slots[0], vec![InputButton::Key(KeyCode::Kp1)]);
+        assert_eq!(bindings.weapon_slots[8], vec![InputButton::Key(KeyCode::Kp9)]);
+        let (_, report) = parsed("weapon_0 = Kp0\nweapon_10 = Kp0\nweapon_x = Kp0");
+        assert_eq!(report.len(), 3);
+        assert!(report.iter().all(|line| line.contains("unknown action")));
+    }
+
+    #[test]
+    fn unknown_button_is_reported_and_the_others_apply() {
+        let (bindings, report) = parsed("reload = R, Banana");
+        assert_eq!(report, vec![format!("{} line 1: unknown button 'Banana'", KEYS_PATH)]);
+        assert_eq!(bindings.reload, vec![InputButton::Key(KeyCode::R)]);
+        let (bindings, _) = parsed("reload = Banana, T");
+        assert_eq!(bindings.reload, vec![InputButton::Key(KeyCode::T)]);
+    }
+
+    #[test]
+    fn action_without_a_known_button_keeps_its_default() {
+        let (bindings, report) = parsed("forward = Banana\nback =");
+        assert_eq!(bindings.forward, vec![InputButton::Key(KeyCode::W)]);
+        assert_eq!(bindings.back, vec![InputButton::Key(KeyCode::S)]);
+        assert_eq!(report, vec![
+            format!("{} line 1: unknown button 'Banana'", KEYS_PATH),
+            format!("{} line 1: no buttons left for forward, it keeps its default", KEYS_PATH),
+            format!("{} line 2: unknown button ''", KEYS_PATH),
+            format!("{} line 2: no buttons left for back, it keeps its default", KEYS_PATH),
+        ]);
+    }
+
+    #[test]
+    fn malformed_lines_and_unknown_actions_are_reported_with_their_line() {
+        let (bindings, report) = parsed("interact F\n\njump = Space");
+        assert_eq!(report, vec![
+            format!("{} line 1: expected `action = button`", KEYS_PATH),
+            format!("{} line 3: unknown action 'jump'", KEYS_PATH),
+        ]);
+        assert_eq!(bindings.interact, vec![InputButton::Key(KeyCode::E)]);
+    }
+
+    #[test]
+    fn button_bound_to_pause_and_the_weapon_wheel_only_pauses() {
+        let (bindings, report) = parsed("pause = Tab");
+        assert_eq!(bindings.pause, vec![InputButton::Key(KeyCode::Tab)]);
+        assert_eq!(bindings.weapon_wheel, vec![InputButton::Mouse(MouseButton::Middle)]);
+        assert_eq!(report, vec![format!("{}: Tab is bound to pause and weapon_wheel, it only pauses", KEYS_PATH)]);
+        let (bindings, report) = parsed("weapon_wheel = Escape, MouseMiddle");
+        assert_eq!(bindings.weapon_wheel, vec![InputButton::Mouse(MouseButton::Middle)]);
+        assert_eq!(report.len(), 1);
+    }
+
+    #[test]
+    fn buttons_are_shown_with_short_names() {
+        let bindings = KeyBindings::default();
+        assert_eq!(describe(&bindings.shoot), "Space / LMB");
+        assert_eq!(describe(&bindings.weapon_slots[2]), "3");
+        assert_eq!(describe(&bindings.pause), "Esc");
+        assert_eq!(describe(&bindings.sprint), "LeftShift");
+    }
+}
@@ -1,26 +1,129 @@
 use core::panic;
-use std::{ collections::{ HashMap, VecDeque }, f32::consts::PI, process::exit, time::Duration };
+use std::{
+    collections::{ HashMap, VecDeque },
+    f32::consts::{ FRAC_PI_4, PI },
+    fs,
+    process::exit,
+    rc::Rc,
+    time::{ Duration, SystemTime },
+};
 use miniquad::{ BlendFactor, BlendState, BlendValue, Equation };
-use ::rand::random;
+use ::rand::{ random, Rng };
 use config::config::{
+    ADRENALINE_DURATION,
+    ADRENALINE_SPEED_BONUS,
+    PLAYER_SPEED,
+    WALK_SPEED_FACTOR,
+    CROUCH_SPEED_FACTOR,
+    MOVE_ACCELERATION,
+    MOVE_DECELERATION,
+    BOBBING_MIN_SPEED,
+    STAMINA_DRAIN,
+    STAMINA_REGEN,
     AMOUNT_OF_RAYS,
+    CORPSE_FADE_TIME,
+    CORPSE_LIFETIME,
+    DEFAULT_MAP_ID,
+    STRESS_ENEMIES,
+    STRESS_SEED,
+    ENEMY_LOD_HALF_DISTANCE,
+    ENEMY_LOD_QUARTER_DISTANCE,
+    ENEMY_TURN_SPEED,
     ENEMY_VIEW_DISTANCE,
-    HALF_PLAYER_FOV,
+    ENEMY_GROWL_INTERVAL,
+    SOUND_FALLOFF_DISTANCE,
+    ENEMY_SIGHT_MEMORY,
+    ENEMY_ATTACK_WINDUP,
+    ENEMY_PATH_INTERVAL,
     HALF_SCREEN_HEIGHT,
+    GRID_SNAP_SPEED,
+    STATE_HASH_QUANTUM,
+    SIMULATION_SEED,
+    REPULSION_DURATION,
+    REPULSION_RADIUS,
+    REPULSION_STRENGTH,
     HALF_SCREEN_WIDTH,
+    HEALTH_REGEN_INTERVAL,
+    HEALTH_REGEN_MAX,
+    BURST_SHOT_INTERVAL,
+    PELLET_JITTER,
+    AUTO_FIRE_BLOOM_PER_SHOT,
+    AUTO_FIRE_BLOOM_DECAY,
+    AUTO_FIRE_MAX_BLOOM,
+    COMPASS_SPAN,
+    COMPASS_WIDTH,
+    HIT_STOP_DURATION,
+    HIT_STOP_KILL_DISTANCE,
+    HIT_STOP_TIME_SCALE,
+    WEAPON_WHEEL_TIME_SCALE,
+    WEAPON_WHEEL_RADIUS,
+    WEAPON_WHEEL_DEADZONE,
+    MAP_RELOAD_POLL_INTERVAL,
+    MINIMAP_PARALLAX_STRENGTH,
+    MINIMAP_TILE_HEIGHT,
+    MINIMAP_TILE_WIDTH,
+    MOUSE_LOOK_MAX_FRAME_TIME,
+    MOUSE_SENSITIVITY,
+    MAX_PITCH,
+    TITLE_CARD_TIME,
     MAP_X_OFFSET,
+    MAX_ENEMIES_PER_LEVEL,
+    MAX_SUBSTEPS,
+    MAX_SUBSTEP_LENGTH,
+    OUT_OF_COMBAT_TIME,
     PHYSICS_FRAME_TIME,
     PLAYER_FOV,
+    HALF_PLAYER_FOV,
+    PLAYER_HIT_COOLDOWN,
+    QUICK_TURN_DURATION,
     RAY_VERTICAL_STRIPE_WIDTH,
     SCREEN_HEIGHT,
     SCREEN_WIDTH,
-    TILE_SIZE_X_PIXEL,
-    TILE_SIZE_Y_PIXEL,
-    WORLD_HEIGHT,
-    WORLD_WIDTH,
+    SHADE_DISTANCE,
+    SOLID_ENEMIES,
+    SUBSTEP_THRESHOLD,
+    TELEPORT_LOCKOUT,
+    ELEVATOR_RIDE_TIME,
+    DECORATION_REGION_SIZE,
+    DECORATION_SWAY_SPEED,
+    AMMO_PICKUP_AMOUNT,
+    HEALTH_PICKUP_AMOUNT,
+    PLAYER_MAX_HEALTH,
+    PICKUP_SCALE,
+    PICKUP_LABEL_DISTANCE,
+    PROJECTILE_RADIUS,
+    NEAR_MISS_DISTANCE,
+    NEAR_MISS_NUDGE,
+    KNOCKBACK_SPEED,
+    WEAPON_LOWER_TIME,
+    WEAPON_RAISE_TIME,
+    WATER_SPEED_MULTIPLIER,
+    WATER_VOLUME_MULTIPLIER,
+    TURN_STEP_DURATION,
+};
+use content::{ ContentRegistry, EnemyKindDef, LootEntryDef, WeaponDef };
+use image_utils::{ downsample_spritesheet, load_and_convert_texture, parse_spritesheet_dimensions };
+use map::{
+    validate::{ Diagnostic, DoorWalls },
+    DecorationPlacement,
+    FloorGrid,
+    FloorType,
+    Grid,
+    Note,
+    ReverbProfile,
+    ReverbZone,
+    ScriptedSequence,
+    SequenceAction,
+    SequenceTrigger,
+    TeleporterPair,
 };
-use image_utils::load_and_convert_texture;
 use once_cell::sync::Lazy;
+use serde::{ de::IntoDeserializer, Deserialize };
+use settings::Settings;
+use records::Records;
+use checkpoints::HashCheckpoints;
+use theme::{ HudTheme, MarkerShape };
+use font::TextStyle;
 use macroquad::{
     audio::{ load_sound, play_sound, PlaySoundParams, Sound },
     prelude::*,
@@ -32,19 +135,80 @@ use shaders::shaders::{
     ENEMY_DEFAULT_FRAGMENT_SHADER,
     ENEMY_DEFAULT_VERTEX_SHADER,
     FLOOR_FRAGMENT_SHADER,
+    RAYCAST_FRAGMENT_SHADER,
 };
 pub mod config;
 pub mod shaders;
 pub mod image_utils;
+pub mod map;
+mod content;
+pub mod angles;
+pub mod settings;
+pub mod theme;
+pub mod font;
+mod crash;
+mod checkpoints;
+mod keys;
+mod records;
+#[cfg(feature = "golden-tests")]
+mod golden;
 #[derive(Hash, Eq, PartialEq, Copy, Clone)]
 enum Textures {
     Stone,
+    Ceiling,
+    Door,
     Weapon,
     SkeletonFrontSpriteSheet,
     SkeletonBackSpriteSheet,
     SkeletonSideSpriteSheet,
     BloodAnimationSpriteSheet,
     ExplosionAnimationSpriteSheet,
+    AmmoPickup,
+    HealthPickup,
+}
+
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug, Deserialize)]
+enum Sounds {
+    PistolShoot,
+    Reload,
+    ShotgunShoot,
+    ShotgunReload,
+    DryFire,
+    Teleport,
+    ElevatorHum,
+    Sting,
+    Pickup,
+    Grunt,
+    Growl,
+    Explosion,
+    WhizzLeft, // the panning is in the files, macroquad can't pan
+    WhizzRight,
+}
+impl Sounds {
+    /// by the names the content files use, which are the variant names
+    fn from_name(name: &str) -> Option<Self> {
+        let name: serde::de::value::StrDeserializer<serde::de::value::Error> = name.into_deserializer();
+        Sounds::deserialize(name).ok()
+    }
+}
+
+impl Textures {
+    /// spritesheets encode their layout in the file name, see parse_spritesheet_dimensions
+    fn file_name(&self) -> &'static str {
+        match self {
+            Textures::Stone => "stone.png",
+            Textures::Ceiling => "ceiling.png",
+            Textures::Door => "door.png",
+            Textures::Weapon => "weapon.png",
+            Textures::SkeletonFrontSpriteSheet => "SkeletonFrontSpriteSheet.png",
+            Textures::SkeletonBackSpriteSheet => "SkeletonBackSpriteSheet.png",
+            Textures::SkeletonSideSpriteSheet => "SkeletonSideSpriteSheet.png",
+            Textures::BloodAnimationSpriteSheet => "blood_6x4.png",
+            Textures::ExplosionAnimationSpriteSheet => "explosion_8x6.png",
+            Textures::AmmoPickup => "ammo.png",
+            Textures::HealthPickup => "health.png",
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -56,6 +220,54 @@ pub struct WallHandle(pub u16);
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct DoorHandle(pub u16);
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PickupHandle(pub u16);
+
+/// stable identity of an entity placed by the map, unlike a handle it survives swap_remove and reloads,
+/// ordinal counts the spawns on the same tile so a later respawn there gets its own id
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SpawnId {
+    pub x: u16,
+    pub y: u16,
+    pub ordinal: u16,
+}
+
+/// the SpawnId of every handle index and the reverse lookup, kept in sync with the SoA vectors it sits next to
+struct SpawnIndex {
+    ids: Vec<SpawnId>,
+    indices: HashMap<SpawnId, u16>,
+    spawns_per_tile: HashMap<(u16, u16), u16>,
+}
+impl SpawnIndex {
+    fn new() -> Self {
+        SpawnIndex {
+            ids: Vec::new(),
+            indices: HashMap::new(),
+            spawns_per_tile: HashMap::new(),
+        }
+    }
+    /// assigns the next SpawnId on the tile, spawns that are already gone still count
+    fn push(&mut self, x: usize, y: usize) -> SpawnId {
+        let spawns = self.spawns_per_tile.entry((x as u16, y as u16)).or_insert(0);
+        let id = SpawnId { x: x as u16, y: y as u16, ordinal: *spawns };
+        *spawns += 1;
+        self.indices.insert(id, self.ids.len() as u16);
+        self.ids.push(id);
+        id
+    }
+    /// mirrors Vec::swap_remove, the last entity takes over the removed index
+    fn swap_remove(&mut self, index: usize) {
+        let removed = self.ids.swap_remove(index);
+        self.indices.remove(&removed);
+        if let Some(moved) = self.ids.get(index) {
+            self.indices.insert(*moved, index as u16);
+        }
+    }
+    fn index_of(&self, id: SpawnId) -> Option<u16> {
+        self.indices.get(&id).copied()
+    }
+}
+
 static TEXTURE_TYPE_TO_TEXTURE2D: Lazy<HashMap<Textures, Texture2D>> = Lazy::new(|| {
     let mut map = HashMap::new();
     map.insert(
@@ -65,6 +277,17 @@ static TEXTURE_TYPE_TO_TEXTURE2D: Lazy<HashMap<Textures, Texture2D>> = Lazy::new
             Some(ImageFormat::Png)
         )
     );
+    map.insert(
+        Textures::Ceiling,
+        Texture2D::from_file_with_format(
+            include_bytes!("../textures/ceiling.png"),
+            Some(ImageFormat::Png)
+        )
+    );
+    map.insert(
+        Textures::Door,
+        load_and_convert_texture(include_bytes!("../textures/door.png"), ImageFormat::Png)
+    );
     map.insert(
         Textures::Weapon,
         load_and_convert_texture(include_bytes!("../textures/weapon.png"), ImageFormat::Png)
@@ -92,12 +315,56 @@ static TEXTURE_TYPE_TO_TEXTURE2D: Lazy<HashMap<Textures, Texture2D>> = Lazy::new
     );
     map.insert(
         Textures::BloodAnimationSpriteSheet,
-        load_and_convert_texture(include_bytes!("../textures/blood.png"), ImageFormat::Png)
+        load_and_convert_texture(include_bytes!("../textures/blood_6x4.png"), ImageFormat::Png)
     );
     map.insert(
         Textures::ExplosionAnimationSpriteSheet,
-        load_and_convert_texture(include_bytes!("../textures/explosion.png"), ImageFormat::Png)
+        load_and_convert_texture(include_bytes!("../textures/explosion_8x6.png"), ImageFormat::Png)
+    );
+    map.insert(
+        Textures::AmmoPickup,
+        load_and_convert_texture(include_bytes!("../textures/ammo.png"), ImageFormat::Png)
     );
+    map.insert(
+        Textures::HealthPickup,
+        load_and_convert_texture(include_bytes!("../textures/health.png"), ImageFormat::Png)
+    );
+    map
+});
+/// the spritesheet of an animation with its size, AnimationState keeps the size so updating an animation
+/// never asks the gpu for it
+#[cfg(not(test))]
+fn sprite_sheet(texture_type: Textures) -> (Texture2D, Vec2) {
+    let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&texture_type).expect("Failed to load spritesheet");
+    (texture.clone(), texture.size())
+}
+/// tests run without a window, so the texture is a placeholder that is never drawn and the size comes from the file
+#[cfg(test)]
+fn sprite_sheet(texture_type: Textures) -> (Texture2D, Vec2) {
+    let bytes = fs::read(format!("textures/{}", texture_type.file_name())).expect("Failed to read spritesheet");
+    let image = Image::from_file_with_format(&bytes, Some(ImageFormat::Png)).expect("Failed to decode spritesheet");
+    (
+        Texture2D::from_miniquad_texture(miniquad::TextureId::from_raw_id(miniquad::RawId::OpenGl(0))),
+        Vec2::new(image.width() as f32, image.height() as f32),
+    )
+}
+/// half and quarter resolution copies of the enemy spritesheets with their frame widths, for far away enemies
+static ENEMY_SPRITE_LODS: Lazy<HashMap<Textures, [(Texture2D, f32); 2]>> = Lazy::new(|| {
+    let mut map = HashMap::new();
+    for texture_type in [
+        Textures::SkeletonFrontSpriteSheet,
+        Textures::SkeletonSideSpriteSheet,
+        Textures::SkeletonBackSpriteSheet,
+    ] {
+        let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&texture_type).expect(
+            "Failed to load enemy spritesheet"
+        );
+        let frame_width = texture.width() / 3.0; // same frame layout as default_skeleton
+        map.insert(texture_type, [
+            downsample_spritesheet(texture, frame_width, 2),
+            downsample_spritesheet(texture, frame_width, 4),
+        ]);
+    }
     map
 });
 
@@ -120,10 +387,23 @@ enum EntityType {
     None,
     Enemy(EnemyHandle),
     Door(DoorHandle),
+    Pickup(PickupHandle),
 }
+#[derive(Debug)]
 enum WorldEventType {
     PlayerHitEnemy,
     EnemyHitPlayer,
+    EnemyShotPlayer {
+        damage: u8,
+    },
+    ProjectileHitPlayer {
+        damage: u8,
+        direction: Vec2, // of the flight, the player is pushed along it
+    },
+    ProjectileHitEnemy {
+        damage: f32, // less than the weapon's towards the edge of a blast
+        damage_type: DamageType,
+    },
 }
 #[derive(PartialEq, Clone, Copy, Eq, Hash)]
 struct Tile {
@@ -143,18 +423,44 @@ struct WorldEventHandleBased { // to avoid multiple tile lookups and inaccuracie
     event_type: WorldEventType,
 
     other_involved: u16,
+    penetration_depth: u8, // how many enemies a shot passed through before this hit
 }
 impl WorldEventHandleBased {
     fn enemy_hit_player(enemy_handle: EnemyHandle) -> Self {
         WorldEventHandleBased {
             event_type: WorldEventType::EnemyHitPlayer,
             other_involved: enemy_handle.0,
+            penetration_depth: 0,
         }
     }
-    fn player_hit_enemy(enemy_handle: EnemyHandle) -> Self {
+    fn enemy_shot_player(enemy_handle: EnemyHandle, damage: u8) -> Self {
+        WorldEventHandleBased {
+            event_type: WorldEventType::EnemyShotPlayer { damage },
+            other_involved: enemy_handle.0,
+            penetration_depth: 0,
+        }
+    }
+    /// the shooter may be gone by the time it lands, so there is no handle
+    fn projectile_hit_player(damage: u8, direction: Vec2) -> Self {
+        WorldEventHandleBased {
+            event_type: WorldEventType::ProjectileHitPlayer { damage, direction },
+            other_involved: 0,
+            penetration_depth: 0,
+        }
+    }
+    /// carries its own damage, the player may have switched weapons while it flew
+    fn projectile_hit_enemy(enemy_handle: EnemyHandle, damage: f32, damage_type: DamageType) -> Self {
+        WorldEventHandleBased {
+            event_type: WorldEventType::ProjectileHitEnemy { damage, damage_type },
+            other_involved: enemy_handle.0,
+            penetration_depth: 0,
+        }
+    }
+    fn player_hit_enemy(enemy_handle: EnemyHandle, penetration_depth: u8) -> Self {
         WorldEventHandleBased {
             event_type: WorldEventType::PlayerHitEnemy,
             other_involved: enemy_handle.0,
+            penetration_depth,
         }
     }
 }
@@ -293,6 +599,7 @@ struct AnimationState {
     spritesheet_offset_per_frame: Vec2,
     animation_type: AnimationType,
     sprite_sheet: Texture2D,
+    sheet_size: Vec2,
     color: Color,
     physics_frames_per_update: f32,
     elapsed_time: f32,
@@ -301,16 +608,15 @@ struct AnimationState {
 }
 impl AnimationState {
     fn default_weapon() -> Self {
-        let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::Weapon).expect(
-            "Failed to load Weapon texture"
-        );
+        let (texture, sheet_size) = sprite_sheet(Textures::Weapon);
         const FRAMES_AMOUNT: u16 = 1;
-        let single_sprite_dimension_x = texture.width() / (FRAMES_AMOUNT as f32);
+        let single_sprite_dimension_x = sheet_size.x / (FRAMES_AMOUNT as f32);
         AnimationState {
             frame: 0,
             frames_amount: FRAMES_AMOUNT,
             spritesheet_offset_per_frame: Vec2::new(single_sprite_dimension_x, 0.0),
-            sprite_sheet: texture.clone(),
+            sprite_sheet: texture,
+            sheet_size,
             color: WHITE,
             animation_type: AnimationType::None,
             physics_frames_per_update: 0.0,
@@ -320,16 +626,15 @@ impl AnimationState {
         }
     }
     fn default_skeleton() -> Self {
-        let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::SkeletonFrontSpriteSheet).expect(
-            "Failed to load Skeleton Front Spritesheet"
-        );
+        let (texture, sheet_size) = sprite_sheet(Textures::SkeletonFrontSpriteSheet);
         const FRAMES_AMOUNT: u16 = 3;
-        let single_sprite_dimension_x = texture.width() / (FRAMES_AMOUNT as f32);
+        let single_sprite_dimension_x = sheet_size.x / (FRAMES_AMOUNT as f32);
         AnimationState {
             frame: 0,
             frames_amount: FRAMES_AMOUNT,
             spritesheet_offset_per_frame: Vec2::new(single_sprite_dimension_x, 0.0),
-            sprite_sheet: texture.clone(),
+            sprite_sheet: texture,
+            sheet_size,
             color: WHITE,
             animation_type: AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront),
             physics_frames_per_update: 20.0 * PHYSICS_FRAME_TIME,
@@ -338,54 +643,49 @@ impl AnimationState {
             callback_event: AnimationCallbackEvent::none(),
         }
     }
-    fn default_explosion() -> Self {
-        let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(
-            &Textures::ExplosionAnimationSpriteSheet
-        ).expect("Failed to load Explosion Animation");
-        const FRAMES_PER_ROW: u16 = 8;
-        const ROWS: u16 = 6;
-        let single_sprite_dimension_x = texture.width() / (FRAMES_PER_ROW as f32);
-        let single_sprite_dimension_y = texture.height() / (ROWS as f32);
+    fn from_texture_named(
+        texture_type: Textures,
+        animation_type: AnimationType,
+        physics_frames_per_update: f32,
+        callback_event: AnimationCallbackEvent
+    ) -> Self {
+        let (texture, sheet_size) = sprite_sheet(texture_type);
+        let (frames_per_row, rows) = parse_spritesheet_dimensions(texture_type.file_name()).unwrap_or((1, 1));
+        let single_sprite_dimension_x = sheet_size.x / (frames_per_row as f32);
+        // a single row is addressed by x offset only, see get_source_rect
+        let single_sprite_dimension_y = if rows > 1 { sheet_size.y / (rows as f32) } else { 0.0 };
         AnimationState {
             frame: 0,
-            frames_amount: FRAMES_PER_ROW * ROWS,
+            frames_amount: frames_per_row * rows,
             spritesheet_offset_per_frame: Vec2::new(
                 single_sprite_dimension_x,
                 single_sprite_dimension_y
             ),
-            sprite_sheet: texture.clone(),
+            sprite_sheet: texture,
+            sheet_size,
             color: WHITE,
-            animation_type: AnimationType::GeneralAnimation(GeneralAnimation::Explosion),
-            physics_frames_per_update: 0.25 * PHYSICS_FRAME_TIME,
+            animation_type,
+            physics_frames_per_update,
             elapsed_time: 0.0,
             flip_x: false,
-            callback_event: AnimationCallbackEvent::remove_on_finish(),
+            callback_event,
         }
     }
+    fn default_explosion() -> Self {
+        AnimationState::from_texture_named(
+            Textures::ExplosionAnimationSpriteSheet,
+            AnimationType::GeneralAnimation(GeneralAnimation::Explosion),
+            0.25 * PHYSICS_FRAME_TIME,
+            AnimationCallbackEvent::remove_on_finish()
+        )
+    }
     fn default_blood_particles() -> Self {
-        let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::BloodAnimationSpriteSheet).expect(
-            "Failed to load Explosion Animation"
-        );
-        const FRAMES_PER_ROW: u16 = 6;
-        const ROWS: u16 = 4;
-        let single_sprite_dimension_x = texture.width() / (FRAMES_PER_ROW as f32);
-        let single_sprite_dimension_y = texture.height() / (ROWS as f32);
-
-        AnimationState {
-            frame: 0,
-            frames_amount: FRAMES_PER_ROW * ROWS,
-            spritesheet_offset_per_frame: Vec2::new(
-                single_sprite_dimension_x,
-                single_sprite_dimension_y
-            ),
-            sprite_sheet: texture.clone(),
-            color: WHITE,
-            animation_type: AnimationType::GeneralAnimation(GeneralAnimation::Blood),
-            physics_frames_per_update: 0.5 * PHYSICS_FRAME_TIME,
-            elapsed_time: 0.0,
-            flip_x: false,
-            callback_event: AnimationCallbackEvent::remove_on_finish(),
-        }
+        AnimationState::from_texture_named(
+            Textures::BloodAnimationSpriteSheet,
+            AnimationType::GeneralAnimation(GeneralAnimation::Blood),
+            0.5 * PHYSICS_FRAME_TIME,
+            AnimationCallbackEvent::remove_on_finish()
+        )
     }
 
     fn set_physics_frames_per_update(&mut self, frames: f32) {
@@ -416,12 +716,14 @@ impl AnimationState {
             }
         }
     }
+    /// the whole cell of the current frame, margins included; they were white in the file and turned transparent
+    /// on load, the enemy shader discards them
     fn get_source_rect(&self) -> Rect {
         let has_rows = self.spritesheet_offset_per_frame.y > 0.0;
         if has_rows {
             let frame_offset = (self.frame as f32) * self.spritesheet_offset_per_frame.x;
-            let x_idx = frame_offset % self.sprite_sheet.width();
-            let y_idx = (frame_offset / self.sprite_sheet.width()).floor();
+            let x_idx = frame_offset % self.sheet_size.x;
+            let y_idx = (frame_offset / self.sheet_size.x).floor();
             Rect {
                 x: x_idx,
                 y: y_idx * self.spritesheet_offset_per_frame.y,
@@ -434,7 +736,7 @@ impl AnimationState {
                 x: x_idx,
                 y: 0.0,
                 w: self.spritesheet_offset_per_frame.x,
-                h: self.sprite_sheet.height(),
+                h: self.sheet_size.y,
             }
         }
     }
@@ -457,20 +759,22 @@ impl AnimationState {
     }
     fn change_animation(
         &mut self,
-        new_spritesheet: Texture2D,
+        new_spritesheet: Textures,
         new_animation_type: AnimationType,
         sprite_offset: Vec2
     ) {
         self.frame = 0;
-        let frames_amount_per_row = (new_spritesheet.width() / sprite_offset.x).trunc() as u16;
+        let (texture, sheet_size) = sprite_sheet(new_spritesheet);
+        let frames_amount_per_row = (sheet_size.x / sprite_offset.x).trunc() as u16;
         let amount_of_rows = if sprite_offset.y == 0.0 {
             1.0
         } else {
-            new_spritesheet.height() / sprite_offset.y
+            sheet_size.y / sprite_offset.y
         };
         self.frames_amount = frames_amount_per_row * (amount_of_rows as u16);
         self.spritesheet_offset_per_frame = sprite_offset;
-        self.sprite_sheet = new_spritesheet;
+        self.sprite_sheet = texture;
+        self.sheet_size = sheet_size;
         self.animation_type = new_animation_type;
     }
 }
@@ -480,6 +784,15 @@ enum EnemyAnimationType {
     SkeletonSide,
     SkeletonBack,
 }
+impl EnemyAnimationType {
+    fn texture(&self) -> Textures {
+        match self {
+            EnemyAnimationType::SkeletonFront => Textures::SkeletonFrontSpriteSheet,
+            EnemyAnimationType::SkeletonSide => Textures::SkeletonSideSpriteSheet,
+            EnemyAnimationType::SkeletonBack => Textures::SkeletonBackSpriteSheet,
+        }
+    }
+}
 
 struct UpdateEnemyAnimation;
 impl UpdateEnemyAnimation {
@@ -488,16 +801,27 @@ impl UpdateEnemyAnimation {
         enemy_positions: &Vec<Vec2>,
         aggressive_states: &Vec<bool>,
         velocities: &Vec<Vec2>,
+        smooth_facing_angles: &mut [f32],
         animation_states: &mut Vec<CompositeAnimationState>
     ) -> Vec<AnimationCallbackEvent> {
         let mut res: Vec<AnimationCallbackEvent> = Vec::new();
-        for (((enemy_pos, velocity), is_aggressive), animation_state) in enemy_positions
+        for (index, ((((enemy_pos, velocity), is_aggressive), smooth_facing), animation_state)) in enemy_positions
             .iter()
             .zip(velocities.iter())
             .zip(aggressive_states.iter())
-            .zip(animation_states.iter_mut()) {
+            .zip(smooth_facing_angles.iter_mut())
+            .zip(animation_states.iter_mut())
+            .enumerate() {
             let callback_event = animation_state.update(PHYSICS_FRAME_TIME);
-            res.extend(callback_event);
+            // the handle was stored when the death started, a swap_remove since then may have moved the enemy
+            res.extend(
+                callback_event.into_iter().map(|mut event| {
+                    if event.event_type == AnimationCallbackEventType::KillEnemy {
+                        event.target_handle = AllHandleTypes::EnemyHandle(EnemyHandle(index as u16));
+                    }
+                    event
+                })
+            );
 
             if *is_aggressive {
                 if
@@ -505,79 +829,65 @@ impl UpdateEnemyAnimation {
                     AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront)
                 {
                     animation_state.main_state.change_animation(
-                        TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::SkeletonFrontSpriteSheet)
-                            .expect("Failed to load spritesheet skeleton")
-                            .clone(),
+                        Textures::SkeletonFrontSpriteSheet,
                         AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront),
                         Vec2::new(31.0, 0.0)
                     );
                 }
                 continue;
             }
-            let to_player = player_origin - *enemy_pos;
-            let vel_enemy_rel_player = velocity.angle_between(to_player);
+            if *velocity == Vec2::ZERO {
+                continue;
+            }
+            // eases towards the walking direction so a bounce off a wall doesn't flip the sprite instantly
+            let target_facing = angles::angle_to(Vec2::ZERO, *velocity);
+            let turn_factor = (ENEMY_TURN_SPEED * PHYSICS_FRAME_TIME).min(1.0);
+            *smooth_facing = angles::wrap_pi(
+                *smooth_facing + angles::relative_angle(*smooth_facing, target_facing) * turn_factor
+            );
+            // angle between where the enemy faces and where the player stands, near 0 means it walks towards us
+            let vel_enemy_rel_player = angles::relative_angle(
+                *smooth_facing,
+                angles::angle_to(*enemy_pos, player_origin)
+            );
             match vel_enemy_rel_player {
-                angle if angle > 0.0 && angle < std::f32::consts::FRAC_PI_4 => {
+                angle if angles::within_fov(angle, FRAC_PI_4) => {
                     if
                         animation_state.main_state.animation_type !=
-                        AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonSide)
+                        AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront)
                     {
                         animation_state.main_state.change_animation(
-                            TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::SkeletonSideSpriteSheet)
-                                .expect("Failed to load spritesheet skeleton")
-                                .clone(),
-                            AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonSide),
+                            Textures::SkeletonFrontSpriteSheet,
+                            AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront),
                             Vec2::new(31.0, 0.0)
                         );
                     }
-                    animation_state.main_state.flip_x = true;
                 }
-                angle if angle <= 0.0 && angle > -PI => {
+                angle if angles::within_fov(angle, 3.0 * FRAC_PI_4) => {
                     if
                         animation_state.main_state.animation_type !=
                         AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonSide)
                     {
                         animation_state.main_state.change_animation(
-                            TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::SkeletonSideSpriteSheet)
-                                .expect("Failed to load spritesheet skeleton")
-                                .clone(),
+                            Textures::SkeletonSideSpriteSheet,
                             AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonSide),
                             Vec2::new(31.0, 0.0)
                         );
                     }
-                    animation_state.main_state.flip_x = false;
+                    animation_state.main_state.flip_x = angle > 0.0;
                 }
-                angle if
-                    (angle > 0.0 && angle > std::f32::consts::FRAC_2_PI) ||
-                    (angle < 0.0 && angle > -std::f32::consts::FRAC_2_PI)
-                => {
+                _ => {
                     if
                         animation_state.main_state.animation_type !=
                         AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonBack)
                     {
                         animation_state.main_state.change_animation(
-                            TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::SkeletonBackSpriteSheet)
-                                .expect("Failed to load spritesheet skeleton")
-                                .clone(),
+                            Textures::SkeletonBackSpriteSheet,
                             AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonBack),
                             Vec2::new(31.0, 0.0)
                         );
                     }
                 }
-                _ => {
-                    if
-                        animation_state.main_state.animation_type !=
-                        AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront)
-                    {
-                        animation_state.main_state.change_animation(
-                            TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::SkeletonFrontSpriteSheet)
-                                .expect("Failed to load spritesheet skeleton")
-                                .clone(),
-                            AnimationType::EnemyAnimationType(EnemyAnimationType::SkeletonFront),
-                            Vec2::new(31.0, 0.0)
-                        );
-                    }
-                }
             }
         }
         res
@@ -588,9 +898,21 @@ struct CallbackHandler;
 impl CallbackHandler {
     fn handle_animation_callbacks(
         callbacks: Vec<AnimationCallbackEvent>,
-        world_layout: &mut [[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
-        enemies: &mut Enemies
-    ) {
+        world_layout: &mut Grid<EntityType>,
+        enemies: &mut Enemies,
+        corpses: &mut Vec<CorpseData>,
+        content: &ContentRegistry,
+        rng: &mut SimulationRng
+    ) -> Vec<LootItem> {
+        let mut drops = Vec::new();
+        let mut callbacks = callbacks;
+        // highest index first, so destroying an enemy never moves one that is still waiting here
+        callbacks.sort_by_key(|callback| {
+            std::cmp::Reverse(match callback.target_handle {
+                AllHandleTypes::EnemyHandle(EnemyHandle(idx)) => Some(idx),
+                _ => None,
+            })
+        });
         for callback in callbacks {
             match callback.event_type {
                 AnimationCallbackEventType::KillEnemy => {
@@ -608,7 +930,7 @@ impl CallbackHandler {
 
                     for y in start_tile_y..end_tile_y {
                         for x in start_tile_x..end_tile_x {
-                            if y < world_layout.len() && x < world_layout[y].len() {
+                            if world_layout.contains(x, y) {
                                 if let EntityType::Enemy(id) = world_layout[y][x] {
                                     if id.0 == enemy_idx {
                                         world_layout[y][x] = EntityType::None;
@@ -617,12 +939,33 @@ impl CallbackHandler {
                             }
                         }
                     }
-                    enemies.destroy_enemy(enemy_idx);
+                    corpses.push(
+                        CorpseData::from_animation(
+                            enemy_pos,
+                            &enemies.animation_states[enemy_idx as usize].main_state
+                        )
+                    );
+                    let kind = &content.enemies[&enemies.kinds[enemy_idx as usize]];
+                    let loot_table = LootTable::from_defs(&kind.loot);
+                    if let Some(item) = loot_table.roll(rng) {
+                        drops.push(item);
+                    }
+                    if let Some(moved) = enemies.destroy_enemy(enemy_idx) {
+                        // the tiles of the enemy that took over the index still carry its old handle
+                        for row in world_layout.rows_mut() {
+                            for tile in row.iter_mut() {
+                                if *tile == EntityType::Enemy(moved) {
+                                    *tile = EntityType::Enemy(EnemyHandle(enemy_idx));
+                                }
+                            }
+                        }
+                    }
                 }
                 AnimationCallbackEventType::None => {}
                 _ => {}
             }
         }
+        drops
     }
 }
 
@@ -641,6 +984,8 @@ impl CollisionData {
         }
     }
 }
+/// the wall a door slides into while it opens
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum DoorDirection {
     LEFT,
     RIGHT,
@@ -653,9 +998,11 @@ struct Doors {
     opened: Vec<bool>,
     directions: Vec<DoorDirection>,
     animation_progress: Vec<f32>,
+    locked: Vec<bool>, // by a scripted sequence, the player can't open it
     animation_duration: f32,
     door_width: f32,
     door_height: f32,
+    spawn_ids: SpawnIndex,
 }
 
 impl Doors {
@@ -665,29 +1012,34 @@ impl Doors {
             opened: Vec::new(),
             directions: Vec::new(),
             animation_progress: Vec::new(),
+            locked: Vec::new(),
             animation_duration,
             door_width,
             door_height,
+            spawn_ids: SpawnIndex::new(),
         }
     }
 
     fn add_door(&mut self, position: Vec2, direction: DoorDirection) -> DoorHandle {
+        self.spawn_ids.push(position.x as usize, position.y as usize);
         self.positions.push(position);
         self.opened.push(false);
         self.directions.push(direction);
         self.animation_progress.push(0.0);
+        self.locked.push(false);
         DoorHandle((self.positions.len() - 1) as u16)
     }
 
-    fn render_door(&self, door_h: DoorHandle) {
+    fn render_door(&self, door_h: DoorHandle, minimap: MinimapTransform, color: Color) {
         if let Some(rect_hitbox) = self.get_door_hitbox(door_h) {
+            let screen_pos = minimap.to_screen(Vec2::new(rect_hitbox.x, rect_hitbox.y));
             draw_rectangle_ex(
-                rect_hitbox.x * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25 + MAP_X_OFFSET,
-                rect_hitbox.y * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                rect_hitbox.w * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25,
-                rect_hitbox.h * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
+                screen_pos.x,
+                screen_pos.y,
+                rect_hitbox.w * minimap.tile_size.x,
+                rect_hitbox.h * minimap.tile_size.y,
                 DrawRectangleParams {
-                    color: WHITE,
+                    color,
                     ..Default::default()
                 }
             );
@@ -701,28 +1053,39 @@ impl Doors {
             }
         }
     }
-    fn get_door_hitbox(&self, door_h: DoorHandle) -> Option<Rect> {
+    /// the share of its tile the door still blocks, 1.0 while closed and None once it is fully opened
+    fn closed_share(&self, door_h: DoorHandle) -> Option<f32> {
         let door_index = door_h.0 as usize;
         if door_index >= self.positions.len() {
             return None;
         }
-        let door_opened = self.opened[door_index];
-        let position = &self.positions[door_index];
         let progress = self.animation_progress[door_index];
-        if door_opened && progress >= 1.0 {
+        if self.opened[door_index] && progress >= 1.0 {
             // fully opened, see update_animation
             return None;
         }
-        let door_width = self.door_width * (progress - 1.0).abs();
-        let door_height = self.door_height;
-        return Some(Rect::new(position.x, position.y, door_width, door_height));
+        Some(1.0 - progress)
+    }
+    /// what is left of the door stays against the wall it slides into
+    fn get_door_hitbox(&self, door_h: DoorHandle) -> Option<Rect> {
+        let closed = self.closed_share(door_h)?;
+        let position = self.positions[door_h.0 as usize];
+        let (width, height) = (self.door_width, self.door_height);
+        let hitbox = match self.directions[door_h.0 as usize] {
+            DoorDirection::LEFT => Rect::new(position.x, position.y, width * closed, height),
+            DoorDirection::RIGHT => Rect::new(position.x + width * (1.0 - closed), position.y, width * closed, height),
+            DoorDirection::UP => Rect::new(position.x, position.y, width, height * closed),
+            DoorDirection::DOWN => Rect::new(position.x, position.y + height * (1.0 - closed), width, height * closed),
+        };
+        Some(hitbox)
     }
 
+    /// where the ray enters the rect and whether it came through one of the left or right sides
     fn get_ray_intersection_point(
         rect: &Rect,
         ray_origin: Vec2,
         ray_direction: Vec2
-    ) -> Option<Vec2> {
+    ) -> Option<(Vec2, bool)> {
         let mut tmin = (rect.x - ray_origin.x) / ray_direction.x; // closest intersection | x
         let mut tmax = (rect.x + rect.w - ray_origin.x) / ray_direction.x; // farthest | x
 
@@ -747,7 +1110,7 @@ impl Doors {
             return None;
         }
 
-        Some(Vec2::new(ray_origin.x + t * ray_direction.x, ray_origin.y + t * ray_direction.y))
+        Some((Vec2::new(ray_origin.x + t * ray_direction.x, ray_origin.y + t * ray_direction.y), tmin > tymin))
     }
     fn open_door(&mut self, handle: DoorHandle) {
         let index = handle.0 as usize;
@@ -756,6 +1119,13 @@ impl Doors {
             self.animation_progress[index] = 0.0;
         }
     }
+    fn handle_of(&self, spawn_id: SpawnId) -> Option<DoorHandle> {
+        self.spawn_ids.index_of(spawn_id).map(DoorHandle)
+    }
+    #[allow(unused)]
+    fn spawn_id(&self, handle: DoorHandle) -> SpawnId {
+        self.spawn_ids.ids[handle.0 as usize]
+    }
     fn close_door(&mut self, handle: DoorHandle) {
         let index = handle.0 as usize;
         if index < self.opened.len() {
@@ -763,26 +1133,55 @@ impl Doors {
             self.animation_progress[index] = 0.0;
         }
     }
+    /// shuts the door for good until unlock_door
+    fn lock_door(&mut self, handle: DoorHandle) {
+        // a closed door keeps its animation
+        if self.opened.get(handle.0 as usize) == Some(&true) {
+            self.close_door(handle);
+        }
+        if let Some(locked) = self.locked.get_mut(handle.0 as usize) {
+            *locked = true;
+        }
+    }
+    fn unlock_door(&mut self, handle: DoorHandle) {
+        if let Some(locked) = self.locked.get_mut(handle.0 as usize) {
+            *locked = false;
+        }
+    }
 }
 #[allow(unused)]
 struct EnemyInformation {
     idx: u16,
     pos: Vec2,
     vel: Vec2,
-    health: u8,
+    health: f32,
     size: Vec2,
     aggressive: bool,
     is_alive: bool,
 }
+#[derive(Debug)]
+struct EnemyCapacityError {
+    capacity: usize,
+}
 struct Enemies {
     positions: Vec<Vec2>,
     velocities: Vec<Vec2>,
-    healths: Vec<u8>,
+    healths: Vec<f32>,
     sizes: Vec<Vec2>,
     animation_states: Vec<CompositeAnimationState>,
     aggressive_states: Vec<bool>,
     collision_data: CollisionData,
     alives: Vec<bool>,
+    kinds: Vec<String>, // enemy ids in the content registry
+    speeds: Vec<f32>,
+    smooth_facing_angles: Vec<f32>,
+    teleport_cooldowns: Vec<f32>,
+    scatter_velocities: Vec<Vec2>, // added to the velocity while a blast nearby scatters it, see RepulsionSystem
+    paths: Vec<EnemyPath>, // see PathfindingSystem
+    sightings: Vec<Option<Sighting>>, // see EnemyAggressionSystem
+    attack_types: Vec<AttackType>,
+    attack_cooldowns: Vec<f32>, // seconds until a ranged enemy can fire, see EnemyAttackSystem
+    spawn_ids: SpawnIndex,
 }
 
 impl Enemies {
@@ -796,6 +1195,16 @@ impl Enemies {
             collision_data: CollisionData::new(0),
             aggressive_states: Vec::new(),
             alives: Vec::new(),
+            kinds: Vec::new(),
+            speeds: Vec::new(),
+            smooth_facing_angles: Vec::new(),
+            teleport_cooldowns: Vec::new(),
+            scatter_velocities: Vec::new(),
+            paths: Vec::new(),
+            sightings: Vec::new(),
+            attack_types: Vec::new(),
+            attack_cooldowns: Vec::new(),
+            spawn_ids: SpawnIndex::new(),
         }
     }
 
@@ -803,14 +1212,19 @@ impl Enemies {
         &mut self,
         pos: Vec2,
         velocity: Vec2,
-        health: u8,
         size: Vec2,
-        animation: AnimationState
-    ) -> EnemyHandle {
+        mut animation: AnimationState,
+        kind: &EnemyKindDef
+    ) -> Result<EnemyHandle, EnemyCapacityError> {
         let index = self.positions.len();
+        if index >= MAX_ENEMIES_PER_LEVEL {
+            return Err(EnemyCapacityError { capacity: MAX_ENEMIES_PER_LEVEL });
+        }
+        animation.color = Color::from_rgba(kind.tint[0], kind.tint[1], kind.tint[2], 255);
+        self.spawn_ids.push(pos.x as usize, pos.y as usize);
         self.positions.push(pos);
-        self.velocities.push(velocity);
-        self.healths.push(health);
+        self.velocities.push(velocity * kind.speed);
+        self.healths.push(kind.health as f32);
         self.sizes.push(size);
         self.animation_states.push(CompositeAnimationState {
             main_state: animation,
@@ -821,9 +1235,21 @@ impl Enemies {
         self.collision_data.collision_times.push(Duration::from_secs(0));
         self.aggressive_states.push(false);
         self.alives.push(true);
-        EnemyHandle(index as u16)
+        self.kinds.push(kind.id.clone());
+        self.speeds.push(kind.speed);
+        self.smooth_facing_angles.push(angles::angle_to(Vec2::ZERO, velocity));
+        self.teleport_cooldowns.push(0.0);
+        self.scatter_velocities.push(Vec2::ZERO);
+        self.paths.push(EnemyPath::Unplanned);
+        self.sightings.push(None);
+        self.attack_types.push(kind.attack);
+        // a fresh enemy takes a full cooldown before its first shot
+        self.attack_cooldowns.push(kind.attack.cooldown().unwrap_or(0.0));
+        Ok(EnemyHandle(index as u16))
     }
-    fn destroy_enemy(&mut self, idx: u16) {
+    /// the last enemy takes over idx, returns its old handle when there was one to move
+    fn destroy_enemy(&mut self, idx: u16) -> Option<EnemyHandle> {
+        let last = self.positions.len() - 1;
         self.positions.swap_remove(idx as usize);
         self.velocities.swap_remove(idx as usize);
         self.healths.swap_remove(idx as usize);
@@ -834,6 +1260,88 @@ impl Enemies {
         self.collision_data.collision_times.swap_remove(idx as usize);
         self.aggressive_states.swap_remove(idx as usize);
         self.alives.swap_remove(idx as usize);
+        self.kinds.swap_remove(idx as usize);
+        self.speeds.swap_remove(idx as usize);
+        self.smooth_facing_angles.swap_remove(idx as usize);
+        self.teleport_cooldowns.swap_remove(idx as usize);
+        self.scatter_velocities.swap_remove(idx as usize);
+        self.paths.swap_remove(idx as usize);
+        self.sightings.swap_remove(idx as usize);
+        self.attack_types.swap_remove(idx as usize);
+        self.attack_cooldowns.swap_remove(idx as usize);
+        self.spawn_ids.swap_remove(idx as usize);
+        (last != (idx as usize)).then_some(EnemyHandle(last as u16))
+    }
+    /// how far a ranged enemy is into aiming at the player, from 0 to 1 when it fires, None while it isn't
+    fn aim_progress(&self, index: usize) -> Option<f32> {
+        match self.attack_types[index] {
+            attack if attack.cooldown().is_some() && self.attack_cooldowns[index] < ENEMY_ATTACK_WINDUP => {
+                Some(1.0 - self.attack_cooldowns[index] / ENEMY_ATTACK_WINDUP)
+            }
+            _ => None,
+        }
+    }
+    /// the live handle of a spawned enemy, None once it was destroyed
+    fn handle_of(&self, spawn_id: SpawnId) -> Option<EnemyHandle> {
+        self.spawn_ids.index_of(spawn_id).map(EnemyHandle)
+    }
+    fn spawn_id(&self, handle: EnemyHandle) -> SpawnId {
+        self.spawn_ids.ids[handle.0 as usize]
+    }
+    /// dying enemies still have their handle until the death animation ends
+    fn alive_count(&self) -> usize {
+        self.alives.iter().filter(|&&alive| alive).count()
+    }
+    /// an enemy of a sequence step bursts out of the floor, None when it couldn't be added
+    fn spawn_scripted(
+        &mut self,
+        world_layout: &mut Grid<EntityType>,
+        content: &ContentRegistry,
+        enemy: &str,
+        x: usize,
+        y: usize
+    ) -> Option<EnemyHandle> {
+        let Some(kind) = content.enemies.get(enemy) else {
+            eprintln!("Warning: a sequence spawns the unknown enemy '{}'", enemy);
+            return None;
+        };
+        let new_enemy = self.new_enemy(
+            Vec2::new(x as f32, y as f32),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            AnimationState::default_skeleton(),
+            kind
+        );
+        let handle = match new_enemy {
+            Ok(handle) => handle,
+            Err(err) => {
+                eprintln!("Warning: no room for '{}', level is at its capacity of {} enemies", enemy, err.capacity);
+                return None;
+            }
+        };
+        if world_layout[y][x] == EntityType::None {
+            world_layout[y][x] = EntityType::Enemy(handle);
+        }
+        self.animation_states[handle.0 as usize].add_effect(AnimationState::default_explosion(), None);
+        Some(handle)
+    }
+    /// reinforcements of a sequence, skipped while the level still has its fill of living enemies
+    fn spawn_reinforcement(
+        &mut self,
+        world_layout: &mut Grid<EntityType>,
+        content: &ContentRegistry,
+        enemy: &str,
+        x: usize,
+        y: usize
+    ) -> Option<EnemyHandle> {
+        if !content.allows_spawn(self.alive_count()) {
+            return None;
+        }
+        self.spawn_scripted(world_layout, content, enemy, x, y)
+    }
+    /// None for a handle that doesn't point at an enemy anymore
+    fn get(&self, handle: EnemyHandle) -> Option<EnemyInformation> {
+        ((handle.0 as usize) < self.positions.len()).then(|| self.get_enemy_information(handle.0))
     }
     fn get_enemy_information(&self, idx: u16) -> EnemyInformation {
         let idx = idx as usize;
@@ -851,88 +1359,796 @@ impl Enemies {
     }
 
 }
+/// the ammo kinds loot and pickups carry, by the base weapon that takes them
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+enum WeaponType {
+    Pistol,
+    Shotgun,
+    Chaingun,
+    RocketLauncher,
+}
+impl WeaponType {
+    /// the weapon of the content that takes this ammo
+    fn weapon_id(&self) -> &'static str {
+        match self {
+            WeaponType::Pistol => "pistol",
+            WeaponType::Shotgun => "shotgun",
+            WeaponType::Chaingun => "chaingun",
+            WeaponType::RocketLauncher => "rocket_launcher",
+        }
+    }
+    /// how the ammo reads on a pickup label
+    fn ammo_name(&self) -> &'static str {
+        match self {
+            WeaponType::Pistol => "pistol rounds",
+            WeaponType::Shotgun => "shells",
+            WeaponType::Chaingun => "chaingun rounds",
+            WeaponType::RocketLauncher => "rockets",
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+enum PowerUpType {
+    Berserk,
+    Haste,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+enum DamageType {
+    Ballistic,
+    Explosive,
+    Melee,
+}
+/// what an enemy kind does besides hurting the player on contact
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+enum AttackType {
+    Melee, // contact only
+    Ranged {
+        damage: u8,
+        range: f32, // tiles
+        cooldown: f32, // seconds between shots, the last ENEMY_ATTACK_WINDUP of it is spent aiming
+    },
+    Fireball {
+        damage: u8,
+        range: f32,
+        cooldown: f32,
+        speed: f32, // tiles per second, it can be dodged and walls stop it
+    },
+}
+impl AttackType {
+    /// seconds between attacks of the kinds that attack from a distance
+    fn cooldown(&self) -> Option<f32> {
+        match *self {
+            AttackType::Melee => None,
+            AttackType::Ranged { cooldown, .. } | AttackType::Fireball { cooldown, .. } => Some(cooldown),
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+enum FireMode {
+    Single,
+    Burst(u8), // shots per trigger press, BURST_SHOT_INTERVAL apart
+    Auto, // fires whenever the weapon is ready while the trigger is held
+}
+impl FireMode {
+    fn label(self) -> String {
+        match self {
+            FireMode::Single => "SINGLE".to_string(),
+            FireMode::Burst(shots) => format!("BURST {}", shots),
+            FireMode::Auto => "AUTO".to_string(),
+        }
+    }
+}
+/// damage after the resistance of the target was applied
+struct ResolvedDamage {
+    amount: f32,
+    multiplier: f32,
+}
+struct DamageSystem;
+impl DamageSystem {
+    fn resolve(base: f32, damage_type: DamageType, resistances: &HashMap<DamageType, f32>) -> ResolvedDamage {
+        let multiplier = resistances.get(&damage_type).copied().unwrap_or(1.0).max(0.0);
+        ResolvedDamage {
+            amount: base * multiplier,
+            multiplier,
+        }
+    }
+    /// what a hit on an enemy deals after its resistances, projectiles carry their damage and hitscan shots take
+    /// the held weapon's, halved per enemy the shot passed through
+    fn of_hit(
+        event: &WorldEventHandleBased,
+        weapon: &Weapon,
+        resistances: &HashMap<DamageType, f32>
+    ) -> ResolvedDamage {
+        let (base_damage, damage_type) = match event.event_type {
+            WorldEventType::ProjectileHitEnemy { damage, damage_type } => (damage, damage_type),
+            _ => (weapon.damage_at_depth(event.penetration_depth), weapon.damage_type),
+        };
+        DamageSystem::resolve(base_damage, damage_type, resistances)
+    }
+    /// every living enemy whose center is within `radius` of the blast and not behind a wall or closed door,
+    /// the damage falls off linearly to nothing at the edge
+    fn blast(
+        center: Vec2,
+        radius: f32,
+        damage: u8,
+        damage_type: DamageType,
+        enemies: &Enemies,
+        doors: &Doors,
+        world_layout: &Grid<EntityType>
+    ) -> Vec<WorldEventHandleBased> {
+        (0..enemies.positions.len())
+            .filter_map(|enemy| {
+                let enemy_center = enemies.positions[enemy] + enemies.sizes[enemy] * 0.5;
+                let distance = center.distance(enemy_center);
+                let in_blast =
+                    enemies.alives[enemy] &&
+                    distance < radius &&
+                    RaycastSystem::line_of_sight(center, enemy_center, doors, world_layout);
+                // to a tenth, so the hit markers stay readable
+                let damage = ((damage as f32) * (1.0 - distance / radius) * 10.0).round() / 10.0;
+                (in_blast && damage > 0.0).then(|| {
+                    WorldEventHandleBased::projectile_hit_enemy(EnemyHandle(enemy as u16), damage, damage_type)
+                })
+            })
+            .collect()
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+enum LootItem {
+    HealthPack(u8),
+    AmmoPickup(WeaponType, u16),
+    WeaponPickup(WeaponType),
+    PowerUp(PowerUpType),
+}
+struct LootEntry {
+    item: LootItem,
+    weight: f32,
+}
+/// weights are absolute chances, if they sum up to less than 1.0 the rest is the chance of no drop
+struct LootTable {
+    entries: Vec<LootEntry>,
+    cumulative_weights: Vec<f32>,
+}
+impl LootTable {
+    fn new(entries: Vec<LootEntry>) -> Self {
+        let mut cumulative_weights = Vec::with_capacity(entries.len());
+        let mut total = 0.0;
+        for entry in &entries {
+            total += entry.weight.max(0.0);
+            cumulative_weights.push(total);
+        }
+        LootTable {
+            entries,
+            cumulative_weights,
+        }
+    }
+    fn from_defs(defs: &[LootEntryDef]) -> Self {
+        LootTable::new(
+            defs
+                .iter()
+                .map(|def| LootEntry {
+                    item: def.item,
+                    weight: def.weight,
+                })
+                .collect()
+        )
+    }
+    fn roll(&self, rng: &mut impl Rng) -> Option<LootItem> {
+        let total = *self.cumulative_weights.last()?;
+        if total <= 0.0 {
+            return None;
+        }
+        let roll = rng.gen::<f32>() * total.max(1.0);
+        // cumulative weights are sorted, so the first entry above the roll is the picked one
+        let idx = self.cumulative_weights.partition_point(|&weight| weight <= roll);
+        self.entries.get(idx).map(|entry| entry.item)
+    }
+}
+struct WeaponSounds {
+    fire: Sounds,
+    reload: Sounds,
+    empty: Sounds, // pulling the trigger with nothing left to reload from
+}
 struct Weapon {
+    id: String,
+    sounds: WeaponSounds,
     reload_frames_t: u8, // in physics frames
     damage: u8,
+    damage_type: DamageType,
+    penetration: u8,
     range: u8,
     elapsed_reload_t: u8,
+    fire_modes: Vec<FireMode>,
+    fire_mode_index: usize,
+    magazine_size: u8, // 0 never runs dry
+    current_ammo: u8,
+    reserve_ammo: u16,
+    reloading_magazine: bool, // the running reload refills the magazine when it is done
+    pellets: u8, // more than one fans out over spread instead of the single aimed shot
+    spread: f32, // radians
+    bloom: f32, // radians the aim strays at most, grows with sustained automatic fire
+    projectile_speed: f32, // tiles per second, above 0 a shot launches a rocket instead of tracing rays
+    blast_radius: f32,
 }
 impl Weapon {
-    fn default() -> Self {
+    fn from_def(def: &WeaponDef) -> Self {
         Weapon {
-            reload_frames_t: 30,
-            damage: 1,
-            range: 8,
+            id: def.id.clone(),
+            sounds: WeaponSounds {
+                fire: def.fire_sound,
+                reload: def.reload_sound,
+                empty: def.empty_sound,
+            },
+            reload_frames_t: def.reload_frames,
+            damage: def.damage,
+            damage_type: def.damage_type,
+            penetration: def.penetration,
+            range: def.range,
             elapsed_reload_t: 0,
+            fire_modes: def.fire_modes.clone(),
+            fire_mode_index: 0,
+            magazine_size: def.magazine_size,
+            current_ammo: def.magazine_size,
+            reserve_ammo: def.reserve_ammo,
+            reloading_magazine: false,
+            pellets: def.pellets.max(1),
+            spread: def.spread.to_radians(),
+            bloom: 0.0,
+            projectile_speed: def.projectile_speed,
+            blast_radius: def.blast_radius,
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.magazine_size > 0 && self.current_ammo == 0
+    }
+    /// nothing left to fire or reload, the weapon wheel greys it out
+    fn is_spent(&self) -> bool {
+        self.is_empty() && self.reserve_ammo == 0
+    }
+    /// starts refilling the magazine from the reserve, false when there is nothing to reload or it is busy
+    fn start_reload(&mut self) -> bool {
+        let can_reload =
+            self.elapsed_reload_t == 0 && self.current_ammo < self.magazine_size && self.reserve_ammo > 0;
+        if can_reload {
+            self.elapsed_reload_t = 1;
+            self.reloading_magazine = true;
+        }
+        can_reload
+    }
+    fn fire_mode(&self) -> FireMode {
+        self.fire_modes.get(self.fire_mode_index).copied().unwrap_or(FireMode::Single)
+    }
+    /// every enemy a hitscan shot passed through halves the damage, before the resistances of the one it hits
+    fn damage_at_depth(&self, penetration_depth: u8) -> f32 {
+        (self.damage as f32) * 0.5f32.powi(penetration_depth as i32)
+    }
+}
+/// The quick select overlay while its button is held. The weapons sit around a circle clockwise from the top,
+/// the mouse direction or the turn keys pick one and it is switched to once the button is let go.
+struct WeaponWheel {
+    highlighted: usize,
+    pointer: Vec2, // mouse movement since the wheel opened, clamped so turning back is quick
+}
+impl WeaponWheel {
+    fn new(active: usize) -> Self {
+        WeaponWheel { highlighted: active, pointer: Vec2::ZERO }
+    }
+    /// radians of slot `index` of `count` from the positive x axis, the first one at the top
+    fn slot_angle(index: usize, count: usize) -> f32 {
+        -PI / 2.0 + (index as f32) * std::f32::consts::TAU / (count as f32)
+    }
+    /// the slot of `count` whose angle is closest to the direction
+    fn slot_at(direction: Vec2, count: usize) -> usize {
+        let angle = angles::wrap_tau(direction.y.atan2(direction.x) + PI / 2.0);
+        ((angle / (std::f32::consts::TAU / (count as f32))).round() as usize) % count
+    }
+    /// moves the pointer by the mouse movement, pointing at a spent weapon keeps the highlight where it was
+    fn point(&mut self, delta: Vec2, weapons: &[Weapon]) {
+        self.pointer = (self.pointer + delta).clamp_length_max(WEAPON_WHEEL_DEADZONE * 2.0);
+        if self.pointer.length() < WEAPON_WHEEL_DEADZONE {
+            return;
+        }
+        let slot = Self::slot_at(self.pointer, weapons.len());
+        if !weapons[slot].is_spent() {
+            self.highlighted = slot;
+        }
+    }
+    /// highlights the next weapon that isn't spent, 1 is clockwise and -1 counterclockwise
+    fn step(&mut self, direction: i32, weapons: &[Weapon]) {
+        let count = weapons.len() as i32;
+        for offset in 1..count {
+            let slot = (self.highlighted as i32 + direction * offset).rem_euclid(count) as usize;
+            if !weapons[slot].is_spent() {
+                self.highlighted = slot;
+                return;
+            }
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum WeaponSwitch {
+    Ready,
+    Lowering {
+        next: usize, // index into WeaponInventory::weapons that is raised afterwards
+        remaining: f32,
+    },
+    Raising {
+        remaining: f32,
+    },
+}
+/// the weapons the player carries, switching lowers the active one and raises the next before it can fire again
+struct WeaponInventory {
+    weapons: Vec<Weapon>, // in slot order, the number keys select them by position
+    active: usize,
+    switch: WeaponSwitch,
+    queued: Option<usize>, // requested while raising, switched to as soon as the raise is done
+    buffered_shot: bool, // trigger pulled while raising, fired on the first ready frame
+}
+impl WeaponInventory {
+    fn new(weapons: Vec<Weapon>, active: usize) -> Self {
+        WeaponInventory {
+            weapons,
+            active,
+            switch: WeaponSwitch::Ready,
+            queued: None,
+            buffered_shot: false,
+        }
+    }
+    fn active_weapon(&self) -> &Weapon {
+        &self.weapons[self.active]
+    }
+    fn active_weapon_mut(&mut self) -> &mut Weapon {
+        &mut self.weapons[self.active]
+    }
+    fn is_ready(&self) -> bool {
+        self.switch == WeaponSwitch::Ready
+    }
+    /// starts switching to weapons[index], requests during a switch replace each other so the latest one wins
+    fn request_switch(&mut self, index: usize) {
+        if index >= self.weapons.len() {
+            return;
+        }
+        match &mut self.switch {
+            WeaponSwitch::Ready => {
+                if index != self.active {
+                    self.switch = WeaponSwitch::Lowering {
+                        next: index,
+                        remaining: WEAPON_LOWER_TIME,
+                    };
+                }
+            }
+            WeaponSwitch::Lowering { next, .. } => {
+                *next = index;
+            }
+            WeaponSwitch::Raising { .. } => {
+                self.queued = if index != self.active { Some(index) } else { None };
+            }
+        }
+    }
+    /// only a raise buffers the trigger, while lowering the shot is dropped
+    fn buffer_shot(&mut self) {
+        if let WeaponSwitch::Raising { .. } = self.switch {
+            self.buffered_shot = true;
+        }
+    }
+    /// advances the switch by one physics frame, true when the weapon just became ready with a shot buffered
+    fn update(&mut self, dt: f32) -> bool {
+        match self.switch {
+            WeaponSwitch::Ready => false,
+            WeaponSwitch::Lowering { next, remaining } => {
+                let remaining = remaining - dt;
+                self.switch = if remaining > 0.0 {
+                    WeaponSwitch::Lowering { next, remaining }
+                } else {
+                    self.active = next;
+                    WeaponSwitch::Raising { remaining: WEAPON_RAISE_TIME }
+                };
+                false
+            }
+            WeaponSwitch::Raising { remaining } => {
+                let remaining = remaining - dt;
+                if remaining > 0.0 {
+                    self.switch = WeaponSwitch::Raising { remaining };
+                    return false;
+                }
+                self.switch = WeaponSwitch::Ready;
+                if let Some(next) = self.queued.take() {
+                    self.buffered_shot = false;
+                    self.request_switch(next);
+                    return false;
+                }
+                std::mem::take(&mut self.buffered_shot)
+            }
+        }
+    }
+    /// how far the active weapon is below its resting position, 0 is ready and 1 fully lowered
+    fn lowered(&self) -> f32 {
+        match self.switch {
+            WeaponSwitch::Ready => 0.0,
+            WeaponSwitch::Lowering { remaining, .. } => 1.0 - remaining / WEAPON_LOWER_TIME,
+            WeaponSwitch::Raising { remaining } => remaining / WEAPON_RAISE_TIME,
         }
     }
 }
 struct WeaponSystem;
 impl WeaponSystem {
     fn update_reload(player_weapon: &mut Weapon) {
+        if player_weapon.elapsed_reload_t == 0 || player_weapon.reloading_magazine {
+            player_weapon.bloom = (player_weapon.bloom - AUTO_FIRE_BLOOM_DECAY * PHYSICS_FRAME_TIME).max(0.0);
+        }
         if player_weapon.elapsed_reload_t > 0 {
             player_weapon.elapsed_reload_t += 1;
         }
         if player_weapon.elapsed_reload_t >= player_weapon.reload_frames_t {
             player_weapon.elapsed_reload_t = 0;
+            if player_weapon.reloading_magazine {
+                player_weapon.reloading_magazine = false;
+                let refill = ((player_weapon.magazine_size - player_weapon.current_ammo) as u16).min(
+                    player_weapon.reserve_ammo
+                );
+                player_weapon.current_ammo += refill as u8;
+                player_weapon.reserve_ammo -= refill;
+            }
+        }
+    }
+}
+/// a scripted rotation (quick turn or turn step) that overrides the normal turning until done
+struct EasedTurn {
+    start_angle: f32,
+    target_angle: f32,
+    duration: f32,
+    elapsed: f32,
+}
+impl EasedTurn {
+    fn new(start_angle: f32, target_angle: f32, duration: f32) -> Self {
+        EasedTurn {
+            start_angle,
+            target_angle,
+            duration,
+            elapsed: 0.0,
         }
     }
+    /// eased angle for the current progress, None once the turn is complete and target_angle should be used
+    fn advance(&mut self, dt: f32) -> Option<f32> {
+        self.elapsed += dt;
+        let t = (self.elapsed / self.duration).min(1.0);
+        if t >= 1.0 {
+            return None;
+        }
+        let eased = t * t * (3.0 - 2.0 * t);
+        let turn = angles::relative_angle(self.start_angle, self.target_angle);
+        Some(angles::wrap_tau(self.start_angle + turn * eased))
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ShotRay {
+    Left,
+    Center,
+    Right,
+    Pellet(u8), // counted from the left edge of the fan
+}
+/// the ray of the spread that connected and how far away the first enemy on it was, the nearest pellet for a fan
+#[derive(Clone, Copy, Debug)]
+struct ShotHit {
+    ray: ShotRay,
+    distance: f32,
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ShotOutcome {
+    Fired,
+    StillReloading,
+    OutOfAmmo, // the magazine is empty, a reload from the reserve starts if there is one
 }
 struct ShootEvent {
-    world_event: Option<WorldEventHandleBased>,
-    still_reloading: bool,
+    world_events: Vec<WorldEventHandleBased>, // nearest hit first
+    outcome: ShotOutcome,
+    hit: Option<ShotHit>,
 }
 struct Player {
     pos: Vec2,
     angle: f32,
     vel: Vec2,
     health: u16,
-    weapon: Weapon,
+    hit_cooldown: f32,
+    out_of_combat_time: f32, // seconds since the last damage taken or aggressive enemy
+    regen_elapsed: f32,
+    adrenaline_remaining: f32,
+    stamina: f32,
+    max_stamina: f32,
+    is_sprinting: bool,
+    sprint_exhausted: bool, // the stamina ran out, sprinting stays locked until it is full again
+    sprint_multiplier: f32,
+    walking: bool, // toggled, slows the player down for precise positioning, survives level changes
+    is_crouching: bool,
+    crouch_eye_offset: f32, // in wall heights, the collision box stays the same
+    pitch: f32, // radians, positive looks down, only moves the picture since shots stay level
+    target_vel: Vec2, // from the input, vel eases towards it in MovementSystem::update_player
+    teleport_cooldown: f32,
+    eased_turn: Option<EasedTurn>,
+    weapons: WeaponInventory,
+    burst_shots_left: u8, // scheduled shots of the current burst, the trigger is ignored until they are fired
     animation_state: CompositeAnimationState,
-    bobbing_time: f32,
-    bobbing_speed: f32,
-    bobbing_amount: f32,
+    bobbing_time: f32, // seconds of walking, the sway itself is configured in Settings
 }
 impl Player {
+    /// standing at `pos` with full health and stamina
+    fn new(pos: Vec2, weapons: WeaponInventory) -> Self {
+        Player {
+            pos,
+            angle: 0.0,
+            vel: Vec2::new(0.0, 0.0),
+            health: PLAYER_MAX_HEALTH,
+            hit_cooldown: 0.0,
+            out_of_combat_time: 0.0,
+            regen_elapsed: 0.0,
+            adrenaline_remaining: 0.0,
+            stamina: 1.0,
+            max_stamina: 1.0,
+            is_sprinting: false,
+            sprint_exhausted: false,
+            sprint_multiplier: 1.75,
+            walking: false,
+            is_crouching: false,
+            crouch_eye_offset: 0.3,
+            pitch: 0.0,
+            target_vel: Vec2::ZERO,
+            teleport_cooldown: 0.0,
+            eased_turn: None,
+            burst_shots_left: 0,
+            weapons,
+            animation_state: CompositeAnimationState::new(AnimationState::default_weapon()),
+            bobbing_time: 0.0,
+        }
+    }
+    fn weapon(&self) -> &Weapon {
+        self.weapons.active_weapon()
+    }
+    /// nothing hurts while the PLAYER_HIT_COOLDOWN of the last hit runs, contact with an enemy lasts many frames
+    fn can_be_hit(&self) -> bool {
+        self.hit_cooldown <= 0.0
+    }
+    fn take_hit(&mut self, damage: u16) {
+        self.hit_cooldown = PLAYER_HIT_COOLDOWN;
+        self.health = self.health.saturating_sub(damage);
+        self.out_of_combat_time = 0.0;
+    }
+    fn update_hit_cooldown(&mut self) {
+        self.hit_cooldown = (self.hit_cooldown - PHYSICS_FRAME_TIME).max(0.0);
+    }
+    /// out of combat the player slowly heals up to HEALTH_REGEN_MAX, see Settings::health_regeneration
+    fn update_regeneration(&mut self, in_combat: bool, enabled: bool) {
+        if in_combat {
+            self.out_of_combat_time = 0.0;
+        } else {
+            self.out_of_combat_time += PHYSICS_FRAME_TIME;
+        }
+        if !self.is_regenerating(enabled) {
+            self.regen_elapsed = 0.0;
+            return;
+        }
+        self.regen_elapsed += PHYSICS_FRAME_TIME;
+        if self.regen_elapsed >= HEALTH_REGEN_INTERVAL {
+            self.regen_elapsed = 0.0;
+            self.health += 1;
+        }
+    }
+    fn is_regenerating(&self, enabled: bool) -> bool {
+        enabled && self.health < HEALTH_REGEN_MAX && self.out_of_combat_time >= OUT_OF_COMBAT_TIME
+    }
+    /// a kill at 1 health speeds the player up for ADRENALINE_DURATION, true when it did
+    fn grant_adrenaline(&mut self, enabled: bool) -> bool {
+        if !enabled || self.health != 1 {
+            return false;
+        }
+        self.adrenaline_remaining = ADRENALINE_DURATION;
+        true
+    }
+    fn update_adrenaline(&mut self) {
+        self.adrenaline_remaining = (self.adrenaline_remaining - PHYSICS_FRAME_TIME).max(0.0);
+    }
+    /// before sprinting, walking and crouching
+    fn base_speed(&self) -> f32 {
+        if self.adrenaline_remaining > 0.0 {
+            PLAYER_SPEED * (1.0 + ADRENALINE_SPEED_BONUS)
+        } else {
+            PLAYER_SPEED
+        }
+    }
+    /// `direction` is at most 1 long, a stick tilted halfway moves at half the speed
+    fn target_velocity(&self, direction: Vec2) -> Vec2 {
+        let mut speed = self.base_speed();
+        // sprinting overrides walking while it lasts
+        if self.is_sprinting {
+            speed *= self.sprint_multiplier;
+        } else if self.walking {
+            speed *= WALK_SPEED_FACTOR;
+        }
+        if self.is_crouching {
+            speed *= CROUCH_SPEED_FACTOR;
+        }
+        direction * speed
+    }
+    /// a running burst ends with the weapon going down
+    fn request_switch(&mut self, index: usize) {
+        self.burst_shots_left = 0;
+        self.weapons.request_switch(index);
+    }
+    /// one shot of the active weapon, or the first shot of a burst that schedules the others, see fire_burst_shot
+    fn pull_trigger(
+        &mut self,
+        world_layout: &Grid<EntityType>,
+        enemies: &Enemies,
+        projectiles: &mut Projectiles,
+        scheduler: &mut Scheduler,
+        rng: &mut SimulationRng
+    ) -> ShootEvent {
+        let shoot_event = self.shoot(world_layout, enemies, projectiles, rng);
+        if let FireMode::Burst(shots) = self.weapon().fire_mode() {
+            if shoot_event.outcome == ShotOutcome::Fired {
+                self.burst_shots_left = shots.saturating_sub(1);
+                for shot in 1..shots {
+                    scheduler.schedule((shot as f32) * BURST_SHOT_INTERVAL, ScheduledAction::FireBurstShot);
+                }
+            }
+        }
+        shoot_event
+    }
+    /// a later shot of the running burst, None once the burst was cut short or the weapon is being switched
+    fn fire_burst_shot(
+        &mut self,
+        world_layout: &Grid<EntityType>,
+        enemies: &Enemies,
+        projectiles: &mut Projectiles,
+        rng: &mut SimulationRng
+    ) -> Option<ShootEvent> {
+        if self.burst_shots_left == 0 || !self.weapons.is_ready() {
+            return None;
+        }
+        self.burst_shots_left -= 1;
+        let shoot_event = self.fire(world_layout, enemies, projectiles, rng);
+        if shoot_event.outcome == ShotOutcome::OutOfAmmo {
+            // an empty magazine ends the burst
+            self.burst_shots_left = 0;
+        }
+        Some(shoot_event)
+    }
+    /// where the view is vertically, from crouching and the pitch
+    fn eye_level(&self) -> EyeLevel {
+        EyeLevel {
+            offset: if self.is_crouching { self.crouch_eye_offset } else { 0.0 },
+            horizon: HALF_SCREEN_HEIGHT - self.pitch * HALF_SCREEN_HEIGHT,
+        }
+    }
     fn shoot(
         &mut self,
-        world_layout: [[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
-        enemies: &Enemies
+        world_layout: &Grid<EntityType>,
+        enemies: &Enemies,
+        projectiles: &mut Projectiles,
+        rng: &mut SimulationRng
+    ) -> ShootEvent {
+        if self.weapon().elapsed_reload_t > 0 {
+            return ShootEvent {
+                world_events: Vec::new(),
+                outcome: ShotOutcome::StillReloading,
+                hit: None,
+            };
+        }
+        self.fire(world_layout, enemies, projectiles, rng)
+    }
+    /// fires without waiting for the reload, which starts over, used by the later shots of a burst
+    fn fire(
+        &mut self,
+        world_layout: &Grid<EntityType>,
+        enemies: &Enemies,
+        projectiles: &mut Projectiles,
+        rng: &mut SimulationRng
     ) -> ShootEvent {
         const RAY_SPREAD: f32 = PLAYER_FOV / 2.0 / 10.0; // basically defines the hitbox of the player shooting
-        let angles = [self.angle - RAY_SPREAD, self.angle, self.angle + RAY_SPREAD];
-        if self.weapon.elapsed_reload_t > 0 {
+        let weapon = self.weapons.active_weapon_mut();
+        // a single shot traces every ray and the nearest hit wins, the center ray comes first so it wins ties,
+        // every pellet of a fan hits on its own
+        let pellets = weapon.pellets;
+        let rays: Vec<(ShotRay, f32)> = if pellets > 1 {
+            let step = weapon.spread / ((pellets - 1) as f32);
+            (0..pellets)
+                .map(|i| {
+                    let jitter = rng.gen_range(-PELLET_JITTER..=PELLET_JITTER);
+                    (ShotRay::Pellet(i), self.angle + weapon.spread / 2.0 - (i as f32) * step + jitter)
+                })
+                .collect()
+        } else {
+            vec![
+                (ShotRay::Center, self.angle),
+                (ShotRay::Left, self.angle - RAY_SPREAD),
+                (ShotRay::Right, self.angle + RAY_SPREAD)
+            ]
+        };
+        if weapon.is_empty() {
+            // reload on empty, the trigger pull itself doesn't fire
+            weapon.start_reload();
             return ShootEvent {
-                world_event: None,
-                still_reloading: true,
+                world_events: Vec::new(),
+                outcome: ShotOutcome::OutOfAmmo,
+                hit: None,
             };
         }
-        self.weapon.elapsed_reload_t = 1; // start reloading
-        for &angle in &angles {
-            let hit_enemy = RaycastSystem::shoot_bullet_raycast(self.pos, angle, &world_layout);
-            match hit_enemy {
-                Some(enemy) => {
+        if weapon.magazine_size > 0 {
+            weapon.current_ammo -= 1;
+        }
+        // the whole shot strays together, so the side rays keep their place around the center
+        let stray = if weapon.bloom > 0.0 {
+            rng.gen_range(-weapon.bloom..=weapon.bloom)
+        } else {
+            0.0
+        };
+        if weapon.fire_mode() == FireMode::Auto {
+            weapon.bloom = (weapon.bloom + AUTO_FIRE_BLOOM_PER_SHOT).min(AUTO_FIRE_MAX_BLOOM);
+        }
+        let rays: Vec<(ShotRay, f32)> = rays
+            .into_iter()
+            .map(|(ray, angle)| (ray, angle + stray))
+            .collect();
+        weapon.elapsed_reload_t = 1; // start reloading
+        if weapon.projectile_speed > 0.0 {
+            // hits once it lands, see ProjectileSystem
+            projectiles.add(
+                self.pos + Vec2::splat(0.5),
+                Vec2::from_angle(self.angle + stray) * weapon.projectile_speed,
+                weapon.damage,
+                ProjectileOwner::Player { damage_type: weapon.damage_type, blast_radius: weapon.blast_radius }
+            );
+            return ShootEvent {
+                world_events: Vec::new(),
+                outcome: ShotOutcome::Fired,
+                hit: None,
+            };
+        }
+        let max_hits = 1 + (self.weapon().penetration as usize);
+        let range = self.weapon().range as u32;
+        let mut nearest: Option<ShotHit> = None;
+        let mut world_events = Vec::new();
+        for (ray, angle) in rays {
+            let hit_enemies: Vec<(EnemyHandle, f32)> = RaycastSystem::shoot_bullet_raycast(
+                self.pos,
+                angle,
+                world_layout,
+                &enemies.alives,
+                max_hits
+            )
+                .into_iter()
+                .map(|enemy| {
                     let enemy_pos = enemies.positions
                         .get(enemy.0 as usize)
                         .expect("Invalid enemy handle");
-                    let enemy_dist = self.pos.distance(*enemy_pos);
-                    let event = if (enemy_dist.round() as u32) > (self.weapon.range as u32) {
-                        None
-                    } else {
-                        Some(WorldEventHandleBased::player_hit_enemy(enemy))
-                    };
-                    return ShootEvent {
-                        world_event: event,
-                        still_reloading: false,
-                    };
-                }
-                _ => {}
+                    (enemy, self.pos.distance(*enemy_pos))
+                })
+                .take_while(|(_, enemy_dist)| (enemy_dist.round() as u32) <= range)
+                .collect();
+            let Some(&(_, distance)) = hit_enemies.first() else {
+                continue;
+            };
+            let is_nearest = nearest.is_none_or(|hit| distance < hit.distance);
+            if is_nearest && pellets == 1 {
+                world_events.clear();
+            }
+            if is_nearest || pellets > 1 {
+                // the penetration depth starts over for every pellet
+                world_events.extend(
+                    hit_enemies
+                        .into_iter()
+                        .enumerate()
+                        .map(|(depth, (enemy, _))| WorldEventHandleBased::player_hit_enemy(enemy, depth as u8))
+                );
+            }
+            if is_nearest {
+                nearest = Some(ShotHit { ray, distance });
             }
         }
-        return ShootEvent {
-            world_event: None,
-            still_reloading: false,
-        };
+        ShootEvent {
+            world_events,
+            outcome: ShotOutcome::Fired,
+            hit: nearest,
+        }
     }
 }
 struct SurroundingObjects {
@@ -946,7 +2162,7 @@ struct SurroundingObjectsSystem;
 impl SurroundingObjectsSystem {
     fn get_surrounding_objects(
         player_pos: &Vec2,
-        world_layout: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
+        world_layout: &Grid<EntityType>,
         check_radius: u16
     ) -> SurroundingObjects {
         let player_tile = Tile::from_vec2(*player_pos);
@@ -956,9 +2172,9 @@ impl SurroundingObjectsSystem {
         };
 
         let start_x = ((player_tile.x as i32) - (check_radius as i32)).max(0) as usize;
-        let end_x = (player_tile.x + check_radius + 1).min(WORLD_WIDTH as u16) as usize;
+        let end_x = (player_tile.x + check_radius + 1).min(world_layout.width() as u16) as usize;
         let start_y = ((player_tile.y as i32) - (check_radius as i32)).max(0) as usize;
-        let end_y = (player_tile.y + check_radius + 1).min(WORLD_HEIGHT as u16) as usize;
+        let end_y = (player_tile.y + check_radius + 1).min(world_layout.height() as u16) as usize;
 
         for y in start_y..end_y {
             for x in start_x..end_x {
@@ -981,7 +2197,7 @@ struct MovingEntityCollisionSystem;
 impl MovingEntityCollisionSystem {
     fn check_player_enemy_collisions(
         player_pos: &Vec2,
-        world_layout: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
+        world_layout: &Grid<EntityType>,
         enemy_positions: &Vec<Vec2>,
         enemy_sizes: &Vec<Vec2>,
         enemy_alives: &Vec<bool>
@@ -1022,17 +2238,113 @@ impl MovingEntityCollisionSystem {
         distance_x < min_distance_x && distance_y < min_distance_y
     }
 }
+/// moves whoever stands on one end of a teleporter pair next to the other end
+struct TeleporterSystem;
+impl TeleporterSystem {
+    fn partner(teleporters: &[TeleporterPair], tile: Tile) -> Option<(usize, usize)> {
+        teleporters.iter().find_map(|pair| pair.partner((tile.x as usize, tile.y as usize)))
+    }
+    /// a free tile next to the destination end, None while all of them are occupied so the teleport waits
+    fn landing_tile(
+        destination: (usize, usize),
+        teleporters: &[TeleporterPair],
+        world_layout: &Grid<EntityType>
+    ) -> Option<Vec2> {
+        const NEIGHBOURS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        NEIGHBOURS.iter().find_map(|(dx, dy)| {
+            let x = (destination.0 as isize) + dx;
+            let y = (destination.1 as isize) + dy;
+            if x < 0 || y < 0 || !world_layout.contains(x as usize, y as usize) {
+                return None;
+            }
+            let (x, y) = (x as usize, y as usize);
+            // landing on another teleporter would chain the jump
+            let is_teleporter = teleporters.iter().any(|pair| pair.partner((x, y)).is_some());
+            if world_layout[y][x] == EntityType::None && !is_teleporter {
+                Some(Vec2::new(x as f32, y as f32))
+            } else {
+                None
+            }
+        })
+    }
+    /// one physics frame of the player's lockout, true when the player was teleported
+    fn update_player(player: &mut Player, teleporters: &[TeleporterPair], world_layout: &mut Grid<EntityType>) -> bool {
+        player.teleport_cooldown = (player.teleport_cooldown - PHYSICS_FRAME_TIME).max(0.0);
+        if player.teleport_cooldown > 0.0 {
+            return false;
+        }
+        let player_tile = Tile::from_vec2(player.pos);
+        let landing = TeleporterSystem::partner(teleporters, player_tile).and_then(|end|
+            TeleporterSystem::landing_tile(end, teleporters, world_layout)
+        );
+        let Some(landing) = landing else {
+            return false;
+        };
+        // same tile convention as MovementSystem::update_player
+        if world_layout[player_tile.y as usize][player_tile.x as usize] == EntityType::Player {
+            world_layout[player_tile.y as usize][player_tile.x as usize] = EntityType::None;
+        }
+        player.pos = landing;
+        world_layout[landing.y as usize][landing.x as usize] = EntityType::Player;
+        player.teleport_cooldown = TELEPORT_LOCKOUT;
+        true
+    }
+    fn update_enemies(
+        enemies: &mut Enemies,
+        teleporters: &[TeleporterPair],
+        world_layout: &mut Grid<EntityType>,
+        content: &ContentRegistry
+    ) {
+        for id in 0..enemies.positions.len() {
+            let cooldown = &mut enemies.teleport_cooldowns[id];
+            *cooldown = (*cooldown - PHYSICS_FRAME_TIME).max(0.0);
+            if *cooldown > 0.0 || !enemies.alives[id] || !content.enemies[&enemies.kinds[id]].uses_teleporters {
+                continue;
+            }
+            let enemy_tile = Tile::from_vec2(enemies.positions[id]);
+            let landing = TeleporterSystem::partner(teleporters, enemy_tile).and_then(|end|
+                TeleporterSystem::landing_tile(end, teleporters, world_layout)
+            );
+            if let Some(landing) = landing {
+                TeleporterSystem::move_enemy(enemies, id, landing, world_layout);
+                enemies.teleport_cooldowns[id] = TELEPORT_LOCKOUT;
+            }
+        }
+    }
+    fn move_enemy(
+        enemies: &mut Enemies,
+        id: usize,
+        destination: Vec2,
+        world_layout: &mut Grid<EntityType>
+    ) {
+        let handle = EntityType::Enemy(EnemyHandle(id as u16));
+        for tile in MovementSystem::get_occupied_tiles(enemies.positions[id], enemies.sizes[id]) {
+            if world_layout[tile.y as usize][tile.x as usize] == handle {
+                world_layout[tile.y as usize][tile.x as usize] = EntityType::None;
+            }
+        }
+        enemies.positions[id] = destination;
+        for tile in MovementSystem::get_occupied_tiles(destination, enemies.sizes[id]) {
+            if world_layout[tile.y as usize][tile.x as usize] == EntityType::None {
+                world_layout[tile.y as usize][tile.x as usize] = handle;
+            }
+        }
+    }
+}
 struct MovementSystem;
 impl MovementSystem {
     fn update_enemies(
         enemies: &mut Enemies,
+        player_pos: Vec2,
         walls: &Vec<Vec2>,
         doors: &Doors,
-        world_layout: &mut [[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
+        world_layout: &mut Grid<EntityType>,
+        floor_types: &FloorGrid,
         current_time: Duration
     ) {
         const COLLISION_THRESHOLD: u32 = 5;
         const COLLISION_TIME_WINDOW: Duration = Duration::from_secs(2);
+        let world_size = Vec2::new(world_layout.width() as f32, world_layout.height() as f32);
 
         for (id, ((pos, vel), size)) in enemies.positions
             .iter_mut()
@@ -1040,10 +2352,26 @@ impl MovementSystem {
             .zip(enemies.sizes.iter())
             .enumerate() {
             let prev_tiles = Self::get_occupied_tiles(*pos, *size);
-            let mut new_pos = *pos + *vel * PHYSICS_FRAME_TIME;
-
-            let (collided_x, collided_y) = Self::resolve_wall_collisions(&mut new_pos, walls, *pos);
-            Self::player_resolve_door_collision(pos, doors);
+            let velocity = *vel + enemies.scatter_velocities[id];
+            let displacement = velocity * PHYSICS_FRAME_TIME * Self::floor_speed(floor_types, *pos);
+            let steps = Self::substeps(displacement);
+            let step = displacement / (steps as f32);
+            let mut new_pos = *pos;
+            let (mut collided_x, mut collided_y) = (false, false);
+            for _ in 0..steps {
+                let step_start = new_pos;
+                new_pos += step;
+                if SOLID_ENEMIES && enemies.alives[id] {
+                    Self::resolve_box_overlap(&mut new_pos, *size, player_pos, Vec2::new(1.0, 1.0));
+                }
+                let (step_x, step_y) = Self::resolve_wall_collisions(&mut new_pos, walls, step_start);
+                collided_x |= step_x;
+                collided_y |= step_y;
+                Self::player_resolve_door_collision(&mut new_pos, doors);
+            }
+            let (edge_x, edge_y) = Self::keep_inside_map(&mut new_pos, *size, world_size);
+            collided_x |= edge_x;
+            collided_y |= edge_y;
             if collided_x {
                 enemies.collision_data.x_collisions[id] += 1;
             }
@@ -1099,33 +2427,85 @@ impl MovementSystem {
         }
     }
 
+    /// speed multiplier of the floor under an entity at `pos`
+    fn floor_speed(floor_types: &FloorGrid, pos: Vec2) -> f32 {
+        let tile = Tile::from_vec2(pos);
+        match floor_types.get(tile.x as usize, tile.y as usize) {
+            Some(FloorType::Water) => WATER_SPEED_MULTIPLIER,
+            _ => 1.0,
+        }
+    }
+
+    /// how many collision checked steps a movement needs so that none of them skips over a wall,
+    /// slow movers keep the single step
+    fn substeps(displacement: Vec2) -> u32 {
+        let length = displacement.length();
+        if length <= SUBSTEP_THRESHOLD {
+            return 1;
+        }
+        ((length / MAX_SUBSTEP_LENGTH).ceil() as u32).min(MAX_SUBSTEPS)
+    }
+
+    /// the edge of the map stops movers like a wall would, maps don't need a border of walls,
+    /// returns whether it stopped them along x and y
+    fn keep_inside_map(position: &mut Vec2, size: Vec2, world_size: Vec2) -> (bool, bool) {
+        let inside = position.clamp(Vec2::ZERO, (world_size - size).max(Vec2::ZERO));
+        let stopped = (inside.x != position.x, inside.y != position.y);
+        *position = inside;
+        stopped
+    }
+
+    /// Replays the movement from old_position one axis at a time and stops each axis at the wall it runs into,
+    /// the movement along the other axis still happens. Running diagonally into a wall slides along it instead of
+    /// catching on the seams between its tiles. Walls the box overlapped before the movement are left alone.
+    /// Returns whether the movement was stopped along x and y.
     fn resolve_wall_collisions(
         position: &mut Vec2,
         walls: &Vec<Vec2>,
         old_position: Vec2
     ) -> (bool, bool) {
-        let mut collided_x = false;
-        let mut collided_y = false;
-
-        for wall in walls.iter() {
-            let point_1 = Vec2::new(wall.x + 0.5, wall.y + 0.5);
-            let point_2 = Vec2::new(position.x + 0.5, position.y + 0.5);
-
-            let distance_x = (point_2.x - point_1.x).abs();
-            let distance_y = (point_2.y - point_1.y).abs();
-
-            if distance_x < 1.0 && distance_y < 1.0 {
-                if distance_x > distance_y {
-                    position.x = old_position.x;
-                    collided_x = true;
-                } else {
-                    position.y = old_position.y;
-                    collided_y = true;
+        let overlaps = |position: Vec2, wall: Vec2| {
+            (position.x - wall.x).abs() < 1.0 && (position.y - wall.y).abs() < 1.0
+        };
+        let delta = *position - old_position;
+        let mut resolved = old_position;
+        let mut collided = [false; 2];
+        for axis in 0..2 {
+            let start = resolved;
+            resolved[axis] += delta[axis];
+            for wall in walls.iter() {
+                if !overlaps(resolved, *wall) || overlaps(start, *wall) {
+                    continue;
                 }
+                // back against the face it came from
+                resolved[axis] = if delta[axis] > 0.0 {
+                    resolved[axis].min(wall[axis] - 1.0)
+                } else {
+                    resolved[axis].max(wall[axis] + 1.0)
+                };
+                collided[axis] = true;
             }
         }
+        *position = resolved;
+        (collided[0], collided[1])
+    }
 
-        (collided_x, collided_y)
+    /// pushes the box out along the axis of least penetration, leaving a sliver of overlap
+    /// so MovingEntityCollisionSystem still registers the contact
+    fn resolve_box_overlap(position: &mut Vec2, size: Vec2, other_pos: Vec2, other_size: Vec2) {
+        const CONTACT_EPSILON: f32 = 0.02;
+        let delta = *position + size * 0.5 - (other_pos + other_size * 0.5);
+        let min_distance = (size + other_size) * 0.5 - Vec2::splat(CONTACT_EPSILON);
+        let overlap_x = min_distance.x - delta.x.abs();
+        let overlap_y = min_distance.y - delta.y.abs();
+        if overlap_x <= 0.0 || overlap_y <= 0.0 {
+            return;
+        }
+        if delta.x.abs() > delta.y.abs() {
+            position.x += overlap_x * delta.x.signum();
+        } else {
+            position.y += overlap_y * delta.y.signum();
+        }
     }
 
     fn get_occupied_tiles(pos: Vec2, size: Vec2) -> Vec<Tile> {
@@ -1147,14 +2527,30 @@ impl MovementSystem {
         player: &mut Player,
         walls: &Vec<Vec2>,
         doors: &Doors,
-        world_layout: &mut [[EntityType; WORLD_WIDTH]; WORLD_HEIGHT]
+        enemies: &Enemies,
+        world_layout: &mut Grid<EntityType>,
+        floor_types: &FloorGrid
     ) {
         let prev_tile = Tile::from_vec2(player.pos);
-        player.pos += player.vel * PHYSICS_FRAME_TIME * 1.5;
-        Self::player_resolve_wall_collisions(&mut player.pos, walls); // we could only iterate over a subset using Surrounding
-        Self::player_resolve_door_collision(&mut player.pos, doors); // we could only iterate over a subset using Surrounding.
-        if player.vel.length() > 0.0 {
-            player.bobbing_time += PHYSICS_FRAME_TIME ;
+        let rate = if player.target_vel == Vec2::ZERO { MOVE_DECELERATION } else { MOVE_ACCELERATION };
+        player.vel = player.vel.lerp(player.target_vel, (rate * PHYSICS_FRAME_TIME).min(1.0));
+        let displacement =
+            player.vel * PHYSICS_FRAME_TIME * 1.5 * Self::floor_speed(floor_types, player.pos);
+        let steps = Self::substeps(displacement);
+        for _ in 0..steps {
+            let step_start = player.pos;
+            player.pos += displacement / (steps as f32);
+            if SOLID_ENEMIES {
+                Self::player_resolve_enemy_collisions(&mut player.pos, enemies, world_layout);
+            }
+            Self::player_resolve_wall_collisions(&mut player.pos, step_start, walls); // we could only iterate over a subset using Surrounding
+            Self::player_resolve_door_collision(&mut player.pos, doors); // we could only iterate over a subset using Surrounding.
+        }
+        let world_size = Vec2::new(world_layout.width() as f32, world_layout.height() as f32);
+        Self::keep_inside_map(&mut player.pos, Vec2::ONE, world_size);
+        if player.vel.length() > BOBBING_MIN_SPEED {
+            // faster steps sway faster
+            player.bobbing_time += PHYSICS_FRAME_TIME * player.vel.length() / PLAYER_SPEED;
         } else {
             player.bobbing_time = 0.0;
         }
@@ -1184,7 +2580,36 @@ impl MovementSystem {
         }
     }
 
-    fn player_resolve_wall_collisions(position: &mut Vec2, walls: &Vec<Vec2>) {
+    fn player_resolve_enemy_collisions(
+        position: &mut Vec2,
+        enemies: &Enemies,
+        world_layout: &Grid<EntityType>
+    ) {
+        let check_radius = 2; // based on maximum enemy size
+        let surrounding_objects = SurroundingObjectsSystem::get_surrounding_objects(
+            position,
+            world_layout,
+            check_radius
+        );
+        for enemy_handle in surrounding_objects.enemies {
+            let enemy_index = enemy_handle.0 as usize;
+            if !enemies.alives[enemy_index] {
+                continue;
+            }
+            Self::resolve_box_overlap(
+                position,
+                Vec2::new(1.0, 1.0),
+                enemies.positions[enemy_index],
+                enemies.sizes[enemy_index]
+            );
+        }
+    }
+
+    /// slides along the walls like the enemies, see resolve_wall_collisions
+    fn player_resolve_wall_collisions(position: &mut Vec2, old_position: Vec2, walls: &Vec<Vec2>) {
+        Self::resolve_wall_collisions(position, walls, old_position);
+        // anything that still overlaps was already in the way before the step, like a wall an enemy pushed
+        // the player into, and is pushed out along its shallowest axis
         for wall in walls.iter() {
             let point_1 = Vec2::new(wall.x + 0.5, wall.y + 0.5);
             let point_2 = Vec2::new(position.x + 0.5, position.y + 0.5);
@@ -1208,7 +2633,7 @@ impl MovementSystem {
             let door_pos = doors.positions[i];
             let door_opened = doors.opened[i];
             if door_opened {
-                return;
+                continue;
             }
             let point_1 = Vec2::new(door_pos.x + 0.5, door_pos.y + 0.5);
             let point_2 = Vec2::new(position.x + 0.5, position.y + 0.5);
@@ -1233,31 +2658,59 @@ impl RaycastSystem {
     fn raycast(
         origin: Vec2,
         player_angle: f32,
+        fov: f32,
         doors: &Doors,
-        tile_map: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT]
+        tile_map: &Grid<EntityType>
     ) -> Vec<RaycastStepResult> {
         let mut res = Vec::new();
         for i in 0..AMOUNT_OF_RAYS {
             let ray_angle =
                 player_angle +
-                config::config::PLAYER_FOV / 2.0 -
-                ((i as f32) / (AMOUNT_OF_RAYS as f32)) * config::config::PLAYER_FOV;
+                fov / 2.0 -
+                ((i as f32) / (AMOUNT_OF_RAYS as f32)) * fov;
 
-            let step_result = RaycastSystem::daa_raycast(origin, ray_angle, doors, tile_map);
-            if let Some(step) = step_result {
-                res.push(step);
+            // every ray keeps its result, the renderers use the index as the screen column
+            let mut step_result = RaycastSystem::daa_raycast(origin, ray_angle, doors, tile_map).unwrap_or_else(||
+                RaycastSystem::edge_of_map(origin, ray_angle, tile_map)
+            );
+            // the distance to the camera plane instead of along the ray, or walls would bow out towards the edges
+            let to_camera_plane = (ray_angle - player_angle).cos();
+            step_result.corrected_distance *= to_camera_plane;
+            if let Some(behind) = &mut step_result.behind_door {
+                behind.corrected_distance *= to_camera_plane;
             }
+            res.push(step_result);
         }
         res
     }
+    /// where a ray that hit nothing leaves the map, there is no wall to draw there
+    fn edge_of_map(origin: Vec2, specific_angle: f32, tile_map: &Grid<EntityType>) -> RaycastStepResult {
+        let direction = Vec2::new(specific_angle.cos(), specific_angle.sin());
+        let to_edge_x =
+            (if direction.x > 0.0 { (tile_map.width() as f32) - origin.x } else { origin.x }) /
+            direction.x.abs();
+        let to_edge_y =
+            (if direction.y > 0.0 { (tile_map.height() as f32) - origin.y } else { origin.y }) /
+            direction.y.abs();
+        let is_x_side = to_edge_x < to_edge_y;
+        let distance = to_edge_x.min(to_edge_y);
+        RaycastStepResult {
+            entity_type: EntityType::None,
+            intersection_pos: origin + direction * distance,
+            intersection_site: RaycastSystem::intersected_site(direction, is_x_side),
+            corrected_distance: distance,
+            behind_door: None,
+        }
+    }
 
-    fn daa_raycast(
+    /// walks the tiles along `direction` from `origin` until `stop_on` returns something or the ray leaves
+    /// the map, the walls of the view and the line of sight of the enemies both step through the grid here
+    fn cast_ray<T>(
         origin: Vec2,
-        specific_angle: f32,
-        doors: &Doors,
-        tile_map: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT]
-    ) -> Option<RaycastStepResult> {
-        let direction = Vec2::new(specific_angle.cos(), specific_angle.sin());
+        direction: Vec2,
+        tile_map: &Grid<EntityType>,
+        mut stop_on: impl FnMut(RayStep) -> Option<T>
+    ) -> Option<T> {
         let relative_tile_dist_x = 1.0 / direction.x.abs();
         let relative_tile_dist_y = 1.0 / direction.y.abs();
         let step_x: isize = if direction.x > 0.0 { 1 } else { -1 };
@@ -1274,12 +2727,7 @@ impl RaycastSystem {
         } else {
             ((curr_map_tile_y as f32) + 1.0 - origin.y) * relative_tile_dist_y
         };
-        while
-            curr_map_tile_x > 0 &&
-            curr_map_tile_x < WORLD_WIDTH &&
-            curr_map_tile_y > 0 &&
-            curr_map_tile_y < WORLD_HEIGHT
-        {
+        loop {
             let is_x_side = dist_side_x < dist_side_y;
             if is_x_side {
                 dist_side_x += relative_tile_dist_x;
@@ -1288,191 +2736,294 @@ impl RaycastSystem {
                 dist_side_y += relative_tile_dist_y;
                 curr_map_tile_y = ((curr_map_tile_y as isize) + step_y) as usize;
             }
-            match tile_map[curr_map_tile_y][curr_map_tile_x] {
+            // a map without a border of walls lets the ray run off the grid
+            let Some(&tile) = tile_map.get(curr_map_tile_x, curr_map_tile_y) else {
+                break;
+            };
+            let step = RayStep {
+                tile,
+                is_x_side,
+                distance: if is_x_side {
+                    dist_side_x - relative_tile_dist_x
+                } else {
+                    dist_side_y - relative_tile_dist_y
+                },
+            };
+            if let Some(result) = stop_on(step) {
+                return Some(result);
+            }
+        }
+        None
+    }
+    fn intersected_site(direction: Vec2, is_x_side: bool) -> IntersectedSite {
+        if is_x_side {
+            if direction.x > 0.0 { IntersectedSite::XLeft } else { IntersectedSite::XRight }
+        } else {
+            if direction.y > 0.0 { IntersectedSite::YTop } else { IntersectedSite::YBottom }
+        }
+    }
+
+    fn daa_raycast(
+        origin: Vec2,
+        specific_angle: f32,
+        doors: &Doors,
+        tile_map: &Grid<EntityType>
+    ) -> Option<RaycastStepResult> {
+        let direction = Vec2::new(specific_angle.cos(), specific_angle.sin());
+        RaycastSystem::cast_ray(origin, direction, tile_map, |step| {
+            let tile_intersection = origin + direction * step.distance;
+            let intersection_site = RaycastSystem::intersected_site(direction, step.is_x_side);
+            match step.tile {
                 EntityType::Wall(handle) => {
-                    let distance = if is_x_side {
-                        dist_side_x - relative_tile_dist_x
-                    } else {
-                        dist_side_y - relative_tile_dist_y
-                    };
-                    return Some(RaycastStepResult {
+                    Some(RaycastStepResult {
                         entity_type: EntityType::Wall(handle),
-                        intersection_pos: Vec2::new(
-                            origin.x + direction.x * distance,
-                            origin.y + direction.y * distance
-                        ),
-                        intersection_site: if is_x_side {
-                            if direction.x > 0.0 {
-                                IntersectedSite::XLeft
-                            } else {
-                                IntersectedSite::XRight
-                            }
-                        } else {
-                            if direction.y > 0.0 {
-                                IntersectedSite::YTop
-                            } else {
-                                IntersectedSite::YBottom
-                            }
-                        },
-                        corrected_distance: if is_x_side {
-                            dist_side_x - relative_tile_dist_x
-                        } else {
-                            dist_side_y - relative_tile_dist_y
-                        },
-                    });
+                        intersection_pos: tile_intersection,
+                        intersection_site,
+                        corrected_distance: step.distance,
+                        behind_door: None,
+                    })
                 }
                 EntityType::Door(handle) => {
-                    let hitbox = &doors.get_door_hitbox(handle);
-                    if hitbox.is_none() {continue;}
-                    let distance = if is_x_side {
-                        dist_side_x - relative_tile_dist_x
-                    } else {
-                        dist_side_y - relative_tile_dist_y
-                    };
-                    let corrected_distance = if is_x_side {
-                        dist_side_x - relative_tile_dist_x
-                    } else {
-                        dist_side_y - relative_tile_dist_y
-                    };
-                    let tile_intersection = Vec2::new(
-                        origin.x + direction.x * distance,
-                        origin.y + direction.y * distance
-                    );
-
-                    if !doors.opened[handle.0 as usize] {
-                        return Some(RaycastStepResult {
-                            entity_type: EntityType::Door(handle),
-                            intersection_pos: Vec2::new(
-                                origin.x + direction.x * distance,
-                                origin.y + direction.y * distance
-                            ),
-                            intersection_site: if is_x_side {
-                                if direction.x > 0.0 {
-                                    IntersectedSite::XLeft
-                                } else {
-                                    IntersectedSite::XRight
-                                }
-                            } else {
-                                if direction.y > 0.0 {
-                                    IntersectedSite::YTop
-                                } else {
-                                    IntersectedSite::YBottom
-                                }
-                            },
-                            corrected_distance: if is_x_side {
-                                dist_side_x - relative_tile_dist_x
-                            } else {
-                                dist_side_y - relative_tile_dist_y
-                            },
-                        });
-                    }
-                    if
-                        let Some(point) = Doors::get_ray_intersection_point(
-                            &hitbox.expect("Invalid handle to door"),
+                    let hitbox = doors.get_door_hitbox(handle)?;
+                    let behind_door = || {
+                        RaycastSystem::raycast_behind_door(
                             tile_intersection,
-                            direction
+                            step.distance,
+                            specific_angle,
+                            doors,
+                            tile_map
                         )
-                    {
+                    };
+                    if !doors.opened[handle.0 as usize] {
                         return Some(RaycastStepResult {
                             entity_type: EntityType::Door(handle),
-                            intersection_pos: point,
-                            intersection_site: if is_x_side {
-                                if direction.x > 0.0 {
-                                    IntersectedSite::XLeft
-                                } else {
-                                    IntersectedSite::XRight
-                                }
-                            } else {
-                                if direction.y > 0.0 {
-                                    IntersectedSite::YTop
-                                } else {
-                                    IntersectedSite::YBottom
-                                }
-                            },
-                            corrected_distance: corrected_distance +
-                            point.distance(tile_intersection),
+                            intersection_pos: tile_intersection,
+                            intersection_site,
+                            corrected_distance: step.distance,
+                            behind_door: behind_door(),
                         });
                     }
+                    let (point, is_x_side) = Doors::get_ray_intersection_point(
+                        &hitbox,
+                        tile_intersection,
+                        direction
+                    )?;
+                    // a ray that passes the open part of the tile hits the edge of the door, which faces
+                    // the other way than the side of the tile it came through
+                    let intersection_site = if point == tile_intersection {
+                        intersection_site
+                    } else {
+                        RaycastSystem::intersected_site(direction, is_x_side)
+                    };
+                    Some(RaycastStepResult {
+                        entity_type: EntityType::Door(handle),
+                        intersection_pos: point,
+                        intersection_site,
+                        corrected_distance: step.distance + point.distance(tile_intersection),
+                        behind_door: behind_door(),
+                    })
                 }
-                _ => {}
+                _ => None,
             }
+        })
+    }
+    /// whether an enemy at `from` can see `to`, walls and closed doors block the view, the centers of both
+    /// tiles are expected
+    fn line_of_sight(from: Vec2, to: Vec2, doors: &Doors, tile_map: &Grid<EntityType>) -> bool {
+        let distance = from.distance(to);
+        let direction = (to - from).normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return true;
         }
-        return None;
+        RaycastSystem::cast_ray(from, direction, tile_map, |step| {
+            if step.distance >= distance {
+                // entered a tile beyond the target, nothing was in the way
+                return Some(true);
+            }
+            match step.tile {
+                EntityType::Wall(_) => Some(false),
+                EntityType::Door(handle) if !doors.opened[handle.0 as usize] => Some(false),
+                _ => None,
+            }
+        }).unwrap_or(true)
     }
+    /// continues a ray that entered a door tile at tile_intersection, only one door deep
+    fn raycast_behind_door(
+        tile_intersection: Vec2,
+        distance_to_door_tile: f32,
+        specific_angle: f32,
+        doors: &Doors,
+        tile_map: &Grid<EntityType>
+    ) -> Option<Box<RaycastStepResult>> {
+        const INSIDE_DOOR_TILE: f32 = 0.001;
+        let direction = Vec2::new(specific_angle.cos(), specific_angle.sin());
+        // starting inside the door tile makes the first dda step leave it
+        let origin = tile_intersection + direction * INSIDE_DOOR_TILE;
+        let mut behind = RaycastSystem::daa_raycast(origin, specific_angle, doors, tile_map)?;
+        behind.corrected_distance += distance_to_door_tile + INSIDE_DOOR_TILE;
+        behind.behind_door = None;
+        Some(Box::new(behind))
+    }
+    /// enemies along the ray in the order they are hit, at most max_hits, walls and doors stop the shot,
+    /// dying enemies still fill their tiles during the death animation but the shot passes through them
     fn shoot_bullet_raycast(
         origin: Vec2,
         specific_angle: f32,
-        tile_map: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT]
-    ) -> Option<EnemyHandle> {
-        // NOTE returns a handle
+        tile_map: &Grid<EntityType>,
+        enemy_alives: &[bool],
+        max_hits: usize
+    ) -> Vec<EnemyHandle> {
+        let mut hits = Vec::new();
         let direction = Vec2::new(specific_angle.cos(), specific_angle.sin());
-        let relative_tile_dist_x = 1.0 / direction.x.abs();
-        let relative_tile_dist_y = 1.0 / direction.y.abs();
-        let step_x: isize = if direction.x > 0.0 { 1 } else { -1 };
-        let step_y: isize = if direction.y > 0.0 { 1 } else { -1 };
-        let mut curr_map_tile_x = origin.x.trunc() as usize;
-        let mut curr_map_tile_y = origin.y.trunc() as usize;
-        let mut dist_side_x = if direction.x < 0.0 {
-            (origin.x - (curr_map_tile_x as f32)) * relative_tile_dist_x
-        } else {
-            ((curr_map_tile_x as f32) + 1.0 - origin.x) * relative_tile_dist_x
-        };
-        let mut dist_side_y = if direction.y < 0.0 {
-            (origin.y - (curr_map_tile_y as f32)) * relative_tile_dist_y
-        } else {
-            ((curr_map_tile_y as f32) + 1.0 - origin.y) * relative_tile_dist_y
-        };
-
-        while
-            curr_map_tile_x > 0 &&
-            curr_map_tile_x < WORLD_WIDTH &&
-            curr_map_tile_y > 0 &&
-            curr_map_tile_y < WORLD_HEIGHT
-        {
-            let is_x_side = dist_side_x < dist_side_y;
-            if is_x_side {
-                dist_side_x += relative_tile_dist_x;
-                curr_map_tile_x = ((curr_map_tile_x as isize) + step_x) as usize;
-            } else {
-                dist_side_y += relative_tile_dist_y;
-                curr_map_tile_y = ((curr_map_tile_y as isize) + step_y) as usize;
-            }
-            match tile_map[curr_map_tile_y][curr_map_tile_x] {
-                EntityType::Wall(_) => {
-                    return None;
+        RaycastSystem::cast_ray(origin, direction, tile_map, |step| {
+            match step.tile {
+                EntityType::Wall(_) | EntityType::Door(_) => Some(()),
+                // an enemy can cover more than one tile
+                EntityType::Enemy(handle) if enemy_alives[handle.0 as usize] && !hits.contains(&handle) => {
+                    hits.push(handle);
+                    (hits.len() >= max_hits).then_some(())
                 }
-                EntityType::Door(_) => {
-                    return None;
-                }
-                EntityType::Enemy(handle) => {
-                    return Some(handle);
-                }
-                _ => {}
+                _ => None,
             }
+        });
+        hits
+    }
+}
+/// maps world tiles to minimap pixels, the minimap stays anchored to the top right corner when scaled
+#[derive(Clone, Copy)]
+struct MinimapTransform {
+    origin: Vec2,
+    tile_size: Vec2,
+    background: Rect,
+    ui_scale: f32,
+}
+impl MinimapTransform {
+    fn new(ui_scale: f32, minimap_offset: Vec2) -> Self {
+        let background_x =
+            (SCREEN_WIDTH as f32) - ((SCREEN_WIDTH as f32) - MAP_X_OFFSET) * ui_scale;
+        MinimapTransform {
+            origin: Vec2::new(background_x, 0.0) - minimap_offset,
+            tile_size: Vec2::new(MINIMAP_TILE_WIDTH, MINIMAP_TILE_HEIGHT) * ui_scale,
+            background: Rect::new(
+                background_x,
+                0.0,
+                (SCREEN_WIDTH as f32) - background_x,
+                270.0 * ui_scale
+            ),
+            ui_scale,
         }
-        None
+    }
+    /// the whole screen, at zoom 1.0 the full map fits on it
+    fn overview(camera_pos: Vec2, zoom: f32, ui_scale: f32, world_size: Vec2) -> Self {
+        let fit = ((SCREEN_WIDTH as f32) / world_size.x).min((SCREEN_HEIGHT as f32) / world_size.y);
+        let tile_size = Vec2::splat(fit * zoom);
+        MinimapTransform {
+            origin: Vec2::new(HALF_SCREEN_WIDTH, HALF_SCREEN_HEIGHT) - camera_pos * tile_size,
+            tile_size,
+            background: Rect::new(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+            ui_scale,
+        }
+    }
+    fn to_screen(self, world_pos: Vec2) -> Vec2 {
+        self.origin + world_pos * self.tile_size
     }
 }
 struct RenderMap;
 impl RenderMap {
-    #[inline(always)]
+    /// debug outlines of the acoustic zones, the one the player is in is highlighted
+    fn render_reverb_zones(zones: &[ReverbZone], current: Option<usize>, minimap: MinimapTransform) {
+        for (i, zone) in zones.iter().enumerate() {
+            let screen_pos = minimap.to_screen(Vec2::new(zone.x as f32, zone.y as f32));
+            let size = Vec2::new(zone.width as f32, zone.height as f32) * minimap.tile_size;
+            let color = match zone.profile {
+                ReverbProfile::SmallRoom => GREEN,
+                ReverbProfile::Corridor => ORANGE,
+                ReverbProfile::LargeHall => PURPLE,
+            };
+            let thickness = if current == Some(i) { 3.0 } else { 1.0 };
+            draw_rectangle_lines(screen_pos.x, screen_pos.y, size.x, size.y, thickness, color);
+        }
+    }
+    /// a circle over the reach of every field, fading with its age
+    fn render_repulsion_fields(fields: &[RepulsionField], minimap: MinimapTransform) {
+        for field in fields {
+            let center = minimap.to_screen(field.center);
+            let alpha = 1.0 - field.age / REPULSION_DURATION;
+            let radius = REPULSION_RADIUS * minimap.tile_size.x;
+            draw_circle_lines(center.x, center.y, radius, 2.0, Color::new(1.0, 0.5, 0.0, alpha));
+        }
+    }
+    /// a warning sign on the tile of every map diagnostic
+    fn render_map_warnings(warnings: &[Diagnostic], minimap: MinimapTransform) {
+        for warning in warnings {
+            let (x, y) = warning.tile;
+            let top_left = minimap.to_screen(Vec2::new(x as f32, y as f32));
+            let size = minimap.tile_size;
+            draw_triangle(
+                Vec2::new(top_left.x + size.x * 0.5, top_left.y),
+                Vec2::new(top_left.x, top_left.y + size.y),
+                top_left + size,
+                YELLOW
+            );
+            font::draw("!", top_left.x + size.x * 0.35, top_left.y + size.y, TextStyle::Fixed(size.y), BLACK);
+        }
+    }
+    /// notes that were read are drawn hollow
+    fn render_notes(notes: &[Note], read: &[bool], minimap: MinimapTransform, theme: &HudTheme) {
+        for (note, read) in notes.iter().zip(read) {
+            let top_left = minimap.to_screen(Vec2::new(note.x as f32 + 0.2, note.y as f32 + 0.2));
+            let size = minimap.tile_size * 0.6;
+            if *read {
+                draw_rectangle_lines(top_left.x, top_left.y, size.x, size.y, 2.0, theme.minimap_note.color());
+            } else {
+                draw_rectangle(top_left.x, top_left.y, size.x, size.y, theme.minimap_note.color());
+            }
+        }
+    }
+    /// both ends of every pair, linked by a line
+    fn render_teleporters(teleporters: &[TeleporterPair], minimap: MinimapTransform, theme: &HudTheme) {
+        let color = theme.minimap_teleporter.color();
+        let radius = minimap.tile_size.x.min(minimap.tile_size.y) * 0.4;
+        for pair in teleporters {
+            let [a, b] = [pair.a, pair.b].map(|(x, y)|
+                minimap.to_screen(Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+            );
+            draw_line(a.x, a.y, b.x, b.y, 1.0, Color::new(color.r, color.g, color.b, 0.4));
+            draw_circle_lines(a.x, a.y, radius, 2.0, color);
+            draw_circle_lines(b.x, b.y, radius, 2.0, color);
+        }
+    }
+    fn render_exit(exit: (usize, usize), minimap: MinimapTransform) {
+        let top_left = minimap.to_screen(Vec2::new(exit.0 as f32 + 0.15, exit.1 as f32 + 0.15));
+        let size = minimap.tile_size * 0.7;
+        draw_rectangle_lines(top_left.x, top_left.y, size.x, size.y, 2.0, GREEN);
+    }
+    #[inline(always)]
     fn render_world_layout(
-        world_layout: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
-        doors: &Doors
+        world_layout: &Grid<EntityType>,
+        doors: &Doors,
+        minimap: MinimapTransform,
+        theme: &HudTheme
     ) {
-        draw_rectangle(MAP_X_OFFSET, 0.0, (SCREEN_WIDTH as f32) - MAP_X_OFFSET, 270.0, GRAY);
+        draw_rectangle(
+            minimap.background.x,
+            minimap.background.y,
+            minimap.background.w,
+            minimap.background.h,
+            theme.minimap_background.color()
+        );
         let mut draw_doors = Vec::new();
-        for y in 0..WORLD_HEIGHT {
-            for x in 0..WORLD_WIDTH {
+        for y in 0..world_layout.height() {
+            for x in 0..world_layout.width() {
                 match world_layout[y][x] {
                     EntityType::Wall(_) => {
+                        let screen_pos = minimap.to_screen(Vec2::new(x as f32, y as f32));
                         draw_rectangle(
-                            (x as f32) * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25 +
-                                MAP_X_OFFSET,
-                            (y as f32) * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                            (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25,
-                            (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                            BROWN
+                            screen_pos.x,
+                            screen_pos.y,
+                            minimap.tile_size.x,
+                            minimap.tile_size.y,
+                            theme.minimap_wall.color()
                         );
                     }
                     EntityType::Door(handle) => {
@@ -1483,100 +3034,252 @@ impl RenderMap {
             }
         }
         for door in draw_doors {
-            doors.render_door(door);
+            doors.render_door(door, minimap, theme.minimap_door.color());
+        }
+    }
+    /// a dot for every projectile in flight
+    fn render_projectiles_on_map(projectiles: &Projectiles, minimap: MinimapTransform) {
+        for &position in &projectiles.positions {
+            let center = minimap.to_screen(position);
+            draw_circle(
+                center.x,
+                center.y,
+                minimap.tile_size.x * PROJECTILE_RADIUS * 2.0,
+                Color::from_rgba(255, 120, 20, 255)
+            );
         }
     }
     #[inline(always)]
-    fn render_player_and_enemies_on_map(player_pos: Vec2, enemies: &Enemies) {
-        draw_rectangle(
-            player_pos.x * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25 + MAP_X_OFFSET,
-            player_pos.y * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-            (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25,
-            (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-            BLUE
-        );
+    fn render_player_and_enemies_on_map(
+        player_pos: Vec2,
+        player_visible: bool,
+        enemies: &Enemies,
+        pickups: &Pickups,
+        minimap: MinimapTransform,
+        theme: &HudTheme
+    ) {
+        for (&position, item) in pickups.positions.iter().zip(pickups.items.iter()) {
+            let center = minimap.to_screen(position + Vec2::splat(0.5));
+            let size = minimap.tile_size * 0.5;
+            let color = theme.minimap_pickup.color();
+            match item {
+                LootItem::HealthPack(_) => {
+                    draw_rectangle(center.x - size.x * 0.5, center.y - size.y * 0.15, size.x, size.y * 0.3, color);
+                    draw_rectangle(center.x - size.x * 0.15, center.y - size.y * 0.5, size.x * 0.3, size.y, color);
+                }
+                _ => {
+                    draw_rectangle(center.x - size.x * 0.5, center.y - size.y * 0.5, size.x, size.y, color);
+                }
+            }
+        }
+        if player_visible {
+            let player_screen_pos = minimap.to_screen(player_pos);
+            draw_rectangle(
+                player_screen_pos.x,
+                player_screen_pos.y,
+                minimap.tile_size.x,
+                minimap.tile_size.y,
+                theme.minimap_player.color()
+            );
+        }
         for i in 0..enemies.positions.len() {
             let enemy_pos = &enemies.positions[i];
             let enemy_size = &enemies.sizes[i];
             let health = &enemies.healths[i];
-            let x = enemy_pos.x * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25 + MAP_X_OFFSET;
-            let y = enemy_pos.y * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25;
-            draw_rectangle(
-                x,
-                y,
-                enemy_size.x * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25,
-                enemy_size.y * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                RED
-            );
-            let font_size = 16.0;
-            draw_text(
-                &format!("{}", health),
-                x + enemy_size.x * 0.5 * (TILE_SIZE_X_PIXEL as f32) * 0.25 - font_size * 0.25,
-                y + enemy_size.x * 0.5 * (TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                font_size,
-                WHITE
+            let screen_pos = minimap.to_screen(*enemy_pos);
+            let x = screen_pos.x;
+            let y = screen_pos.y;
+            let marker_size = *enemy_size * minimap.tile_size;
+            match theme.minimap_enemy_marker {
+                MarkerShape::Square => {
+                    draw_rectangle(x, y, marker_size.x, marker_size.y, theme.minimap_enemy.color());
+                }
+                MarkerShape::Diamond => {
+                    // four sided polygon starting at 0 degrees, so its corners point up, down and to the sides
+                    draw_poly(
+                        x + marker_size.x * 0.5,
+                        y + marker_size.y * 0.5,
+                        4,
+                        marker_size.x.max(marker_size.y) * 0.7,
+                        0.0,
+                        theme.minimap_enemy.color()
+                    );
+                }
+            }
+            font::draw_centered(
+                &format!("{}", health.ceil()),
+                x + enemy_size.x * 0.5 * minimap.tile_size.x,
+                y + enemy_size.x * 0.5 * minimap.tile_size.y,
+                TextStyle::Fixed(16.0 * minimap.ui_scale),
+                theme.minimap_enemy_health.color()
             );
         }
     }
-    #[inline(always)]
-    fn render_rays(player_origin: Vec2, raycast_result: &Vec<RaycastStepResult>) {
-        for result in raycast_result.iter() {
-            draw_line(
-                player_origin.x * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25 + MAP_X_OFFSET,
-                player_origin.y * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                result.intersection_pos.x * (config::config::TILE_SIZE_X_PIXEL as f32) * 0.25 +
-                    MAP_X_OFFSET,
-                result.intersection_pos.y * (config::config::TILE_SIZE_Y_PIXEL as f32) * 0.25,
-                1.0,
-                WHITE
-            );
+    /// view radius, facing and the line to the player of the enemy picked with the vision overlay key
+    fn render_enemy_vision(vision: &EnemyVisionDebug, player_pos: Vec2, minimap: MinimapTransform) {
+        let center = minimap.to_screen(vision.position + Vec2::splat(0.5));
+        let player_center = minimap.to_screen(player_pos + Vec2::splat(0.5));
+        let sees_player = vision.player_distance <= vision.view_distance && vision.line_of_sight;
+        let (ray_color, radius_color) = if sees_player {
+            (GREEN, Color::new(0.0, 0.89, 0.19, 0.6))
+        } else {
+            (RED, Color::new(0.8, 0.8, 0.8, 0.6))
+        };
+        draw_ellipse_lines(
+            center.x,
+            center.y,
+            vision.view_distance * minimap.tile_size.x,
+            vision.view_distance * minimap.tile_size.y,
+            0.0,
+            2.0,
+            radius_color
+        );
+        draw_line(center.x, center.y, player_center.x, player_center.y, 2.0, ray_color);
+        let facing_end = minimap.to_screen(vision.position + Vec2::splat(0.5) + vision.facing * 1.5);
+        draw_line(center.x, center.y, facing_end.x, facing_end.y, 3.0, YELLOW);
+        font::draw(
+            &format!(
+                "enemy {}: {:.1}/{:.1} tiles{}{}",
+                vision.enemy.0,
+                vision.player_distance,
+                vision.view_distance,
+                if vision.line_of_sight { "" } else { ", blocked" },
+                if vision.aggressive { ", aggressive" } else { "" }
+            ),
+            center.x + minimap.tile_size.x,
+            center.y - minimap.tile_size.y,
+            TextStyle::Debug,
+            WHITE
+        );
+    }
+    /// what the player sees as one translucent fan from the player through the ray hits, a ray that hit
+    /// nothing ends on the map edge so the fan has no gaps
+    fn render_view_polygon(
+        player_origin: Vec2,
+        raycast_result: &[RaycastStepResult],
+        minimap: MinimapTransform,
+        theme: &HudTheme
+    ) {
+        let origin = minimap.to_screen(player_origin);
+        let mut color = theme.minimap_ray.color();
+        color.a *= 0.35;
+        for edge in Self::view_polygon(raycast_result).windows(2) {
+            draw_triangle(origin, minimap.to_screen(edge[0]), minimap.to_screen(edge[1]), color);
+        }
+    }
+
+    /// the outline of the view cone, fanned out from the ray origin
+    fn view_polygon(raycast_result: &[RaycastStepResult]) -> Vec<Vec2> {
+        let hits: Vec<Vec2> = raycast_result
+            .iter()
+            .map(|result| result.intersection_pos)
+            .collect();
+        decimate_collinear(&hits)
+    }
+}
+/// drops the points that lie on the line between their neighbours, a wall seen by hundreds of rays
+/// becomes its two ends
+fn decimate_collinear(points: &[Vec2]) -> Vec<Vec2> {
+    const TOLERANCE: f32 = 0.01; // tiles away from the line
+    let Some((&first, rest)) = points.split_first() else {
+        return Vec::new();
+    };
+    let mut kept = vec![first];
+    // every point dropped since the last kept one has to stay on the line, checking only the newest lets a corner
+    // seen by many rays drift away one small step at a time
+    let mut skipped = Vec::new();
+    for (i, &point) in rest.iter().enumerate() {
+        let Some(&next) = rest.get(i + 1) else {
+            kept.push(point);
+            break;
+        };
+        let start = kept[kept.len() - 1];
+        let line = next - start;
+        skipped.push(point);
+        let on_line =
+            line.length() > 0.0 &&
+            skipped.iter().all(|&skipped| {
+                line.perp_dot(skipped - start).abs() / line.length() < TOLERANCE &&
+                    line.dot(skipped - start) > 0.0 &&
+                    line.dot(next - skipped) > 0.0
+            });
+        if !on_line {
+            kept.push(point);
+            skipped.clear();
         }
     }
+    kept
+}
+/// the vertical placement of the view, everything the walls do the sprites and the floor have to follow
+#[derive(Clone, Copy)]
+struct EyeLevel {
+    offset: f32, // how far the eye is below standing height, in wall heights
+    horizon: f32, // screen y of the horizon, moved by the pitch
 }
 struct RenderPlayerPOV;
 impl RenderPlayerPOV {
     fn render_possible_interactions(
         player_pos: Vec2,
         player_angle: f32,
-        interactables: &Vec<InteractionEvent>,
+        fov: f32,
+        interactables: &[InteractionEvent],
         doors: &Doors,
+        interact: &str,
+        theme: &HudTheme
     ) {
-        for interactable in interactables {
+        for interactable in interactables.iter().filter(|event| event.in_range) {
                 match interactable.interaction_type {
                     InteractionType::OpenDoor(handle) => {
                         let door_pos = doors.positions[handle.0 as usize];
-                        let direction_to_door = door_pos - player_pos;
-                        let angle_to_door = direction_to_door.y.atan2(direction_to_door.x);
-                
-
-                        let mut relative_angle = angle_to_door - player_angle;
-                        
-                        // Wrap relative_angle to the range (-PI, PI)
-                        if relative_angle > std::f32::consts::PI {
-                            relative_angle -= 2.0 * std::f32::consts::PI;
-                        } else if relative_angle < -std::f32::consts::PI {
-                            relative_angle += 2.0 * std::f32::consts::PI;
-                        }
-                        if relative_angle.abs() <= HALF_PLAYER_FOV {
-                            let screen_position_ratio = (relative_angle + HALF_PLAYER_FOV) / (2.0 * HALF_PLAYER_FOV);
+                        let angle_to_door = angles::angle_to(player_pos, door_pos);
+                        let relative_angle = angles::relative_angle(player_angle, angle_to_door);
+                        if angles::within_fov(relative_angle, fov / 2.0) {
+                            let screen_position_ratio = (relative_angle + fov / 2.0) / fov;
                             let screen_x = (1.0 - screen_position_ratio) * SCREEN_WIDTH as f32;
-                        draw_text(
-                            "Press E to Open door",
+                        font::draw_centered(
+                            &format!("Press {} to Open door", interact),
                             screen_x,
                             (SCREEN_HEIGHT as f32) / 2.0,
-                            25.0,
-                            WHITE
+                            TextStyle::Prompt,
+                            theme.prompt_text.color()
                         );
                     }
                 }
                     InteractionType::CloseDoor(_) => {
-                        draw_text(
-                            "Press E to Close door",
+                        font::draw_centered(
+                            &format!("Press {} to Close door", interact),
+                            HALF_SCREEN_WIDTH,
+                            (SCREEN_HEIGHT as f32) / 2.0,
+                            TextStyle::Prompt,
+                            theme.prompt_text.color()
+                        );
+                    }
+                    InteractionType::ReadNote(_) => {
+                        font::draw_centered(
+                            &format!("Press {} to Read", interact),
                             HALF_SCREEN_WIDTH,
                             (SCREEN_HEIGHT as f32) / 2.0,
-                            25.0,
-                            WHITE
+                            TextStyle::Prompt,
+                            theme.prompt_text.color()
+                        );
+                    }
+                    InteractionType::InspectPickup(pickup) => {
+                        let relative_angle = angles::relative_angle(
+                            player_angle,
+                            angles::angle_to(player_pos, pickup.position)
                         );
+                        if angles::within_fov(relative_angle, fov / 2.0) {
+                            let screen_position_ratio = (relative_angle + fov / 2.0) / fov;
+                            let screen_x = (1.0 - screen_position_ratio) * SCREEN_WIDTH as f32;
+                            // below the prompts, over the sprite on the floor
+                            font::draw_centered(
+                                &format!("+{} {}", pickup.amount, pickup.weapon.ammo_name()),
+                                screen_x,
+                                (SCREEN_HEIGHT as f32) * 0.6,
+                                TextStyle::Prompt,
+                                theme.prompt_text.color()
+                            );
+                        }
                     }
             }
         }
@@ -1584,19 +3287,28 @@ impl RenderPlayerPOV {
     
 
     #[inline(always)]
-    fn render_floor(material: &Material, player_angle: f32, player_pos: Vec2) {
+    fn render_floor(
+        material: &Material,
+        player_angle: f32,
+        fov: f32,
+        player_pos: Vec2,
+        eye: EyeLevel,
+        floor_types: &Texture2D
+    ) {
         let left_most_ray_dir = Vec2::new(
-            (player_angle + HALF_PLAYER_FOV).cos(),
-            (player_angle + HALF_PLAYER_FOV).sin()
+            (player_angle + fov / 2.0).cos(),
+            (player_angle + fov / 2.0).sin()
         );
         let right_most_ray_dir = Vec2::new(
-            (player_angle - HALF_PLAYER_FOV).cos(),
-            (player_angle - HALF_PLAYER_FOV).sin()
+            (player_angle - fov / 2.0).cos(),
+            (player_angle - fov / 2.0).sin()
         );
         material.set_uniform("u_player_pos", player_pos);
         material.set_uniform("u_left_ray_dir", left_most_ray_dir);
         material.set_uniform("u_right_ray_dir", right_most_ray_dir);
         material.set_uniform("u_half_screen_height", HALF_SCREEN_HEIGHT as f32);
+        material.set_uniform("u_eye_offset", eye.offset);
+        material.set_uniform("u_pitch", (HALF_SCREEN_HEIGHT - eye.horizon) / HALF_SCREEN_HEIGHT);
         material.set_uniform("u_screen_width", SCREEN_WIDTH as f32);
         material.set_uniform("u_screen_height", SCREEN_HEIGHT as f32);
         material.set_texture(
@@ -1605,160 +3317,318 @@ impl RenderPlayerPOV {
                 .expect("Couldnt load stone texture")
                 .clone()
         );
+        material.set_texture(
+            "u_ceiling_texture",
+            TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::Ceiling)
+                .expect("Couldnt load ceiling texture")
+                .clone()
+        );
+        material.set_texture("u_floor_types", floor_types.clone());
+        material.set_uniform("u_world_size", floor_types.size());
+        material.set_uniform("u_time", get_time() as f32);
         gl_use_material(&material);
+        // split at the horizon, which the pitch moves away from the middle
         material.set_uniform("is_ceiling", 1.0 as f32);
         draw_rectangle(
             0.0,
             0.0,
             SCREEN_WIDTH as f32,
-            HALF_SCREEN_HEIGHT as f32,
+            eye.horizon,
             Color::from_rgba(255, 255, 255, 255)
         );
         material.set_uniform("is_ceiling", -1.0 as f32);
         draw_rectangle(
             0.0,
-            HALF_SCREEN_HEIGHT,
+            eye.horizon,
             SCREEN_WIDTH as f32,
-            HALF_SCREEN_HEIGHT as f32,
+            (SCREEN_HEIGHT as f32) - eye.horizon,
             Color::from_rgba(255, 255, 255, 255)
         );
         gl_use_default_material();
     }
+    /// walls, doors, floor and ceiling in a single fullscreen draw of RAYCAST_FRAGMENT_SHADER
+    fn render_world_gpu(
+        material: &Material,
+        tiles: &Texture2D,
+        floor_types: &Texture2D,
+        player_angle: f32,
+        fov: f32,
+        player_pos: Vec2,
+        eye: EyeLevel
+    ) {
+        let left_most_ray_dir = Vec2::new(
+            (player_angle + fov / 2.0).cos(),
+            (player_angle + fov / 2.0).sin()
+        );
+        let right_most_ray_dir = Vec2::new(
+            (player_angle - fov / 2.0).cos(),
+            (player_angle - fov / 2.0).sin()
+        );
+        let texture = |texture_type: Textures| {
+            TEXTURE_TYPE_TO_TEXTURE2D.get(&texture_type).expect("Texture failed to initialize").clone()
+        };
+        material.set_uniform("u_player_pos", player_pos);
+        material.set_uniform("u_player_angle", player_angle);
+        material.set_uniform("u_fov", fov);
+        material.set_uniform("u_eye_offset", eye.offset);
+        material.set_uniform("u_pitch", (HALF_SCREEN_HEIGHT - eye.horizon) / HALF_SCREEN_HEIGHT);
+        material.set_uniform("u_rays", AMOUNT_OF_RAYS as f32);
+        material.set_uniform("u_left_ray_dir", left_most_ray_dir);
+        material.set_uniform("u_right_ray_dir", right_most_ray_dir);
+        material.set_uniform("u_screen_width", SCREEN_WIDTH as f32);
+        material.set_uniform("u_screen_height", SCREEN_HEIGHT as f32);
+        material.set_uniform("u_world_size", tiles.size());
+        material.set_uniform("u_time", get_time() as f32);
+        material.set_texture("u_tiles", tiles.clone());
+        material.set_texture("u_floor_types", floor_types.clone());
+        material.set_texture("u_wall_texture", texture(Textures::Stone));
+        material.set_texture("u_door_texture", texture(Textures::Door));
+        material.set_texture("u_floor_texture", texture(Textures::Stone));
+        material.set_texture("u_ceiling_texture", texture(Textures::Ceiling));
+        gl_use_material(material);
+        draw_rectangle(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32, WHITE);
+        gl_use_default_material();
+    }
     #[inline(always)]
     fn render_walls_and_doors(
         raycast_step_res: &Vec<RaycastStepResult>,
-        z_buffer: &mut [f32; AMOUNT_OF_RAYS]
+        z_buffer: &mut [f32; AMOUNT_OF_RAYS],
+        walls: &[Vec2],
+        notes: &[Note],
+        eye: EyeLevel
     ) {
         let block_texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::Stone).expect(
             "Stone texture failed to initialize"
         );
-        let text_width = block_texture.width();
-        let text_height = block_texture.height();
+        let door_texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&Textures::Door).expect(
+            "Door texture failed to initialize"
+        );
 
         for (i, result) in raycast_step_res.iter().enumerate() {
-            let distance = result.corrected_distance;
-            z_buffer[i] = distance;
-
-            let wall_height = ((SCREEN_HEIGHT as f32) / (distance - 0.5 + 0.000001)).min(
-                SCREEN_HEIGHT as f32
-            );
-            let shade = 1.0 - (distance / (WORLD_WIDTH.min(WORLD_HEIGHT) as f32)).clamp(0.0, 1.0);
-
-            let is_x_side =
-                result.intersection_site == IntersectedSite::XLeft ||
-                result.intersection_site == IntersectedSite::XRight;
-
-            let text_coord_x = if is_x_side {
-                (result.intersection_pos.y * text_width) % text_width
-            } else {
-                (result.intersection_pos.x * text_width) % text_width
-            };
+            z_buffer[i] = result.corrected_distance;
             match result.entity_type {
-                EntityType::Wall(_) => {
-                    let wall_color = GREEN;
-                    let wall_color = Color::new(
-                        wall_color.r * shade,
-                        wall_color.g * shade,
-                        wall_color.b * shade,
-                        1.0
-                    );
-                    let wall_color = if is_x_side {
-                        wall_color
-                    } else {
-                        Color::new(wall_color.r * 0.8, wall_color.g * 0.8, wall_color.b * 0.8, 1.0)
-                    };
-                    draw_texture_ex(
-                        block_texture,
-                        (i as f32) * RAY_VERTICAL_STRIPE_WIDTH,
-                        config::config::HALF_SCREEN_HEIGHT - wall_height / 2.0,
-                        wall_color,
-                        DrawTextureParams {
-                            source: {
-                                Some(Rect {
-                                    x: text_coord_x,
-                                    y: 0.0,
-                                    w: 1.0,
-                                    h: text_height,
-                                })
-                            },
-                            dest_size: Some(Vec2::new(RAY_VERTICAL_STRIPE_WIDTH, wall_height)),
-                            ..Default::default()
-                        }
-                    );
+                EntityType::Wall(handle) => {
+                    RenderPlayerPOV::render_stripe(i, result, block_texture, GREEN, eye);
+                    let wall_tile = Tile::from_vec2(walls[handle.0 as usize]);
+                    let has_note = notes
+                        .iter()
+                        .any(|note| note.x == (wall_tile.x as usize) && note.y == (wall_tile.y as usize));
+                    if has_note {
+                        RenderPlayerPOV::render_note_stripe(i, result, eye);
+                    }
                 }
                 EntityType::Door(_) => {
-                    let wall_color = BROWN;
-                    let wall_color = Color::new(
-                        wall_color.r * shade,
-                        wall_color.g * shade,
-                        wall_color.b * shade,
-                        1.0
-                    );
-                    let wall_color = if is_x_side {
-                        wall_color
-                    } else {
-                        Color::new(wall_color.r * 0.8, wall_color.g * 0.8, wall_color.b * 0.8, 1.0)
-                    };
-                    draw_texture_ex(
-                        block_texture,
-                        (i as f32) * RAY_VERTICAL_STRIPE_WIDTH,
-                        config::config::HALF_SCREEN_HEIGHT - wall_height / 2.0,
-                        wall_color,
-                        DrawTextureParams {
-                            source: {
-                                Some(Rect {
-                                    x: text_coord_x,
-                                    y: 0.0,
-                                    w: 1.0,
-                                    h: text_height,
-                                })
-                            },
-                            dest_size: Some(Vec2::new(RAY_VERTICAL_STRIPE_WIDTH, wall_height)),
-                            ..Default::default()
+                    if let Some(behind) = &result.behind_door {
+                        match behind.entity_type {
+                            EntityType::Door(_) => {
+                                RenderPlayerPOV::render_stripe(i, behind, door_texture, WHITE, eye);
+                            }
+                            _ => {
+                                RenderPlayerPOV::render_stripe(i, behind, block_texture, GREEN, eye);
+                            }
                         }
-                    );
+                    }
+                    RenderPlayerPOV::render_stripe(i, result, door_texture, WHITE, eye);
                 }
                 _ => {}
             }
         }
     }
+    /// on screen, of a wall `distance` ahead of the camera plane
+    fn wall_height(distance: f32) -> f32 {
+        ((SCREEN_HEIGHT as f32) / (distance - 0.5 + 0.000001)).min(SCREEN_HEIGHT as f32)
+    }
+    /// a sheet of paper in the middle of every face of a wall with a note
+    fn render_note_stripe(column: usize, result: &RaycastStepResult, eye: EyeLevel) {
+        let along_face = match result.intersection_site {
+            IntersectedSite::XLeft | IntersectedSite::XRight => result.intersection_pos.y.fract(),
+            IntersectedSite::YTop | IntersectedSite::YBottom => result.intersection_pos.x.fract(),
+        };
+        if !(0.35..=0.65).contains(&along_face) {
+            return;
+        }
+        let wall_height = Self::wall_height(result.corrected_distance);
+        let shade =
+            1.0 - (result.corrected_distance / SHADE_DISTANCE).clamp(0.0, 1.0);
+        draw_rectangle(
+            (column as f32) * RAY_VERTICAL_STRIPE_WIDTH,
+            eye.horizon - wall_height * (0.2 + eye.offset),
+            RAY_VERTICAL_STRIPE_WIDTH,
+            wall_height * 0.25,
+            Color::new(0.93 * shade, 0.87 * shade, 0.7 * shade, 1.0)
+        );
+    }
+    #[inline(always)]
+    /// a lower eye moves the stripe up by the same share of its height, the pitch moves it with the horizon
+    fn render_stripe(
+        column: usize,
+        result: &RaycastStepResult,
+        texture: &Texture2D,
+        tint: Color,
+        eye: EyeLevel
+    ) {
+        let distance = result.corrected_distance;
+        let text_width = texture.width();
+        let text_height = texture.height();
+        let wall_height = Self::wall_height(distance);
+        let shade = 1.0 - (distance / SHADE_DISTANCE).clamp(0.0, 1.0);
+
+        let is_x_side =
+            result.intersection_site == IntersectedSite::XLeft ||
+            result.intersection_site == IntersectedSite::XRight;
+
+        let text_coord_x = if is_x_side {
+            (result.intersection_pos.y * text_width) % text_width
+        } else {
+            (result.intersection_pos.x * text_width) % text_width
+        };
+        let color = Color::new(tint.r * shade, tint.g * shade, tint.b * shade, 1.0);
+        let color = if is_x_side {
+            color
+        } else {
+            Color::new(color.r * 0.8, color.g * 0.8, color.b * 0.8, 1.0)
+        };
+        draw_texture_ex(
+            texture,
+            (column as f32) * RAY_VERTICAL_STRIPE_WIDTH,
+            eye.horizon - wall_height * (0.5 + eye.offset),
+            color,
+            DrawTextureParams {
+                source: {
+                    Some(Rect {
+                        x: text_coord_x,
+                        y: 0.0,
+                        w: 1.0,
+                        h: text_height,
+                    })
+                },
+                dest_size: Some(Vec2::new(RAY_VERTICAL_STRIPE_WIDTH, wall_height)),
+                ..Default::default()
+            }
+        );
+    }
+    /// the enemies in the field of view, farthest first
+    fn seen_enemies(
+        player_pos: Vec2,
+        player_angle: f32,
+        fov: f32,
+        world_layout: &Grid<EntityType>,
+        enemies: &Enemies
+    ) -> Vec<SeenEnemy> {
+        let mut seen_enemies = Vec::new();
+        // an enemy between two tiles is in the layout twice, indexed by handle
+        let mut already_seen = vec![false; enemies.positions.len()];
+        for row in world_layout.rows() {
+            for &entity in row {
+                match entity {
+                    EntityType::Enemy(enemy_handle) => {
+                        let Some(enemy) = enemies.get(enemy_handle) else {
+                            continue;
+                        };
+                        let enemy_pos = enemy.pos;
+                        let angle_to_enemy = angles::angle_to(player_pos, enemy_pos);
+                        let angle_diff = angles::relative_angle(player_angle, angle_to_enemy);
+                        let index = enemy_handle.0 as usize;
+                        if angles::within_fov(angle_diff, fov / 2.0) && !already_seen[index] {
+                            already_seen[index] = true;
+                            seen_enemies.push(SeenEnemy {
+                                enemy_handle: enemy_handle,
+                                relative_angle: angle_diff,
+                                // to the camera plane, the depth the sprite is scaled and clipped with
+                                distance: player_pos.distance(enemy_pos) * angle_diff.cos(),
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // farthest first so nearer enemies are drawn over the ones behind them, ties keep the scan order
+        // so two enemies at the same depth don't swap places between frames
+        seen_enemies.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+        seen_enemies
+    }
     #[inline(always)]
     fn render_enemies(
         material: &Material,
         z_buffer: &[f32; AMOUNT_OF_RAYS],
-        player_pos: Vec2,
+        eye: EyeLevel,
+        fov: f32,
         enemies: &Vec<SeenEnemy>,
-        positions: &Vec<Vec2>,
-        animation_states: &Vec<CompositeAnimationState>,
-        healths: &Vec<u8>
+        enemies_data: &Enemies
     ) {
+        let (animation_states, healths) = (&enemies_data.animation_states, &enemies_data.healths);
         gl_use_material(material);
         material.set_uniform("screen_size", Vec2::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32));
         for enemy in enemies {
             let health = healths[enemy.enemy_handle.0 as usize];
-            material.set_uniform("u_relative_health", (health as f32) / 3.0);
-            let rel_sprite_x = (enemy.relative_angle - HALF_PLAYER_FOV).abs() / (PI / 2.0);
+            material.set_uniform("u_relative_health", health / 3.0);
+            let rel_sprite_x = (enemy.relative_angle - fov / 2.0).abs() / fov;
             let sprite_x = rel_sprite_x * (SCREEN_WIDTH as f32);
             let animation = &animation_states[enemy.enemy_handle.0 as usize];
-            let distance_to_player: f32 =
-                player_pos.distance(positions[enemy.enemy_handle.0 as usize]) + 0.0001;
+            // to the camera plane like the z buffer, see RaycastSystem::raycast
+            let distance_to_player: f32 = enemy.distance + 0.0001;
             let sprite_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
                 SCREEN_HEIGHT as f32
             );
-            let screen_y = HALF_SCREEN_HEIGHT - sprite_height / 2.0;
+            let screen_y = eye.horizon - sprite_height * (0.5 + eye.offset);
             let texture_width = animation.main_state.spritesheet_offset_per_frame.x;
             let growth_factor = sprite_height / animation.main_state.sprite_sheet.height();
             let aspect_ratio =
                 animation.main_state.spritesheet_offset_per_frame.x /
                 animation.main_state.sprite_sheet.height();
             let shade =
-                1.0 - (distance_to_player / (WORLD_WIDTH.min(WORLD_HEIGHT) as f32)).clamp(0.0, 1.0);
+                1.0 - (distance_to_player / SHADE_DISTANCE).clamp(0.0, 1.0);
             let color = Color::new(
                 animation.main_state.color.r * shade,
                 animation.main_state.color.g * shade,
                 animation.main_state.color.b * shade,
                 1.0
             );
+            // a ranged enemy glows red while it aims
+            let color = match enemies_data.aim_progress(enemy.enemy_handle.0 as usize) {
+                Some(aim) =>
+                    Color::new(
+                        color.r + (shade - color.r) * aim,
+                        color.g * (1.0 - aim),
+                        color.b * (1.0 - aim),
+                        1.0
+                    ),
+                None => color,
+            };
+            let lod = match animation.main_state.animation_type {
+                AnimationType::EnemyAnimationType(enemy_animation) if
+                    distance_to_player > ENEMY_LOD_HALF_DISTANCE
+                => {
+                    let lods = ENEMY_SPRITE_LODS.get(&enemy_animation.texture());
+                    let level = if distance_to_player > ENEMY_LOD_QUARTER_DISTANCE { 1 } else { 0 };
+                    lods.map(|lods| &lods[level])
+                }
+                _ => None,
+            };
+            if let Some((lod_texture, lod_frame_width)) = lod {
+                // impostor: one draw call and a single depth test at the sprite center instead of per column
+                let sprite_width = texture_width * growth_factor * aspect_ratio;
+                let center_x = sprite_x + sprite_width * 0.5;
+                if
+                    center_x < (SCREEN_WIDTH as f32) &&
+                    z_buffer[center_x as usize] >= distance_to_player
+                {
+                    draw_texture_ex(lod_texture, sprite_x, screen_y, color, DrawTextureParams {
+                        dest_size: Some(Vec2::new(sprite_width, sprite_height)),
+                        source: Some(Rect {
+                            x: lod_frame_width * (animation.main_state.frame as f32),
+                            y: 0.0,
+                            w: *lod_frame_width,
+                            h: lod_texture.height(),
+                        }),
+                        flip_x: animation.main_state.need_to_flip_x(),
+                        ..Default::default()
+                    });
+                }
+                animation.render_effects(Vec2::new(sprite_x, screen_y), Vec2::new(1.5, 1.5));
+                continue;
+            }
             let curr_animation_text_coord_x =
                 animation.main_state.spritesheet_offset_per_frame.x *
                 (animation.main_state.frame as f32);
@@ -1809,19 +3679,24 @@ impl RenderPlayerPOV {
     }
 
     #[inline(always)]
-    fn render_weapon(player: &Player, bobbing_offset: f32) {
+    /// bobbing_offset is a fraction of the drawn weapon size
+    fn render_weapon(player: &Player, bobbing_offset: Vec2) {
         let weapon_texture = &player.animation_state.main_state.sprite_sheet;
+        // slides down until the top of the sprite is below the screen while switching
+        let switch_offset =
+            player.weapons.lowered() * ((SCREEN_HEIGHT as f32) * 0.15 + weapon_texture.height());
         player.animation_state.render_effects(
             Vec2::new(
                 (SCREEN_WIDTH as f32) * 0.5 - 50.0,
-                (SCREEN_HEIGHT as f32) * 0.85 - weapon_texture.height()
+                (SCREEN_HEIGHT as f32) * 0.85 - weapon_texture.height() + switch_offset
             ),
             Vec2::new(0.75, 0.75)
         );
         draw_texture_ex(
             weapon_texture,
-            HALF_SCREEN_WIDTH - weapon_texture.width() * 0.5  + bobbing_offset*weapon_texture.width() * 2.0,
-            (SCREEN_HEIGHT as f32) * 0.85 - weapon_texture.height(),
+            HALF_SCREEN_WIDTH - weapon_texture.width() * 0.5  + bobbing_offset.x * weapon_texture.width() * 2.0,
+            (SCREEN_HEIGHT as f32) * 0.85 - weapon_texture.height() + switch_offset +
+                bobbing_offset.y * weapon_texture.height() * 2.0,
             Color::from_rgba(255, 255, 255, 255),
             DrawTextureParams {
                 dest_size: Some(
@@ -1831,75 +3706,709 @@ impl RenderPlayerPOV {
             }
         )
     }
-    #[inline(always)]
-    fn render_health(health: u16) {
-        let bar_width = 30.0;
-        let bar_height = 10.0;
-        let spacing = 5.0;
-        let start_x = (SCREEN_WIDTH as f32) * 0.45 - 3.0 * (bar_width + spacing) * 0.5;
-        let y_pos = (SCREEN_HEIGHT as f32) * 0.9;
-        draw_text("Health: ", start_x, (SCREEN_HEIGHT as f32) * 0.88, 26.0, GREEN);
-        for i in 0..3 {
-            let x_pos = start_x + (i as f32) * (bar_width + spacing);
-            let color = if i < health {
-                Color::from_rgba(0, 255, 0, 255) // Active health bar color
-            } else {
-                Color::from_rgba(100, 100, 100, 255) // Inactive health bar color
-            };
-
-            draw_rectangle(x_pos, y_pos, bar_width, bar_height, color);
-
-            if i < health {
-                draw_rectangle_lines(
-                    x_pos - 1.0,
-                    y_pos - 1.0,
-                    bar_width + 2.0,
-                    bar_height + 2.0,
-                    2.0,
-                    Color::from_rgba(0, 255, 0, 150)
+    fn render_corpses(
+        z_buffer: &[f32; AMOUNT_OF_RAYS],
+        player_pos: Vec2,
+        player_angle: f32,
+        fov: f32,
+        eye: EyeLevel,
+        corpses: &[CorpseData]
+    ) {
+        for corpse in corpses {
+            let relative_angle = angles::relative_angle(
+                player_angle,
+                angles::angle_to(player_pos, corpse.position)
+            );
+            if !angles::within_fov(relative_angle, fov / 2.0) {
+                continue;
+            }
+            let sprite_x =
+                ((relative_angle - fov / 2.0).abs() / fov) * (SCREEN_WIDTH as f32);
+            let distance_to_player = player_pos.distance(corpse.position) * relative_angle.cos() + 0.0001;
+            let full_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
+                SCREEN_HEIGHT as f32
+            );
+            // flattened and resting on the floor line of a standing enemy
+            let sprite_height = full_height * 0.4;
+            let screen_y = eye.horizon + full_height * (0.5 - eye.offset) - sprite_height;
+            let column_width = full_height / corpse.source.h;
+            let shade =
+                1.0 - (distance_to_player / SHADE_DISTANCE).clamp(0.0, 1.0);
+            let alpha = (corpse.lifetime / CORPSE_FADE_TIME).min(1.0);
+            let color = Color::new(
+                corpse.color.r * shade,
+                corpse.color.g * shade,
+                corpse.color.b * shade,
+                alpha
+            );
+            for x in 0..corpse.source.w as usize {
+                let screen_x = sprite_x + (x as f32) * column_width;
+                if
+                    screen_x >= (SCREEN_WIDTH as f32) ||
+                    z_buffer[screen_x as usize] < distance_to_player
+                {
+                    continue;
+                }
+                let source_x = if corpse.flip_x {
+                    corpse.source.x + (corpse.source.w - 1.0 - (x as f32))
+                } else {
+                    corpse.source.x + (x as f32)
+                };
+                draw_texture_ex(
+                    &corpse.sprite_sheet,
+                    screen_x,
+                    screen_y,
+                    color,
+                    DrawTextureParams {
+                        dest_size: Some(Vec2::new(column_width, sprite_height)),
+                        source: Some(Rect {
+                            x: source_x,
+                            y: corpse.source.y,
+                            w: 1.0,
+                            h: corpse.source.h,
+                        }),
+                        ..Default::default()
+                    }
                 );
             }
         }
     }
-}
-#[derive(Clone, Copy, PartialEq)]
-enum IntersectedSite {
-    XLeft,
-    XRight,
-    YTop,
-    YBottom,
-}
-#[derive(Clone, Copy)]
-struct RaycastStepResult {
-    intersection_site: IntersectedSite,
-    intersection_pos: Vec2,
-    corrected_distance: f32,
-    entity_type: EntityType,
-}
-struct SeenEnemy {
-    enemy_handle: EnemyHandle,
-    relative_angle: f32,
-}
-enum InteractionType {
-    OpenDoor(DoorHandle),
-    CloseDoor(DoorHandle),
-}
-
-struct InteractionEvent {
-    interaction_type: InteractionType,
-}
-
-struct ProximityBasedInteractionSystem;
-impl ProximityBasedInteractionSystem {
-    fn get_possible_interactions(
-        player_pos: &Vec2,
+    /// the visible decorations as billboards standing on the floor, clipped per column against the walls,
+    /// returns how many were visible
+    fn render_decorations(
+        z_buffer: &[f32; AMOUNT_OF_RAYS],
+        player_pos: Vec2,
         player_angle: f32,
-        world_layout: &[[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
-        door_positions: &Vec<Vec2>,  // Assuming Vec2 is the type for positions
-        door_opened_states: &Vec<bool>,
-        interaction_radius: f32
-    ) -> Option<InteractionEvent> {
-        let surrounding_objects = SurroundingObjectsSystem::get_surrounding_objects(
+        fov: f32,
+        eye: EyeLevel,
+        decorations: &Decorations,
+        sprites: &HashMap<String, DecorationSprite>
+    ) -> usize {
+        let visible = decorations.visible(player_pos, player_angle, fov);
+        for &index in &visible {
+            let index = index as usize;
+            let sprite = match sprites.get(&decorations.kinds[index]) {
+                Some(sprite) => sprite,
+                None => {
+                    continue;
+                }
+            };
+            let position = decorations.positions[index];
+            let relative_angle = angles::relative_angle(
+                player_angle,
+                angles::angle_to(player_pos, position)
+            );
+            let distance_to_player = player_pos.distance(position) * relative_angle.cos() + 0.0001;
+            let full_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
+                SCREEN_HEIGHT as f32
+            );
+            let frames = sprite.frames.max(1);
+            let frame_width = sprite.texture.width() / (frames as f32);
+            let sprite_height = full_height * sprite.scale;
+            let sprite_width = (sprite_height * frame_width) / sprite.texture.height();
+            // sprites start at the projected position, so the middle of the tile is half a sprite further
+            let center_x =
+                ((relative_angle - fov / 2.0).abs() / fov) * (SCREEN_WIDTH as f32) +
+                full_height * 0.5;
+            let floor_y = eye.horizon + full_height * (0.5 - eye.offset);
+            let screen_y = floor_y - sprite.ground_offset * full_height - sprite_height;
+            let frame =
+                (((get_time() as f32) * DECORATION_SWAY_SPEED + decorations.sway_offsets[index]) as usize) %
+                (frames as usize);
+            let shade =
+                1.0 - (distance_to_player / SHADE_DISTANCE).clamp(0.0, 1.0);
+            let color = Color::new(shade, shade, shade, 1.0);
+            let column_width = sprite_width / frame_width;
+            for x in 0..frame_width as usize {
+                let screen_x = center_x - sprite_width / 2.0 + (x as f32) * column_width;
+                if
+                    screen_x < 0.0 ||
+                    screen_x >= (SCREEN_WIDTH as f32) ||
+                    z_buffer[screen_x as usize] < distance_to_player
+                {
+                    continue;
+                }
+                draw_texture_ex(
+                    &sprite.texture,
+                    screen_x,
+                    screen_y,
+                    color,
+                    DrawTextureParams {
+                        dest_size: Some(Vec2::new(column_width, sprite_height)),
+                        source: Some(Rect {
+                            x: (frame as f32) * frame_width + (x as f32),
+                            y: 0.0,
+                            w: 1.0,
+                            h: sprite.texture.height(),
+                        }),
+                        ..Default::default()
+                    }
+                );
+            }
+        }
+        visible.len()
+    }
+    /// glowing balls at the height of a thrown fireball, hidden when the wall in their middle column is closer
+    fn render_projectiles(
+        z_buffer: &[f32; AMOUNT_OF_RAYS],
+        player_pos: Vec2,
+        player_angle: f32,
+        fov: f32,
+        eye: EyeLevel,
+        projectiles: &Projectiles
+    ) {
+        // projectile positions are centers, so the view starts from the center of the player as well
+        let origin = player_pos + Vec2::splat(0.5);
+        let mut visible: Vec<(Vec2, f32)> = projectiles.positions
+            .iter()
+            .filter_map(|&position| {
+                let relative_angle = angles::relative_angle(player_angle, angles::angle_to(origin, position));
+                angles::within_fov(relative_angle, fov / 2.0).then_some((position, relative_angle))
+            })
+            .collect();
+        visible.sort_by(|a, b| origin.distance(b.0).total_cmp(&origin.distance(a.0)));
+        for (position, relative_angle) in visible {
+            let distance_to_player = origin.distance(position) * relative_angle.cos() + 0.0001;
+            let screen_x = ((relative_angle - fov / 2.0).abs() / fov) * (SCREEN_WIDTH as f32);
+            if screen_x >= (SCREEN_WIDTH as f32) || z_buffer[screen_x as usize] < distance_to_player {
+                continue;
+            }
+            let full_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
+                SCREEN_HEIGHT as f32
+            );
+            // a bit below the middle of a wall, about where a skeleton's hands are
+            let screen_y = eye.horizon + full_height * (0.1 - eye.offset);
+            let radius = full_height * PROJECTILE_RADIUS;
+            draw_circle(screen_x, screen_y, radius, Color::from_rgba(255, 120, 20, 230));
+            draw_circle(screen_x, screen_y, radius * 0.55, Color::from_rgba(255, 230, 120, 255));
+        }
+    }
+    /// the explosion animation of every recent blast as a billboard, as wide as a bit more than its radius
+    fn render_explosions(
+        z_buffer: &[f32; AMOUNT_OF_RAYS],
+        player_pos: Vec2,
+        player_angle: f32,
+        fov: f32,
+        eye: EyeLevel,
+        explosions: &[Explosion]
+    ) {
+        let origin = player_pos + Vec2::splat(0.5);
+        for explosion in explosions {
+            let relative_angle = angles::relative_angle(player_angle, angles::angle_to(origin, explosion.position));
+            if !angles::within_fov(relative_angle, fov / 2.0) {
+                continue;
+            }
+            let distance_to_player = origin.distance(explosion.position) * relative_angle.cos() + 0.0001;
+            let screen_x = ((relative_angle - fov / 2.0).abs() / fov) * (SCREEN_WIDTH as f32);
+            if screen_x >= (SCREEN_WIDTH as f32) || z_buffer[screen_x as usize] < distance_to_player {
+                continue;
+            }
+            let full_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
+                SCREEN_HEIGHT as f32
+            );
+            let animation = &explosion.animation;
+            let source = animation.get_source_rect();
+            let height = full_height * explosion.radius * 0.6;
+            let width = height * (source.w / source.h);
+            let screen_y = eye.horizon - full_height * eye.offset;
+            draw_texture_ex(
+                &animation.sprite_sheet,
+                screen_x - width * 0.5,
+                screen_y - height * 0.5,
+                animation.color,
+                DrawTextureParams {
+                    dest_size: Some(Vec2::new(width, height)),
+                    source: Some(source),
+                    ..Default::default()
+                }
+            );
+        }
+    }
+    /// medkits and ammo boxes as billboards on the floor, farthest first and clipped per column against the walls
+    fn render_pickups(
+        z_buffer: &[f32; AMOUNT_OF_RAYS],
+        player_pos: Vec2,
+        player_angle: f32,
+        fov: f32,
+        eye: EyeLevel,
+        pickups: &Pickups
+    ) {
+        let mut visible: Vec<(Vec2, f32, LootItem)> = pickups.positions
+            .iter()
+            .zip(pickups.items.iter())
+            .filter_map(|(&position, &item)| {
+                let relative_angle = angles::relative_angle(
+                    player_angle,
+                    angles::angle_to(player_pos, position)
+                );
+                angles::within_fov(relative_angle, fov / 2.0).then_some((position, relative_angle, item))
+            })
+            .collect();
+        visible.sort_by(|a, b| player_pos.distance(b.0).total_cmp(&player_pos.distance(a.0)));
+        for (position, relative_angle, item) in visible {
+            let texture = TEXTURE_TYPE_TO_TEXTURE2D.get(&Pickups::texture(item)).expect(
+                "Pickup texture failed to initialize"
+            );
+            let distance_to_player = player_pos.distance(position) * relative_angle.cos() + 0.0001;
+            let full_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
+                SCREEN_HEIGHT as f32
+            );
+            let sprite_height = full_height * PICKUP_SCALE;
+            let sprite_width = (sprite_height * texture.width()) / texture.height();
+            // the same placement as the decorations, standing on the floor in the middle of the tile
+            let center_x =
+                ((relative_angle - fov / 2.0).abs() / fov) * (SCREEN_WIDTH as f32) +
+                full_height * 0.5;
+            let floor_y = eye.horizon + full_height * (0.5 - eye.offset);
+            let shade = 1.0 - (distance_to_player / SHADE_DISTANCE).clamp(0.0, 1.0);
+            let color = Color::new(shade, shade, shade, 1.0);
+            let column_width = sprite_width / texture.width();
+            for x in 0..texture.width() as usize {
+                let screen_x = center_x - sprite_width / 2.0 + (x as f32) * column_width;
+                if
+                    screen_x < 0.0 ||
+                    screen_x >= (SCREEN_WIDTH as f32) ||
+                    z_buffer[screen_x as usize] < distance_to_player
+                {
+                    continue;
+                }
+                draw_texture_ex(
+                    texture,
+                    screen_x,
+                    floor_y - sprite_height,
+                    color,
+                    DrawTextureParams {
+                        dest_size: Some(Vec2::new(column_width, sprite_height)),
+                        source: Some(Rect {
+                            x: x as f32,
+                            y: 0.0,
+                            w: 1.0,
+                            h: texture.height(),
+                        }),
+                        ..Default::default()
+                    }
+                );
+            }
+        }
+    }
+    /// pulsing glow on the floor of every teleporter end and of the exit, which glows green
+    fn render_floor_pads(
+        z_buffer: &[f32; AMOUNT_OF_RAYS],
+        player_pos: Vec2,
+        player_angle: f32,
+        fov: f32,
+        eye: EyeLevel,
+        teleporters: &[TeleporterPair],
+        exit: Option<(usize, usize)>
+    ) {
+        let pulse = ((get_time() * 4.0).sin() as f32) * 0.5 + 0.5;
+        let teleporter_pads = teleporters.iter().flat_map(|pair| [(pair.a, false), (pair.b, false)]);
+        for ((x, y), is_exit) in teleporter_pads.chain(exit.map(|exit| (exit, true))) {
+            let pad_pos = Vec2::new(x as f32, y as f32);
+            let relative_angle = angles::relative_angle(
+                player_angle,
+                angles::angle_to(player_pos, pad_pos)
+            );
+            if !angles::within_fov(relative_angle, fov / 2.0) {
+                continue;
+            }
+            let distance_to_player = player_pos.distance(pad_pos) * relative_angle.cos() + 0.0001;
+            let full_height = ((SCREEN_HEIGHT as f32) / distance_to_player - 0.5).min(
+                SCREEN_HEIGHT as f32
+            );
+            // sprites start at the projected position, so the middle of the tile is half a sprite further
+            let center_x =
+                ((relative_angle - fov / 2.0).abs() / fov) * (SCREEN_WIDTH as f32) +
+                full_height * 0.5;
+            let column = (center_x as usize).min(AMOUNT_OF_RAYS - 1);
+            if z_buffer[column] < distance_to_player {
+                continue;
+            }
+            let floor_y = eye.horizon + full_height * (0.45 - eye.offset);
+            let (fill, outline) = if is_exit {
+                (Color::new(0.3, 1.0, 0.4, 0.25 + 0.35 * pulse), Color::new(0.6, 1.0, 0.6, 0.6 + 0.4 * pulse))
+            } else {
+                (Color::new(0.5, 0.8, 1.0, 0.25 + 0.35 * pulse), Color::new(0.7, 0.9, 1.0, 0.6 + 0.4 * pulse))
+            };
+            draw_ellipse(center_x, floor_y, full_height * 0.4, full_height * 0.07, 0.0, fill);
+            draw_ellipse_lines(center_x, floor_y, full_height * 0.45, full_height * 0.08, 0.0, 2.0, outline);
+        }
+    }
+    fn render_floating_texts(floating_texts: &Vec<FloatingText>) {
+        for text in floating_texts {
+            let alpha = text.lifetime.min(1.0); // fade out during the last second
+            font::draw_centered(
+                &text.text,
+                text.position.x,
+                text.position.y,
+                TextStyle::Prompt,
+                Color::new(text.color.r, text.color.g, text.color.b, alpha)
+            );
+        }
+    }
+    /// the text of a title card, it fades out during its last second like the floating texts
+    fn render_title_card(title: &FloatingText) {
+        let alpha = title.lifetime.min(1.0);
+        font::draw_centered(
+            &title.text,
+            title.position.x,
+            title.position.y,
+            TextStyle::Title,
+            Color::new(title.color.r, title.color.g, title.color.b, alpha)
+        );
+    }
+    /// a wide bar across the top with the name of the boss above it
+    fn render_boss_bar(name: &str, health: f32, ui_scale: f32, theme: &HudTheme) {
+        let width = (SCREEN_WIDTH as f32) * 0.4;
+        let height = 12.0 * ui_scale;
+        let x_pos = HALF_SCREEN_WIDTH - width * 0.5;
+        let y_pos = 150.0 * ui_scale;
+        font::draw_centered(
+            name,
+            HALF_SCREEN_WIDTH,
+            y_pos - 8.0 * ui_scale,
+            TextStyle::Hud,
+            theme.health_label.color()
+        );
+        draw_rectangle(x_pos, y_pos, width, height, theme.health_inactive.color());
+        draw_rectangle(x_pos, y_pos, width * health.clamp(0.0, 1.0), height, theme.health_active.color());
+        draw_rectangle_lines(x_pos, y_pos, width, height, 2.0 * ui_scale, theme.health_outline.color());
+    }
+    /// right of the health bars, see render_health
+    fn render_fire_mode(fire_mode: FireMode, ui_scale: f32, theme: &HudTheme) {
+        let bar_width = 30.0 * ui_scale;
+        let spacing = 5.0 * ui_scale;
+        let x_pos =
+            (SCREEN_WIDTH as f32) * theme.health_position[0] + 1.5 * (bar_width + spacing) + spacing;
+        let y_pos = (SCREEN_HEIGHT as f32) * theme.health_position[1] + 10.0 * ui_scale;
+        font::draw(&fire_mode.label(), x_pos, y_pos, TextStyle::Hud, theme.health_label.color());
+    }
+    /// A strip across the top that scrolls with the view with a tick every 15°, directions are the ones of the
+    /// minimap with north up. The view is drawn mirrored against the minimap and so is the strip, which keeps
+    /// the objective marker above the objective, past the ends it waits at the nearer one.
+    fn render_compass(player_angle: f32, objective: Option<f32>, ui_scale: f32, theme: &HudTheme) {
+        const NORTH: f32 = -PI / 2.0;
+        const CARDINALS: [&str; 4] = ["N", "E", "S", "W"];
+        let width = (SCREEN_WIDTH as f32) * COMPASS_WIDTH;
+        let height = 24.0 * ui_scale;
+        let top = 6.0 * ui_scale;
+        let bottom = top + height;
+        draw_rectangle(HALF_SCREEN_WIDTH - width / 2.0, top, width, height, theme.compass_background.color());
+        let strip_x = |bearing: f32| {
+            HALF_SCREEN_WIDTH + angles::strip_position(player_angle, bearing, COMPASS_SPAN) * (width / 2.0)
+        };
+        for degrees in (0..360).step_by(15) {
+            let bearing = NORTH + (degrees as f32).to_radians();
+            // unlike the objective, ticks past the ends are left out instead of piling up there
+            if angles::relative_angle(player_angle, bearing).abs() > COMPASS_SPAN / 2.0 {
+                continue;
+            }
+            let x = strip_x(bearing);
+            if degrees % 90 == 0 {
+                let letter = CARDINALS[degrees / 90];
+                font::draw_centered(letter, x, bottom - 6.0 * ui_scale, TextStyle::Hud, theme.compass_text.color());
+            } else {
+                let tick = if degrees % 45 == 0 { 0.5 } else { 0.3 } * height;
+                draw_line(x, bottom - tick, x, bottom, 2.0 * ui_scale, theme.compass_tick.color());
+            }
+        }
+        draw_line(HALF_SCREEN_WIDTH, top, HALF_SCREEN_WIDTH, top + 4.0 * ui_scale, 2.0 * ui_scale, WHITE);
+        if let Some(bearing) = objective {
+            let center = Vec2::new(strip_x(bearing), bottom + 6.0 * ui_scale);
+            let size = 5.0 * ui_scale;
+            draw_triangle(
+                center - Vec2::new(size, -size),
+                center + Vec2::new(size, size),
+                center - Vec2::new(0.0, size),
+                theme.compass_objective.color()
+            );
+        }
+    }
+    /// three segments under the health bars that empty from the right, dimmed while sprinting is locked
+    fn render_stamina(fraction: f32, exhausted: bool, ui_scale: f32, theme: &HudTheme) {
+        let bar_width = 30.0 * ui_scale;
+        let bar_height = 5.0 * ui_scale;
+        let spacing = 5.0 * ui_scale;
+        let start_x =
+            (SCREEN_WIDTH as f32) * theme.health_position[0] - 3.0 * (bar_width + spacing) * 0.5;
+        let y_pos = (SCREEN_HEIGHT as f32) * theme.health_position[1] + 15.0 * ui_scale;
+        let mut color = theme.stamina_active.color();
+        if exhausted {
+            color.a *= 0.4;
+        }
+        for i in 0..3 {
+            let x_pos = start_x + (i as f32) * (bar_width + spacing);
+            let filled = (fraction * 3.0 - (i as f32)).clamp(0.0, 1.0);
+            draw_rectangle(x_pos, y_pos, bar_width, bar_height, theme.health_inactive.color());
+            draw_rectangle(x_pos, y_pos, bar_width * filled, bar_height, color);
+        }
+    }
+    /// a small figure and label below the ammo while walking is toggled on or the player crouches,
+    /// crouching squats the figure
+    fn render_stance_indicator(crouching: bool, ui_scale: f32, theme: &HudTheme) {
+        let bar_width = 30.0 * ui_scale;
+        let spacing = 5.0 * ui_scale;
+        let x_pos = (SCREEN_WIDTH as f32) * theme.health_position[0] - 1.5 * (bar_width + spacing);
+        let y_pos = (SCREEN_HEIGHT as f32) * theme.health_position[1] + 65.0 * ui_scale;
+        let color = theme.health_label.color();
+        let size = 12.0 * ui_scale;
+        let (head_height, hip_height) = if crouching { (0.65, 0.2) } else { (1.0, 0.35) };
+        let head = Vec2::new(x_pos + size * 0.5, y_pos - size * head_height);
+        let hip = Vec2::new(head.x, y_pos - size * hip_height);
+        draw_circle(head.x, head.y - size * 0.1, size * 0.15, color);
+        draw_line(head.x, head.y, hip.x, hip.y, 2.0 * ui_scale, color);
+        draw_line(hip.x, hip.y, hip.x - size * 0.25, y_pos, 2.0 * ui_scale, color);
+        draw_line(hip.x, hip.y, hip.x + size * 0.2, y_pos, 2.0 * ui_scale, color);
+        let label = if crouching { "Crouch" } else { "Walk" };
+        font::draw(label, x_pos + size * 1.2, y_pos, TextStyle::Hud, color);
+    }
+    /// the held weapon by name, with its ammo unless it never runs dry
+    fn render_ammo(weapon: &Weapon, ui_scale: f32, theme: &HudTheme) {
+        let bar_width = 30.0 * ui_scale;
+        let spacing = 5.0 * ui_scale;
+        let x_pos = (SCREEN_WIDTH as f32) * theme.health_position[0] - 1.5 * (bar_width + spacing);
+        let y_pos = (SCREEN_HEIGHT as f32) * theme.health_position[1] + 40.0 * ui_scale;
+        let text = if weapon.magazine_size == 0 {
+            weapon.id.clone()
+        } else if weapon.reloading_magazine {
+            format!("{}: Reloading...", weapon.id)
+        } else {
+            format!("{}: {} / {}", weapon.id, weapon.current_ammo, weapon.reserve_ammo)
+        };
+        let color = if weapon.is_empty() && !weapon.reloading_magazine {
+            theme.ammo_empty.color()
+        } else {
+            theme.health_label.color()
+        };
+        font::draw(&text, x_pos, y_pos, TextStyle::Hud, color);
+    }
+    /// every weapon around a circle with its ammo, the highlighted one ringed and the spent ones greyed out
+    fn render_weapon_wheel(wheel: &WeaponWheel, weapons: &[Weapon], ui_scale: f32, theme: &HudTheme) {
+        let center = Vec2::new(HALF_SCREEN_WIDTH, HALF_SCREEN_HEIGHT);
+        let radius = WEAPON_WHEEL_RADIUS * ui_scale;
+        draw_circle(center.x, center.y, radius * 1.5, Color::new(0.0, 0.0, 0.0, 0.5));
+        for (index, weapon) in weapons.iter().enumerate() {
+            let angle = WeaponWheel::slot_angle(index, weapons.len());
+            let position = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+            if index == wheel.highlighted {
+                let color = theme.crosshair_interactable.color();
+                draw_circle_lines(position.x, position.y, 45.0 * ui_scale, 3.0, color);
+            }
+            let color = if weapon.is_spent() { theme.health_inactive.color() } else { theme.health_label.color() };
+            let ammo = if weapon.magazine_size == 0 {
+                "-".to_string()
+            } else {
+                format!("{} / {}", weapon.current_ammo, weapon.reserve_ammo)
+            };
+            font::draw_centered(&weapon.id, position.x, position.y - 4.0 * ui_scale, TextStyle::Hud, color);
+            font::draw_centered(&ammo, position.x, position.y + 16.0 * ui_scale, TextStyle::Hud, color);
+        }
+    }
+    /// a small cross, swapped for a door or page icon when aiming at something usable, greyed out with the distance when it's too far
+    fn render_crosshair(interactables: &[InteractionEvent], ui_scale: f32, theme: &HudTheme) {
+        let center = Vec2::new(HALF_SCREEN_WIDTH, HALF_SCREEN_HEIGHT);
+        // pickup labels are not aimed at
+        let aimed = interactables
+            .iter()
+            .find(|event| !matches!(event.interaction_type, InteractionType::InspectPickup(_)));
+        let interactable = match aimed {
+            Some(interactable) => interactable,
+            None => {
+                let size = 6.0 * ui_scale;
+                let color = theme.crosshair.color();
+                draw_line(center.x - size, center.y, center.x + size, center.y, 2.0, color);
+                draw_line(center.x, center.y - size, center.x, center.y + size, 2.0, color);
+                return;
+            }
+        };
+        let color = if interactable.in_range {
+            theme.crosshair_interactable.color()
+        } else {
+            theme.crosshair_out_of_range.color()
+        };
+        let (width, height) = (12.0 * ui_scale, 18.0 * ui_scale);
+        draw_rectangle_lines(center.x - width / 2.0, center.y - height / 2.0, width, height, 2.0, color);
+        match interactable.interaction_type {
+            InteractionType::OpenDoor(_) => {
+                draw_circle(center.x + width * 0.25, center.y, 1.5 * ui_scale, color);
+            }
+            InteractionType::CloseDoor(_) => {
+                draw_circle(center.x - width * 0.25, center.y, 1.5 * ui_scale, color);
+            }
+            InteractionType::ReadNote(_) => {
+                // lines of text on a page
+                for line in 0..3 {
+                    let y = center.y - height * 0.2 + (line as f32) * height * 0.2;
+                    draw_line(center.x - width * 0.3, y, center.x + width * 0.3, y, 1.0, color);
+                }
+            }
+            InteractionType::InspectPickup(_) => {}
+        }
+        if !interactable.in_range {
+            font::draw(
+                &format!("{:.1}m", interactable.distance),
+                center.x + width,
+                center.y + height / 2.0,
+                TextStyle::Hud,
+                color
+            );
+        }
+    }
+    /// bands that get stronger towards the border, the color's alpha is the strength at the very edge
+    fn render_vignette(color: Color) {
+        const BANDS: usize = 8;
+        let band_width = (SCREEN_HEIGHT as f32) * 0.025;
+        for band in 0..BANDS {
+            let inset = (band as f32) * band_width;
+            // the bands overlap towards the edge, so each one only adds a part
+            let band_color = Color { a: color.a * (1.0 - (band as f32) / (BANDS as f32)) * 0.35, ..color };
+            let inner_width = (SCREEN_WIDTH as f32) - 2.0 * inset;
+            let inner_height = (SCREEN_HEIGHT as f32) - 2.0 * inset;
+            draw_rectangle(inset, inset, inner_width, band_width, band_color);
+            draw_rectangle(inset, (SCREEN_HEIGHT as f32) - inset - band_width, inner_width, band_width, band_color);
+            draw_rectangle(inset, inset + band_width, band_width, inner_height - 2.0 * band_width, band_color);
+            draw_rectangle(
+                (SCREEN_WIDTH as f32) - inset - band_width,
+                inset + band_width,
+                band_width,
+                inner_height - 2.0 * band_width,
+                band_color
+            );
+        }
+    }
+    /// warm tint and streaks at the screen edges, strength fades with the remaining adrenaline
+    fn render_adrenaline(strength: f32) {
+        let edge_width = (SCREEN_WIDTH as f32) * 0.08;
+        let color = Color::new(1.0, 0.85, 0.4, 0.12 * strength);
+        draw_rectangle(0.0, 0.0, edge_width, SCREEN_HEIGHT as f32, color);
+        draw_rectangle(
+            (SCREEN_WIDTH as f32) - edge_width,
+            0.0,
+            edge_width,
+            SCREEN_HEIGHT as f32,
+            color
+        );
+        let streak_color = Color::new(1.0, 1.0, 0.9, 0.35 * strength);
+        for i in 0..12 {
+            let y = ((i as f32) + 0.5) * (SCREEN_HEIGHT as f32) / 12.0;
+            let length = edge_width * (0.5 + 0.5 * (((i * 7) % 5) as f32) / 4.0);
+            draw_line(0.0, y, length, y, 2.0, streak_color);
+            draw_line(
+                SCREEN_WIDTH as f32,
+                y,
+                (SCREEN_WIDTH as f32) - length,
+                y,
+                2.0,
+                streak_color
+            );
+        }
+    }
+    #[inline(always)]
+    fn render_health(health: u16, regenerating: bool, ui_scale: f32, theme: &HudTheme) {
+        let bar_width = 30.0 * ui_scale;
+        let bar_height = 10.0 * ui_scale;
+        let spacing = 5.0 * ui_scale;
+        let start_x =
+            (SCREEN_WIDTH as f32) * theme.health_position[0] -
+            (PLAYER_MAX_HEALTH as f32) * (bar_width + spacing) * 0.5;
+        let y_pos = (SCREEN_HEIGHT as f32) * theme.health_position[1];
+        font::draw(
+            "Health: ",
+            start_x,
+            y_pos - 0.02 * (SCREEN_HEIGHT as f32) * ui_scale,
+            TextStyle::Hud,
+            theme.health_label.color()
+        );
+        for i in 0..PLAYER_MAX_HEALTH {
+            let x_pos = start_x + (i as f32) * (bar_width + spacing);
+            let color = if i < health {
+                theme.health_active.color()
+            } else {
+                theme.health_inactive.color()
+            };
+
+            draw_rectangle(x_pos, y_pos, bar_width, bar_height, color);
+            if regenerating && i == health {
+                // the bar that is filling up pulses
+                let pulse = ((get_time() * 4.0).sin() as f32) * 0.5 + 0.5;
+                let mut pulse_color = theme.health_active.color();
+                pulse_color.a = 0.15 + 0.35 * pulse;
+                draw_rectangle(x_pos, y_pos, bar_width, bar_height, pulse_color);
+            }
+
+            if i < health {
+                draw_rectangle_lines(
+                    x_pos - 1.0 * ui_scale,
+                    y_pos - 1.0 * ui_scale,
+                    bar_width + 2.0 * ui_scale,
+                    bar_height + 2.0 * ui_scale,
+                    2.0 * ui_scale,
+                    theme.health_outline.color()
+                );
+            }
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq)]
+enum IntersectedSite {
+    XLeft,
+    XRight,
+    YTop,
+    YBottom,
+}
+#[derive(Clone)]
+struct RaycastStepResult {
+    intersection_site: IntersectedSite,
+    intersection_pos: Vec2,
+    corrected_distance: f32,
+    entity_type: EntityType,
+    behind_door: Option<Box<RaycastStepResult>>, // what shows through the gaps of a hit door
+}
+/// a tile entered by RaycastSystem::cast_ray
+#[derive(Clone, Copy)]
+struct RayStep {
+    tile: EntityType,
+    is_x_side: bool, // crossed a vertical grid line to get here
+    distance: f32, // along the ray to where it entered the tile
+}
+struct SeenEnemy {
+    enemy_handle: EnemyHandle,
+    relative_angle: f32,
+    distance: f32,
+}
+#[derive(Debug)]
+enum InteractionType {
+    OpenDoor(DoorHandle),
+    CloseDoor(DoorHandle),
+    ReadNote(usize), // index into World::notes
+    InspectPickup(PickupData), // only labeled, walking over the pickup collects it
+}
+/// an ammo pickup in view and close enough to be labeled
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct PickupData {
+    handle: PickupHandle,
+    position: Vec2,
+    weapon: WeaponType,
+    amount: u16,
+}
+
+struct InteractionEvent {
+    interaction_type: InteractionType,
+    distance: f32,
+    in_range: bool, // aimed at but too far away, only shown greyed out on the crosshair
+}
+
+struct ProximityBasedInteractionSystem;
+impl ProximityBasedInteractionSystem {
+    fn get_possible_interactions(
+        player_pos: &Vec2,
+        player_angle: f32,
+        world_layout: &Grid<EntityType>,
+        door_positions: &Vec<Vec2>,  // Assuming Vec2 is the type for positions
+        door_opened_states: &Vec<bool>,
+        interaction_radius: f32
+    ) -> Option<InteractionEvent> {
+        let surrounding_objects = SurroundingObjectsSystem::get_surrounding_objects(
             player_pos,
             world_layout,
             2
@@ -1926,6 +4435,8 @@ impl ProximityBasedInteractionSystem {
                         } else {
                             InteractionType::OpenDoor(*door_handle)
                         },
+                        distance,
+                        in_range: true,
                     });
                 }
             }
@@ -1933,42 +4444,499 @@ impl ProximityBasedInteractionSystem {
         
         None
     }
-    
+
+    /// what E acts on and the crosshair shows, the door under the center ray wins over the proximity scan,
+    /// which still finds fully opened doors the ray passes through
+    fn get_aimed_interaction(
+        player_pos: Vec2,
+        player_angle: f32,
+        world_layout: &Grid<EntityType>,
+        doors: &Doors,
+        walls: &[Vec2],
+        notes: &[Note],
+        interaction_radius: f32
+    ) -> Option<InteractionEvent> {
+        let center_ray = RaycastSystem::daa_raycast(
+            player_pos + Vec2::new(0.5, 0.5),
+            player_angle,
+            doors,
+            world_layout
+        );
+        if
+            let Some(RaycastStepResult {
+                entity_type: EntityType::Door(handle),
+                corrected_distance,
+                ..
+            }) = center_ray
+        {
+            return Some(InteractionEvent {
+                interaction_type: if doors.opened[handle.0 as usize] {
+                    InteractionType::CloseDoor(handle)
+                } else {
+                    InteractionType::OpenDoor(handle)
+                },
+                distance: corrected_distance,
+                in_range: corrected_distance <= interaction_radius,
+            });
+        }
+        if
+            let Some(RaycastStepResult {
+                entity_type: EntityType::Wall(handle),
+                corrected_distance,
+                ..
+            }) = center_ray
+        {
+            let wall_tile = Tile::from_vec2(walls[handle.0 as usize]);
+            let note = notes
+                .iter()
+                .position(|note| note.x == (wall_tile.x as usize) && note.y == (wall_tile.y as usize));
+            if let Some(note) = note {
+                return Some(InteractionEvent {
+                    interaction_type: InteractionType::ReadNote(note),
+                    distance: corrected_distance,
+                    in_range: corrected_distance <= interaction_radius,
+                });
+            }
+        }
+        ProximityBasedInteractionSystem::get_possible_interactions(
+            &player_pos,
+            player_angle,
+            world_layout,
+            &doors.positions,
+            &doors.opened,
+            interaction_radius
+        )
+    }
+
+    /// labels for the ammo pickups within `label_distance` that are in view and not behind a wall, nearest first
+    fn get_pickup_interactions(
+        player_pos: Vec2,
+        player_angle: f32,
+        pickups: &Pickups,
+        doors: &Doors,
+        world_layout: &Grid<EntityType>,
+        label_distance: f32
+    ) -> Vec<InteractionEvent> {
+        let mut labels: Vec<InteractionEvent> = pickups.positions
+            .iter()
+            .zip(pickups.items.iter())
+            .enumerate()
+            .filter_map(|(index, (&position, &item))| {
+                let LootItem::AmmoPickup(weapon, amount) = item else {
+                    return None;
+                };
+                let distance = player_pos.distance(position);
+                let relative_angle = angles::relative_angle(player_angle, angles::angle_to(player_pos, position));
+                let visible =
+                    distance <= label_distance &&
+                    angles::within_fov(relative_angle, HALF_PLAYER_FOV) &&
+                    RaycastSystem::line_of_sight(
+                        player_pos + Vec2::new(0.5, 0.5),
+                        position + Vec2::new(0.5, 0.5),
+                        doors,
+                        world_layout
+                    );
+                visible.then_some(InteractionEvent {
+                    interaction_type: InteractionType::InspectPickup(PickupData {
+                        handle: PickupHandle(index as u16),
+                        position,
+                        weapon,
+                        amount,
+                    }),
+                    distance,
+                    in_range: true,
+                })
+            })
+            .collect();
+        labels.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        labels
+    }
+}
+/// what the aggression check of one enemy saw, only gathered for the enemy selected in the map overview
+#[derive(Clone, Copy)]
+struct EnemyVisionDebug {
+    enemy: EnemyHandle,
+    position: Vec2,
+    facing: Vec2, // normalized velocity
+    view_distance: f32,
+    player_distance: f32, // between the tiles of the enemy and the player, like the check measures it
+    line_of_sight: bool, // no wall or closed door in between, regardless of the distance
+    aggressive: bool,
+}
+/// where an enemy last saw the player
+#[derive(Clone, Copy)]
+struct Sighting {
+    position: Vec2,
+    age: f32, // seconds, forgotten after ENEMY_SIGHT_MEMORY
 }
+/// Enemies chase a player they can see within ENEMY_VIEW_DISTANCE. One that loses sight keeps heading to
+/// where it last saw them for ENEMY_SIGHT_MEMORY before going passive.
 struct EnemyAggressionSystem;
 impl EnemyAggressionSystem {
+    /// returns the vision of debug_enemy after the check, None when there is no such living enemy
     fn toggle_enemy_aggressive(
         player_pos: Vec2,
-        enemy_positions: &Vec<Vec2>,
-        enemy_velocities: &mut Vec<Vec2>,
-        aggressive_states: &mut Vec<bool>,
-        enemy_alives: &Vec<bool>
-    ) {
+        enemies: &mut Enemies,
+        doors: &Doors,
+        world_layout: &Grid<EntityType>,
+        debug_enemy: Option<EnemyHandle>
+    ) -> Option<EnemyVisionDebug> {
+        const LAST_SIGHTING_REACHED: f32 = 0.2; // tiles
         let tile_pos_player = player_pos.trunc();
-        for (((enemy_pos, enemy_vel), is_aggressive), is_alive) in enemy_positions
+        for ((((((enemy_pos, enemy_vel), is_aggressive), is_alive), speed), path), sighting) in enemies.positions
             .iter()
-            .zip(enemy_velocities.iter_mut())
-            .zip(aggressive_states.iter_mut())
-            .zip(enemy_alives.iter()) {
+            .zip(enemies.velocities.iter_mut())
+            .zip(enemies.aggressive_states.iter_mut())
+            .zip(enemies.alives.iter())
+            .zip(enemies.speeds.iter())
+            .zip(enemies.paths.iter_mut())
+            .zip(enemies.sightings.iter_mut()) {
             if !is_alive {
                 continue;
             }
             let dist_vector = tile_pos_player - enemy_pos.trunc();
-            if dist_vector.length() <= ENEMY_VIEW_DISTANCE {
+            let sees_player =
+                dist_vector.length() <= ENEMY_VIEW_DISTANCE &&
+                RaycastSystem::line_of_sight(
+                    *enemy_pos + Vec2::splat(0.5),
+                    player_pos + Vec2::splat(0.5),
+                    doors,
+                    world_layout
+                );
+            if sees_player {
+                *sighting = Some(Sighting { position: player_pos, age: 0.0 });
+            } else if let Some(last) = sighting {
+                last.age += PHYSICS_FRAME_TIME;
+                if last.age > ENEMY_SIGHT_MEMORY {
+                    *sighting = None;
+                }
+            }
+            if let Some(last) = sighting {
+                let direction = if sees_player {
+                    Self::chase_direction(*enemy_pos, dist_vector, path)
+                } else if enemy_pos.distance(last.position) > LAST_SIGHTING_REACHED {
+                    // it was in view from here a moment ago, so the straight way is free
+                    (last.position - *enemy_pos).normalize_or_zero()
+                } else {
+                    // looks around where the player vanished until it forgets them
+                    Vec2::ZERO
+                };
                 if *is_aggressive {
-                    *enemy_vel = dist_vector.normalize() * 2.5;
+                    *enemy_vel = direction * 2.5 * *speed;
                     continue;
                 }
                 *is_aggressive = true;
-                *enemy_vel = dist_vector.normalize();
+                *enemy_vel = direction * *speed;
             } else if *is_aggressive {
                 *is_aggressive = false;
-                *enemy_vel = Vec2::new(1.0, -1.0);
+                *enemy_vel = Vec2::new(1.0, -1.0) * *speed;
+                *path = EnemyPath::Unplanned;
             }
         }
+        let enemy = debug_enemy?;
+        let index = enemy.0 as usize;
+        if !*enemies.alives.get(index)? {
+            return None;
+        }
+        Some(Self::vision_debug(enemy, player_pos, enemies, doors, world_layout))
     }
-}
-struct PlayEnemyAnimation;
+    fn vision_debug(
+        enemy: EnemyHandle,
+        player_pos: Vec2,
+        enemies: &Enemies,
+        doors: &Doors,
+        world_layout: &Grid<EntityType>
+    ) -> EnemyVisionDebug {
+        let index = enemy.0 as usize;
+        let position = enemies.positions[index];
+        EnemyVisionDebug {
+            enemy,
+            position,
+            facing: enemies.velocities[index].normalize_or_zero(),
+            view_distance: ENEMY_VIEW_DISTANCE,
+            player_distance: (player_pos.trunc() - position.trunc()).length(),
+            line_of_sight: RaycastSystem::line_of_sight(
+                position + Vec2::splat(0.5),
+                player_pos + Vec2::splat(0.5),
+                doors,
+                world_layout
+            ),
+            aggressive: enemies.aggressive_states[index],
+        }
+    }
+    /// towards the next waypoint of the path, straight at the player when next to them, waypoints that
+    /// were reached are dropped
+    fn chase_direction(enemy_pos: Vec2, to_player: Vec2, path: &mut EnemyPath) -> Vec2 {
+        const WAYPOINT_REACHED: f32 = 0.2; // tiles
+        // an enemy on the player's tile has no direction to chase, normalize would give NaN
+        if to_player.abs().max_element() <= 1.0 {
+            return to_player.normalize_or_zero();
+        }
+        let path = match path {
+            EnemyPath::Unplanned => {
+                return to_player.normalize_or_zero();
+            }
+            EnemyPath::Unreachable => {
+                return Vec2::ZERO;
+            }
+            EnemyPath::Waypoints(path) => path,
+        };
+        while let Some(next) = path.last() {
+            let waypoint = Vec2::new(next.x as f32, next.y as f32);
+            if enemy_pos.distance(waypoint) > WAYPOINT_REACHED {
+                return (waypoint - enemy_pos).normalize_or_zero();
+            }
+            path.pop();
+        }
+        to_player.normalize_or_zero()
+    }
+}
+/// Ranged enemies fire at a player they can see within range. Shots hit instantly, so the enemy stands still
+/// and aims for the last ENEMY_ATTACK_WINDUP of its cooldown, see Enemies::aim_progress.
+struct EnemyAttackSystem;
+impl EnemyAttackSystem {
+    /// runs after the aggression check, returns a hit for every enemy that fires this frame,
+    /// fireballs are launched into `projectiles` instead
+    fn update(player_pos: Vec2, enemies: &mut Enemies, projectiles: &mut Projectiles) -> Vec<WorldEventHandleBased> {
+        let mut shots = Vec::new();
+        for index in 0..enemies.positions.len() {
+            let (damage, range, cooldown) = match enemies.attack_types[index] {
+                AttackType::Melee => {
+                    continue;
+                }
+                | AttackType::Ranged { damage, range, cooldown }
+                | AttackType::Fireball { damage, range, cooldown, .. } => (damage, range, cooldown),
+            };
+            let remaining = &mut enemies.attack_cooldowns[index];
+            *remaining = (*remaining - PHYSICS_FRAME_TIME).max(0.0);
+            let in_sight =
+                enemies.alives[index] &&
+                enemies.aggressive_states[index] &&
+                enemies.sightings[index].is_some_and(|sighting| sighting.age == 0.0) && // seen this frame
+                enemies.positions[index].distance(player_pos) <= range;
+            if !in_sight {
+                // a lost target has to be aimed at again
+                *remaining = remaining.max(ENEMY_ATTACK_WINDUP);
+                continue;
+            }
+            if *remaining > ENEMY_ATTACK_WINDUP {
+                continue;
+            }
+            enemies.velocities[index] = Vec2::ZERO;
+            // holds the first frame of the front sheet as the aiming pose
+            enemies.animation_states[index].main_state.reset_frames();
+            if *remaining > 0.0 {
+                continue;
+            }
+            *remaining = cooldown;
+            if let AttackType::Fireball { speed, .. } = enemies.attack_types[index] {
+                // towards where the player stands now, it doesn't follow
+                let origin = enemies.positions[index] + enemies.sizes[index] * 0.5;
+                let velocity = (player_pos + Vec2::splat(0.5) - origin).normalize_or_zero() * speed;
+                projectiles.add(origin, velocity, damage, ProjectileOwner::Enemy);
+                continue;
+            }
+            enemies.animation_states[index].add_effect(AnimationState::default_explosion(), None);
+            shots.push(WorldEventHandleBased::enemy_shot_player(EnemyHandle(index as u16), damage));
+        }
+        shots
+    }
+}
+struct ProjectileSystem;
+impl ProjectileSystem {
+    /// moves every projectile one physics frame, the ones that hit something are removed, returns the hits,
+    /// the blasts that went off and the enemy projectiles that just missed the player
+    fn update(
+        projectiles: &mut Projectiles,
+        player_pos: Vec2,
+        world_layout: &Grid<EntityType>,
+        doors: &Doors,
+        enemies: &Enemies
+    ) -> (Vec<WorldEventHandleBased>, Vec<Explosion>, Vec<NearMiss>) {
+        let mut hits = Vec::new();
+        let mut explosions = Vec::new();
+        let mut near_misses = Vec::new();
+        let mut index = 0;
+        while index < projectiles.positions.len() {
+            // speeds come from the content, a fast projectile checks its way in substeps so it can't skip a wall
+            let displacement = projectiles.velocities[index] * PHYSICS_FRAME_TIME;
+            let steps = MovementSystem::substeps(displacement);
+            let step = displacement / (steps as f32);
+            let mut blocked = false;
+            for _ in 0..steps {
+                projectiles.positions[index] += step;
+                let position = projectiles.positions[index];
+                blocked = Self::blocked(position, world_layout, doors);
+                let struck = match projectiles.owners[index] {
+                    ProjectileOwner::Enemy => Self::struck_player(player_pos, position),
+                    ProjectileOwner::Player { .. } => Self::struck_enemy(enemies, position).is_some(),
+                };
+                if struck {
+                    break;
+                }
+                if projectiles.owners[index] == ProjectileOwner::Enemy {
+                    // a wall right behind the player still lets the whizz be heard
+                    near_misses.extend(Self::track_approach(projectiles, index, player_pos, blocked));
+                }
+                if blocked {
+                    break;
+                }
+            }
+            let position = projectiles.positions[index];
+            let hit = match projectiles.owners[index] {
+                ProjectileOwner::Player { damage_type, blast_radius } if blast_radius > 0.0 => {
+                    if blocked || Self::struck_enemy(enemies, position).is_some() {
+                        // from where it was the substep before, the wall it flew into would block every line of sight
+                        let center = if blocked { position - step } else { position };
+                        hits.extend(
+                            DamageSystem::blast(
+                                center,
+                                blast_radius,
+                                projectiles.damages[index],
+                                damage_type,
+                                enemies,
+                                doors,
+                                world_layout
+                            )
+                        );
+                        explosions.push(Explosion {
+                            position: center,
+                            radius: blast_radius,
+                            animation: AnimationState::default_explosion(),
+                        });
+                        projectiles.remove(index);
+                        continue;
+                    }
+                    None
+                }
+                ProjectileOwner::Enemy => {
+                    Self::struck_player(player_pos, position).then(|| {
+                        WorldEventHandleBased::projectile_hit_player(
+                            projectiles.damages[index],
+                            projectiles.velocities[index]
+                        )
+                    })
+                }
+                ProjectileOwner::Player { damage_type, .. } => {
+                    Self::struck_enemy(enemies, position)
+                        .map(|enemy| {
+                            WorldEventHandleBased::projectile_hit_enemy(
+                                EnemyHandle(enemy as u16),
+                                projectiles.damages[index] as f32,
+                                damage_type
+                            )
+                        })
+                }
+            };
+            if blocked || hit.is_some() {
+                hits.extend(hit);
+                projectiles.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        (hits, explosions, near_misses)
+    }
+    /// a near miss once an enemy projectile that came within NEAR_MISS_DISTANCE of the player flies away
+    /// again or ends, at most once per projectile
+    fn track_approach(projectiles: &mut Projectiles, index: usize, player_pos: Vec2, ends: bool) -> Option<NearMiss> {
+        let position = projectiles.positions[index];
+        let gap = (player_pos + Vec2::splat(0.5)).distance(position) - (0.5 + PROJECTILE_RADIUS);
+        let closest = &mut projectiles.closest_approaches[index];
+        if gap < *closest {
+            *closest = gap;
+            if !ends {
+                return None;
+            }
+        }
+        if projectiles.whizzed[index] || *closest >= NEAR_MISS_DISTANCE {
+            return None;
+        }
+        projectiles.whizzed[index] = true;
+        Some(NearMiss { position })
+    }
+    /// walls, the closed part of a door and the edge of the map stop a projectile
+    fn blocked(position: Vec2, world_layout: &Grid<EntityType>, doors: &Doors) -> bool {
+        if position.x < 0.0 || position.y < 0.0 {
+            return true;
+        }
+        match world_layout.get(position.x.floor() as usize, position.y.floor() as usize) {
+            None | Some(EntityType::Wall(_)) => true,
+            Some(EntityType::Door(handle)) => doors
+                .get_door_hitbox(*handle)
+                .is_some_and(|hitbox| hitbox.contains(position)),
+            Some(_) => false,
+        }
+    }
+    fn struck_player(player_pos: Vec2, position: Vec2) -> bool {
+        (player_pos + Vec2::splat(0.5)).distance(position) < 0.5 + PROJECTILE_RADIUS
+    }
+    fn struck_enemy(enemies: &Enemies, position: Vec2) -> Option<usize> {
+        (0..enemies.positions.len())
+            .find(|&enemy| enemies.alives[enemy] && Self::enemy_rect(enemies, enemy).contains(position))
+    }
+    fn enemy_rect(enemies: &Enemies, enemy: usize) -> Rect {
+        let (position, size) = (enemies.positions[enemy], enemies.sizes[enemy]);
+        Rect::new(position.x, position.y, size.x, size.y)
+    }
+}
+/// the way of a chasing enemy to the player
+enum EnemyPath {
+    Unplanned, // not chasing or waiting for the next update, heads straight at the player meanwhile
+    Unreachable, // walls and closed doors are in the way, the enemy waits instead of running into them
+    Waypoints(Vec<Tile>), // the next one last
+}
+struct PathfindingSystem;
+impl PathfindingSystem {
+    /// the tile to step on next towards `target` for every tile that can reach it, one breadth first search
+    /// from the target serves every enemy
+    fn steps_towards(target: Tile, world_layout: &Grid<EntityType>, doors: &Doors) -> Grid<Option<Tile>> {
+        let mut next_steps = Grid::same_size(world_layout, None);
+        next_steps[target.y as usize][target.x as usize] = Some(target);
+        let mut queue = VecDeque::from([target]);
+        while let Some(tile) = queue.pop_front() {
+            let (x, y) = (tile.x as usize, tile.y as usize);
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (next_x, next_y) in neighbours {
+                let unvisited = next_steps.get(next_x, next_y).is_some_and(|step| step.is_none());
+                if unvisited && Self::walkable(world_layout[next_y][next_x], doors) {
+                    next_steps[next_y][next_x] = Some(tile);
+                    queue.push_back(Tile { x: next_x as u16, y: next_y as u16 });
+                }
+            }
+        }
+        next_steps
+    }
+    /// walls and doors that aren't fully open block the way
+    fn walkable(entity: EntityType, doors: &Doors) -> bool {
+        match entity {
+            EntityType::Wall(_) => false,
+            EntityType::Door(handle) => doors.get_door_hitbox(handle).is_none(),
+            _ => true,
+        }
+    }
+    /// the tiles after `from` up to the target in reverse order
+    fn path(from: Tile, next_steps: &Grid<Option<Tile>>) -> EnemyPath {
+        let mut path = Vec::new();
+        let mut tile = from;
+        while let Some(&Some(next)) = next_steps.get(tile.x as usize, tile.y as usize) {
+            if next == tile {
+                // the target points at itself
+                path.reverse();
+                return EnemyPath::Waypoints(path);
+            }
+            path.push(next);
+            tile = next;
+        }
+        EnemyPath::Unreachable
+    }
+}
+struct PlayEnemyAnimation;
 impl PlayEnemyAnimation {
     fn play_death(
         enemy_handle: EnemyHandle,
@@ -1990,613 +4958,5401 @@ impl PlayEnemyAnimation {
     }
 }
 
-struct CameraShake {
-    duration: f32,
-    intensity: f32,
-    current_time: f32,
+struct FloatingText {
+    text: String,
+    position: Vec2, // screen space
+    lifetime: f32,
+    color: Color,
+}
+impl FloatingText {
+    fn notification(text: &str) -> Self {
+        FloatingText {
+            text: text.to_string(),
+            position: Vec2::new(HALF_SCREEN_WIDTH, (SCREEN_HEIGHT as f32) * 0.2),
+            lifetime: 2.0,
+            color: YELLOW,
+        }
+    }
+    /// large text in the middle of the screen for set pieces, see SequenceAction::Title
+    fn title(text: &str) -> Self {
+        FloatingText {
+            text: text.to_string(),
+            position: Vec2::new(HALF_SCREEN_WIDTH, (SCREEN_HEIGHT as f32) * 0.35),
+            lifetime: TITLE_CARD_TIME,
+            color: WHITE,
+        }
+    }
+    /// one notification per map warning below `first_line` others, debug builds only, the log always gets them
+    fn map_warnings(path: &str, warnings: &[Diagnostic], first_line: usize) -> Vec<Self> {
+        let mut notifications = Vec::new();
+        for (i, warning) in warnings.iter().enumerate() {
+            let line = format!("{}: {}", path, warning.message);
+            eprintln!("Warning: {} ({})", line, warning.kind.explanation());
+            if cfg!(debug_assertions) {
+                let mut notification = FloatingText::notification(&line);
+                notification.position.y += ((first_line + i) as f32) * 25.0;
+                notification.lifetime = 5.0;
+                notifications.push(notification);
+            }
+        }
+        notifications
+    }
+    /// damage number next to the crosshair, grayed out when the target resisted the damage type
+    fn hit_marker(damage: &ResolvedDamage, penetration_depth: u8) -> Self {
+        let (text, color) = if damage.multiplier < 1.0 {
+            (format!("{} resisted", damage.amount), GRAY)
+        } else if damage.multiplier > 1.0 {
+            (format!("{}!", damage.amount), ORANGE)
+        } else {
+            (format!("{}", damage.amount), WHITE)
+        };
+        FloatingText {
+            text,
+            // hits further down a penetrating shot stack below the first one
+            position: Vec2::new(
+                HALF_SCREEN_WIDTH + 60.0,
+                HALF_SCREEN_HEIGHT - 40.0 + (penetration_depth as f32) * 30.0
+            ),
+            lifetime: 0.6,
+            color,
+        }
+    }
 }
 
-impl CameraShake {
-    fn new(duration: f32, intensity: f32) -> Self {
-        Self {
-            duration,
-            intensity,
-            current_time: 0.0,
+/// one texel per tile for the floor shader, red marks water
+fn floor_types_texture(floor_types: &FloorGrid) -> Texture2D {
+    let mut bytes = Vec::with_capacity(floor_types.width() * floor_types.height() * 4);
+    for row in floor_types.rows() {
+        for floor_type in row {
+            let water = if *floor_type == FloorType::Water { 255 } else { 0 };
+            bytes.extend_from_slice(&[water, 0, 0, 255]);
         }
     }
+    let texture = Texture2D::from_rgba8(floor_types.width() as u16, floor_types.height() as u16, &bytes);
+    texture.set_filter(FilterMode::Nearest);
+    texture
+}
+
+/// every weapon of the content in slot order, the pistol is held at the start
+fn starting_weapons(content: &ContentRegistry) -> WeaponInventory {
+    let mut defs: Vec<&WeaponDef> = content.weapons.values().collect();
+    defs.sort_by(|a, b| (a.slot, &a.id).cmp(&(b.slot, &b.id)));
+    let active = defs
+        .iter()
+        .position(|def| def.id == "pistol")
+        .unwrap_or(0);
+    WeaponInventory::new(defs.into_iter().map(Weapon::from_def).collect(), active)
+}
+
+fn tiles_texture(world_layout: &Grid<EntityType>, doors: &Doors, walls: &[Vec2], notes: &[Note]) -> Texture2D {
+    let texture = Texture2D::from_rgba8(
+        world_layout.width() as u16,
+        world_layout.height() as u16,
+        &tile_texels(world_layout, doors, walls, notes)
+    );
+    texture.set_filter(FilterMode::Nearest);
+    texture
+}
 
-    fn update(&mut self, dt: f32) -> Vec2 {
-        if self.current_time >= self.duration {
-            return Vec2::ZERO;
+/// one texel per tile for the raycast shader, see RAYCAST_FRAGMENT_SHADER for the channels
+fn tile_texels(
+    world_layout: &Grid<EntityType>,
+    doors: &Doors,
+    walls: &[Vec2],
+    notes: &[Note]
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(world_layout.width() * world_layout.height() * 4);
+    for row in world_layout.rows() {
+        for entity in row {
+            let texel = match entity {
+                EntityType::Wall(handle) => {
+                    let wall_tile = Tile::from_vec2(walls[handle.0 as usize]);
+                    let has_note = notes
+                        .iter()
+                        .any(|note| note.x == (wall_tile.x as usize) && note.y == (wall_tile.y as usize));
+                    [255, 0, if has_note { 255 } else { 0 }, 255]
+                }
+                EntityType::Door(handle) => {
+                    let closed = doors.closed_share(*handle).unwrap_or(0.0);
+                    let direction = match doors.directions[handle.0 as usize] {
+                        DoorDirection::LEFT => 0,
+                        DoorDirection::RIGHT => 85,
+                        DoorDirection::UP => 170,
+                        DoorDirection::DOWN => 255,
+                    };
+                    [128, (closed.clamp(0.0, 1.0) * 255.0).round() as u8, direction, 255]
+                }
+                _ => [0, 0, 0, 255],
+            };
+            bytes.extend_from_slice(&texel);
         }
-        self.current_time += dt;
-        let progress = self.current_time / self.duration;
-        let damping = 1.0 - progress;
+    }
+    bytes
+}
 
-        let angle = random::<f32>() * std::f32::consts::TAU;
-        let offset = Vec2::new(angle.cos(), angle.sin()) * self.intensity * damping;
-        offset
+/// splits text into lines no wider than max_width, a single word that is too wide gets a line of its own
+fn wrap_text(text: &str, max_width: f32, style: TextStyle) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if !line.is_empty() && font::measure(&candidate, style).x > max_width {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        } else {
+            line = candidate;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
     }
+    lines
 }
-enum VisualEffect {
-    CameraShake(CameraShake),
-    None,
+
+/// sprite of a decoration kind, shared by every placed decoration of that kind
+struct DecorationSprite {
+    texture: Texture2D,
+    frames: u8,
+    scale: f32,
+    ground_offset: f32,
 }
-enum GameState {
-    GameGoing,
-    GameOver,
+/// the decorations within one DECORATION_REGION_SIZE square of tiles, culled together
+struct DecorationRegion {
+    center: Vec2,
+    members: Vec<u16>,
 }
-struct World {
-    world_layout: [[EntityType; WORLD_WIDTH]; WORLD_HEIGHT],
-    background_material: Material,
-    camera_shake_material: Material,
-    enemy_default_material: Material,
-    shoot_sound: Sound,
-    reload_sound: Sound,
-    walls: Vec<Vec2>,
-    doors: Doors,
-    enemies: Enemies,
-    player: Player,
-    player_interactables: Vec<InteractionEvent>,
-    postprocessing: VisualEffect,
-    game_state: GameState,
+/// medkits and ammo boxes placed by the map, each one holds its tile in world_layout until the player walks over it
+struct Pickups {
+    positions: Vec<Vec2>,
+    items: Vec<LootItem>, // applied like a drop, see World::apply_loot
+    spawn_ids: SpawnIndex,
 }
-impl World {
-    async fn default() -> Self {
-        let mut walls = Vec::new();
-        let mut enemies = Enemies::new();
-        let mut doors = Doors::new(1.0, 1.0, 1.0);
-        let mut player = Player {
-            pos: Vec2::new(0.0, 0.0),
-            angle: 0.0,
-            vel: Vec2::new(0.0, 0.0),
-            health: 3,
-            weapon: Weapon::default(),
-            animation_state: CompositeAnimationState::new(AnimationState::default_weapon()),
-            bobbing_amount: 0.1,
-            bobbing_time: 0.0,
-            bobbing_speed: 11.0,
+impl Pickups {
+    fn new() -> Self {
+        Pickups {
+            positions: Vec::new(),
+            items: Vec::new(),
+            spawn_ids: SpawnIndex::new(),
+        }
+    }
+    fn add(&mut self, position: Vec2, item: LootItem) -> PickupHandle {
+        self.spawn_ids.push(position.x as usize, position.y as usize);
+        self.positions.push(position);
+        self.items.push(item);
+        PickupHandle((self.positions.len() - 1) as u16)
+    }
+    fn texture(item: LootItem) -> Textures {
+        match item {
+            LootItem::HealthPack(_) => Textures::HealthPickup,
+            _ => Textures::AmmoPickup,
+        }
+    }
+    /// the pickup on the tile of `pos`, Tile::from_vec2 decides the tile like for the player
+    fn at_tile(&self, pos: Vec2) -> Option<PickupHandle> {
+        let tile = Tile::from_vec2(pos);
+        self.positions
+            .iter()
+            .position(|&position| Tile::from_vec2(position) == tile)
+            .map(|index| PickupHandle(index as u16))
+    }
+    /// the last pickup takes over the index, returns its old handle when there was one to move
+    fn remove(&mut self, handle: PickupHandle) -> Option<PickupHandle> {
+        let last = self.positions.len() - 1;
+        self.positions.swap_remove(handle.0 as usize);
+        self.items.swap_remove(handle.0 as usize);
+        self.spawn_ids.swap_remove(handle.0 as usize);
+        (last != (handle.0 as usize)).then_some(PickupHandle(last as u16))
+    }
+    /// removes the pickup and its tile, the tile of the pickup that takes over the handle follows it
+    fn take(&mut self, handle: PickupHandle, world_layout: &mut Grid<EntityType>) -> LootItem {
+        let item = self.items[handle.0 as usize];
+        let tile = Tile::from_vec2(self.positions[handle.0 as usize]);
+        if world_layout[tile.y as usize][tile.x as usize] == EntityType::Pickup(handle) {
+            world_layout[tile.y as usize][tile.x as usize] = EntityType::None;
+        }
+        if let Some(moved) = self.remove(handle) {
+            let tile = Tile::from_vec2(self.positions[handle.0 as usize]);
+            if world_layout[tile.y as usize][tile.x as usize] == EntityType::Pickup(moved) {
+                world_layout[tile.y as usize][tile.x as usize] = EntityType::Pickup(handle);
+            }
+        }
+        item
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ProjectileOwner {
+    Enemy, // hurts the player
+    Player {
+        damage_type: DamageType,
+        blast_radius: f32, // tiles, 0 only hurts the enemy it hits
+    },
+}
+/// what is left of a blast in the world, only drawn, the damage is dealt when it goes off
+struct Explosion {
+    position: Vec2,
+    radius: f32,
+    animation: AnimationState, // gone once it played through
+}
+/// where something blew up a moment ago, surviving enemies nearby scatter from it, see RepulsionSystem
+struct RepulsionField {
+    center: Vec2,
+    age: f32, // seconds, gone after REPULSION_DURATION
+}
+/// Pushes enemies away from recent blasts on top of wherever they are steering, the push fades with the age
+/// of the field and the distance to its center, so the chase takes over again once it is gone.
+struct RepulsionSystem;
+impl RepulsionSystem {
+    /// ages the fields and sets the scatter velocity of every enemy, alive or not
+    fn update(fields: &mut Vec<RepulsionField>, enemies: &mut Enemies) {
+        for field in fields.iter_mut() {
+            field.age += PHYSICS_FRAME_TIME;
+        }
+        fields.retain(|field| field.age < REPULSION_DURATION);
+        for (index, scatter) in enemies.scatter_velocities.iter_mut().enumerate() {
+            *scatter = Self::push(fields, enemies.positions[index] + enemies.sizes[index] * 0.5);
+        }
+    }
+    /// velocity added to an enemy whose center is at `position`
+    fn push(fields: &[RepulsionField], position: Vec2) -> Vec2 {
+        fields
+            .iter()
+            .map(|field| {
+                let away = position - field.center;
+                let closeness = (1.0 - away.length() / REPULSION_RADIUS).max(0.0);
+                let freshness = 1.0 - field.age / REPULSION_DURATION;
+                away.normalize_or_zero() * REPULSION_STRENGTH * closeness * freshness
+            })
+            .sum()
+    }
+}
+/// an enemy projectile that passed the player closely without hitting, see ProjectileSystem::track_approach
+struct NearMiss {
+    position: Vec2, // where the projectile was when it was found to have missed
+}
+impl NearMiss {
+    /// the whizz of the side of the view it passed on, the player's center is its top left corner plus half a tile
+    fn whizz(&self, player_pos: Vec2, player_angle: f32) -> Sounds {
+        let facing = Vec2::new(player_angle.cos(), player_angle.sin());
+        if facing.perp_dot(self.position - (player_pos + Vec2::splat(0.5))) < 0.0 {
+            Sounds::WhizzLeft
+        } else {
+            Sounds::WhizzRight
+        }
+    }
+}
+/// shots that fly through the level instead of hitting at once, see ProjectileSystem
+struct Projectiles {
+    positions: Vec<Vec2>, // centers, unlike the top left corners of the other entities
+    velocities: Vec<Vec2>,
+    damages: Vec<u8>,
+    owners: Vec<ProjectileOwner>,
+    closest_approaches: Vec<f32>, // tiles between the projectile and the player at their closest so far
+    whizzed: Vec<bool>, // a projectile whizzes past the player once
+}
+impl Projectiles {
+    fn new() -> Self {
+        Projectiles {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            damages: Vec::new(),
+            owners: Vec::new(),
+            closest_approaches: Vec::new(),
+            whizzed: Vec::new(),
+        }
+    }
+    fn add(&mut self, position: Vec2, velocity: Vec2, damage: u8, owner: ProjectileOwner) {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.damages.push(damage);
+        self.owners.push(owner);
+        self.closest_approaches.push(f32::INFINITY);
+        self.whizzed.push(false);
+    }
+    /// nothing refers to a projectile by index, so the swap_remove needs no fixing up
+    fn remove(&mut self, index: usize) {
+        self.positions.swap_remove(index);
+        self.velocities.swap_remove(index);
+        self.damages.swap_remove(index);
+        self.owners.swap_remove(index);
+        self.closest_approaches.swap_remove(index);
+        self.whizzed.swap_remove(index);
+    }
+    fn clear(&mut self) {
+        *self = Projectiles::new();
+    }
+}
+/// props placed by the map, they are only drawn, never collide and have no world_layout entry
+struct Decorations {
+    positions: Vec<Vec2>,
+    kinds: Vec<String>, // key into World::decoration_sprites
+    sway_offsets: Vec<f32>, // so neighbouring props don't sway in step
+    regions: Vec<DecorationRegion>,
+}
+impl Decorations {
+    fn new(placements: &[DecorationPlacement], sprites: &HashMap<String, DecorationSprite>) -> Self {
+        let mut decorations = Decorations {
+            positions: Vec::new(),
+            kinds: Vec::new(),
+            sway_offsets: Vec::new(),
+            regions: Vec::new(),
         };
-        let layout = config::config::WORLD_LAYOUT;
-        let mut world_layout = [[EntityType::None; WORLD_WIDTH]; WORLD_HEIGHT];
-        for y in 0..WORLD_HEIGHT {
-            for x in 0..WORLD_WIDTH {
-                match layout[y][x] {
-                    0 => {
-                        world_layout[y][x] = EntityType::None;
-                    }
-                    1 => {
-                        world_layout[y][x] = EntityType::Wall(WallHandle(walls.len() as u16));
-                        walls.push(Vec2::new(x as f32, y as f32));
-                    }
-                    2 => {
-                        world_layout[y][x] = EntityType::Player;
-                        if player.pos != Vec2::ZERO {
-                            panic!("Multiple player entities in world layout");
-                        }
-                        player.pos = Vec2::new(x as f32, y as f32);
-                    }
-                    3 => {
-                        let handle = enemies.new_enemy(
-                            Vec2::new(x as f32, y as f32),
-                            Vec2::new(1.0, -1.0),
-                            3,
-                            Vec2::new(1.0, 1.0),
-                            AnimationState::default_skeleton()
-                        );
-                        world_layout[y][x] = EntityType::Enemy(handle);
-                    }
-                    4 | 5 => {
-                        let direction; // Default direction
-                        if
-                            y > 0 &&
-                            y < WORLD_HEIGHT - 1 &&
-                            layout[y - 1][x] != 0 &&
-                            layout[y + 1][x] != 0
-                        {
-                            // Block above and below, door should be LEFT or RIGHT
-                            if layout[y][x] == 4 {
-                                direction = DoorDirection::RIGHT;
-                            } else {
-                                direction = DoorDirection::LEFT;
-                            }
-                        } else if
-                            x > 0 &&
-                            x < WORLD_WIDTH - 1 &&
-                            layout[y][x - 1] != 0 &&
-                            layout[y][x + 1] != 0
-                        {
-                            // Block left and right, door should be UP or DOWN
-                            if layout[y][x] == 4 {
-                                direction = DoorDirection::DOWN;
-                            } else {
-                                direction = DoorDirection::UP;
-                            }
-                        } else {
-                            panic!("Invalid door layout at ({}, {})", x, y);
-                        }
-
-                        let handle = doors.add_door(Vec2::new(x as f32, y as f32), direction);
-                        world_layout[y][x] = EntityType::Door(handle);
-                    }
-                    _ => panic!("Invalid entity type in world layout"),
-                };
+        let mut regions: HashMap<(usize, usize), Vec<u16>> = HashMap::new();
+        for placement in placements {
+            if !sprites.contains_key(&placement.id) {
+                eprintln!(
+                    "Warning: skipping decoration '{}' at ({}, {}), it has no loaded sprite",
+                    placement.id,
+                    placement.x,
+                    placement.y
+                );
+                continue;
+            }
+            let index = decorations.positions.len() as u16;
+            // entities sit at the top left corner of their tile, the placement is the center of the prop
+            decorations.positions.push(Vec2::new(placement.x - 0.5, placement.y - 0.5));
+            decorations.kinds.push(placement.id.clone());
+            decorations.sway_offsets.push(::rand::thread_rng().gen_range(0.0..2.0));
+            let region = (
+                (placement.x as usize) / DECORATION_REGION_SIZE,
+                (placement.y as usize) / DECORATION_REGION_SIZE,
+            );
+            regions.entry(region).or_default().push(index);
+        }
+        decorations.regions = regions
+            .into_iter()
+            .map(|((region_x, region_y), members)| DecorationRegion {
+                center: Vec2::new(
+                    ((region_x as f32) + 0.5) * (DECORATION_REGION_SIZE as f32) - 0.5,
+                    ((region_y as f32) + 0.5) * (DECORATION_REGION_SIZE as f32) - 0.5
+                ),
+                members,
+            })
+            .collect();
+        decorations
+    }
+    /// decorations in front of the player, farthest first,
+    /// regions that can't reach into the field of view are skipped without looking at their members
+    fn visible(&self, player_pos: Vec2, player_angle: f32, fov: f32) -> Vec<u16> {
+        // corner to center plus the widest sprite overhang
+        let region_radius = (DECORATION_REGION_SIZE as f32) * std::f32::consts::FRAC_1_SQRT_2 + 1.0;
+        let mut visible: Vec<(u16, f32)> = Vec::new();
+        for region in &self.regions {
+            let distance = player_pos.distance(region.center);
+            if distance > region_radius {
+                let relative_angle = angles::relative_angle(
+                    player_angle,
+                    angles::angle_to(player_pos, region.center)
+                );
+                let angular_radius = (region_radius / distance).asin();
+                if !angles::within_fov(relative_angle, fov / 2.0 + angular_radius) {
+                    continue;
+                }
+            }
+            for &index in &region.members {
+                let position = self.positions[index as usize];
+                let relative_angle = angles::relative_angle(
+                    player_angle,
+                    angles::angle_to(player_pos, position)
+                );
+                if angles::within_fov(relative_angle, fov / 2.0) {
+                    visible.push((index, player_pos.distance(position)));
+                }
             }
         }
+        visible.sort_by(|a, b| b.1.total_cmp(&a.1));
+        visible
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
 
-        let background_material = load_material(
-            ShaderSource::Glsl {
-                vertex: &DEFAULT_VERTEX_SHADER,
-                fragment: &FLOOR_FRAGMENT_SHADER,
+/// last frame of a killed enemy, drawn lying on the floor until its lifetime runs out
+struct CorpseData {
+    position: Vec2,
+    sprite_sheet: Texture2D,
+    source: Rect,
+    flip_x: bool,
+    color: Color,
+    lifetime: f32,
+}
+impl CorpseData {
+    fn from_animation(position: Vec2, animation: &AnimationState) -> Self {
+        CorpseData {
+            position,
+            sprite_sheet: animation.sprite_sheet.clone(),
+            source: Rect {
+                x: animation.spritesheet_offset_per_frame.x * (animation.frame as f32),
+                y: 0.0,
+                w: animation.spritesheet_offset_per_frame.x,
+                h: animation.sheet_size.y,
             },
-            MaterialParams {
-                uniforms: vec![
-                    UniformDesc {
-                        name: "u_player_pos".to_string(),
-                        uniform_type: UniformType::Float2,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "u_left_ray_dir".to_string(),
-                        uniform_type: UniformType::Float2,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "u_right_ray_dir".to_string(),
-                        uniform_type: UniformType::Float2,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "u_half_screen_height".to_string(),
-                        uniform_type: UniformType::Float1,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "u_screen_width".to_string(),
-                        uniform_type: UniformType::Float1,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "u_screen_height".to_string(),
-                        uniform_type: UniformType::Float1,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "is_ceiling".to_string(),
-                        uniform_type: UniformType::Float1,
-                        array_count: 1,
+            flip_x: animation.need_to_flip_x(),
+            color: animation.color,
+            lifetime: CORPSE_LIFETIME,
+        }
+    }
+}
+
+/// what an effect does to the frame, see VisualEffects for how several are put together
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum EffectKind {
+    CameraShake {
+        intensity: f32, // pixels of random offset, damped over the duration
+    },
+    Tint(Color), // full screen overlay
+    Vignette(Color), // darkened screen edges
+    Adrenaline, // glowing edges and streaks, see RenderPlayerPOV::render_adrenaline
+    Nudge(Vec2), // pixels the view is pushed, added to the shake
+}
+/// what started an effect, held effects are replaced and released by their source
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum EffectSource {
+    Shot,
+    PlayerHit,
+    Teleport,
+    Water,
+    Adrenaline,
+    Elevator,
+    Sequence,
+    NearMiss,
+}
+struct ActiveEffect {
+    kind: EffectKind,
+    source: EffectSource,
+    duration: Option<f32>, // None stays at full strength until VisualEffects::release
+    elapsed: f32,
+}
+impl ActiveEffect {
+    /// 1 when it starts, fading linearly to 0 at the end of the duration
+    fn strength(&self) -> f32 {
+        match self.duration {
+            Some(duration) => (1.0 - self.elapsed / duration).clamp(0.0, 1.0),
+            None => 1.0,
+        }
+    }
+}
+/// Every running screen effect. When drawn, the shake offsets add up, the tints are drawn over each other
+/// in the order they were added so their colors multiply, and of the vignettes only the strongest shows.
+struct VisualEffects {
+    effects: Vec<ActiveEffect>,
+}
+impl VisualEffects {
+    fn new() -> Self {
+        VisualEffects { effects: Vec::new() }
+    }
+    fn add(&mut self, source: EffectSource, kind: EffectKind, duration: f32) {
+        self.effects.push(ActiveEffect {
+            kind,
+            source,
+            duration: Some(duration),
+            elapsed: 0.0,
+        });
+    }
+    /// keeps one effect of the source running without a timer, replacing the one it had
+    fn hold(&mut self, source: EffectSource, kind: EffectKind) {
+        self.release(source);
+        self.effects.push(ActiveEffect {
+            kind,
+            source,
+            duration: None,
+            elapsed: 0.0,
+        });
+    }
+    fn release(&mut self, source: EffectSource) {
+        self.effects.retain(|effect| effect.source != source);
+    }
+    fn clear(&mut self) {
+        self.effects.clear();
+    }
+    /// advances every timer and drops only the effects that ran out
+    fn update(&mut self, dt: f32) {
+        for effect in &mut self.effects {
+            effect.elapsed += dt;
+        }
+        self.effects.retain(|effect| effect.duration.is_none_or(|duration| effect.elapsed < duration));
+    }
+    fn shake_offset(&self) -> Vec2 {
+        self.effects
+            .iter()
+            .filter_map(|effect| {
+                match effect.kind {
+                    EffectKind::CameraShake { intensity } => {
+                        let angle = random::<f32>() * std::f32::consts::TAU;
+                        Some(Vec2::new(angle.cos(), angle.sin()) * intensity * effect.strength())
                     }
-                ],
-                textures: vec!["u_floor_texture".to_string()],
-                ..Default::default()
+                    EffectKind::Nudge(offset) => Some(offset * effect.strength()),
+                    _ => None,
+                }
+            })
+            .sum()
+    }
+    /// overlay colors with their alpha faded, in the order they are drawn
+    fn tints(&self) -> impl Iterator<Item = Color> + '_ {
+        self.effects.iter().filter_map(|effect| {
+            match effect.kind {
+                EffectKind::Tint(color) => Some(Color { a: color.a * effect.strength(), ..color }),
+                _ => None,
+            }
+        })
+    }
+    fn strongest_vignette(&self) -> Option<Color> {
+        self.effects
+            .iter()
+            .filter_map(|effect| {
+                match effect.kind {
+                    EffectKind::Vignette(color) => Some(Color { a: color.a * effect.strength(), ..color }),
+                    _ => None,
+                }
+            })
+            .max_by(|a, b| a.a.total_cmp(&b.a))
+    }
+    fn adrenaline_strength(&self) -> Option<f32> {
+        self.effects
+            .iter()
+            .filter(|effect| effect.kind == EffectKind::Adrenaline)
+            .map(|effect| effect.strength())
+            .max_by(|a, b| a.total_cmp(b))
+    }
+}
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum InputButton {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+impl InputButton {
+    fn is_down(&self) -> bool {
+        match self {
+            InputButton::Key(key) => is_key_down(*key),
+            InputButton::Mouse(button) => is_mouse_button_down(*button),
+        }
+    }
+    fn is_pressed(&self) -> bool {
+        match self {
+            InputButton::Key(key) => is_key_pressed(*key),
+            InputButton::Mouse(button) => is_mouse_button_pressed(*button),
+        }
+    }
+}
+/// every action can be triggered by any of its buttons, keys.cfg can change them, see keys::load
+#[derive(Clone)]
+struct KeyBindings {
+    forward: Vec<InputButton>,
+    back: Vec<InputButton>,
+    strafe_left: Vec<InputButton>,
+    strafe_right: Vec<InputButton>,
+    turn_left: Vec<InputButton>,
+    turn_right: Vec<InputButton>,
+    quick_turn: Vec<InputButton>,
+    sprint: Vec<InputButton>,
+    walk_toggle: Vec<InputButton>,
+    crouch: Vec<InputButton>,
+    map_overview: Vec<InputButton>,
+    pause: Vec<InputButton>,
+    cycle_hud_theme: Vec<InputButton>,
+    cycle_fire_mode: Vec<InputButton>,
+    toggle_renderer: Vec<InputButton>,
+    reload: Vec<InputButton>,
+    weapon_slots: Vec<Vec<InputButton>>, // weapon_slots[i] selects the i-th weapon of the inventory
+    shoot: Vec<InputButton>,
+    interact: Vec<InputButton>,
+    weapon_wheel: Vec<InputButton>, // held, see WeaponWheel
+}
+impl KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            forward: vec![InputButton::Key(KeyCode::W)],
+            back: vec![InputButton::Key(KeyCode::S)],
+            strafe_left: vec![InputButton::Key(KeyCode::A)],
+            strafe_right: vec![InputButton::Key(KeyCode::D)],
+            // the mouse turns as well, see MouseLook
+            turn_left: vec![InputButton::Key(KeyCode::Left)],
+            turn_right: vec![InputButton::Key(KeyCode::Right)],
+            quick_turn: vec![InputButton::Key(KeyCode::Q)],
+            sprint: vec![InputButton::Key(KeyCode::LeftShift)],
+            walk_toggle: vec![InputButton::Key(KeyCode::CapsLock)],
+            crouch: vec![InputButton::Key(KeyCode::LeftControl)],
+            map_overview: vec![InputButton::Key(KeyCode::M)],
+            pause: vec![InputButton::Key(KeyCode::Escape)],
+            cycle_hud_theme: vec![InputButton::Key(KeyCode::T)],
+            cycle_fire_mode: vec![InputButton::Key(KeyCode::B)],
+            toggle_renderer: vec![InputButton::Key(KeyCode::G)],
+            reload: vec![InputButton::Key(KeyCode::R)],
+            weapon_slots: [
+                KeyCode::Key1,
+                KeyCode::Key2,
+                KeyCode::Key3,
+                KeyCode::Key4,
+                KeyCode::Key5,
+                KeyCode::Key6,
+                KeyCode::Key7,
+                KeyCode::Key8,
+                KeyCode::Key9,
+            ]
+                .into_iter()
+                .map(|key| vec![InputButton::Key(key)])
+                .collect(),
+            shoot: vec![InputButton::Key(KeyCode::Space), InputButton::Mouse(MouseButton::Left)],
+            interact: vec![InputButton::Key(KeyCode::E)],
+            weapon_wheel: vec![InputButton::Key(KeyCode::Tab), InputButton::Mouse(MouseButton::Middle)],
+        }
+    }
+    fn is_down(buttons: &[InputButton]) -> bool {
+        buttons.iter().any(|button| button.is_down())
+    }
+    fn is_pressed(buttons: &[InputButton]) -> bool {
+        buttons.iter().any(|button| button.is_pressed())
+    }
+}
+#[derive(Clone, Copy, Debug)]
+struct EndlessRun {
+    seed: u64, // every level of the run is generated from it, see map::generate::endless_level
+    depth: u32, // the level the player is on, starting at 1
+}
+/// Counts physics steps instead of reading the clock, so a run takes the same time on every machine and
+/// stops while the simulation does, in the map overview, behind a note and after the game is over.
+struct SpeedrunTimer {
+    steps: u64,
+    par_time: Option<f32>, // seconds, from the map
+    invalidated: bool, // the level was changed underneath the run
+    splits: Vec<u64>, // steps at the end of every finished level of an endless run
+    best_splits: Vec<u64>, // the personal best of the same seed from Records, compared against but not updated
+}
+impl SpeedrunTimer {
+    fn new(par_time: Option<f32>) -> Self {
+        SpeedrunTimer { steps: 0, par_time, invalidated: false, splits: Vec::new(), best_splits: Vec::new() }
+    }
+    /// only steps of the running game count, World::update isn't called in any other state either
+    fn tick(&mut self, game_state: &GameState) {
+        if game_state.simulates() {
+            self.steps += 1;
+        }
+    }
+    fn seconds(&self) -> f64 {
+        SpeedrunTimer::steps_to_seconds(self.steps)
+    }
+    fn steps_to_seconds(steps: u64) -> f64 {
+        (steps as f64) * (PHYSICS_FRAME_TIME as f64)
+    }
+    /// seconds over the par time, negative while the run is still under it
+    fn par_delta(&self) -> Option<f64> {
+        self.par_time.map(|par_time| self.seconds() - (par_time as f64))
+    }
+    /// ends the current level
+    fn split(&mut self) {
+        self.splits.push(self.steps);
+    }
+    /// seconds the split is behind the personal best, negative when ahead, None without a best that far
+    fn split_delta(&self, index: usize) -> Option<f64> {
+        let split = *self.splits.get(index)?;
+        let best = *self.best_splits.get(index)?;
+        Some(SpeedrunTimer::steps_to_seconds(split) - SpeedrunTimer::steps_to_seconds(best))
+    }
+    /// a run that finished more levels is better, between runs that finished as many the faster one,
+    /// an invalidated run never is
+    fn beats_best(&self) -> bool {
+        if self.invalidated || self.splits.is_empty() {
+            return false;
+        }
+        match self.splits.len().cmp(&self.best_splits.len()) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => self.splits.last() < self.best_splits.last(),
+        }
+    }
+    /// minutes:seconds.milliseconds
+    fn format(seconds: f64) -> String {
+        let milliseconds = (seconds * 1000.0).round() as u64;
+        format!("{}:{:02}.{:03}", milliseconds / 60_000, (milliseconds / 1000) % 60, milliseconds % 1000)
+    }
+    /// with a sign, red when behind and green when ahead
+    fn format_delta(delta: f64) -> (String, Color) {
+        if delta > 0.0 {
+            (format!("+{}", SpeedrunTimer::format(delta)), RED)
+        } else {
+            (format!("-{}", SpeedrunTimer::format(-delta)), GREEN)
+        }
+    }
+}
+/// SplitMix64, the one rng the simulation draws from (loot, pellet jitter, bloom), so the same inputs play out
+/// the same. Whatever is only drawn or heard (sway, growls, shake) keeps using thread_rng and stays out of it.
+struct SimulationRng {
+    state: u64,
+}
+impl SimulationRng {
+    fn new(seed: u64) -> Self {
+        SimulationRng { state: seed }
+    }
+}
+impl ::rand::RngCore for SimulationRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+/// FNV-1a over the simulation state, stable across runs and builds unlike the std hashers.
+/// Floats are rounded to 1/STATE_HASH_QUANTUM first, so noise far below what the game can show doesn't
+/// change the hash. Anything only drawn (animations, effects, corpses, facing angles) is left out,
+/// the rng state is in, so two runs that drew differently part ways right there.
+struct StateHasher {
+    hash: u64,
+}
+impl StateHasher {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    /// steps are the physics frames so far, see SpeedrunTimer::steps
+    fn simulation(
+        steps: u64,
+        rng: &SimulationRng,
+        player: &Player,
+        enemies: &Enemies,
+        doors: &Doors,
+        projectiles: &Projectiles
+    ) -> u64 {
+        let mut hasher = StateHasher { hash: StateHasher::OFFSET_BASIS };
+        hasher.write(steps);
+        hasher.write(rng.state);
+        hasher.write_vec2(player.pos);
+        hasher.write_vec2(player.vel);
+        hasher.write_f32(player.angle);
+        hasher.write(player.health as u64);
+        hasher.write_f32(player.stamina);
+        hasher.write(player.weapons.active as u64);
+        hasher.write(enemies.positions.len() as u64);
+        for index in 0..enemies.positions.len() {
+            hasher.write_vec2(enemies.positions[index]);
+            hasher.write_vec2(enemies.velocities[index]);
+            hasher.write_f32(enemies.healths[index]);
+            hasher.write(enemies.alives[index] as u64);
+            hasher.write(enemies.aggressive_states[index] as u64);
+            hasher.write_f32(enemies.attack_cooldowns[index]);
+        }
+        hasher.write(doors.positions.len() as u64);
+        for index in 0..doors.positions.len() {
+            hasher.write(doors.opened[index] as u64);
+            hasher.write(doors.locked[index] as u64);
+            hasher.write_f32(doors.animation_progress[index]);
+        }
+        hasher.write(projectiles.positions.len() as u64);
+        for index in 0..projectiles.positions.len() {
+            hasher.write_vec2(projectiles.positions[index]);
+            hasher.write_vec2(projectiles.velocities[index]);
+            hasher.write(projectiles.damages[index] as u64);
+        }
+        hasher.hash
+    }
+    fn write(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.hash = (self.hash ^ (byte as u64)).wrapping_mul(StateHasher::PRIME);
+        }
+    }
+    fn write_f32(&mut self, value: f32) {
+        self.write((value * STATE_HASH_QUANTUM).round() as i64 as u64);
+    }
+    fn write_vec2(&mut self, value: Vec2) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+    }
+}
+/// turns the player with the mouse, the cursor is grabbed and hidden while the game is running
+/// until escape gives it back, a click in the window grabs it again
+struct MouseLook {
+    grabbed: bool,
+    last_position: Option<Vec2>, // None drops the next movement
+}
+impl MouseLook {
+    fn new() -> Self {
+        MouseLook { grabbed: false, last_position: None }
+    }
+    /// pausing is how the cursor is freed
+    fn update(&mut self, playing: bool) {
+        self.set_grabbed(playing);
+    }
+    fn set_grabbed(&mut self, grabbed: bool) {
+        if grabbed == self.grabbed {
+            return;
+        }
+        set_cursor_grab(grabbed);
+        show_mouse(!grabbed);
+        self.grabbed = grabbed;
+        // the cursor moved freely while it was released
+        self.last_position = None;
+    }
+    /// pixels the mouse moved since the last frame
+    fn take_delta(&mut self) -> Vec2 {
+        let position = Vec2::from(mouse_position());
+        // macroquad has no focus events, a long frame is the sign that the window was in the background
+        // and whatever the mouse did meanwhile would snap the view
+        let delta = match self.last_position {
+            Some(last) if self.grabbed && get_frame_time() < MOUSE_LOOK_MAX_FRAME_TIME => position - last,
+            _ => Vec2::ZERO,
+        };
+        self.last_position = Some(position);
+        delta
+    }
+}
+
+enum GameState {
+    GameGoing,
+    Paused,
+    GameOver,
+    MapOverview {
+        camera_pos: Vec2, // world tile the screen is centered on
+        zoom: f32,
+    },
+    ReadingNote {
+        note: usize, // index into World::notes
+        page: usize,
+    },
+}
+impl GameState {
+    /// only the running game steps the world, every other state waits behind its overlay
+    fn simulates(&self) -> bool {
+        matches!(self, GameState::GameGoing)
+    }
+}
+/// the panel of a note, it pauses the game like the pause menu while the music keeps playing
+struct NoteReader;
+impl NoteReader {
+    fn open(note: usize, notes_read: &mut [bool]) -> GameState {
+        notes_read[note] = true;
+        GameState::ReadingNote { note, page: 0 }
+    }
+    /// one frame of input on the open panel, `turn` is +1 for the next page and -1 for the previous one,
+    /// the pages don't wrap around
+    fn input(game_state: &mut GameState, notes: &[Note], turn: isize, close: bool) {
+        let GameState::ReadingNote { note, page } = game_state else {
+            return;
+        };
+        if close {
+            *game_state = GameState::GameGoing;
+            return;
+        }
+        let last_page = notes[*note].pages.len() - 1;
+        *page = page.saturating_add_signed(turn).min(last_page);
+    }
+}
+struct AudioSystem {
+    sounds: HashMap<Sounds, Sound>,
+    volume: f32, // master * sfx volume from the settings
+}
+impl AudioSystem {
+    async fn load(volume: f32) -> Self {
+        let mut sounds = HashMap::new();
+        sounds.insert(
+            Sounds::PistolShoot,
+            load_sound("sounds/pistol_shoot.wav").await.expect("Failed to load pistol shoot sound")
+        );
+        sounds.insert(
+            Sounds::Reload,
+            load_sound("sounds/reload.wav").await.expect("Failed to load reload sound")
+        );
+        sounds.insert(
+            Sounds::ShotgunShoot,
+            load_sound("sounds/shotgun_shoot.wav").await.expect("Failed to load shotgun shoot sound")
+        );
+        sounds.insert(
+            Sounds::ShotgunReload,
+            load_sound("sounds/shotgun_reload.wav").await.expect("Failed to load shotgun reload sound")
+        );
+        sounds.insert(Sounds::DryFire, load_sound("sounds/dry_fire.wav").await.expect("Failed to load dry fire"));
+        sounds.insert(
+            Sounds::Teleport,
+            load_sound("sounds/teleport.wav").await.expect("Failed to load teleport sound")
+        );
+        sounds.insert(
+            Sounds::ElevatorHum,
+            load_sound("sounds/elevator_hum.wav").await.expect("Failed to load elevator hum")
+        );
+        sounds.insert(Sounds::Sting, load_sound("sounds/sting.wav").await.expect("Failed to load sting"));
+        sounds.insert(Sounds::Pickup, load_sound("sounds/pickup.wav").await.expect("Failed to load pickup sound"));
+        sounds.insert(Sounds::Grunt, load_sound("sounds/grunt.wav").await.expect("Failed to load grunt"));
+        sounds.insert(Sounds::Growl, load_sound("sounds/growl.wav").await.expect("Failed to load growl"));
+        sounds.insert(Sounds::Explosion, load_sound("sounds/explosion.wav").await.expect("Failed to load explosion"));
+        sounds.insert(Sounds::WhizzLeft, load_sound("sounds/whizz_left.wav").await.expect("Failed to load whizz"));
+        sounds.insert(Sounds::WhizzRight, load_sound("sounds/whizz_right.wav").await.expect("Failed to load whizz"));
+        AudioSystem { sounds, volume }
+    }
+    fn play(&self, sound: Sounds, volume: f32) {
+        let sound = self.sounds.get(&sound).expect("Sound was not loaded");
+        play_sound(sound, PlaySoundParams {
+            volume: volume * self.volume,
+            looped: false,
+        });
+    }
+    /// (delay, volume) of the repeats of any sound played inside a reverb zone
+    fn reverb_tail(volume: f32, profile: ReverbProfile) -> Vec<(f32, f32)> {
+        match profile {
+            ReverbProfile::SmallRoom => vec![(0.06, volume * 0.3), (0.12, volume * 0.12)],
+            ReverbProfile::Corridor => vec![(0.1, volume * 0.4), (0.2, volume * 0.25)],
+            ReverbProfile::LargeHall => vec![(0.35, volume * 0.35), (0.75, volume * 0.2)],
+        }
+    }
+    /// (delay, volume) of the repeats following a gunshot, open areas (long average ray distance) echo later and louder
+    fn gunshot_echoes(volume: f32, average_view_distance: f32) -> Vec<(f32, f32)> {
+        const OPEN_AREA_DISTANCE: f32 = 12.0;
+        const MIN_DELAY: f32 = 0.15;
+        const MAX_DELAY: f32 = 0.3;
+        let openness = (average_view_distance / OPEN_AREA_DISTANCE).clamp(0.0, 1.0);
+        let delay = MIN_DELAY + (MAX_DELAY - MIN_DELAY) * openness;
+        let mut echoes = vec![(delay, volume * (0.15 + 0.2 * openness))];
+        if openness > 0.5 {
+            echoes.push((delay * 2.0, volume * 0.15 * openness));
+        }
+        echoes
+    }
+}
+#[derive(Clone, Copy)]
+enum ScheduledAction {
+    PlaySound {
+        sound: Sounds,
+        volume: f32,
+    },
+    EndHitStop,
+    FireBurstShot,
+    ElevatorArrive,
+    SequenceStep {
+        sequence: u16, // index into Sequences::sequences
+        step: u16,
+    },
+}
+struct ScheduledTimer {
+    remaining: f32,
+    action: ScheduledAction,
+}
+struct Scheduler {
+    timers: Vec<ScheduledTimer>,
+}
+impl Scheduler {
+    fn new() -> Self {
+        Scheduler { timers: Vec::new() }
+    }
+    fn schedule(&mut self, delay: f32, action: ScheduledAction) {
+        self.timers.push(ScheduledTimer {
+            remaining: delay,
+            action,
+        });
+    }
+    fn update(&mut self, dt: f32) -> Vec<ScheduledAction> {
+        // a delay of whole physics frames counted down frame by frame ends a rounding error above zero,
+        // which would hold the timer back for one more frame
+        const DUE_TOLERANCE: f32 = 1e-4;
+        let mut due = Vec::new();
+        for timer in self.timers.iter_mut() {
+            timer.remaining -= dt;
+            if timer.remaining <= DUE_TOLERANCE {
+                due.push(timer.action);
+            }
+        }
+        self.timers.retain(|timer| timer.remaining > DUE_TOLERANCE);
+        due
+    }
+    fn cancel_all(&mut self) {
+        self.timers.clear();
+    }
+}
+/// what only lives for a moment in the running level, dropped with it, see World::clear_transient
+struct Transients {
+    projectiles: Projectiles,
+    explosions: Vec<Explosion>,
+    repulsion_fields: Vec<RepulsionField>,
+    scheduler: Scheduler,
+}
+impl Transients {
+    fn new() -> Self {
+        Transients {
+            projectiles: Projectiles::new(),
+            explosions: Vec::new(),
+            repulsion_fields: Vec::new(),
+            scheduler: Scheduler::new(),
+        }
+    }
+    fn clear(&mut self) {
+        self.projectiles.clear();
+        self.explosions.clear();
+        self.repulsion_fields.clear();
+        self.scheduler.cancel_all();
+    }
+    /// a blast that just went off, drawn until its animation ends and scattering the enemies around it
+    fn add_explosion(&mut self, explosion: Explosion) {
+        self.repulsion_fields.push(RepulsionField { center: explosion.position, age: 0.0 });
+        self.explosions.push(explosion);
+    }
+    fn age_explosions(&mut self) {
+        self.explosions.retain_mut(|explosion| {
+            explosion.animation.next(PHYSICS_FRAME_TIME).event_type != AnimationCallbackEventType::AnimationFinished
+        });
+    }
+    /// a scheduled step of a sequence the level doesn't have, left over from the one before
+    fn has_stale_sequence_step(&self, sequences: &Sequences) -> bool {
+        self.scheduler.timers.iter().any(|timer| {
+            matches!(timer.action, ScheduledAction::SequenceStep { sequence, .. }
+                if sequence as usize >= sequences.sequences.len())
+        })
+    }
+}
+/// how fast the simulation runs compared to real time
+struct TimeScale {
+    scale: f32,
+    hit_stop_previous: Option<f32>, // the scale to go back to while a hit-stop runs
+    slow_motion: Option<f32>, // on top of the scale, kept apart so a hit-stop ending meanwhile can't undo it
+}
+impl TimeScale {
+    fn new() -> Self {
+        TimeScale { scale: 1.0, hit_stop_previous: None, slow_motion: None }
+    }
+    /// what the simulation runs at
+    fn current(&self) -> f32 {
+        self.scale * self.slow_motion.unwrap_or(1.0)
+    }
+    /// nearly freezes the simulation for HIT_STOP_DURATION, impacts during a running hit-stop don't extend it
+    fn hit_stop(&mut self, scheduler: &mut Scheduler) {
+        if self.hit_stop_previous.is_some() {
+            return;
+        }
+        self.hit_stop_previous = Some(self.scale);
+        self.scale = HIT_STOP_TIME_SCALE;
+        scheduler.schedule(HIT_STOP_DURATION, ScheduledAction::EndHitStop);
+    }
+    /// back to the scale from before the hit-stop, nothing happens without one running
+    fn end_hit_stop(&mut self) {
+        if let Some(previous) = self.hit_stop_previous.take() {
+            self.scale = previous;
+        }
+    }
+}
+/// The scripted sequences of the running map, see map::parse_sequences. The steps go through the scheduler,
+/// so they wait while the game is paused and swapping the level cancels whatever was still to come.
+struct Sequences {
+    sequences: Vec<ScriptedSequence>,
+    started: Vec<bool>, // a sequence runs once per level, however often its trigger fires
+}
+impl Sequences {
+    fn new(sequences: Vec<ScriptedSequence>) -> Self {
+        Sequences { started: vec![false; sequences.len()], sequences }
+    }
+    /// schedules every step of the sequence, false when it already ran. The steps are sorted by their time
+    /// and the scheduler hands out timers that are due together in the order they were scheduled.
+    fn start(&mut self, index: usize, scheduler: &mut Scheduler) -> bool {
+        if self.started[index] {
+            return false;
+        }
+        self.started[index] = true;
+        for (step, sequence_step) in self.sequences[index].steps.iter().enumerate() {
+            scheduler.schedule(sequence_step.at, ScheduledAction::SequenceStep {
+                sequence: index as u16,
+                step: step as u16,
+            });
+        }
+        true
+    }
+    fn action(&self, sequence: u16, step: u16) -> &SequenceAction {
+        &self.sequences[sequence as usize].steps[step as usize].action
+    }
+}
+/// what interact does in the exit elevator of an endless run
+#[derive(PartialEq, Debug)]
+enum ElevatorRide {
+    Outside, // the player isn't in the cabin, interact does what it does anywhere else
+    Riding, // already on the way, interact again does nothing
+    Blocked, // a living enemy stands in the cabin or its doorway, the doors stay open
+    Departing, // the door closed behind the player
+}
+struct ElevatorSystem;
+impl ElevatorSystem {
+    /// the door in the wall around an elevator cabin
+    fn door(cabin: (usize, usize), world_layout: &Grid<EntityType>) -> Option<DoorHandle> {
+        let (x, y) = cabin;
+        [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+            .iter()
+            .find_map(|&(x, y)| {
+                match world_layout.get(x, y) {
+                    Some(&EntityType::Door(handle)) => Some(handle),
+                    _ => None,
+                }
+            })
+    }
+    /// closes the door of `cabin` behind the player, enemies never ride along
+    fn ride(
+        riding: bool,
+        cabin: (usize, usize),
+        player_pos: Vec2,
+        world_layout: &Grid<EntityType>,
+        doors: &mut Doors,
+        enemies: &Enemies
+    ) -> ElevatorRide {
+        if riding {
+            return ElevatorRide::Riding;
+        }
+        let player_tile = Tile::from_vec2(player_pos);
+        if (player_tile.x as usize, player_tile.y as usize) != cabin {
+            return ElevatorRide::Outside;
+        }
+        let door = Self::door(cabin, world_layout);
+        let cabin_tile = Tile { x: cabin.0 as u16, y: cabin.1 as u16 };
+        let door_tile = door.map(|door| Tile::from_vec2(doors.positions[door.0 as usize]));
+        let blocked = (0..enemies.positions.len()).any(|index| {
+            let tile = Tile::from_vec2(enemies.positions[index]);
+            enemies.alives[index] && (tile == cabin_tile || Some(tile) == door_tile)
+        });
+        if blocked {
+            return ElevatorRide::Blocked;
+        }
+        if let Some(door) = door {
+            doors.close_door(door);
+        }
+        ElevatorRide::Departing
+    }
+}
+/// the enemy of a boss step, see SequenceTrigger::BossKilled
+struct Boss {
+    spawn_id: SpawnId,
+    max_health: f32,
+}
+struct Level {
+    world_layout: Grid<EntityType>,
+    walls: Vec<Vec2>,
+    doors: Doors,
+    enemies: Enemies,
+    pickups: Pickups,
+    player_spawn: Vec2,
+}
+impl Level {
+    fn from_layout(layout: &map::Layout, content: &ContentRegistry) -> Result<Self, String> {
+        let mut walls = Vec::new();
+        let mut enemies = Enemies::new();
+        let mut pickups = Pickups::new();
+        let mut doors = Doors::new(1.0, 1.0, 1.0);
+        let mut player_spawn = None;
+        let mut world_layout = Grid::same_size(layout, EntityType::None);
+        for y in 0..layout.height() {
+            for x in 0..layout.width() {
+                match layout[y][x] {
+                    0 => {
+                        world_layout[y][x] = EntityType::None;
+                    }
+                    1 => {
+                        world_layout[y][x] = EntityType::Wall(WallHandle(walls.len() as u16));
+                        walls.push(Vec2::new(x as f32, y as f32));
+                    }
+                    2 => {
+                        world_layout[y][x] = EntityType::Player;
+                        if player_spawn.is_some() {
+                            return Err("Multiple player entities in world layout".to_string());
+                        }
+                        player_spawn = Some(Vec2::new(x as f32, y as f32));
+                    }
+                    6 | 8 => {
+                        let item = if layout[y][x] == 6 {
+                            LootItem::AmmoPickup(WeaponType::Pistol, AMMO_PICKUP_AMOUNT)
+                        } else {
+                            LootItem::HealthPack(HEALTH_PICKUP_AMOUNT)
+                        };
+                        let handle = pickups.add(Vec2::new(x as f32, y as f32), item);
+                        world_layout[y][x] = EntityType::Pickup(handle);
+                    }
+                    tile if content.spawn_table.contains_key(&tile) => {
+                        let kind = &content.enemies[&content.spawn_table[&tile]];
+                        let new_enemy = enemies.new_enemy(
+                            Vec2::new(x as f32, y as f32),
+                            Vec2::new(1.0, -1.0),
+                            Vec2::new(1.0, 1.0),
+                            AnimationState::default_skeleton(),
+                            kind
+                        );
+                        match new_enemy {
+                            Ok(handle) => {
+                                world_layout[y][x] = EntityType::Enemy(handle);
+                            }
+                            Err(err) => {
+                                eprintln!(
+                                    "Warning: skipping enemy at ({}, {}), level is at its capacity of {} enemies",
+                                    x,
+                                    y,
+                                    err.capacity
+                                );
+                            }
+                        }
+                    }
+                    4 | 5 => {
+                        let direction = match (map::validate::door_walls(layout, x, y), layout[y][x]) {
+                            // the door slides into one of the walls beside it
+                            (Some(DoorWalls::AboveBelow), 4) => DoorDirection::DOWN,
+                            (Some(DoorWalls::AboveBelow), _) => DoorDirection::UP,
+                            (Some(DoorWalls::LeftRight), 4) => DoorDirection::RIGHT,
+                            (Some(DoorWalls::LeftRight), _) => DoorDirection::LEFT,
+                            (None, _) => {
+                                return Err(format!("Invalid door layout at ({}, {})", x, y));
+                            }
+                        };
+
+                        let handle = doors.add_door(Vec2::new(x as f32, y as f32), direction);
+                        world_layout[y][x] = EntityType::Door(handle);
+                    }
+                    tile => {
+                        return Err(format!("Invalid entity type {} in world layout", tile));
+                    }
+                };
+            }
+        }
+        Ok(Level {
+            world_layout,
+            walls,
+            doors,
+            enemies,
+            pickups,
+            player_spawn: player_spawn.ok_or("No player entity in world layout")?,
+        })
+    }
+    /// moves the player over from the level before, everything else about them carries on as it was
+    fn place_player(&mut self, player: &mut Player, to_spawn: bool) {
+        let player_pos = if to_spawn { self.player_spawn } else { player.pos };
+        let player_pos = player_pos.clamp(
+            Vec2::ZERO,
+            Vec2::new((self.world_layout.width() - 1) as f32, (self.world_layout.height() - 1) as f32)
+        );
+        let player_tile = Tile::from_vec2(player_pos);
+        let spawn_tile = Tile::from_vec2(self.player_spawn);
+        match self.world_layout[player_tile.y as usize][player_tile.x as usize] {
+            EntityType::Wall(_) | EntityType::Door(_) => {
+                player.pos = self.player_spawn;
+            }
+            _ => {
+                self.world_layout[spawn_tile.y as usize][spawn_tile.x as usize] = EntityType::None;
+                self.world_layout[player_tile.y as usize][player_tile.x as usize] = EntityType::Player;
+                player.pos = player_pos;
+            }
+        }
+    }
+}
+struct World {
+    world_layout: Grid<EntityType>,
+    background_material: Material,
+    raycast_material: Material, // the experimental gpu renderer, see Settings::gpu_raycasting
+    camera_shake_material: Material,
+    enemy_default_material: Material,
+    audio: AudioSystem,
+    time_scale: TimeScale,
+    weapon_wheel: Option<WeaponWheel>, // open while its button is held
+    growl_cooldown: f32, // seconds until the next aggressive enemy nearby growls
+    average_view_distance: f32,
+    reverb_zones: Vec<ReverbZone>,
+    current_reverb_zone: Option<usize>, // index into reverb_zones, updated once per physics frame
+    teleporters: Vec<TeleporterPair>,
+    notes: Vec<Note>,
+    floor_types: FloorGrid,
+    floor_types_texture: Texture2D, // floor_types for the floor shader
+    decoration_sprites: HashMap<String, DecorationSprite>, // per decoration kind of the content
+    decorations: Decorations,
+    pickups: Pickups,
+    transients: Transients,
+    tiles_texture: Texture2D, // tile_texels for the raycast shader, refreshed every gpu rendered frame
+    notes_read: Vec<bool>, // per note, for the minimap and the notes found count
+    map_warnings: Vec<Diagnostic>, // marked on the minimap in debug builds
+    debug_enemy: Option<SpawnId>, // picked in the map overview of debug builds for the vision overlay
+    path_target: Option<Tile>, // the player tile the enemy paths lead to, None plans them again on the next update
+    path_age: u32, // physics frames since the enemy paths were planned
+    riding_elevator: bool, // the controls are locked until ScheduledAction::ElevatorArrive
+    paused_time: f64, // seconds spent outside of GameGoing, see World::game_time
+    enemy_vision_debug: Option<EnemyVisionDebug>, // of debug_enemy, refreshed every physics frame
+    walls: Vec<Vec2>,
+    doors: Doors,
+    enemies: Enemies,
+    player: Player,
+    player_interactables: Vec<InteractionEvent>,
+    floating_texts: Vec<FloatingText>,
+    corpses: Vec<CorpseData>,
+    minimap_offset: Vec2,
+    ui_scale: f32,
+    settings: Settings,
+    hud_theme: HudTheme,
+    effects: VisualEffects,
+    speedrun_timer: SpeedrunTimer,
+    rng: SimulationRng, // seeded again with every level, see SimulationRng
+    game_state: GameState,
+    key_bindings: KeyBindings,
+    records: Records, // read again with every load_world, written on a new record
+    content: Rc<ContentRegistry>,
+    map_path: String,
+    map_modified: Option<SystemTime>,
+    map_poll_elapsed: f32,
+    update_time: f64, // seconds the last physics step took, for the debug timings
+    exit: Option<(usize, usize)>,
+    endless: Option<EndlessRun>,
+    sequences: Sequences,
+    boss: Option<Boss>,
+    boss_bar: Option<String>, // the name above the boss health bar, shown once a boss_bar step ran
+    title_card: Option<FloatingText>,
+    mouse_look: MouseLook,
+}
+impl World {
+    /// map_id is a map of the content or the path of a .map file, which is played without a content entry
+    async fn default(content: Rc<ContentRegistry>, map_id: &str, settings: &Settings) -> Self {
+        let map_path = match content.maps.get(map_id) {
+            Some(map_def) => map_def.file.clone(),
+            None if map_id.ends_with(".map") => map_id.to_string(),
+            None => {
+                eprintln!("Unknown map {}, using {}", map_id, DEFAULT_MAP_ID);
+                content.maps[DEFAULT_MAP_ID].file.clone()
+            }
+        };
+        let map_file = map::load_map(&map_path, &content.spawn_tiles()).unwrap_or_else(|err| {
+            eprintln!("Failed to load map {}: {}, using the built-in layout", map_path, err);
+            map::MapFile::built_in()
+        });
+        Self::with_map(content, map_path, map_file, settings).await
+    }
+
+    /// the generated stress test map, enemy_density scales STRESS_ENEMIES
+    async fn stress(content: Rc<ContentRegistry>, enemy_density: f32, settings: &Settings) -> Self {
+        let enemies = ((STRESS_ENEMIES as f32) * enemy_density.max(0.0)).round() as usize;
+        let map_file = map::generate::stress_map(STRESS_SEED, enemies).unwrap_or_else(|err| {
+            eprintln!("Failed to generate the stress map: {}, using the built-in layout", err);
+            map::MapFile::built_in()
+        });
+        // no file behind it, so there is nothing to hot reload
+        Self::with_map(content, "<stress>".to_string(), map_file, settings).await
+    }
+
+    /// the first generated level of an endless run, the exit of each level leads to the next one
+    async fn endless(content: Rc<ContentRegistry>, run_seed: u64, settings: &Settings) -> Self {
+        let map_file = map::generate::endless_level(run_seed, 1).unwrap_or_else(|err| {
+            eprintln!("Failed to generate endless level 1: {}, using the built-in layout", err);
+            map::MapFile::built_in()
+        });
+        let entry = map_file.entry;
+        let mut world = Self::with_map(content, "<endless>".to_string(), map_file, settings).await;
+        world.endless = Some(EndlessRun { seed: run_seed, depth: 1 });
+        world.speedrun_timer.best_splits = world.records.endless_best_splits(run_seed);
+        if let Some(entry) = entry {
+            world.open_elevator(entry);
+        }
+        let deepest = world.records.deepest_endless_level;
+        let text = if deepest > 0 {
+            format!("Endless run, seed {}, deepest level so far {}", run_seed, deepest)
+        } else {
+            format!("Endless run, seed {}", run_seed)
+        };
+        world.floating_texts.push(FloatingText::notification(&text));
+        world
+    }
+
+    async fn with_map(
+        content: Rc<ContentRegistry>,
+        map_path: String,
+        map_file: map::MapFile,
+        settings: &Settings
+    ) -> Self {
+        let (map_file, level) = match Level::from_layout(&map_file.layout, &content) {
+            Ok(level) => (map_file, level),
+            Err(err) => {
+                // validate catches most of this at load time, generated and stress maps skip the loader
+                eprintln!("Invalid world layout in {}: {}, using the built-in layout", map_path, err);
+                let map_file = map::MapFile::built_in();
+                let level = Level::from_layout(&map_file.layout, &content).expect("Invalid built-in layout");
+                (map_file, level)
+            }
+        };
+        // build the impostor spritesheets now instead of on the first far away enemy
+        Lazy::force(&ENEMY_SPRITE_LODS);
+        let map_modified = fs::metadata(&map_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let player = Player::new(level.player_spawn, starting_weapons(&content));
+        let background_material = load_material(
+            ShaderSource::Glsl {
+                vertex: &DEFAULT_VERTEX_SHADER,
+                fragment: &FLOOR_FRAGMENT_SHADER,
+            },
+            MaterialParams {
+                uniforms: vec![
+                    UniformDesc {
+                        name: "u_player_pos".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_left_ray_dir".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_right_ray_dir".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_half_screen_height".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_eye_offset".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_pitch".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_screen_width".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_screen_height".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "is_ceiling".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_world_size".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_time".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    }
+                ],
+                textures: vec![
+                    "u_floor_texture".to_string(),
+                    "u_ceiling_texture".to_string(),
+                    "u_floor_types".to_string()
+                ],
+                ..Default::default()
+            }
+        ).expect("Failed to load background material");
+        let raycast_material = load_material(
+            ShaderSource::Glsl {
+                vertex: DEFAULT_VERTEX_SHADER,
+                fragment: RAYCAST_FRAGMENT_SHADER,
+            },
+            MaterialParams {
+                uniforms: vec![
+                    UniformDesc {
+                        name: "u_player_pos".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_player_angle".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_fov".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_eye_offset".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_pitch".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_rays".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_left_ray_dir".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_right_ray_dir".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_screen_width".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_screen_height".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_world_size".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "u_time".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    }
+                ],
+                textures: vec![
+                    "u_tiles".to_string(),
+                    "u_floor_types".to_string(),
+                    "u_wall_texture".to_string(),
+                    "u_door_texture".to_string(),
+                    "u_floor_texture".to_string(),
+                    "u_ceiling_texture".to_string()
+                ],
+                ..Default::default()
+            }
+        ).expect("Failed to load raycast material");
+        let camera_shake_material = load_material(
+            ShaderSource::Glsl {
+                vertex: &CAMERA_SHAKE_VERTEX_SHADER,
+                fragment: &DEFAULT_FRAGMENT_SHADER,
+            },
+            MaterialParams {
+                uniforms: vec![
+                    UniformDesc {
+                        name: "screen_size".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "shake_offset".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    }
+                ],
+                pipeline_params: PipelineParams {
+                    color_blend: Some(
+                        BlendState::new(
+                            Equation::Add,
+                            BlendFactor::Value(BlendValue::SourceAlpha),
+                            BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
+                        )
+                    ),
+                    alpha_blend: Some(
+                        BlendState::new(Equation::Add, BlendFactor::Zero, BlendFactor::One)
+                    ),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        ).expect("Failed to load camera shake material");
+        let enemy_default_material = load_material(
+            ShaderSource::Glsl {
+                vertex: &ENEMY_DEFAULT_VERTEX_SHADER,
+                fragment: &ENEMY_DEFAULT_FRAGMENT_SHADER,
+            },
+            MaterialParams {
+                uniforms: vec![
+                    UniformDesc {
+                        name: "u_relative_health".to_string(),
+                        uniform_type: UniformType::Float1,
+                        array_count: 1,
+                    },
+                    UniformDesc {
+                        name: "screen_size".to_string(),
+                        uniform_type: UniformType::Float2,
+                        array_count: 1,
+                    }
+                ],
+
+                pipeline_params: PipelineParams {
+                    color_blend: Some(
+                        BlendState::new(
+                            Equation::Add,
+                            BlendFactor::Value(BlendValue::SourceAlpha),
+                            BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
+                        )
+                    ),
+                    alpha_blend: Some(
+                        BlendState::new(Equation::Add, BlendFactor::Zero, BlendFactor::One)
+                    ),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        ).expect("Failed to load default enemy material");
+        let audio = AudioSystem::load(settings.master_volume * settings.sfx_volume).await;
+        crash::set_settings(settings);
+        crash::set_map_path(&map_path);
+        let mut floating_texts = Vec::new();
+        let hud_theme = HudTheme::load(&settings.hud_theme).unwrap_or_else(|err| {
+            let warning = format!("HUD theme {}, using classic", err);
+            eprintln!("{}", warning);
+            floating_texts.push(FloatingText::notification(&warning));
+            HudTheme::classic()
+        });
+        let first_line = floating_texts.len();
+        floating_texts.extend(FloatingText::map_warnings(&map_path, &map_file.warnings, first_line));
+        let tiles_texture = tiles_texture(&level.world_layout, &level.doors, &level.walls, &map_file.notes);
+        let mut decoration_sprites = HashMap::new();
+        for def in content.decorations.values() {
+            match load_texture(&def.sprite).await {
+                Ok(texture) => {
+                    texture.set_filter(FilterMode::Nearest);
+                    decoration_sprites.insert(def.id.clone(), DecorationSprite {
+                        texture,
+                        frames: def.frames,
+                        scale: def.scale,
+                        ground_offset: def.ground_offset,
+                    });
+                }
+                Err(err) => {
+                    eprintln!("Warning: decoration '{}' has no sprite: {}", def.id, err);
+                }
+            }
+        }
+        Self {
+            world_layout: level.world_layout,
+            background_material: background_material,
+            raycast_material,
+            camera_shake_material: camera_shake_material,
+            enemy_default_material: enemy_default_material,
+            walls: level.walls,
+            doors: level.doors,
+            enemies: level.enemies,
+            pickups: level.pickups,
+            transients: Transients::new(),
+            player,
+            player_interactables: Vec::new(),
+            floating_texts,
+            corpses: Vec::new(),
+            minimap_offset: Vec2::ZERO,
+            ui_scale: screen_dpi_scale(),
+            settings: settings.clone(),
+            hud_theme,
+            audio,
+            time_scale: TimeScale::new(),
+            weapon_wheel: None,
+            growl_cooldown: ENEMY_GROWL_INTERVAL,
+            average_view_distance: 0.0,
+            reverb_zones: map_file.reverb_zones,
+            teleporters: map_file.teleporters,
+            notes_read: vec![false; map_file.notes.len()],
+            notes: map_file.notes,
+            floor_types_texture: floor_types_texture(&map_file.floor_types),
+            floor_types: map_file.floor_types,
+            decorations: Decorations::new(&map_file.decorations, &decoration_sprites),
+            decoration_sprites,
+            tiles_texture,
+            map_warnings: map_file.warnings,
+            debug_enemy: None,
+            path_target: None,
+            path_age: 0,
+            riding_elevator: false,
+            paused_time: 0.0,
+            enemy_vision_debug: None,
+            current_reverb_zone: None,
+            effects: VisualEffects::new(),
+            speedrun_timer: SpeedrunTimer::new(map_file.par_time),
+            rng: SimulationRng::new(SIMULATION_SEED),
+            game_state: GameState::GameGoing,
+            key_bindings: KeyBindings::default(),
+            records: Records::load(),
+            content,
+            map_path,
+            map_modified,
+            map_poll_elapsed: 0.0,
+            update_time: 0.0,
+            exit: map_file.exit,
+            endless: None,
+            sequences: Sequences::new(map_file.sequences),
+            boss: None,
+            boss_bar: None,
+            title_card: None,
+            mouse_look: MouseLook::new(),
+        }
+    }
+
+    /// rebuilds the level from a new layout, the player keeps its position unless it ends up inside a wall or door
+    /// or is sent to the new spawn
+    fn reinitialize_layout(&mut self, new_layout: &map::Layout, to_spawn: bool) -> Result<(), String> {
+        let mut level = Level::from_layout(new_layout, &self.content)?;
+        level.place_player(&mut self.player, to_spawn);
+        self.world_layout = level.world_layout;
+        self.walls = level.walls;
+        self.doors = level.doors;
+        self.enemies = level.enemies;
+        self.pickups = level.pickups;
+        // the shader texture can only be refreshed in place while the map keeps its size
+        if self.tiles_texture.size() != self.world_size() {
+            self.tiles_texture = tiles_texture(&self.world_layout, &self.doors, &self.walls, &self.notes);
+        }
+        self.clear_transient();
+        Ok(())
+    }
+
+    /// seconds the simulation has run, the clock stops while the game is paused or a panel is open
+    fn game_time(&self) -> f64 {
+        get_time() - self.paused_time
+    }
+
+    /// the same state after the same steps hashes the same, so a crash report can be matched against a replay
+    fn state_hash(&self) -> u64 {
+        let World { speedrun_timer, rng, player, enemies, doors, transients, .. } = self;
+        StateHasher::simulation(speedrun_timer.steps, rng, player, enemies, doors, &transients.projectiles)
+    }
+
+    /// records the state hash after the step that just ran, or reports the first step a verified run parted ways
+    fn check_state_hash(&mut self, checkpoints: &mut HashCheckpoints) {
+        let Some(divergence) = checkpoints.check(self.speedrun_timer.steps, self.state_hash()) else {
+            return;
+        };
+        let message = format!(
+            "state diverged at step {}: expected hash {:016x}, found {:016x}",
+            divergence.step,
+            divergence.expected,
+            divergence.actual
+        );
+        eprintln!("Warning: {}", message);
+        crash::record_event(format!("{:.2}s {}", get_time(), message));
+        self.floating_texts.push(FloatingText::notification(&message));
+    }
+
+    /// width and height of the running map in tiles
+    fn world_size(&self) -> Vec2 {
+        Vec2::new(self.world_layout.width() as f32, self.world_layout.height() as f32)
+    }
+
+    /// drops everything that only lives for a moment and may point at the old layout's handles,
+    /// has to run whenever the level is swapped out underneath the running world
+    fn clear_transient(&mut self) {
+        self.floating_texts.clear();
+        self.corpses.clear();
+        self.transients.clear();
+        self.player_interactables.clear();
+        self.player.burst_shots_left = 0;
+        self.debug_enemy = None;
+        self.path_target = None;
+        self.enemy_vision_debug = None;
+        // its arrival was just cancelled
+        self.riding_elevator = false;
+        self.boss = None;
+        self.boss_bar = None;
+        self.title_card = None;
+        // the scheduled end of a running hit-stop was just cancelled
+        self.time_scale.end_hit_stop();
+        self.close_weapon_wheel();
+        self.effects.clear();
+    }
+
+    /// see TimeScale::hit_stop, only with Settings::hit_stop on
+    fn hit_stop(&mut self) {
+        if self.settings.hit_stop {
+            self.time_scale.hit_stop(&mut self.transients.scheduler);
+        }
+    }
+
+    /// runs due scheduled actions, in real time so they keep going while the simulation is slowed down
+    fn update_scheduler(&mut self, dt: f32) {
+        for action in self.transients.scheduler.update(dt) {
+            match action {
+                ScheduledAction::PlaySound { sound, volume } => {
+                    self.audio.play(sound, volume);
+                }
+                ScheduledAction::EndHitStop => {
+                    self.time_scale.end_hit_stop();
+                }
+                ScheduledAction::FireBurstShot => {
+                    let burst_shot = self.player.fire_burst_shot(
+                        &self.world_layout,
+                        &self.enemies,
+                        &mut self.transients.projectiles,
+                        &mut self.rng
+                    );
+                    if let Some(shoot_event) = burst_shot {
+                        self.handle_shoot_event(shoot_event);
+                    }
+                }
+                ScheduledAction::ElevatorArrive => {
+                    self.enter_next_level();
+                }
+                ScheduledAction::SequenceStep { sequence, step } => {
+                    self.run_sequence_step(sequence, step);
+                }
+            }
+        }
+    }
+
+    /// starts the sequences whose trigger fired, the player walking into their zone or the boss dying
+    fn update_sequences(&mut self) {
+        let player_tile = Tile::from_vec2(self.player.pos);
+        let (player_x, player_y) = (player_tile.x as usize, player_tile.y as usize);
+        // gone once its death animation finished
+        let boss_killed = self.boss
+            .as_ref()
+            .is_some_and(|boss| self.enemies.handle_of(boss.spawn_id).is_none());
+        if boss_killed {
+            self.boss = None;
+            self.boss_bar = None;
+        }
+        for index in 0..self.sequences.sequences.len() {
+            let fired = match self.sequences.sequences[index].trigger {
+                SequenceTrigger::Enter { x, y, width, height } => {
+                    (x..x + width).contains(&player_x) && (y..y + height).contains(&player_y)
+                }
+                SequenceTrigger::BossKilled => boss_killed,
+            };
+            if fired && self.sequences.start(index, &mut self.transients.scheduler) {
+                crash::record_event(
+                    format!("{:.2}s sequence {} started", get_time(), self.sequences.sequences[index].name)
+                );
+            }
+        }
+    }
+
+    fn run_sequence_step(&mut self, sequence: u16, step: u16) {
+        let action = self.sequences.action(sequence, step).clone();
+        crash::record_event(format!("{:.2}s sequence step {:?}", get_time(), action));
+        let door_at = |doors: &Doors, x: usize, y: usize| {
+            // doors are the first and only spawn on their tile
+            doors.handle_of(SpawnId { x: x as u16, y: y as u16, ordinal: 0 })
+        };
+        match action {
+            SequenceAction::LockDoor { x, y } => {
+                if let Some(door) = door_at(&self.doors, x, y) {
+                    self.doors.lock_door(door);
+                    // paths through the door are blocked now
+                    self.path_target = None;
+                }
+            }
+            SequenceAction::UnlockDoor { x, y } => {
+                if let Some(door) = door_at(&self.doors, x, y) {
+                    self.doors.unlock_door(door);
+                }
+            }
+            SequenceAction::Sound(name) => {
+                match Sounds::from_name(&name) {
+                    Some(sound) => self.play_with_reverb(sound, 0.8, false),
+                    None => eprintln!("Warning: a sequence plays the unknown sound '{}'", name),
+                }
+            }
+            SequenceAction::Boss { enemy, x, y } => {
+                self.spawn_boss(&enemy, x, y);
+            }
+            SequenceAction::Spawn { enemy, x, y } => {
+                self.spawn_enemy(&enemy, x, y);
+            }
+            SequenceAction::BossBar(name) => {
+                self.boss_bar = Some(name);
+            }
+            SequenceAction::Title(text) => {
+                self.title_card = Some(FloatingText::title(&text));
+            }
+        }
+    }
+
+    /// the boss bursts out of the floor with a shake, the intro any enemy kind can play
+    fn spawn_boss(&mut self, enemy: &str, x: usize, y: usize) {
+        let Some(handle) = self.enemies.spawn_scripted(&mut self.world_layout, &self.content, enemy, x, y) else {
+            return;
+        };
+        // the new enemy needs a path
+        self.path_target = None;
+        self.effects.add(EffectSource::Sequence, EffectKind::CameraShake { intensity: 8.0 }, 0.6);
+        let max_health = self.content.enemies[enemy].health as f32;
+        self.boss = Some(Boss { spawn_id: self.enemies.spawn_id(handle), max_health });
+    }
+
+    fn spawn_enemy(&mut self, enemy: &str, x: usize, y: usize) {
+        let alive = self.enemies.alive_count();
+        match self.enemies.spawn_reinforcement(&mut self.world_layout, &self.content, enemy, x, y) {
+            Some(_) => {
+                // the new enemy needs a path
+                self.path_target = None;
+            }
+            None => {
+                crash::record_event(format!("{:.2}s did not spawn {}, {} enemies alive", get_time(), enemy, alive));
+            }
+        }
+    }
+
+    fn pull_trigger(&mut self) {
+        let shoot_event = self.player.pull_trigger(
+            &self.world_layout,
+            &self.enemies,
+            &mut self.transients.projectiles,
+            &mut self.transients.scheduler,
+            &mut self.rng
+        );
+        self.handle_shoot_event(shoot_event);
+    }
+
+    /// opens the wheel while its button is held and switches to the highlighted weapon when it is let go,
+    /// true while it is open
+    fn update_weapon_wheel(&mut self) -> bool {
+        let held = KeyBindings::is_down(&self.key_bindings.weapon_wheel);
+        let Some(wheel) = &mut self.weapon_wheel else {
+            if held && self.player.weapons.weapons.len() > 1 {
+                self.weapon_wheel = Some(WeaponWheel::new(self.player.weapons.active));
+                self.time_scale.slow_motion = Some(WEAPON_WHEEL_TIME_SCALE);
+                // the mouse moved the view until now
+                self.mouse_look.take_delta();
+                return true;
+            }
+            return false;
+        };
+        if !held {
+            let selected = wheel.highlighted;
+            self.close_weapon_wheel();
+            // lowers and raises like a slot key, the new weapon fires once it is up
+            self.switch_weapon(selected);
+            return false;
+        }
+        let weapons = &self.player.weapons.weapons;
+        wheel.point(self.mouse_look.take_delta(), weapons);
+        let step =
+            (KeyBindings::is_pressed(&self.key_bindings.turn_right) as i32) -
+            (KeyBindings::is_pressed(&self.key_bindings.turn_left) as i32);
+        if step != 0 {
+            wheel.step(step, weapons);
+        }
+        true
+    }
+
+    fn close_weapon_wheel(&mut self) {
+        self.weapon_wheel = None;
+        self.time_scale.slow_motion = None;
+    }
+
+    fn switch_weapon(&mut self, index: usize) {
+        let weapons = &self.player.weapons;
+        let Some(weapon) = weapons.weapons.get(index) else {
+            return;
+        };
+        if weapons.is_ready() && index == weapons.active {
+            return;
+        }
+        let id = weapon.id.clone();
+        self.player.request_switch(index);
+        crash::record_event(format!("switch weapon to {}", id));
+        self.floating_texts.push(FloatingText::notification(&format!("Weapon: {}", id)));
+    }
+
+    fn handle_shoot_event(&mut self, shoot_event: ShootEvent) {
+        match shoot_event.outcome {
+            ShotOutcome::StillReloading => {
+                self.play_with_reverb(self.player.weapon().sounds.reload, 0.4, false);
+            }
+            ShotOutcome::OutOfAmmo => {
+                if self.player.weapon().reloading_magazine {
+                    self.play_with_reverb(self.player.weapon().sounds.reload, 0.4, false);
+                } else {
+                    self.play_with_reverb(self.player.weapon().sounds.empty, 0.4, false);
+                    self.floating_texts.push(FloatingText::notification("Out of ammo"));
+                }
+            }
+            ShotOutcome::Fired => {
+                self.play_with_reverb(self.player.weapon().sounds.fire, 0.4, true);
+                self.player.animation_state.add_effect(AnimationState::default_explosion(), None);
+                self.effects.add(EffectSource::Shot, EffectKind::CameraShake { intensity: 10.0 }, 0.2);
+            }
+        }
+        if let Some(hit) = shoot_event.hit {
+            crash::record_event(
+                format!("{:.2}s shot hit with the {:?} ray at {:.1} tiles", get_time(), hit.ray, hit.distance)
+            );
+        }
+        for event in shoot_event.world_events {
+            self.handle_world_event_handle_based(event);
+        }
+    }
+
+    /// switches to the next built-in HUD theme right away and remembers it in the settings
+    fn cycle_hud_theme(&mut self) {
+        let name = HudTheme::next_built_in(&self.settings.hud_theme);
+        self.hud_theme = HudTheme::load(name).expect("Built-in themes always load");
+        self.settings.hud_theme = name.to_string();
+        crash::set_settings(&self.settings);
+        let mut report = Vec::new();
+        self.settings.save(&mut report);
+        for line in report {
+            eprintln!("{}", line);
+        }
+        self.floating_texts.push(FloatingText::notification(&format!("HUD theme: {}", name)));
+    }
+
+    /// switches between the cpu stripes and the raycast shader for comparing them, remembered in the settings
+    fn toggle_renderer(&mut self) {
+        self.settings.gpu_raycasting = !self.settings.gpu_raycasting;
+        crash::set_settings(&self.settings);
+        let mut report = Vec::new();
+        self.settings.save(&mut report);
+        for line in report {
+            eprintln!("{}", line);
+        }
+        let renderer = if self.settings.gpu_raycasting { "gpu (experimental)" } else { "cpu" };
+        self.floating_texts.push(FloatingText::notification(&format!("Renderer: {}", renderer)));
+    }
+
+    /// sends the player, and enemies whose kind allows it, from a teleporter to the free side of its partner
+    fn update_teleporters(&mut self) {
+        if TeleporterSystem::update_player(&mut self.player, &self.teleporters, &mut self.world_layout) {
+            self.effects.add(
+                EffectSource::Teleport,
+                EffectKind::Tint(Color::new(0.8, 0.9, 1.0, 0.6)),
+                TELEPORT_LOCKOUT
+            );
+            self.play_with_reverb(Sounds::Teleport, 0.5, false);
+            crash::record_event(format!("{:.2}s player teleported to {}", get_time(), self.player.pos));
+        }
+        TeleporterSystem::update_enemies(&mut self.enemies, &self.teleporters, &mut self.world_layout, &self.content);
+    }
+
+    fn player_in_water(&self) -> bool {
+        MovementSystem::floor_speed(&self.floor_types, self.player.pos) != 1.0
+    }
+
+    /// plays a sound followed by the tail of the reverb zone the player is in, outside of zones only gunshots
+    /// echo and their echo follows the average view distance, the tail only replays the sound
+    fn play_with_reverb(&mut self, sound: Sounds, volume: f32, is_gunshot: bool) {
+        let volume = if self.player_in_water() { volume * WATER_VOLUME_MULTIPLIER } else { volume };
+        self.audio.play(sound, volume);
+        let tail = match self.current_reverb_zone {
+            Some(zone) => AudioSystem::reverb_tail(volume, self.reverb_zones[zone].profile),
+            None if is_gunshot => AudioSystem::gunshot_echoes(volume, self.average_view_distance),
+            None => Vec::new(),
+        };
+        for (delay, volume) in tail {
+            self.transients.scheduler.schedule(delay, ScheduledAction::PlaySound { sound, volume });
+        }
+    }
+
+    /// play_with_reverb for a sound coming from `source`, quieter the further away it is.
+    /// macroquad can't pan a sound, so there is no left and right
+    fn play_at(&mut self, sound: Sounds, volume: f32, source: Vec2, is_gunshot: bool) {
+        let falloff = 1.0 - (self.player.pos.distance(source) / SOUND_FALLOFF_DISTANCE).clamp(0.0, 1.0);
+        if falloff > 0.0 {
+            self.play_with_reverb(sound, volume * falloff, is_gunshot);
+        }
+    }
+
+    /// now and then the closest aggressive enemy within view distance growls
+    fn update_growls(&mut self) {
+        self.growl_cooldown -= PHYSICS_FRAME_TIME;
+        if self.growl_cooldown > 0.0 {
+            return;
+        }
+        self.growl_cooldown = ENEMY_GROWL_INTERVAL * ::rand::thread_rng().gen_range(0.5..1.5);
+        let closest = (0..self.enemies.positions.len())
+            .filter(|&index| self.enemies.alives[index] && self.enemies.aggressive_states[index])
+            .map(|index| self.enemies.positions[index])
+            .filter(|position| position.distance(self.player.pos) <= ENEMY_VIEW_DISTANCE)
+            .min_by(|a, b| a.distance(self.player.pos).total_cmp(&b.distance(self.player.pos)));
+        if let Some(position) = closest {
+            self.play_at(Sounds::Growl, 0.5, position, false);
+        }
+    }
+
+    /// swaps the running level for the one in map_file, the player keeps its health and weapons
+    fn apply_map_file(&mut self, map_file: map::MapFile, to_spawn: bool) -> Result<(), String> {
+        self.reinitialize_layout(&map_file.layout, to_spawn)?;
+        // a level plays out the same however it was reached
+        self.rng = SimulationRng::new(SIMULATION_SEED);
+        self.reverb_zones = map_file.reverb_zones;
+        self.teleporters = map_file.teleporters;
+        // edited notes might be different ones, so they count as unread again
+        self.notes_read = vec![false; map_file.notes.len()];
+        self.notes = map_file.notes;
+        self.floor_types_texture = floor_types_texture(&map_file.floor_types);
+        self.floor_types = map_file.floor_types;
+        self.decorations = Decorations::new(&map_file.decorations, &self.decoration_sprites);
+        self.map_warnings = map_file.warnings;
+        self.speedrun_timer.par_time = map_file.par_time;
+        // sequences that were running start over, their remaining steps were cancelled with the old layout
+        self.sequences = Sequences::new(map_file.sequences);
+        self.exit = map_file.exit;
+        if let Some(entry) = map_file.entry {
+            self.open_elevator(entry);
+        }
+        debug_assert!(!self.transients.has_stale_sequence_step(&self.sequences), "a sequence step outlived its level");
+        Ok(())
+    }
+
+    /// the player steps out of the elevator they arrived in, facing its opening door
+    fn open_elevator(&mut self, cabin: (usize, usize)) {
+        let Some(door) = ElevatorSystem::door(cabin, &self.world_layout) else {
+            return;
+        };
+        self.doors.open_door(door);
+        self.player.angle = angles::angle_to(self.player.pos, self.doors.positions[door.0 as usize]);
+    }
+
+    /// In an endless run, interact inside the exit elevator closes its door and rides to the next level,
+    /// see ScheduledAction::ElevatorArrive. Enemies don't come along, the doors stay open while one is
+    /// in the way. Returns whether the player is in the elevator, where interact does nothing else.
+    fn start_elevator_ride(&mut self) -> bool {
+        let (Some(_), Some(exit)) = (self.endless, self.exit) else {
+            return false;
+        };
+        let ride = ElevatorSystem::ride(
+            self.riding_elevator,
+            exit,
+            self.player.pos,
+            &self.world_layout,
+            &mut self.doors,
+            &self.enemies
+        );
+        match ride {
+            ElevatorRide::Outside => {
+                return false;
+            }
+            ElevatorRide::Riding => {
+                return true;
+            }
+            ElevatorRide::Blocked => {
+                self.floating_texts.push(FloatingText::notification("Something is in the elevator doors"));
+                return true;
+            }
+            ElevatorRide::Departing => {}
+        }
+        // paths through the door are blocked now
+        self.path_target = None;
+        self.riding_elevator = true;
+        self.effects.add(EffectSource::Elevator, EffectKind::CameraShake { intensity: 4.0 }, ELEVATOR_RIDE_TIME);
+        self.play_with_reverb(Sounds::ElevatorHum, 0.6, false);
+        self.transients.scheduler.schedule(ELEVATOR_RIDE_TIME, ScheduledAction::ElevatorArrive);
+        crash::record_event(format!("{:.2}s elevator ride started", get_time()));
+        true
+    }
+
+    /// generates and enters the next level of an endless run, the player arrives in its entry elevator
+    fn enter_next_level(&mut self) {
+        self.riding_elevator = false;
+        let Some(run) = self.endless else {
+            return;
+        };
+        let depth = run.depth + 1;
+        let entered = map::generate
+            ::endless_level(run.seed, depth)
+            .and_then(|map_file| self.apply_map_file(map_file, true));
+        match entered {
+            Ok(()) => {
+                self.endless = Some(EndlessRun { depth, ..run });
+                crash::record_event(format!("{:.2}s entered endless level {}", get_time(), depth));
+                // the level the elevator came from is finished
+                self.speedrun_timer.split();
+                let deepest = self.records.reach_endless_level(depth);
+                let best_splits = self.speedrun_timer.beats_best();
+                if best_splits {
+                    self.records.set_endless_best_splits(run.seed, self.speedrun_timer.splits.clone());
+                }
+                if deepest || best_splits {
+                    if let Err(err) = self.records.save() {
+                        eprintln!("Could not save the records: {}", err);
+                    }
+                }
+                let text = if deepest {
+                    format!("Level {}, the deepest yet", depth)
+                } else {
+                    format!("Level {}", depth)
+                };
+                self.floating_texts.push(FloatingText::notification(&text));
+            }
+            Err(err) => {
+                // without a next level the elevator is out of order, the run goes on in this one
+                eprintln!("Failed to generate endless level {}: {}", depth, err);
+                if let Some(exit) = self.exit.take() {
+                    self.open_elevator(exit);
+                }
+            }
+        }
+    }
+
+    fn poll_map_file(&mut self) {
+        self.map_poll_elapsed += PHYSICS_FRAME_TIME;
+        if self.map_poll_elapsed < MAP_RELOAD_POLL_INTERVAL {
+            return;
+        }
+        self.map_poll_elapsed = 0.0;
+        let modified = fs::metadata(&self.map_path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_none() || modified == self.map_modified {
+            return;
+        }
+        self.map_modified = modified;
+        let reloaded = map::load_map(&self.map_path, &self.content.spawn_tiles()).and_then(|map_file| {
+            self.apply_map_file(map_file, false)?;
+            // the time no longer belongs to the level it started on
+            self.speedrun_timer.invalidated = true;
+            Ok(())
+        });
+        match reloaded {
+            Ok(()) => {
+                crash::record_event(format!("{:.2}s reloaded {}", get_time(), self.map_path));
+                self.floating_texts.push(FloatingText::notification("Map reloaded"));
+                self.floating_texts.extend(
+                    FloatingText::map_warnings(&self.map_path, &self.map_warnings, 1)
+                );
+            }
+            Err(err) => {
+                eprintln!("Failed to reload map {}: {}, keeping the old layout", self.map_path, err);
+            }
+        }
+    }
+
+    /// pushes the player along `direction` through their velocity, so the push slides along walls and doors
+    /// like walking does and update_player keeps the Player tile right
+    fn knock_back(&mut self, direction: Vec2) {
+        self.player.vel += direction.normalize_or_zero() * KNOCKBACK_SPEED;
+    }
+    fn handle_world_event_handle_based(&mut self, event: WorldEventHandleBased) {
+        crash::record_event(
+            format!(
+                "{:.2}s {:?} enemy {} depth {}",
+                get_time(),
+                event.event_type,
+                event.other_involved,
+                event.penetration_depth
+            )
+        );
+        match event.event_type {
+            | WorldEventType::EnemyHitPlayer
+            | WorldEventType::EnemyShotPlayer { .. }
+            | WorldEventType::ProjectileHitPlayer { .. } => {
+                if !self.player.can_be_hit() {
+                    return;
+                }
+                let damage = match event.event_type {
+                    WorldEventType::ProjectileHitPlayer { damage, direction } => {
+                        self.knock_back(direction);
+                        damage as u16
+                    }
+                    WorldEventType::EnemyShotPlayer { damage } => {
+                        // pushed straight away from the shooter, which stays where it is
+                        let enemy_pos = self.enemies.positions[event.other_involved as usize];
+                        self.knock_back(self.player.pos - enemy_pos);
+                        damage as u16
+                    }
+                    _ => {
+                        let enemy_pos = self.enemies.positions[event.other_involved as usize];
+                        self.play_at(Sounds::Grunt, 0.6, enemy_pos, false);
+                        self.knock_back(self.enemies.velocities[event.other_involved as usize]); // move player away
+                        self.enemies.velocities[event.other_involved as usize] = (
+                            ( self.player.pos - enemy_pos) * -1.0 // make him move back for one frame
+                         ).normalize(); // make sure enemy doesnt keep his insane speed,
+                        1
+                    }
+                };
+
+                self.player.take_hit(damage);
+                if self.player.health == 0 {
+                    self.game_state = GameState::GameOver;
+                }
+                self.effects.add(EffectSource::PlayerHit, EffectKind::CameraShake { intensity: 20.0 }, 0.4);
+                self.effects.add(
+                    EffectSource::PlayerHit,
+                    EffectKind::Vignette(Color::new(0.6, 0.0, 0.0, 0.7)),
+                    PLAYER_HIT_COOLDOWN
+                );
+                self.hit_stop();
+            }
+            WorldEventType::PlayerHitEnemy | WorldEventType::ProjectileHitEnemy { .. } => {
+                let health = self.enemies.healths
+                    .get_mut(event.other_involved as usize)
+                    .expect("Invalid handle in world layout");
+                let e_animation_state =
+                    &mut self.enemies.animation_states[event.other_involved as usize];
+                e_animation_state.add_effect(AnimationState::default_blood_particles(), None);
+                if *health <= 0.0 {
+                    // avoid rescheduling animation callback
+                    return;
+                }
+                let kind = &self.enemies.kinds[event.other_involved as usize];
+                let resistances = &self.content.enemies[kind].resistances;
+                let damage = DamageSystem::of_hit(&event, self.player.weapon(), resistances);
+                self.floating_texts.push(FloatingText::hit_marker(&damage, event.penetration_depth));
+                // fractional multipliers can leave a rounding error behind
+                if *health <= damage.amount + 0.001 {
+                    *health = 0.0;
+                    if self.player.grant_adrenaline(self.settings.adrenaline) {
+                        self.effects.release(EffectSource::Adrenaline);
+                        self.effects.add(EffectSource::Adrenaline, EffectKind::Adrenaline, ADRENALINE_DURATION);
+                    }
+                    let enemy_pos = self.enemies.positions[event.other_involved as usize];
+                    if enemy_pos.distance(self.player.pos) < HIT_STOP_KILL_DISTANCE {
+                        self.hit_stop();
+                    }
+                    PlayEnemyAnimation::play_death(
+                        EnemyHandle(event.other_involved),
+                        &mut self.enemies.velocities,
+                        &mut self.enemies.animation_states,
+                        &mut self.enemies.alives
+                    );
+                    return;
+                }
+
+                *health -= damage.amount;
+            }
+        }
+    }
+
+    fn update_regeneration(&mut self) {
+        let in_combat = self.enemies.aggressive_states
+            .iter()
+            .zip(self.enemies.alives.iter())
+            .any(|(aggressive, alive)| *aggressive && *alive);
+        self.player.update_regeneration(in_combat, self.settings.health_regeneration);
+    }
+
+    /// sprinting drains the stamina, once it is empty the player can't sprint until it is full again
+    fn update_stamina(&mut self) {
+        let player = &mut self.player;
+        if player.is_sprinting {
+            player.stamina = (player.stamina - STAMINA_DRAIN * PHYSICS_FRAME_TIME).max(0.0);
+            if player.stamina == 0.0 {
+                player.is_sprinting = false;
+                player.sprint_exhausted = true;
+            }
+        } else {
+            player.stamina = (player.stamina + STAMINA_REGEN * PHYSICS_FRAME_TIME).min(player.max_stamina);
+            if player.stamina == player.max_stamina {
+                player.sprint_exhausted = false;
+            }
+        }
+    }
+
+    /// re-plans the paths of the chasing enemies when the player reaches another tile or an enemy starts
+    /// chasing, otherwise every ENEMY_PATH_INTERVAL frames so doors that finished opening are used
+    fn update_enemy_paths(&mut self) {
+        let player_tile = Tile::from_vec2(self.player.pos);
+        self.path_age += 1;
+        let enemies = &mut self.enemies;
+        let chasing = |i: usize| enemies.aggressive_states[i] && enemies.alives[i];
+        let unplanned = (0..enemies.positions.len()).any(|i| {
+            chasing(i) && matches!(enemies.paths[i], EnemyPath::Unplanned)
+        });
+        if !unplanned && self.path_target == Some(player_tile) && self.path_age < ENEMY_PATH_INTERVAL {
+            return;
+        }
+        self.path_target = Some(player_tile);
+        self.path_age = 0;
+        if !(0..enemies.positions.len()).any(chasing) {
+            return;
+        }
+        let next_steps = PathfindingSystem::steps_towards(player_tile, &self.world_layout, &self.doors);
+        for i in 0..enemies.positions.len() {
+            if enemies.aggressive_states[i] && enemies.alives[i] {
+                enemies.paths[i] = PathfindingSystem::path(Tile::from_vec2(enemies.positions[i]), &next_steps);
+            }
+        }
+    }
+
+    /// the pickup under the player is used up, update_player already gave its tile to the player
+    fn collect_pickup(&mut self) {
+        if matches!(self.game_state, GameState::GameOver) {
+            // a medkit doesn't undo the hit that ended the game
+            return;
+        }
+        let Some(handle) = self.pickups.at_tile(self.player.pos) else {
+            return;
+        };
+        let item = self.pickups.items[handle.0 as usize];
+        if matches!(item, LootItem::HealthPack(_)) && self.player.health >= PLAYER_MAX_HEALTH {
+            // left for later instead of wasted
+            return;
+        }
+        self.pickups.take(handle, &mut self.world_layout);
+        crash::record_event(format!("{:.2}s picked up {:?}", get_time(), item));
+        self.play_with_reverb(Sounds::Pickup, 0.5, false);
+        self.apply_loot(item);
+    }
+
+    fn apply_loot(&mut self, item: LootItem) {
+        match item {
+            LootItem::HealthPack(amount) => {
+                self.player.health = (self.player.health + (amount as u16)).min(PLAYER_MAX_HEALTH);
+            }
+            LootItem::AmmoPickup(weapon_type, amount) => {
+                let weapon = self.player.weapons.weapons
+                    .iter_mut()
+                    .find(|weapon| weapon.id == weapon_type.weapon_id());
+                // without the weapon there is nothing to put the rounds into
+                if let Some(weapon) = weapon {
+                    weapon.reserve_ammo = weapon.reserve_ammo.saturating_add(amount);
+                    self.floating_texts.push(
+                        FloatingText::notification(&format!("+{} {}", amount, weapon_type.ammo_name()))
+                    );
+                }
+            }
+            LootItem::WeaponPickup(_) | LootItem::PowerUp(_) => {
+                // not dropped by any table yet
+            }
+        }
+    }
+
+    /// forward and leftward movement with a length of at most 1, an analog stick would give the tilt
+    /// as the length, the movement keys are all or nothing
+    fn movement_input(&self) -> Vec2 {
+        let axis = |positive: &[InputButton], negative: &[InputButton]| {
+            (KeyBindings::is_down(positive) as i32 - KeyBindings::is_down(negative) as i32) as f32
+        };
+        // diagonals are as fast as straight lines
+        Vec2::new(
+            axis(&self.key_bindings.forward, &self.key_bindings.back),
+            axis(&self.key_bindings.strafe_left, &self.key_bindings.strafe_right)
+        ).clamp_length_max(1.0)
+    }
+
+    fn handle_input(&mut self) {
+        if self.riding_elevator {
+            // nothing but pausing until the next level, interact again does nothing either
+            self.player.vel = Vec2::ZERO;
+            self.player.target_vel = Vec2::ZERO;
+            if KeyBindings::is_pressed(&self.key_bindings.pause) {
+                self.game_state = GameState::Paused;
+            }
+            return;
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.walk_toggle) {
+            self.player.walking = !self.player.walking;
+        }
+        self.player.is_crouching = KeyBindings::is_down(&self.key_bindings.crouch);
+        let input = self.movement_input();
+        let forward = Vec2::new(self.player.angle.cos(), self.player.angle.sin());
+        // the first ray is the left edge of the view, a quarter turn towards positive angles is left
+        let left = Vec2::new(-self.player.angle.sin(), self.player.angle.cos());
+        let direction = forward * input.x + left * input.y;
+        self.player.is_sprinting =
+            direction != Vec2::ZERO &&
+            KeyBindings::is_down(&self.key_bindings.sprint) &&
+            !self.player.is_crouching &&
+            !self.player.sprint_exhausted &&
+            self.player.stamina > 0.0;
+        if direction != Vec2::ZERO {
+            self.player.target_vel = self.player.target_velocity(direction);
+        } else if self.settings.grid_snap {
+            // ease onto the tile grid once the player stops, through the velocity so collisions still apply
+            let offset = self.player.pos.round() - self.player.pos;
+            self.player.target_vel = if offset.length() > 0.01 {
+                offset * GRID_SNAP_SPEED
+            } else {
+                Vec2::ZERO
+            };
+        } else {
+            self.player.target_vel = Vec2::ZERO;
+        }
+        if self.update_weapon_wheel() {
+            // the mouse and the turn keys pick a weapon, everything else waits until the wheel closes
+            return;
+        }
+        let turn_step = self.settings.turn_step_degrees.to_radians();
+        if
+            KeyBindings::is_pressed(&self.key_bindings.quick_turn) &&
+            self.player.eased_turn.is_none()
+        {
+            let target_angle = if turn_step > 0.0 {
+                angles::snap_step(self.player.angle, turn_step, (PI / turn_step).round() as i32)
+            } else {
+                angles::wrap_tau(self.player.angle + PI)
+            };
+            self.player.eased_turn = Some(
+                EasedTurn::new(self.player.angle, target_angle, QUICK_TURN_DURATION)
+            );
+        }
+        if turn_step > 0.0 {
+            let steps =
+                (KeyBindings::is_pressed(&self.key_bindings.turn_right) as i32) -
+                (KeyBindings::is_pressed(&self.key_bindings.turn_left) as i32);
+            if steps != 0 {
+                // chained presses step on from the pending target so no press is lost
+                let from = self.player.eased_turn
+                    .as_ref()
+                    .map(|turn| turn.target_angle)
+                    .unwrap_or(self.player.angle);
+                let target_angle = angles::snap_step(from, turn_step, steps);
+                self.player.eased_turn = Some(
+                    EasedTurn::new(self.player.angle, target_angle, TURN_STEP_DURATION)
+                );
+            }
+        }
+        // taken every frame so the movement during an eased turn doesn't add up
+        let mouse_look = self.mouse_look.take_delta() * MOUSE_SENSITIVITY * self.settings.mouse_sensitivity;
+        let mouse_turn = mouse_look.x;
+        // the mouse moving down looks down
+        self.player.pitch = (self.player.pitch + mouse_look.y).clamp(-MAX_PITCH, MAX_PITCH);
+        if let Some(eased_turn) = &mut self.player.eased_turn {
+            match eased_turn.advance(get_frame_time()) {
+                Some(angle) => {
+                    self.player.angle = angle;
+                }
+                None => {
+                    self.player.angle = eased_turn.target_angle;
+                    self.player.eased_turn = None;
+                }
+            }
+        } else {
+            // the mouse turns freely even when the keys snap
+            self.player.angle = angles::wrap_tau(self.player.angle + mouse_turn);
+            if turn_step == 0.0 {
+                if KeyBindings::is_down(&self.key_bindings.turn_left) {
+                    self.player.angle -= 0.9 * get_frame_time();
+                    self.player.angle = angles::wrap_tau(self.player.angle);
+                }
+                if KeyBindings::is_down(&self.key_bindings.turn_right) {
+                    self.player.angle += 0.9 * get_frame_time();
+                    self.player.angle = angles::wrap_tau(self.player.angle);
+                }
+            }
+        }
+        let fire_mode = self.player.weapon().fire_mode();
+        // holding the trigger only fires an automatic weapon once it is ready, so the reload sound doesn't repeat
+        let trigger =
+            KeyBindings::is_pressed(&self.key_bindings.shoot) ||
+            (fire_mode == FireMode::Auto &&
+                KeyBindings::is_down(&self.key_bindings.shoot) &&
+                self.player.weapon().elapsed_reload_t == 0 &&
+                !self.player.weapon().is_empty());
+        if trigger && self.player.burst_shots_left == 0 {
+            if self.player.weapons.is_ready() {
+                self.pull_trigger();
+            } else {
+                self.player.weapons.buffer_shot();
+            }
+        }
+        let selected_slot = self.key_bindings.weapon_slots
+            .iter()
+            .position(|slot| KeyBindings::is_pressed(slot));
+        if let Some(index) = selected_slot {
+            self.switch_weapon(index);
+        }
+        if
+            KeyBindings::is_pressed(&self.key_bindings.reload) &&
+            self.player.weapons.is_ready() &&
+            self.player.weapons.active_weapon_mut().start_reload()
+        {
+            // a reload cuts a running burst short
+            self.player.burst_shots_left = 0;
+            self.play_with_reverb(self.player.weapon().sounds.reload, 0.4, false);
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.cycle_fire_mode) {
+            let weapon = self.player.weapons.active_weapon_mut();
+            if weapon.fire_modes.len() > 1 {
+                weapon.fire_mode_index = (weapon.fire_mode_index + 1) % weapon.fire_modes.len();
+                let label = weapon.fire_mode().label();
+                self.floating_texts.push(FloatingText::notification(&format!("Fire mode: {}", label)));
+            }
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.cycle_hud_theme) {
+            self.cycle_hud_theme();
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.toggle_renderer) {
+            self.toggle_renderer();
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.pause) {
+            self.game_state = GameState::Paused;
+            return;
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.map_overview) {
+            self.game_state = GameState::MapOverview {
+                camera_pos: self.player.pos,
+                zoom: 1.0,
+            };
+        }
+        if KeyBindings::is_pressed(&self.key_bindings.interact) && !self.start_elevator_ride() {
+            for interactable in self.player_interactables.iter().filter(|event| event.in_range) {
+                crash::record_event(format!("{:.2}s {:?}", get_time(), interactable.interaction_type));
+                match interactable.interaction_type {
+                    InteractionType::OpenDoor(door_handle) if self.doors.locked[door_handle.0 as usize] => {
+                        self.floating_texts.push(FloatingText::notification("The door is locked"));
+                    }
+                    InteractionType::OpenDoor(door_handle) => {
+                        self.doors.open_door(door_handle);
+                    }
+                    InteractionType::CloseDoor(door_handle) => {
+                        self.doors.close_door(door_handle);
+                        // paths through the door are blocked now
+                        self.path_target = None;
+                    }
+                    InteractionType::ReadNote(note) => {
+                        self.game_state = NoteReader::open(note, &mut self.notes_read);
+                    }
+                    InteractionType::InspectPickup(_) => {}
+                }
+            }
+        }
+    }
+
+    fn handle_pause_input(&mut self) {
+        if KeyBindings::is_pressed(&self.key_bindings.pause) {
+            self.game_state = GameState::GameGoing;
+        }
+    }
+
+    /// the frozen game shows through a dimmed screen
+    fn draw_pause_overlay(&self) {
+        draw_rectangle(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32, Color::new(0.0, 0.0, 0.0, 0.6));
+        font::draw_centered("PAUSED", HALF_SCREEN_WIDTH, HALF_SCREEN_HEIGHT - 50.0, TextStyle::Title, WHITE);
+        font::draw_centered(
+            &format!("Press {} to resume", keys::describe(&self.key_bindings.pause)),
+            HALF_SCREEN_WIDTH,
+            HALF_SCREEN_HEIGHT + 50.0,
+            TextStyle::Prompt,
+            WHITE
+        );
+    }
+
+    /// pans with the movement bindings and zooms with the mouse wheel, the simulation is paused meanwhile
+    fn handle_map_overview_input(&mut self) {
+        let exit =
+            KeyBindings::is_pressed(&self.key_bindings.map_overview) ||
+            is_key_pressed(KeyCode::Escape);
+        if cfg!(debug_assertions) && is_key_pressed(KeyCode::V) {
+            if let GameState::MapOverview { camera_pos, .. } = self.game_state {
+                self.select_debug_enemy(camera_pos);
+            }
+        }
+        let world_size = self.world_size();
+        if let GameState::MapOverview { camera_pos, zoom } = &mut self.game_state {
+            if exit {
+                self.game_state = GameState::GameGoing;
+                return;
+            }
+            let pan_speed = 15.0 / *zoom; // tiles per second
+            let dt = get_frame_time();
+            if KeyBindings::is_down(&self.key_bindings.forward) {
+                camera_pos.y -= pan_speed * dt;
+            }
+            if KeyBindings::is_down(&self.key_bindings.back) {
+                camera_pos.y += pan_speed * dt;
+            }
+            let bindings = &self.key_bindings;
+            if KeyBindings::is_down(&bindings.strafe_left) || KeyBindings::is_down(&bindings.turn_left) {
+                camera_pos.x -= pan_speed * dt;
+            }
+            if KeyBindings::is_down(&bindings.strafe_right) || KeyBindings::is_down(&bindings.turn_right) {
+                camera_pos.x += pan_speed * dt;
+            }
+            *camera_pos = camera_pos.clamp(Vec2::ZERO, world_size);
+            let (_, wheel) = mouse_wheel();
+            if wheel != 0.0 {
+                *zoom = (*zoom * (1.1_f32).powf(wheel.signum())).clamp(0.5, 4.0);
+            }
+        }
+    }
+
+    /// the living enemy closest to `target` gets the vision overlay, picking it again turns the overlay off
+    fn select_debug_enemy(&mut self, target: Vec2) {
+        let closest = (0..self.enemies.positions.len())
+            .filter(|&index| self.enemies.alives[index])
+            .min_by(|&a, &b| {
+                let distance_a = (self.enemies.positions[a] + Vec2::splat(0.5)).distance(target);
+                let distance_b = (self.enemies.positions[b] + Vec2::splat(0.5)).distance(target);
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|index| self.enemies.spawn_id(EnemyHandle(index as u16)));
+        self.debug_enemy = if closest == self.debug_enemy { None } else { closest };
+        // the simulation is paused in the overview, so the first picture comes from here
+        let debug_enemy = self.debug_enemy.and_then(|spawn_id| self.enemies.handle_of(spawn_id));
+        self.enemy_vision_debug = debug_enemy.map(|enemy| {
+            EnemyAggressionSystem::vision_debug(
+                enemy,
+                self.player.pos,
+                &self.enemies,
+                &self.doors,
+                &self.world_layout
+            )
+        });
+    }
+
+    /// turns pages with the turn bindings, closes with interact or Escape, everything else is ignored
+    fn handle_note_input(&mut self) {
+        let bindings = &self.key_bindings;
+        let close = KeyBindings::is_pressed(&bindings.interact) || is_key_pressed(KeyCode::Escape);
+        let next = KeyBindings::is_pressed(&bindings.strafe_right) || KeyBindings::is_pressed(&bindings.turn_right);
+        let previous = KeyBindings::is_pressed(&bindings.strafe_left) || KeyBindings::is_pressed(&bindings.turn_left);
+        NoteReader::input(&mut self.game_state, &self.notes, (next as isize) - (previous as isize), close);
+    }
+
+    fn draw_note_panel(&self) {
+        let GameState::ReadingNote { note, page } = self.game_state else {
+            return;
+        };
+        let ui_scale = self.ui_scale;
+        let panel = Rect::new(
+            (SCREEN_WIDTH as f32) * 0.25,
+            (SCREEN_HEIGHT as f32) * 0.15,
+            (SCREEN_WIDTH as f32) * 0.5,
+            (SCREEN_HEIGHT as f32) * 0.7
+        );
+        let margin = 30.0 * ui_scale;
+        let font_size = 26.0 * ui_scale;
+        draw_rectangle(panel.x, panel.y, panel.w, panel.h, Color::from_rgba(222, 205, 165, 245));
+        draw_rectangle_lines(
+            panel.x,
+            panel.y,
+            panel.w,
+            panel.h,
+            4.0 * ui_scale,
+            Color::from_rgba(120, 90, 50, 255)
+        );
+        let ink = Color::from_rgba(60, 40, 20, 255);
+        let pages = &self.notes[note].pages;
+        let style = TextStyle::Fixed(font_size);
+        let lines = wrap_text(&pages[page], panel.w - 2.0 * margin, style);
+        for (i, line) in lines.iter().enumerate() {
+            let y = panel.y + margin + ((i + 1) as f32) * font_size * 1.2;
+            font::draw(line, panel.x + margin, y, style, ink);
+        }
+        let found = self.notes_read.iter().filter(|read| **read).count();
+        let bindings = &self.key_bindings;
+        let footer = format!(
+            "Page {}/{}   {}/{} to turn pages, {} or Esc to close   Notes found: {}/{}",
+            page + 1,
+            pages.len(),
+            keys::describe(&bindings.strafe_left),
+            keys::describe(&bindings.strafe_right),
+            keys::describe(&bindings.interact),
+            found,
+            self.notes.len()
+        );
+        font::draw(&footer, panel.x + margin, panel.y + panel.h - margin, TextStyle::Fixed(18.0 * ui_scale), ink);
+    }
+
+    fn draw_map_overview(&self) {
+        let GameState::MapOverview { camera_pos, zoom } = self.game_state else {
+            return;
+        };
+        clear_background(DARKGRAY);
+        let overview = MinimapTransform::overview(camera_pos, zoom, self.ui_scale, self.world_size());
+        RenderMap::render_world_layout(&self.world_layout, &self.doors, overview, &self.hud_theme);
+        RenderMap::render_teleporters(&self.teleporters, overview, &self.hud_theme);
+        if let Some(exit) = self.exit {
+            RenderMap::render_exit(exit, overview);
+        }
+        RenderMap::render_notes(&self.notes, &self.notes_read, overview, &self.hud_theme);
+        if cfg!(debug_assertions) {
+            RenderMap::render_reverb_zones(&self.reverb_zones, self.current_reverb_zone, overview);
+            RenderMap::render_repulsion_fields(&self.transients.repulsion_fields, overview);
+        }
+        let player_visible = (get_time() * 3.0).fract() < 0.5;
+        RenderMap::render_player_and_enemies_on_map(
+            self.player.pos,
+            player_visible,
+            &self.enemies,
+            &self.pickups,
+            overview,
+            &self.hud_theme
+        );
+        if let Some(vision) = &self.enemy_vision_debug {
+            RenderMap::render_enemy_vision(vision, self.player.pos, overview);
+        }
+        font::draw(
+            "Map overview: WASD to pan, mouse wheel to zoom, M or Escape to return",
+            10.0 * self.ui_scale,
+            30.0 * self.ui_scale,
+            TextStyle::Hud,
+            WHITE
+        );
+        if cfg!(debug_assertions) {
+            font::draw(
+                "V shows the vision of the enemy closest to the center",
+                10.0 * self.ui_scale,
+                50.0 * self.ui_scale,
+                TextStyle::Hud,
+                WHITE
+            );
+        }
+    }
+
+    fn update(&mut self) {
+        assert!(self.world_layout.height() < 65536 && self.world_layout.width() < 65536);
+        assert!(self.walls.len() < 65536);
+        self.speedrun_timer.tick(&self.game_state);
+        // holstered weapons keep reloading
+        for weapon in &mut self.player.weapons.weapons {
+            WeaponSystem::update_reload(weapon);
+        }
+        if self.player.weapons.update(PHYSICS_FRAME_TIME) {
+            self.pull_trigger();
+        }
+        self.player.update_hit_cooldown();
+        self.update_regeneration();
+        self.update_stamina();
+        self.player.update_adrenaline();
+        if cfg!(debug_assertions) {
+            self.poll_map_file();
+        }
+        let player_tile = Tile::from_vec2(self.player.pos);
+        self.current_reverb_zone = self.reverb_zones
+            .iter()
+            .position(|zone| zone.contains(player_tile.x as usize, player_tile.y as usize));
+        if self.player_in_water() {
+            self.effects.hold(EffectSource::Water, EffectKind::Tint(Color::new(0.1, 0.5, 0.5, 0.25)));
+        } else {
+            self.effects.release(EffectSource::Water);
+        }
+        for text in self.floating_texts.iter_mut().chain(self.title_card.as_mut()) {
+            text.lifetime -= PHYSICS_FRAME_TIME;
+        }
+        if self.title_card.as_ref().is_some_and(|title| title.lifetime <= 0.0) {
+            self.title_card = None;
+        }
+        self.floating_texts.retain(|text| text.lifetime > 0.0);
+        for corpse in self.corpses.iter_mut() {
+            corpse.lifetime -= PHYSICS_FRAME_TIME;
+        }
+        self.corpses.retain(|corpse| corpse.lifetime > 0.0);
+        // the minimap lags behind the movement direction and settles back once the player stops
+        self.minimap_offset = self.minimap_offset.lerp(
+            self.player.vel * MINIMAP_PARALLAX_STRENGTH,
+            0.1
+        );
+        MovementSystem::update_player(
+            &mut self.player,
+            &self.walls,
+            &self.doors,
+            &self.enemies,
+            &mut self.world_layout,
+            &self.floor_types
+        ); // TODO currently chekcing for all walls, which is not necessary, use tilemap
+        RepulsionSystem::update(&mut self.transients.repulsion_fields, &mut self.enemies);
+        let game_time = Duration::from_secs_f32(self.game_time() as f32);
+        MovementSystem::update_enemies(
+            // TODO currently chekcing for all walls, which is not necessary, use tilemap
+            &mut self.enemies,
+            self.player.pos,
+            &self.walls,
+            &self.doors,
+            &mut self.world_layout,
+            &self.floor_types,
+            game_time
+        );
+        self.update_teleporters();
+        self.update_sequences();
+        let event = MovingEntityCollisionSystem::check_player_enemy_collisions(
+            &self.player.pos,
+            &self.world_layout,
+            &self.enemies.positions,
+            &self.enemies.sizes,
+            &self.enemies.alives
+        );
+        if let Some(event) = event {
+            self.handle_world_event_handle_based(event);
+        }
+        let debug_enemy = self.debug_enemy.and_then(|spawn_id| self.enemies.handle_of(spawn_id));
+        self.enemy_vision_debug = EnemyAggressionSystem::toggle_enemy_aggressive(
+            self.player.pos,
+            &mut self.enemies,
+            &self.doors,
+            &self.world_layout,
+            debug_enemy
+        );
+        self.update_enemy_paths();
+        let shots = EnemyAttackSystem::update(self.player.pos, &mut self.enemies, &mut self.transients.projectiles);
+        for shot in shots {
+            let shooter = self.enemies.positions[shot.other_involved as usize];
+            self.play_at(Sounds::PistolShoot, 0.3, shooter, true);
+            self.handle_world_event_handle_based(shot);
+        }
+        let (projectile_hits, explosions, near_misses) = ProjectileSystem::update(
+            &mut self.transients.projectiles,
+            self.player.pos,
+            &self.world_layout,
+            &self.doors,
+            &self.enemies
+        );
+        for hit in projectile_hits {
+            self.handle_world_event_handle_based(hit);
+        }
+        for near_miss in near_misses {
+            let whizz = near_miss.whizz(self.player.pos, self.player.angle);
+            self.play_at(whizz, 0.6, near_miss.position, false);
+            // flinches away from the side it passed on
+            let away = if whizz == Sounds::WhizzLeft { NEAR_MISS_NUDGE } else { -NEAR_MISS_NUDGE };
+            self.effects.add(EffectSource::NearMiss, EffectKind::Nudge(Vec2::new(away, 0.0)), 0.15);
+        }
+        self.transients.age_explosions();
+        for explosion in explosions {
+            self.play_at(Sounds::Explosion, 0.8, explosion.position, true);
+            // felt up close, a blast across the level only rumbles
+            let distance = explosion.position.distance(self.player.pos + Vec2::splat(0.5));
+            let closeness = 1.0 - distance / SOUND_FALLOFF_DISTANCE;
+            if closeness > 0.0 {
+                self.effects.add(EffectSource::Shot, EffectKind::CameraShake { intensity: 25.0 * closeness }, 0.3);
+            }
+            crash::record_event(
+                format!("{:.2}s explosion at ({:.1}, {:.1})", get_time(), explosion.position.x, explosion.position.y)
+            );
+            self.transients.add_explosion(explosion);
+        }
+        self.update_growls();
+        // after the hits of this frame, which can push the player onto or off a pickup
+        self.collect_pickup();
+        if self.enemy_vision_debug.is_none() {
+            // died or was never there
+            self.debug_enemy = None;
+        }
+        self.player_interactables.clear();
+        let opt_interactable = ProximityBasedInteractionSystem::get_aimed_interaction(
+            self.player.pos,
+            self.player.angle,
+            &self.world_layout,
+            &self.doors,
+            &self.walls,
+            &self.notes,
+            2.0
+        );
+        if let Some(interactable) = opt_interactable {
+            self.player_interactables.push(interactable);
+        }
+        self.player_interactables.extend(
+            ProximityBasedInteractionSystem::get_pickup_interactions(
+                self.player.pos,
+                self.player.angle,
+                &self.pickups,
+                &self.doors,
+                &self.world_layout,
+                PICKUP_LABEL_DISTANCE
+            )
+        );
+        self.doors.update_animation(PHYSICS_FRAME_TIME);
+        // we can rewrite the rendering logic to use this, then put the callbacks into a queue and only update visible enemies animations
+        let mut all_animation_callback_events = Vec::new();
+
+        all_animation_callback_events.extend(
+            self.player.animation_state.update(PHYSICS_FRAME_TIME)
+        );
+
+        let animation_callback_events = UpdateEnemyAnimation::update(
+            self.player.pos,
+            &self.enemies.positions,
+            &self.enemies.aggressive_states,
+            &self.enemies.velocities,
+            &mut self.enemies.smooth_facing_angles,
+            &mut self.enemies.animation_states
+        );
+        all_animation_callback_events.extend(animation_callback_events);
+        let drops = CallbackHandler::handle_animation_callbacks(
+            all_animation_callback_events,
+            &mut self.world_layout,
+            &mut self.enemies,
+            &mut self.corpses,
+            &self.content,
+            &mut self.rng
+        );
+        for item in drops {
+            self.apply_loot(item);
+        }
+        crash::update_summary(crash::WorldSummary {
+            game_time: self.game_time(),
+            state_hash: self.state_hash(),
+            player_pos: (self.player.pos.x, self.player.pos.y),
+            player_health: self.player.health,
+            enemies_alive: self.enemies.alives
+                .iter()
+                .filter(|alive| **alive)
+                .count(),
+            enemies_total: self.enemies.alives.len(),
+        });
+    }
+
+    fn draw(&mut self) {
+        clear_background(LIGHTGRAY);
+        let  player_ray_origin = self.player.pos + Vec2::new(0.5, 0.5);
+        let eye = self.player.eye_level();
+        let settings = &self.settings;
+        let fov = settings.fov_degrees.to_radians();
+        let bobbing_offset = if settings.bobbing_enabled && self.player.vel.length() > BOBBING_MIN_SPEED {
+            let phase = self.player.bobbing_time * settings.bobbing_speed;
+            // and wider
+            let amplitude = self.player.vel.length() / PLAYER_SPEED;
+            Vec2::new(phase.sin() * settings.bobbing_amount, (phase * 2.0).sin() * settings.bobbing_amount_y) *
+                amplitude
+        } else {
+            Vec2::ZERO
+        };
+        
+        let start_time: f64 = get_time();
+        let raycast_result = RaycastSystem::raycast(
+            player_ray_origin,
+            self.player.angle,
+            fov,
+            &self.doors,
+            &self.world_layout
+        );
+        let end_time = get_time();
+        let elapsed_time = end_time - start_time;
+        if !raycast_result.is_empty() {
+            self.average_view_distance =
+                raycast_result
+                    .iter()
+                    .map(|result| result.corrected_distance)
+                    .sum::<f32>() / (raycast_result.len() as f32);
+        }
+
+        let mut z_buffer = [f32::MAX; AMOUNT_OF_RAYS as usize];
+        if self.settings.gpu_raycasting {
+            self.tiles_texture.update_from_bytes(
+                self.world_layout.width() as u32,
+                self.world_layout.height() as u32,
+                &tile_texels(&self.world_layout, &self.doors, &self.walls, &self.notes)
+            );
+            RenderPlayerPOV::render_world_gpu(
+                &self.raycast_material,
+                &self.tiles_texture,
+                &self.floor_types_texture,
+                self.player.angle,
+                fov,
+                player_ray_origin,
+                eye
+            );
+            // the shader can't hand its distances back, sprites are occluded by the cpu rays
+            for (i, result) in raycast_result.iter().enumerate() {
+                z_buffer[i] = result.corrected_distance;
+            }
+        } else {
+            RenderPlayerPOV::render_floor(
+                &self.background_material,
+                self.player.angle,
+                fov,
+                player_ray_origin,
+                eye,
+                &self.floor_types_texture
+            );
+            RenderPlayerPOV::render_walls_and_doors(
+                &raycast_result,
+                &mut z_buffer,
+                &self.walls,
+                &self.notes,
+                eye
+            );
+        }
+
+        let seen_enemies = RenderPlayerPOV::seen_enemies(
+            self.player.pos,
+            self.player.angle,
+            fov,
+            &self.world_layout,
+            &self.enemies
+        );
+        RenderPlayerPOV::render_floor_pads(
+            &z_buffer,
+            self.player.pos,
+            self.player.angle,
+            fov,
+            eye,
+            &self.teleporters,
+            self.exit
+        );
+        RenderPlayerPOV::render_corpses(
+            &z_buffer,
+            self.player.pos,
+            self.player.angle,
+            fov,
+            eye,
+            &self.corpses
+        );
+        RenderPlayerPOV::render_pickups(
+            &z_buffer,
+            self.player.pos,
+            self.player.angle,
+            fov,
+            eye,
+            &self.pickups
+        );
+        RenderPlayerPOV::render_projectiles(
+            &z_buffer,
+            self.player.pos,
+            self.player.angle,
+            fov,
+            eye,
+            &self.transients.projectiles
+        );
+        let explosions = &self.transients.explosions;
+        RenderPlayerPOV::render_explosions(&z_buffer, self.player.pos, self.player.angle, fov, eye, explosions);
+        let visible_decorations = RenderPlayerPOV::render_decorations(
+            &z_buffer,
+            self.player.pos,
+            self.player.angle,
+            fov,
+            eye,
+            &self.decorations,
+            &self.decoration_sprites
+        );
+        RenderPlayerPOV::render_enemies(
+            &self.enemy_default_material,
+            &z_buffer,
+            eye,
+            fov,
+            &seen_enemies,
+            &self.enemies
+        );
+
+        self.effects.update(get_frame_time());
+        for tint in self.effects.tints() {
+            draw_rectangle(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32, tint);
+        }
+        if let Some(vignette) = self.effects.strongest_vignette() {
+            RenderPlayerPOV::render_vignette(vignette);
+        }
+        let shake_offset = self.effects.shake_offset();
+        if shake_offset != Vec2::ZERO {
+            gl_use_material(&self.camera_shake_material);
+            self.camera_shake_material.set_uniform(
+                "screen_size",
+                Vec2::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32)
+            );
+            self.camera_shake_material.set_uniform("shake_offset", shake_offset);
+        }
+        RenderPlayerPOV::render_weapon(&self.player, bobbing_offset);
+        RenderPlayerPOV::render_health(
+            self.player.health,
+            self.player.is_regenerating(self.settings.health_regeneration),
+            self.ui_scale,
+            &self.hud_theme
+        );
+        RenderPlayerPOV::render_fire_mode(
+            self.player.weapon().fire_mode(),
+            self.ui_scale,
+            &self.hud_theme
+        );
+        RenderPlayerPOV::render_stamina(
+            self.player.stamina / self.player.max_stamina,
+            self.player.sprint_exhausted,
+            self.ui_scale,
+            &self.hud_theme
+        );
+        RenderPlayerPOV::render_ammo(self.player.weapon(), self.ui_scale, &self.hud_theme);
+        if self.player.walking || self.player.is_crouching {
+            RenderPlayerPOV::render_stance_indicator(
+                self.player.is_crouching,
+                self.ui_scale,
+                &self.hud_theme
+            );
+        }
+        if let Some(strength) = self.effects.adrenaline_strength() {
+            RenderPlayerPOV::render_adrenaline(strength);
+        }
+        RenderPlayerPOV::render_possible_interactions(
+            self.player.pos,
+            self.player.angle,
+            fov,
+            &self.player_interactables,
+            &self.doors,
+            &keys::describe(&self.key_bindings.interact),
+            &self.hud_theme
+        );
+        RenderPlayerPOV::render_crosshair(&self.player_interactables, self.ui_scale, &self.hud_theme);
+        RenderPlayerPOV::render_floating_texts(&self.floating_texts);
+        if let Some(title) = &self.title_card {
+            RenderPlayerPOV::render_title_card(title);
+        }
+        let boss_health = self.boss.as_ref().and_then(|boss| {
+            let handle = self.enemies.handle_of(boss.spawn_id)?;
+            Some(self.enemies.healths[handle.0 as usize] / boss.max_health)
+        });
+        if let (Some(name), Some(health)) = (&self.boss_bar, boss_health) {
+            RenderPlayerPOV::render_boss_bar(name, health, self.ui_scale, &self.hud_theme);
+        }
+        gl_use_default_material();
+        let minimap = MinimapTransform::new(self.ui_scale, self.minimap_offset);
+        RenderMap::render_world_layout(&self.world_layout, &self.doors, minimap, &self.hud_theme);
+        RenderMap::render_teleporters(&self.teleporters, minimap, &self.hud_theme);
+        if let Some(exit) = self.exit {
+            RenderMap::render_exit(exit, minimap);
+        }
+        RenderMap::render_notes(&self.notes, &self.notes_read, minimap, &self.hud_theme);
+        if cfg!(debug_assertions) {
+            RenderMap::render_reverb_zones(&self.reverb_zones, self.current_reverb_zone, minimap);
+            RenderMap::render_map_warnings(&self.map_warnings, minimap);
+        }
+        RenderMap::render_player_and_enemies_on_map(
+            self.player.pos,
+            true,
+            &self.enemies,
+            &self.pickups,
+            minimap,
+            &self.hud_theme
+        );
+        RenderMap::render_projectiles_on_map(&self.transients.projectiles, minimap);
+        if let Some(wheel) = &self.weapon_wheel {
+            RenderPlayerPOV::render_weapon_wheel(wheel, &self.player.weapons.weapons, self.ui_scale, &self.hud_theme);
+        }
+        RenderMap::render_view_polygon(player_ray_origin, &raycast_result, minimap, &self.hud_theme);
+        if let Some(vision) = &self.enemy_vision_debug {
+            RenderMap::render_enemy_vision(vision, self.player.pos, minimap);
+        }
+
+        let ui_scale = self.ui_scale;
+        font::draw(
+            &format!(
+                "Raycasting FPS: {} ({})",
+                1.0 / elapsed_time,
+                if self.settings.gpu_raycasting { "gpu" } else { "cpu" }
+            ),
+            10.0 * ui_scale,
+            30.0 * ui_scale,
+            TextStyle::Debug,
+            RED
+        );
+        if !self.decorations.positions.is_empty() {
+            font::draw(
+                &format!(
+                    "Frame time: {:.2}ms, decorations drawn: {}/{}",
+                    get_frame_time() * 1000.0,
+                    visible_decorations,
+                    self.decorations.positions.len()
+                ),
+                10.0 * ui_scale,
+                370.0 * ui_scale,
+                TextStyle::Debug,
+                RED
+            );
+        }
+        let enemies_alive = self.enemies.alives
+            .iter()
+            .filter(|alive| **alive)
+            .count();
+        font::draw(
+            &format!(
+                "Update: {:.2}ms, raycast: {:.2}ms, enemies: {}/{}",
+                self.update_time * 1000.0,
+                elapsed_time * 1000.0,
+                enemies_alive,
+                self.enemies.alives.len()
+            ),
+            10.0 * ui_scale,
+            390.0 * ui_scale,
+            TextStyle::Debug,
+            RED
+        );
+        if self.settings.compass {
+            // the exit of an endless level is the only objective there is
+            let objective = self.exit.map(|(x, y)| {
+                angles::angle_to(self.player.pos, Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+            });
+            RenderPlayerPOV::render_compass(self.player.angle, objective, ui_scale, &self.hud_theme);
+        }
+        if self.settings.speedrun_timer {
+            self.render_speedrun_timer();
+        }
+        if let Some(run) = self.endless {
+            font::draw_centered(
+                &format!("Level {}", run.depth),
+                HALF_SCREEN_WIDTH,
+                120.0 * ui_scale,
+                TextStyle::Hud,
+                WHITE
+            );
+        }
+        let heading = self.hud_theme.controls_heading.color();
+        let key = self.hud_theme.controls_key.color();
+        let text = self.hud_theme.controls_text.color();
+        // read from the bindings so keys.cfg changes show up here
+        let bindings = &self.key_bindings;
+        let describe = keys::describe;
+        let weapons = match (bindings.weapon_slots.first(), bindings.weapon_slots.last()) {
+            (Some(first), Some(last)) => format!("{}-{}", describe(first), describe(last)),
+            _ => String::new(),
+        };
+        let controls = [
+            (
+                format!(
+                    "{}/{}/{}/{}",
+                    describe(&bindings.forward),
+                    describe(&bindings.strafe_left),
+                    describe(&bindings.back),
+                    describe(&bindings.strafe_right)
+                ),
+                " to move and strafe",
+            ),
+            (describe(&bindings.sprint), " to sprint"),
+            (describe(&bindings.walk_toggle), " to toggle walking"),
+            (describe(&bindings.crouch), " to crouch"),
+            (
+                format!("Mouse / {}/{}", describe(&bindings.turn_left), describe(&bindings.turn_right)),
+                " to rotate",
+            ),
+            (describe(&bindings.shoot), " to shoot"),
+            (describe(&bindings.interact), " to interact"),
+            (describe(&bindings.reload), " to reload"),
+            (describe(&bindings.pause), " to pause"),
+            (describe(&bindings.quick_turn), " to turn around"),
+            (describe(&bindings.map_overview), " for the map overview"),
+            (describe(&bindings.cycle_hud_theme), " to change the HUD theme"),
+            (describe(&bindings.cycle_fire_mode), " to change the fire mode"),
+            (describe(&bindings.toggle_renderer), " to switch the renderer"),
+            (weapons, " to switch weapons"),
+            (describe(&bindings.weapon_wheel), " (hold) for the weapon wheel"),
+        ];
+        font::draw("Controls:", 10.0 * ui_scale, 50.0 * ui_scale, TextStyle::Hud, heading);
+        for (i, (keys, action)) in controls.iter().enumerate() {
+            let y = (70.0 + (i as f32) * 20.0) * ui_scale;
+            font::draw(keys, 10.0 * ui_scale, y, TextStyle::Hud, key);
+            let action_x = 10.0 * ui_scale + font::measure(keys, TextStyle::Hud).x;
+            font::draw(action, action_x, y, TextStyle::Hud, text);
+        }
+    }
+
+    /// top center, the par delta below it is red while over par and green while under it,
+    /// below that the last split against the personal best
+    fn render_speedrun_timer(&self) {
+        let ui_scale = self.ui_scale;
+        let timer = &self.speedrun_timer;
+        let mut time = SpeedrunTimer::format(timer.seconds());
+        if timer.invalidated {
+            time.push_str(" (invalid)");
+        }
+        font::draw_centered(&time, HALF_SCREEN_WIDTH, 70.0 * ui_scale, TextStyle::Prompt, WHITE);
+        let mut y = 95.0 * ui_scale;
+        if let Some(delta) = timer.par_delta() {
+            let (delta, color) = SpeedrunTimer::format_delta(delta);
+            font::draw_centered(&format!("{} par", delta), HALF_SCREEN_WIDTH, y, TextStyle::Hud, color);
+            y += 20.0 * ui_scale;
+        }
+        if let Some(index) = timer.splits.len().checked_sub(1) {
+            let (text, color) = self.split_line(index);
+            font::draw_centered(&text, HALF_SCREEN_WIDTH, y, TextStyle::Hud, color);
+        }
+    }
+
+    /// the time a level was finished at, and how far that is from the personal best when there is one
+    fn split_line(&self, index: usize) -> (String, Color) {
+        let timer = &self.speedrun_timer;
+        let time = SpeedrunTimer::format(SpeedrunTimer::steps_to_seconds(timer.splits[index]));
+        match timer.split_delta(index) {
+            Some(delta) => {
+                let (delta, color) = SpeedrunTimer::format_delta(delta);
+                (format!("Level {}  {}  {}", index + 1, time, delta), color)
+            }
+            None => (format!("Level {}  {}", index + 1, time), WHITE),
+        }
+    }
+
+    /// below the game over message, every split of the run and whether it was a new personal best
+    fn draw_run_summary(&self) {
+        let ui_scale = self.ui_scale;
+        let timer = &self.speedrun_timer;
+        let mut lines: Vec<(String, Color)> = (0..timer.splits.len()).map(|index| self.split_line(index)).collect();
+        let mut total = format!("Total  {}", SpeedrunTimer::format(timer.seconds()));
+        if timer.invalidated {
+            total.push_str(" (invalid)");
+        }
+        lines.push((total, WHITE));
+        if timer.beats_best() {
+            lines.push(("New personal best".to_string(), GOLD));
+        }
+        for (i, (text, color)) in lines.iter().enumerate() {
+            let y = HALF_SCREEN_HEIGHT + (100.0 + (i as f32) * 22.0) * ui_scale;
+            font::draw_centered(text, HALF_SCREEN_WIDTH, y, TextStyle::Hud, *color);
+        }
+    }
+}
+#[derive(Clone, Copy, Debug)]
+enum LaunchMode {
+    Map, // the map id from the first argument
+    Stress {
+        enemy_density: f32,
+    },
+    Endless {
+        run_seed: u64,
+    },
+}
+impl LaunchMode {
+    /// --stress plays the generated stress test map, --stress-density=<multiplier> scales its enemies,
+    /// --endless starts an endless run, --seed=<number> replays one
+    fn from_args(args: &[String]) -> Self {
+        let value = |prefix: &str| args.iter().find_map(|arg| arg.strip_prefix(prefix));
+        if args.iter().any(|arg| arg == "--stress") {
+            let enemy_density = value("--stress-density=").map_or(1.0, |density| {
+                density.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid --stress-density '{}', using 1", density);
+                    1.0
+                })
+            });
+            LaunchMode::Stress { enemy_density }
+        } else if args.iter().any(|arg| arg == "--endless") {
+            let run_seed = value("--seed=").map_or_else(random, |seed| {
+                seed.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid --seed '{}', using a random one", seed);
+                    random()
+                })
+            });
+            LaunchMode::Endless { run_seed }
+        } else {
+            LaunchMode::Map
+        }
+    }
+}
+
+/// a restarted endless run starts over on level 1 of the same seed
+async fn load_world(content: Rc<ContentRegistry>, map_id: &str, launch: LaunchMode, settings: &Settings) -> World {
+    match launch {
+        LaunchMode::Map => World::default(content, map_id, settings).await,
+        LaunchMode::Stress { enemy_density } => World::stress(content, enemy_density, settings).await,
+        LaunchMode::Endless { run_seed } => World::endless(content, run_seed, settings).await,
+    }
+}
+#[macroquad::main(window_conf)]
+async fn main() {
+    crash::install_hook();
+    let mut elapsed_time = 0.0;
+    let content = Rc::new(ContentRegistry::load());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let map_id = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .cloned()
+        .unwrap_or(DEFAULT_MAP_ID.to_string());
+    let (mut settings, mut settings_report) = Settings::load();
+    if args.iter().any(|arg| arg == "--reset-settings") {
+        settings = Settings::reset_to_defaults(&mut settings_report);
+    }
+    let mut key_bindings = KeyBindings::default();
+    let keys_report = keys::load(&mut key_bindings);
+    let launch = LaunchMode::from_args(&args);
+    let mut checkpoints = HashCheckpoints::from_args(&args);
+    #[cfg(feature = "golden-tests")]
+    if args.iter().any(|arg| arg == "--golden") {
+        let passed = golden::run(args.iter().any(|arg| arg == "--bless")).await;
+        exit(if passed { 0 } else { 1 });
+    }
+    let mut world = load_world(content.clone(), &map_id, launch, &settings).await;
+    world.key_bindings = key_bindings.clone();
+    // below the notifications the world already raised while loading
+    let first_line = world.floating_texts.len();
+    for (i, line) in content.report.iter().chain(settings_report.iter()).chain(keys_report.iter()).enumerate() {
+        eprintln!("{}", line);
+        let mut notification = FloatingText::notification(line);
+        notification.position.y += ((first_line + i) as f32) * 25.0;
+        notification.lifetime = 5.0;
+        world.floating_texts.push(notification);
+    }
+    let bg_music = load_sound("sounds/music.wav").await.expect("Failed to load background music");
+    play_sound(&bg_music, PlaySoundParams {
+        looped: true,
+        volume: 0.3 * settings.master_volume * settings.music_volume,
+    });
+    loop {
+        elapsed_time += get_frame_time() * world.time_scale.current();
+        let playing = world.game_state.simulates();
+        world.mouse_look.update(playing);
+        if !playing {
+            world.paused_time += get_frame_time() as f64;
+        }
+        match world.game_state {
+            GameState::GameGoing => {
+                world.handle_input();
+                world.update_scheduler(get_frame_time());
+                if elapsed_time > PHYSICS_FRAME_TIME {
+                    let update_start = get_time();
+                    world.update();
+                    world.update_time = get_time() - update_start;
+                    world.check_state_hash(&mut checkpoints);
+                    elapsed_time = 0.0;
+                }
+                world.draw();
+            }
+            GameState::Paused => {
+                // the music keeps playing
+                world.handle_pause_input();
+                world.draw();
+                world.draw_pause_overlay();
+            }
+            GameState::MapOverview { .. } => {
+                world.handle_map_overview_input();
+                world.draw_map_overview();
+            }
+            GameState::ReadingNote { .. } => {
+                // the simulation waits behind the panel, the music keeps playing
+                world.handle_note_input();
+                world.draw();
+                world.draw_note_panel();
+            }
+            GameState::GameOver => {
+                font::draw_centered("You lost!", HALF_SCREEN_WIDTH, HALF_SCREEN_HEIGHT - 50.0, TextStyle::Title, RED);
+                font::draw_centered(
+                    "Press space to play again or ESC to exit",
+                    HALF_SCREEN_WIDTH,
+                    HALF_SCREEN_HEIGHT + 50.0,
+                    TextStyle::Prompt,
+                    WHITE
+                );
+                if world.settings.speedrun_timer {
+                    world.draw_run_summary();
+                }
+                if is_key_down(KeyCode::Escape) {
+                    exit(0);
+                }
+                if is_key_down(KeyCode::Space) {
+                    // keeps whatever was changed in game, like the HUD theme
+                    let settings = world.settings.clone();
+                    world = load_world(content.clone(), &map_id, launch, &settings).await;
+                    world.key_bindings = key_bindings.clone();
+                    // the steps count from zero again, the recording only covers the first run
+                    checkpoints = HashCheckpoints::Off;
+                }
+            }
+        }
+        font::draw(
+            &format!("FPS: {}", 1.0 / get_frame_time()),
+            10.0 * world.ui_scale,
+            10.0 * world.ui_scale,
+            TextStyle::Debug,
+            WHITE
+        );
+        next_frame().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the systems World::update and World::draw run each physics frame, without the window and the sounds
+    struct Simulation {
+        level: Level,
+        player: Player,
+        projectiles: Projectiles,
+        floor_types: FloorGrid,
+        corpses: Vec<CorpseData>,
+        content: ContentRegistry,
+        rng: SimulationRng,
+        steps: u32,
+    }
+    impl Simulation {
+        fn new(rows: &str) -> Self {
+            let content = ContentRegistry::base();
+            let layout = map::parse_layout(rows).expect("test map should parse");
+            let level = Level::from_layout(&layout, &content).expect("test map should be a valid level");
+            Simulation {
+                player: Player::new(level.player_spawn, starting_weapons(&content)),
+                floor_types: Grid::same_size(&layout, FloorType::Stone),
+                level,
+                projectiles: Projectiles::new(),
+                corpses: Vec::new(),
+                content,
+                rng: SimulationRng::new(SIMULATION_SEED),
+                steps: 0,
+            }
+        }
+        fn step(&mut self) {
+            self.steps += 1;
+            let level = &mut self.level;
+            // walks in a slow circle, so the player runs into the edges of the map and whatever stands there
+            self.player.angle = angles::wrap_pi(self.player.angle + 0.05);
+            self.player.target_vel = Vec2::new(self.player.angle.cos(), self.player.angle.sin()) * PLAYER_SPEED;
+            MovementSystem::update_player(
+                &mut self.player,
+                &level.walls,
+                &level.doors,
+                &level.enemies,
+                &mut level.world_layout,
+                &self.floor_types
+            );
+            MovementSystem::update_enemies(
+                &mut level.enemies,
+                self.player.pos,
+                &level.walls,
+                &level.doors,
+                &mut level.world_layout,
+                &self.floor_types,
+                Duration::from_secs_f32((self.steps as f32) * PHYSICS_FRAME_TIME)
+            );
+            MovingEntityCollisionSystem::check_player_enemy_collisions(
+                &self.player.pos,
+                &level.world_layout,
+                &level.enemies.positions,
+                &level.enemies.sizes,
+                &level.enemies.alives
+            );
+            EnemyAggressionSystem::toggle_enemy_aggressive(
+                self.player.pos,
+                &mut level.enemies,
+                &level.doors,
+                &level.world_layout,
+                None
+            );
+            EnemyAttackSystem::update(self.player.pos, &mut level.enemies, &mut self.projectiles);
+            ProjectileSystem::update(
+                &mut self.projectiles,
+                self.player.pos,
+                &level.world_layout,
+                &level.doors,
+                &level.enemies
+            );
+            ProximityBasedInteractionSystem::get_aimed_interaction(
+                self.player.pos,
+                self.player.angle,
+                &level.world_layout,
+                &level.doors,
+                &level.walls,
+                &[],
+                2.0
+            );
+            level.doors.update_animation(PHYSICS_FRAME_TIME);
+            let callbacks = UpdateEnemyAnimation::update(
+                self.player.pos,
+                &level.enemies.positions,
+                &level.enemies.aggressive_states,
+                &level.enemies.velocities,
+                &mut level.enemies.smooth_facing_angles,
+                &mut level.enemies.animation_states
+            );
+            CallbackHandler::handle_animation_callbacks(
+                callbacks,
+                &mut level.world_layout,
+                &mut level.enemies,
+                &mut self.corpses,
+                &self.content,
+                &mut self.rng
+            );
+            // what draw works out before anything is drawn
+            let rays = RaycastSystem::raycast(
+                self.player.pos + Vec2::new(0.5, 0.5),
+                self.player.angle,
+                PLAYER_FOV,
+                &level.doors,
+                &level.world_layout
+            );
+            assert_eq!(rays.len(), AMOUNT_OF_RAYS);
+            RenderPlayerPOV::seen_enemies(
+                self.player.pos,
+                self.player.angle,
+                PLAYER_FOV,
+                &level.world_layout,
+                &level.enemies
+            );
+        }
+        /// the player is on the map and on exactly one tile of the layout
+        fn assert_player_placed(&self) {
+            let size = Vec2::new(self.level.world_layout.width() as f32, self.level.world_layout.height() as f32);
+            assert!(self.player.pos.cmpge(Vec2::ZERO).all() && self.player.pos.cmplt(size).all());
+            let player_tiles = self.level.world_layout
+                .rows()
+                .flatten()
+                .filter(|tile| **tile == EntityType::Player)
+                .count();
+            assert_eq!(player_tiles, 1);
+        }
+    }
+
+    #[test]
+    fn open_map_with_only_a_player_runs_300_steps() {
+        let mut simulation = Simulation::new("00000\n00000\n00200\n00000");
+        for _ in 0..300 {
+            simulation.step();
+        }
+        simulation.assert_player_placed();
+    }
+
+    #[test]
+    fn map_with_a_player_and_a_door_runs_300_steps() {
+        let mut simulation = Simulation::new("00000\n01410\n00000\n00200");
+        for step in 0..300 {
+            // closed, opening and open for about a hundred steps each
+            if step == 100 {
+                simulation.level.doors.open_door(DoorHandle(0));
+            }
+            simulation.step();
+        }
+        simulation.assert_player_placed();
+        assert_eq!(simulation.level.doors.closed_share(DoorHandle(0)), None);
+    }
+
+    #[test]
+    fn map_with_a_player_and_an_enemy_runs_300_steps() {
+        let mut simulation = Simulation::new("00000\n00000\n70200\n00000");
+        for _ in 0..300 {
+            simulation.step();
+        }
+        simulation.assert_player_placed();
+        assert_eq!(simulation.level.enemies.positions.len(), 1);
+    }
+
+    #[test]
+    fn door_hitbox_shrinks_towards_the_wall_it_slides_into() {
+        let (x, y) = (2.0, 3.0);
+        let cases = [
+            (DoorDirection::LEFT, 0.0, Some(Rect::new(x, y, 1.0, 1.0))),
+            (DoorDirection::LEFT, 0.5, Some(Rect::new(x, y, 0.5, 1.0))),
+            (DoorDirection::LEFT, 1.0, None),
+            (DoorDirection::RIGHT, 0.0, Some(Rect::new(x, y, 1.0, 1.0))),
+            (DoorDirection::RIGHT, 0.5, Some(Rect::new(x + 0.5, y, 0.5, 1.0))),
+            (DoorDirection::RIGHT, 1.0, None),
+            (DoorDirection::UP, 0.0, Some(Rect::new(x, y, 1.0, 1.0))),
+            (DoorDirection::UP, 0.5, Some(Rect::new(x, y, 1.0, 0.5))),
+            (DoorDirection::UP, 1.0, None),
+            (DoorDirection::DOWN, 0.0, Some(Rect::new(x, y, 1.0, 1.0))),
+            (DoorDirection::DOWN, 0.5, Some(Rect::new(x, y + 0.5, 1.0, 0.5))),
+            (DoorDirection::DOWN, 1.0, None),
+        ];
+        for (direction, open_fraction, expected) in cases {
+            let mut doors = Doors::new(1.0, 1.0, 1.0);
+            let handle = doors.add_door(Vec2::new(x, y), direction);
+            if open_fraction > 0.0 {
+                doors.open_door(handle);
+                doors.update_animation(open_fraction);
+            }
+            assert_eq!(doors.get_door_hitbox(handle), expected, "{:?} at {}", direction, open_fraction);
+        }
+    }
+
+    #[test]
+    fn shake_and_vignette_apply_together() {
+        let mut effects = VisualEffects::new();
+        effects.add(EffectSource::Shot, EffectKind::CameraShake { intensity: 10.0 }, 1.0);
+        effects.add(EffectSource::PlayerHit, EffectKind::Vignette(Color::new(1.0, 0.0, 0.0, 0.8)), 1.0);
+        effects.update(0.5);
+        // a random direction, but half the intensity halfway through
+        assert!((effects.shake_offset().length() - 5.0).abs() < 1e-4);
+        let vignette = effects.strongest_vignette().expect("the vignette still runs");
+        assert!((vignette.a - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn expiry_removes_only_the_finished_effect() {
+        let mut effects = VisualEffects::new();
+        effects.add(EffectSource::Shot, EffectKind::CameraShake { intensity: 10.0 }, 0.2);
+        effects.add(EffectSource::PlayerHit, EffectKind::Vignette(Color::new(1.0, 0.0, 0.0, 0.8)), 1.0);
+        effects.hold(EffectSource::Water, EffectKind::Tint(Color::new(0.1, 0.5, 0.5, 0.25)));
+        effects.update(0.3);
+        assert_eq!(effects.shake_offset(), Vec2::ZERO);
+        assert!(effects.strongest_vignette().is_some());
+        assert_eq!(effects.tints().count(), 1);
+        effects.update(1.0);
+        assert!(effects.strongest_vignette().is_none());
+        // held until released
+        assert_eq!(effects.tints().count(), 1);
+        effects.release(EffectSource::Water);
+        assert_eq!(effects.tints().count(), 0);
+    }
+
+    #[test]
+    fn water_slows_the_player_until_they_leave_it() {
+        let mut simulation = Simulation::new("200000000000");
+        for x in 0..4 {
+            simulation.floor_types[0][x] = FloorType::Water;
+        }
+        assert_eq!(MovementSystem::floor_speed(&simulation.floor_types, Vec2::new(2.0, 0.0)), WATER_SPEED_MULTIPLIER);
+        assert_eq!(MovementSystem::floor_speed(&simulation.floor_types, Vec2::new(6.0, 0.0)), 1.0);
+        let level = &mut simulation.level;
+        let player = &mut simulation.player;
+        player.target_vel = Vec2::new(PLAYER_SPEED, 0.0);
+        let full_step = PLAYER_SPEED * PHYSICS_FRAME_TIME * 1.5;
+        let (mut in_water, mut on_stone) = (0, 0);
+        while player.pos.x < 9.0 {
+            let before = player.pos.x;
+            MovementSystem::update_player(
+                player,
+                &level.walls,
+                &level.doors,
+                &level.enemies,
+                &mut level.world_layout,
+                &simulation.floor_types
+            );
+            let step = player.pos.x - before;
+            // far enough from the start and from the shore for the velocity to have settled
+            if (1.5..2.5).contains(&before) {
+                assert!((step - full_step * WATER_SPEED_MULTIPLIER).abs() < full_step * 0.01, "{} in water", step);
+                in_water += 1;
+            } else if (6.0..8.0).contains(&before) {
+                assert!((step - full_step).abs() < full_step * 0.01, "{} on stone", step);
+                on_stone += 1;
+            }
+        }
+        assert!(in_water > 0 && on_stone > 0);
+    }
+
+    #[test]
+    fn shot_passes_through_a_dying_enemy_to_the_one_behind() {
+        let mut simulation = Simulation::new("0000000\n2030300\n0000000");
+        let Simulation { level, player, projectiles, rng, .. } = &mut simulation;
+        player.angle = 0.0;
+        let enemy_at = |x: usize| match level.world_layout.get(x, 1) {
+            Some(&EntityType::Enemy(handle)) => Some(handle),
+            _ => None,
+        };
+        let (front, rear) = (enemy_at(2).expect("enemy in front"), enemy_at(4).expect("enemy behind"));
+        let hits = |event: ShootEvent| event.world_events.iter().map(|hit| hit.other_involved).collect::<Vec<_>>();
+        assert_eq!(hits(player.fire(&level.world_layout, &level.enemies, projectiles, rng)), vec![front.0]);
+        PlayEnemyAnimation::play_death(
+            front,
+            &mut level.enemies.velocities,
+            &mut level.enemies.animation_states,
+            &mut level.enemies.alives
+        );
+        // the death animation is still playing, so the front enemy is still in the layout
+        assert_eq!(enemy_at(2), Some(front));
+        assert_eq!(hits(player.fire(&level.world_layout, &level.enemies, projectiles, rng)), vec![rear.0]);
+    }
+
+    /// the tiles of the layout that carry handle
+    fn enemy_tiles(layout: &Grid<EntityType>, handle: EnemyHandle) -> Vec<(usize, usize)> {
+        let mut tiles = Vec::new();
+        for (y, row) in layout.rows().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if *tile == EntityType::Enemy(handle) {
+                    tiles.push((x, y));
+                }
+            }
+        }
+        tiles
+    }
+
+    fn kill_callbacks(handles: &[u16]) -> Vec<AnimationCallbackEvent> {
+        handles
+            .iter()
+            .map(|&idx| AnimationCallbackEvent {
+                event_type: AnimationCallbackEventType::KillEnemy,
+                target_handle: AllHandleTypes::EnemyHandle(EnemyHandle(idx)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn killing_an_enemy_moves_the_last_one_into_its_handle() {
+        let mut simulation = Simulation::new("3030302\n0000000");
+        let level = &mut simulation.level;
+        assert_eq!(level.enemies.positions.len(), 3);
+        let last = EnemyHandle(2);
+        let last_spawn = level.enemies.spawn_id(last);
+        let last_pos = level.enemies.positions[2];
+        let last_tiles = enemy_tiles(&level.world_layout, last);
+        let killed_tiles = enemy_tiles(&level.world_layout, EnemyHandle(0));
+        assert!(!last_tiles.is_empty() && !killed_tiles.is_empty());
+        CallbackHandler::handle_animation_callbacks(
+            kill_callbacks(&[0]),
+            &mut level.world_layout,
+            &mut level.enemies,
+            &mut simulation.corpses,
+            &simulation.content,
+            &mut simulation.rng
+        );
+        assert_eq!(level.enemies.positions.len(), 2);
+        assert_eq!(simulation.corpses.len(), 1);
+        assert_eq!(level.enemies.handle_of(last_spawn), Some(EnemyHandle(0)));
+        assert_eq!(level.enemies.positions[0], last_pos);
+        assert_eq!(enemy_tiles(&level.world_layout, EnemyHandle(0)), last_tiles);
+        assert!(enemy_tiles(&level.world_layout, last).is_empty());
+        for (x, y) in killed_tiles {
+            assert_eq!(level.world_layout[y][x], EntityType::None);
+        }
+    }
+
+    #[test]
+    fn enemies_dying_in_the_same_frame_keep_the_survivor_on_its_tiles() {
+        let mut simulation = Simulation::new("3030302\n0000000");
+        let level = &mut simulation.level;
+        let survivor_spawn = level.enemies.spawn_id(EnemyHandle(2));
+        let survivor_tiles = enemy_tiles(&level.world_layout, EnemyHandle(2));
+        // lowest first, the handler has to take the higher index first or it would move the survivor twice
+        CallbackHandler::handle_animation_callbacks(
+            kill_callbacks(&[0, 1]),
+            &mut level.world_layout,
+            &mut level.enemies,
+            &mut simulation.corpses,
+            &simulation.content,
+            &mut simulation.rng
+        );
+        assert_eq!(level.enemies.positions.len(), 1);
+        assert_eq!(level.enemies.handle_of(survivor_spawn), Some(EnemyHandle(0)));
+        assert_eq!(enemy_tiles(&level.world_layout, EnemyHandle(0)), survivor_tiles);
+        let enemy_tile_count = level.world_layout
+            .rows()
+            .flatten()
+            .filter(|tile| matches!(tile, EntityType::Enemy(_)))
+            .count();
+        assert_eq!(enemy_tile_count, survivor_tiles.len());
+    }
+
+    /// a timer that ran `steps` physics steps
+    fn timer_at(steps: u64) -> SpeedrunTimer {
+        let mut timer = SpeedrunTimer::new(None);
+        for _ in 0..steps {
+            timer.tick(&GameState::GameGoing);
+        }
+        timer
+    }
+
+    #[test]
+    fn speedrun_timer_stands_still_outside_the_running_game() {
+        let mut timer = timer_at(60);
+        let stopped = [
+            GameState::Paused,
+            GameState::GameOver,
+            GameState::MapOverview { camera_pos: Vec2::ZERO, zoom: 1.0 },
+            GameState::ReadingNote { note: 0, page: 0 },
+        ];
+        for state in &stopped {
+            for _ in 0..100 {
+                timer.tick(state);
+            }
+        }
+        assert_eq!(timer.steps, 60);
+        timer.tick(&GameState::GameGoing);
+        assert_eq!(timer.steps, 61);
+    }
+
+    #[test]
+    fn speedrun_time_comes_from_the_steps() {
+        let timer = timer_at((1.0 / PHYSICS_FRAME_TIME).round() as u64 * 75);
+        assert!((timer.seconds() - 75.0).abs() < 0.01);
+        assert_eq!(SpeedrunTimer::format(75.0), "1:15.000");
+        assert_eq!(SpeedrunTimer::format(3599.9996), "60:00.000");
+        assert_eq!(SpeedrunTimer::format(0.0421), "0:00.042");
+    }
+
+    #[test]
+    fn split_deltas_are_against_the_best_split_of_the_same_level() {
+        let mut timer = timer_at(0);
+        timer.best_splits = vec![600, 1500];
+        for steps in [540, 1620, 2000] {
+            timer.steps = steps;
+            timer.split();
+        }
+        let seconds = SpeedrunTimer::steps_to_seconds;
+        assert_eq!(timer.split_delta(0), Some(seconds(540) - seconds(600)));
+        assert!(timer.split_delta(0).unwrap() < 0.0);
+        assert_eq!(timer.split_delta(1), Some(seconds(1620) - seconds(1500)));
+        assert!(timer.split_delta(1).unwrap() > 0.0);
+        // the best run never got this far
+        assert_eq!(timer.split_delta(2), None);
+        assert_eq!(SpeedrunTimer::format_delta(-1.0), ("-0:01.000".to_string(), GREEN));
+        assert_eq!(SpeedrunTimer::format_delta(2.5), ("+0:02.500".to_string(), RED));
+    }
+
+    #[test]
+    fn deeper_or_faster_runs_beat_the_best() {
+        let run = |splits: Vec<u64>, best: Vec<u64>| {
+            let mut timer = timer_at(0);
+            timer.splits = splits;
+            timer.best_splits = best;
+            timer
+        };
+        assert!(run(vec![600], vec![]).beats_best());
+        assert!(run(vec![900, 2000, 3000], vec![600, 1200]).beats_best());
+        assert!(!run(vec![500], vec![600, 1200]).beats_best());
+        assert!(run(vec![700, 1100], vec![600, 1200]).beats_best());
+        assert!(!run(vec![500, 1200], vec![600, 1200]).beats_best());
+        assert!(!run(vec![], vec![]).beats_best());
+        let mut invalid = run(vec![600], vec![]);
+        invalid.invalidated = true;
+        assert!(!invalid.beats_best());
+    }
+
+    fn scenario_hash() -> u64 {
+        // an archer shooting at the player walking circles next to a door that opens on the way
+        let mut simulation = Simulation::new("000000\n014100\n000000\n700200\n000000");
+        for step in 0..1000 {
+            if step == 200 {
+                simulation.level.doors.open_door(DoorHandle(0));
             }
-        ).expect("Failed to load background material");
-        let camera_shake_material = load_material(
-            ShaderSource::Glsl {
-                vertex: &CAMERA_SHAKE_VERTEX_SHADER,
-                fragment: &DEFAULT_FRAGMENT_SHADER,
-            },
-            MaterialParams {
-                uniforms: vec![
-                    UniformDesc {
-                        name: "screen_size".to_string(),
-                        uniform_type: UniformType::Float2,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "shake_offset".to_string(),
-                        uniform_type: UniformType::Float2,
-                        array_count: 1,
-                    }
-                ],
-                pipeline_params: PipelineParams {
-                    color_blend: Some(
-                        BlendState::new(
-                            Equation::Add,
-                            BlendFactor::Value(BlendValue::SourceAlpha),
-                            BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
-                        )
-                    ),
-                    alpha_blend: Some(
-                        BlendState::new(Equation::Add, BlendFactor::Zero, BlendFactor::One)
-                    ),
-                    ..Default::default()
-                },
-                ..Default::default()
+            simulation.step();
+        }
+        StateHasher::simulation(
+            simulation.steps as u64,
+            &simulation.rng,
+            &simulation.player,
+            &simulation.level.enemies,
+            &simulation.level.doors,
+            &simulation.projectiles
+        )
+    }
+
+    #[test]
+    fn simulation_state_hash_is_reproducible() {
+        assert_eq!(scenario_hash(), scenario_hash());
+    }
+
+    /// fails on any change to how the simulation plays out, a deliberate change updates the constant
+    /// with the hash the failure prints
+    #[test]
+    fn simulation_state_hash_matches_the_recorded_run() {
+        let hash = scenario_hash();
+        assert_eq!(hash, 0xa9b1_53c1_0af5_9cfa, "hash {:#018x}", hash);
+    }
+
+    /// the enemies a shotgun volley into a crowd hits and the rng state it leaves behind
+    fn shotgun_volley(seed: u64) -> (Vec<u16>, u64) {
+        let mut simulation = Simulation::new("0003000\n2000300\n0003000");
+        let Simulation { level, player, projectiles, rng, .. } = &mut simulation;
+        *rng = SimulationRng::new(seed);
+        player.angle = 0.0;
+        player.weapons.active = player.weapons.weapons
+            .iter()
+            .position(|weapon| weapon.id == "shotgun")
+            .expect("base shotgun");
+        let event = player.fire(&level.world_layout, &level.enemies, projectiles, rng);
+        (event.world_events.iter().map(|hit| hit.other_involved).collect(), rng.state)
+    }
+
+    #[test]
+    fn shotgun_jitter_comes_from_the_seeded_rng() {
+        let (hits, state) = shotgun_volley(SIMULATION_SEED);
+        assert_eq!(shotgun_volley(SIMULATION_SEED), (hits, state));
+        assert_ne!(state, SIMULATION_SEED, "the pellets drew their jitter");
+        assert_ne!(shotgun_volley(SIMULATION_SEED + 1).1, state);
+    }
+
+    #[test]
+    fn loot_rolls_from_the_seeded_rng_and_the_draws_change_the_state_hash() {
+        let kill_all = |simulation: &mut Simulation| {
+            let Simulation { level, corpses, content, rng, .. } = simulation;
+            let drops = CallbackHandler::handle_animation_callbacks(
+                kill_callbacks(&[0, 1, 2]),
+                &mut level.world_layout,
+                &mut level.enemies,
+                corpses,
+                content,
+                rng
+            );
+            (drops, rng.state)
+        };
+        let hash = |simulation: &Simulation| {
+            let Simulation { level, player, projectiles, rng, .. } = simulation;
+            StateHasher::simulation(0, rng, player, &level.enemies, &level.doors, projectiles)
+        };
+        let mut simulation = Simulation::new("3030302\n0000000");
+        let mut replay = Simulation::new("3030302\n0000000");
+        assert_eq!(kill_all(&mut simulation), kill_all(&mut replay));
+        assert_eq!(hash(&simulation), hash(&replay));
+        let _: u64 = replay.rng.gen();
+        assert_ne!(hash(&simulation), hash(&replay));
+    }
+
+    #[test]
+    fn state_hash_ignores_noise_below_the_quantum() {
+        let mut simulation = Simulation::new("00000\n00200\n00000");
+        let hash = |simulation: &Simulation| {
+            let Simulation { level, player, projectiles, rng, .. } = simulation;
+            StateHasher::simulation(0, rng, player, &level.enemies, &level.doors, projectiles)
+        };
+        let before = hash(&simulation);
+        simulation.player.pos.x += 0.1 / STATE_HASH_QUANTUM;
+        assert_eq!(hash(&simulation), before);
+        simulation.player.pos.x += 1.0 / STATE_HASH_QUANTUM;
+        assert_ne!(hash(&simulation), before);
+    }
+
+    /// an rng whose gen::<f32>() is always `value`, f32 samples take the top 24 bits of a u32
+    fn rng_rolling(value: f32) -> ::rand::rngs::mock::StepRng {
+        let bits = (value * ((1 << 24) as f32)) as u64;
+        ::rand::rngs::mock::StepRng::new(bits << 8, 0)
+    }
+
+    fn health_and_ammo(health_weight: f32, ammo_weight: f32) -> LootTable {
+        LootTable::new(vec![
+            LootEntry { item: LootItem::HealthPack(1), weight: health_weight },
+            LootEntry { item: LootItem::AmmoPickup(WeaponType::Pistol, 4), weight: ammo_weight }
+        ])
+    }
+
+    #[test]
+    fn weights_below_one_leave_the_rest_to_no_drop() {
+        let table = health_and_ammo(0.25, 0.25);
+        assert_eq!(table.roll(&mut rng_rolling(0.0)), Some(LootItem::HealthPack(1)));
+        assert_eq!(table.roll(&mut rng_rolling(0.24)), Some(LootItem::HealthPack(1)));
+        // a roll on a boundary belongs to the next bucket
+        assert_eq!(table.roll(&mut rng_rolling(0.25)), Some(LootItem::AmmoPickup(WeaponType::Pistol, 4)));
+        assert_eq!(table.roll(&mut rng_rolling(0.49)), Some(LootItem::AmmoPickup(WeaponType::Pistol, 4)));
+        assert_eq!(table.roll(&mut rng_rolling(0.5)), None);
+        assert_eq!(table.roll(&mut rng_rolling(0.99)), None);
+    }
+
+    #[test]
+    fn weights_above_one_are_scaled_and_the_last_bucket_reaches_the_top() {
+        let table = health_and_ammo(1.0, 3.0);
+        assert_eq!(table.roll(&mut rng_rolling(0.24)), Some(LootItem::HealthPack(1)));
+        assert_eq!(table.roll(&mut rng_rolling(0.25)), Some(LootItem::AmmoPickup(WeaponType::Pistol, 4)));
+        // the largest f32 sample is just below 1.0
+        let top = 1.0 - f32::EPSILON / 2.0;
+        assert_eq!(table.roll(&mut rng_rolling(top)), Some(LootItem::AmmoPickup(WeaponType::Pistol, 4)));
+    }
+
+    #[test]
+    fn a_guaranteed_drop_always_drops() {
+        let table = health_and_ammo(0.0, 1.0);
+        for roll in [0.0, 0.5, 1.0 - f32::EPSILON / 2.0] {
+            assert_eq!(table.roll(&mut rng_rolling(roll)), Some(LootItem::AmmoPickup(WeaponType::Pistol, 4)));
+        }
+    }
+
+    #[test]
+    fn empty_or_weightless_tables_drop_nothing() {
+        assert_eq!(LootTable::new(Vec::new()).roll(&mut rng_rolling(0.0)), None);
+        assert_eq!(health_and_ammo(0.0, -1.0).roll(&mut rng_rolling(0.0)), None);
+    }
+
+    /// one physics frame of the weapon input like World::handle_input and World::update, true when a shot fired,
+    /// which only ever happens while the weapon is ready
+    fn weapon_frame(weapons: &mut WeaponInventory, trigger: bool, switch_to: Option<usize>) -> bool {
+        let mut fired = false;
+        if trigger {
+            if weapons.is_ready() {
+                fired = true;
+            } else {
+                weapons.buffer_shot();
             }
-        ).expect("Failed to load camera shake material");
-        let enemy_default_material = load_material(
-            ShaderSource::Glsl {
-                vertex: &ENEMY_DEFAULT_VERTEX_SHADER,
-                fragment: &ENEMY_DEFAULT_FRAGMENT_SHADER,
-            },
-            MaterialParams {
-                uniforms: vec![
-                    UniformDesc {
-                        name: "u_relative_health".to_string(),
-                        uniform_type: UniformType::Float1,
-                        array_count: 1,
-                    },
-                    UniformDesc {
-                        name: "screen_size".to_string(),
-                        uniform_type: UniformType::Float2,
-                        array_count: 1,
-                    }
-                ],
+        }
+        if let Some(index) = switch_to {
+            weapons.request_switch(index);
+        }
+        if weapons.update(PHYSICS_FRAME_TIME) {
+            assert!(weapons.is_ready());
+            fired = true;
+        }
+        fired
+    }
 
-                pipeline_params: PipelineParams {
-                    color_blend: Some(
-                        BlendState::new(
-                            Equation::Add,
-                            BlendFactor::Value(BlendValue::SourceAlpha),
-                            BlendFactor::OneMinusValue(BlendValue::SourceAlpha)
-                        )
-                    ),
-                    alpha_blend: Some(
-                        BlendState::new(Equation::Add, BlendFactor::Zero, BlendFactor::One)
-                    ),
-                    ..Default::default()
-                },
-                ..Default::default()
+    #[test]
+    fn switch_spam_ends_on_the_last_request_without_firing_mid_switch() {
+        let mut weapons = starting_weapons(&ContentRegistry::base());
+        let slots = weapons.weapons.len();
+        assert!(slots > 2);
+        let mut last_request = weapons.active;
+        for frame in 0..90 {
+            // a new slot every few frames, sometimes the active one
+            let switch_to = (frame % 4 == 0).then_some((frame * 7) % slots);
+            if let Some(index) = switch_to {
+                last_request = index;
             }
-        ).expect("Failed to load default enemy material");
-        let shoot_sound = load_sound("sounds/pistol_shoot.wav").await.unwrap();
-        let reload_sound = load_sound("sounds/reload.wav").await.unwrap();
-        Self {
-            world_layout,
-            background_material: background_material,
-            camera_shake_material: camera_shake_material,
-            enemy_default_material: enemy_default_material,
-            walls,
-            doors,
-            enemies,
-            player,
-            player_interactables: Vec::new(),
-            shoot_sound,
-            reload_sound,
-            postprocessing: VisualEffect::None,
-            game_state: GameState::GameGoing,
+            weapon_frame(&mut weapons, true, switch_to);
+        }
+        for _ in 0..120 {
+            weapon_frame(&mut weapons, false, None);
+        }
+        assert!(weapons.is_ready());
+        assert_eq!(weapons.active, last_request);
+    }
+
+    #[test]
+    fn trigger_during_the_raise_fires_on_the_first_ready_frame() {
+        let mut weapons = starting_weapons(&ContentRegistry::base());
+        let other = (weapons.active + 1) % weapons.weapons.len();
+        weapons.request_switch(other);
+        // pulled while lowering, dropped
+        assert!(!weapon_frame(&mut weapons, true, None));
+        while matches!(weapons.switch, WeaponSwitch::Lowering { .. }) {
+            assert!(!weapon_frame(&mut weapons, false, None));
+        }
+        assert!(!weapons.buffered_shot);
+        assert!(!weapon_frame(&mut weapons, true, None));
+        let mut frames = 0;
+        while !weapon_frame(&mut weapons, false, None) {
+            assert!(!weapons.is_ready());
+            frames += 1;
+            assert!(frames < 60, "the buffered shot never fired");
+        }
+        assert_eq!(weapons.active, other);
+        assert!(!weapons.buffered_shot);
+    }
+
+    #[test]
+    fn switch_queued_during_the_raise_drops_the_buffered_shot() {
+        let mut weapons = starting_weapons(&ContentRegistry::base());
+        let slots = weapons.weapons.len();
+        let (first, second) = ((weapons.active + 1) % slots, (weapons.active + 2) % slots);
+        weapons.request_switch(first);
+        while !matches!(weapons.switch, WeaponSwitch::Raising { .. }) {
+            weapon_frame(&mut weapons, false, None);
+        }
+        weapon_frame(&mut weapons, true, Some(second));
+        for _ in 0..120 {
+            assert!(!weapon_frame(&mut weapons, false, None));
+        }
+        assert_eq!(weapons.active, second);
+    }
+
+    /// the enemies a damage 2 shot that passes through one enemy hits, with the damage each one takes
+    fn penetrating_shot(rows: &str) -> Vec<(u16, f32)> {
+        let mut simulation = Simulation::new(rows);
+        let Simulation { level, player, projectiles, rng, .. } = &mut simulation;
+        player.angle = 0.0;
+        let weapon = player.weapons.active_weapon_mut();
+        weapon.damage = 2;
+        weapon.penetration = 1;
+        let event = player.fire(&level.world_layout, &level.enemies, projectiles, rng);
+        event.world_events
+            .iter()
+            .map(|hit| (hit.other_involved, player.weapon().damage_at_depth(hit.penetration_depth)))
+            .collect()
+    }
+
+    #[test]
+    fn penetrating_shot_halves_the_damage_for_the_enemy_behind() {
+        assert_eq!(penetrating_shot("0000000\n2030300\n0000000"), vec![(0, 2.0), (1, 1.0)]);
+    }
+
+    #[test]
+    fn wall_between_two_enemies_stops_a_penetrating_shot() {
+        assert_eq!(penetrating_shot("0000000\n2031300\n0000000"), vec![(0, 2.0)]);
+    }
+
+    #[test]
+    fn penetration_stops_after_the_weapons_limit() {
+        assert_eq!(penetrating_shot("00000000\n20303030\n00000000").len(), 2);
+    }
+
+    /// the ends of the teleporter pair the teleporter tests share
+    const TELEPORTERS: [TeleporterPair; 1] = [TeleporterPair { a: (1, 1), b: (5, 1) }];
+
+    fn teleport_player(simulation: &mut Simulation) -> bool {
+        TeleporterSystem::update_player(&mut simulation.player, &TELEPORTERS, &mut simulation.level.world_layout)
+    }
+
+    #[test]
+    fn teleported_player_lands_next_to_the_partner_on_one_tile() {
+        let mut simulation = Simulation::new("1111111\n1200001\n1111111");
+        assert!(teleport_player(&mut simulation));
+        assert_eq!(simulation.player.pos, Vec2::new(4.0, 1.0));
+        assert_eq!(simulation.level.world_layout[1][4], EntityType::Player);
+        simulation.assert_player_placed();
+    }
+
+    #[test]
+    fn blocked_destination_delays_the_teleport_until_it_is_free() {
+        let mut simulation = Simulation::new("11111111\n12003031\n11111111");
+        for _ in 0..10 {
+            assert!(!teleport_player(&mut simulation));
+        }
+        assert_eq!(simulation.player.pos, Vec2::new(1.0, 1.0));
+        // the enemy on the right of the destination walked away
+        simulation.level.world_layout[1][6] = EntityType::None;
+        assert!(teleport_player(&mut simulation));
+        assert_eq!(simulation.player.pos, Vec2::new(6.0, 1.0));
+        simulation.assert_player_placed();
+    }
+
+    #[test]
+    fn lockout_keeps_the_player_from_teleporting_straight_back() {
+        let mut simulation = Simulation::new("1111111\n1200001\n1111111");
+        assert!(teleport_player(&mut simulation));
+        // steps onto the destination end right away
+        simulation.level.world_layout[1][4] = EntityType::None;
+        simulation.level.world_layout[1][5] = EntityType::Player;
+        simulation.player.pos = Vec2::new(5.0, 1.0);
+        let mut frames = 1;
+        while !teleport_player(&mut simulation) {
+            frames += 1;
+            assert!(frames < 120);
+        }
+        let lockout_frames = (TELEPORT_LOCKOUT / PHYSICS_FRAME_TIME).round() as i32;
+        assert!((frames - lockout_frames).abs() <= 1, "teleported back after {} frames", frames);
+        assert_eq!(simulation.player.pos, Vec2::new(2.0, 1.0));
+        simulation.assert_player_placed();
+    }
+
+    #[test]
+    fn enemies_only_teleport_when_their_kind_allows_it() {
+        let mut simulation = Simulation::new("11111111\n13000021\n11111111");
+        let teleport_enemies = |simulation: &mut Simulation| {
+            let Simulation { level, content, .. } = simulation;
+            TeleporterSystem::update_enemies(&mut level.enemies, &TELEPORTERS, &mut level.world_layout, content);
+        };
+        teleport_enemies(&mut simulation);
+        assert_eq!(enemy_tiles(&simulation.level.world_layout, EnemyHandle(0)), vec![(1, 1)]);
+        simulation.content.enemies.get_mut("skeleton").expect("base skeleton").uses_teleporters = true;
+        teleport_enemies(&mut simulation);
+        // the player blocks the right side of the destination
+        assert_eq!(simulation.level.enemies.positions[0], Vec2::new(4.0, 1.0));
+        assert_eq!(enemy_tiles(&simulation.level.world_layout, EnemyHandle(0)), vec![(4, 1)]);
+        assert_eq!(simulation.level.enemies.teleport_cooldowns[0], TELEPORT_LOCKOUT);
+    }
+
+    /// the frames a burst from the pistol hits the enemy in front on, switch_at switches weapons on that frame
+    fn burst_hit_frames(simulation: &mut Simulation, switch_at: Option<u32>) -> Vec<u32> {
+        let Simulation { level, player, projectiles, rng, .. } = simulation;
+        player.angle = 0.0;
+        let mut scheduler = Scheduler::new();
+        let hits = |event: &ShootEvent| !event.world_events.is_empty();
+        let mut frames = Vec::new();
+        if hits(&player.pull_trigger(&level.world_layout, &level.enemies, projectiles, &mut scheduler, rng)) {
+            frames.push(0);
+        }
+        for frame in 1..60 {
+            if switch_at == Some(frame) {
+                player.request_switch((player.weapons.active + 1) % player.weapons.weapons.len());
+            }
+            for action in scheduler.update(PHYSICS_FRAME_TIME) {
+                if !matches!(action, ScheduledAction::FireBurstShot) {
+                    continue;
+                }
+                let burst_shot = player.fire_burst_shot(&level.world_layout, &level.enemies, projectiles, rng);
+                if burst_shot.is_some_and(|event| hits(&event)) {
+                    frames.push(frame);
+                }
+            }
+        }
+        frames
+    }
+
+    /// a player with the pistol in burst mode in front of an enemy
+    fn burst_simulation() -> Simulation {
+        let mut simulation = Simulation::new("0000000\n2030000\n0000000");
+        let pistol = simulation.player.weapons.active_weapon_mut();
+        pistol.fire_mode_index = pistol.fire_modes
+            .iter()
+            .position(|mode| *mode == FireMode::Burst(3))
+            .expect("the pistol has a three shot burst");
+        simulation
+    }
+
+    #[test]
+    fn burst_hits_three_times_a_burst_interval_apart() {
+        let mut simulation = burst_simulation();
+        let ammo = simulation.player.weapon().current_ammo;
+        let interval = (BURST_SHOT_INTERVAL / PHYSICS_FRAME_TIME).round() as u32;
+        assert_eq!(burst_hit_frames(&mut simulation, None), vec![0, interval, 2 * interval]);
+        assert_eq!(simulation.player.weapon().current_ammo, ammo - 3);
+        assert_eq!(simulation.player.burst_shots_left, 0);
+    }
+
+    #[test]
+    fn switching_weapons_mid_burst_cancels_the_remaining_shots() {
+        let mut simulation = burst_simulation();
+        let ammo = simulation.player.weapon().current_ammo;
+        let interval = (BURST_SHOT_INTERVAL / PHYSICS_FRAME_TIME).round() as u32;
+        let pistol = simulation.player.weapons.active;
+        assert_eq!(burst_hit_frames(&mut simulation, Some(interval + 1)), vec![0, interval]);
+        assert_eq!(simulation.player.weapons.weapons[pistol].current_ammo, ammo - 2);
+        assert_eq!(simulation.player.burst_shots_left, 0);
+    }
+
+    #[test]
+    fn running_out_of_ammo_ends_the_burst() {
+        let mut simulation = burst_simulation();
+        simulation.player.weapons.active_weapon_mut().current_ammo = 2;
+        assert_eq!(burst_hit_frames(&mut simulation, None).len(), 2);
+        assert_eq!(simulation.player.weapon().current_ammo, 0);
+        assert_eq!(simulation.player.burst_shots_left, 0);
+    }
+
+    /// runs the scheduler like World::update_scheduler until the hit-stop ends, returns how many ends were due
+    fn run_out_hit_stop(time_scale: &mut TimeScale, scheduler: &mut Scheduler) -> usize {
+        let mut ends = 0;
+        for _ in 0..60 {
+            for action in scheduler.update(PHYSICS_FRAME_TIME) {
+                if matches!(action, ScheduledAction::EndHitStop) {
+                    time_scale.end_hit_stop();
+                    ends += 1;
+                }
+            }
+        }
+        ends
+    }
+
+    #[test]
+    fn hit_stop_restores_the_exact_previous_time_scale() {
+        let mut time_scale = TimeScale { scale: 0.7, ..TimeScale::new() };
+        let mut scheduler = Scheduler::new();
+        time_scale.hit_stop(&mut scheduler);
+        assert_eq!(time_scale.scale, HIT_STOP_TIME_SCALE);
+        assert_eq!(run_out_hit_stop(&mut time_scale, &mut scheduler), 1);
+        assert_eq!(time_scale.scale, 0.7);
+        assert_eq!(time_scale.hit_stop_previous, None);
+    }
+
+    #[test]
+    fn two_hit_stops_in_the_same_frame_apply_once() {
+        let mut time_scale = TimeScale::new();
+        let mut scheduler = Scheduler::new();
+        time_scale.hit_stop(&mut scheduler);
+        time_scale.hit_stop(&mut scheduler);
+        assert_eq!(time_scale.hit_stop_previous, Some(1.0));
+        assert_eq!(run_out_hit_stop(&mut time_scale, &mut scheduler), 1);
+        assert_eq!(time_scale.scale, 1.0);
+    }
+
+    #[test]
+    fn ending_without_a_hit_stop_keeps_the_time_scale() {
+        let mut time_scale = TimeScale { scale: 0.5, ..TimeScale::new() };
+        time_scale.end_hit_stop();
+        assert_eq!(time_scale.scale, 0.5);
+    }
+
+    /// the handles of the enemies in view, in the order they are drawn
+    fn draw_order(simulation: &Simulation, angle: f32) -> Vec<u16> {
+        let level = &simulation.level;
+        RenderPlayerPOV::seen_enemies(simulation.player.pos, angle, PLAYER_FOV, &level.world_layout, &level.enemies)
+            .iter()
+            .map(|seen| seen.enemy_handle.0)
+            .collect()
+    }
+
+    #[test]
+    fn farther_enemy_is_drawn_first() {
+        // looking down the column, the layout is scanned nearest enemy first
+        let simulation = Simulation::new("020\n000\n030\n000\n030");
+        assert_eq!(enemy_tiles(&simulation.level.world_layout, EnemyHandle(0)), vec![(1, 2)]);
+        assert_eq!(draw_order(&simulation, PI / 2.0), vec![1, 0]);
+        // looking up the column of a mirrored map, the scan order already is the draw order
+        let simulation = Simulation::new("030\n000\n030\n000\n020");
+        assert_eq!(draw_order(&simulation, -PI / 2.0), vec![0, 1]);
+    }
+
+    #[test]
+    fn enemies_at_the_same_depth_keep_their_order() {
+        let simulation = Simulation::new("02000\n00000\n30300");
+        for _ in 0..3 {
+            assert_eq!(draw_order(&simulation, PI / 2.0), vec![0, 1]);
+        }
+    }
+
+    #[test]
+    fn enemy_on_two_tiles_is_drawn_once() {
+        let mut simulation = Simulation::new("020\n000\n030\n000");
+        // halfway onto the next tile, like MovementSystem leaves a walking enemy
+        simulation.level.world_layout[3][1] = EntityType::Enemy(EnemyHandle(0));
+        assert_eq!(draw_order(&simulation, PI / 2.0), vec![0]);
+    }
+
+    /// runs the projectiles of the simulation for a second, the hits on the player, the blasts that went off
+    /// and the near misses
+    fn fly_projectiles(simulation: &mut Simulation) -> (Vec<WorldEventHandleBased>, Vec<Explosion>, Vec<NearMiss>) {
+        let (mut hits, mut explosions, mut near_misses) = (Vec::new(), Vec::new(), Vec::new());
+        for _ in 0..60 {
+            let level = &simulation.level;
+            let (frame_hits, frame_explosions, frame_near_misses) = ProjectileSystem::update(
+                &mut simulation.projectiles,
+                simulation.player.pos,
+                &level.world_layout,
+                &level.doors,
+                &level.enemies
+            );
+            hits.extend(frame_hits);
+            explosions.extend(frame_explosions);
+            near_misses.extend(frame_near_misses);
+        }
+        (hits, explosions, near_misses)
+    }
+
+    #[test]
+    fn fast_rocket_fired_point_blank_at_a_wall_blows_up_in_front_of_it() {
+        for speed in [30.0, 150.0] {
+            let mut simulation = Simulation::new("0000000000\n2000100000\n0000000000");
+            let owner = ProjectileOwner::Player { damage_type: DamageType::Explosive, blast_radius: 1.0 };
+            simulation.projectiles.add(Vec2::new(3.6, 1.5), Vec2::new(speed, 0.0), 3, owner);
+            let (_, explosions, _) = fly_projectiles(&mut simulation);
+            assert_eq!(explosions.len(), 1, "at {} tiles per second", speed);
+            assert!(explosions[0].position.x <= 4.0, "blew up at {} behind the wall", explosions[0].position);
+            assert!(simulation.projectiles.positions.is_empty());
+        }
+    }
+
+    #[test]
+    fn fast_fireball_does_not_pass_through_a_wall_to_the_player() {
+        let mut simulation = Simulation::new("0000000000\n0000100200\n0000000000");
+        simulation.projectiles.add(Vec2::new(3.5, 1.5), Vec2::new(150.0, 0.0), 1, ProjectileOwner::Enemy);
+        let (hits, _, _) = fly_projectiles(&mut simulation);
+        assert!(hits.is_empty());
+        assert!(simulation.projectiles.positions.is_empty());
+    }
+
+    /// charges the enemy of the simulation along velocity for a second, asserting it never ends a frame in a wall
+    fn charge(simulation: &mut Simulation, velocity: Vec2) {
+        let level = &mut simulation.level;
+        level.enemies.velocities[0] = velocity;
+        for frame in 0..60 {
+            MovementSystem::update_enemies(
+                &mut level.enemies,
+                simulation.player.pos,
+                &level.walls,
+                &level.doors,
+                &mut level.world_layout,
+                &simulation.floor_types,
+                Duration::from_secs_f32((frame as f32) * PHYSICS_FRAME_TIME)
+            );
+            let position = level.enemies.positions[0];
+            for wall in &level.walls {
+                let overlap = (Vec2::ONE - (position - *wall).abs()).min_element();
+                assert!(overlap <= 0.001, "the enemy at {} ended frame {} inside a wall", position, frame);
+            }
+        }
+    }
+
+    #[test]
+    fn boss_charging_through_a_doorway_never_ends_inside_a_wall() {
+        for x in [3.0, 3.2, 2.7] {
+            let mut simulation = Simulation::new("0003000\n0000000\n1110111\n0000000\n0000000\n2000000");
+            simulation.level.enemies.positions[0].x = x;
+            charge(&mut simulation, Vec2::new(0.0, 40.0));
+            if x == 3.0 {
+                assert!(simulation.level.enemies.positions[0].y > 2.0, "the lined up charge went through");
+            }
+        }
+    }
+
+    #[test]
+    fn fast_enemy_stops_at_a_closed_door() {
+        let mut simulation = Simulation::new("0003000\n0000000\n1114111\n0000000\n0000000\n2000000");
+        charge(&mut simulation, Vec2::new(0.0, 40.0));
+        assert!(simulation.level.enemies.positions[0].y <= 1.0 + 0.001);
+    }
+
+    /// how far the center of each enemy moves from the blast at `center` over the life of its field
+    fn scatter_distances(simulation: &mut Simulation, center: Vec2) -> Vec<f32> {
+        let level = &mut simulation.level;
+        let enemy_center = |enemies: &Enemies, index: usize| enemies.positions[index] + enemies.sizes[index] * 0.5;
+        let mut fields = vec![RepulsionField { center, age: 0.0 }];
+        level.enemies.velocities.fill(Vec2::ZERO);
+        let before: Vec<f32> = (0..level.enemies.positions.len())
+            .map(|index| enemy_center(&level.enemies, index).distance(center))
+            .collect();
+        for frame in 0..(REPULSION_DURATION / PHYSICS_FRAME_TIME).ceil() as u32 + 10 {
+            RepulsionSystem::update(&mut fields, &mut level.enemies);
+            MovementSystem::update_enemies(
+                &mut level.enemies,
+                simulation.player.pos,
+                &level.walls,
+                &level.doors,
+                &mut level.world_layout,
+                &simulation.floor_types,
+                Duration::from_secs_f32((frame as f32) * PHYSICS_FRAME_TIME)
+            );
+        }
+        assert!(fields.is_empty(), "the field outlived REPULSION_DURATION");
+        before
+            .iter()
+            .enumerate()
+            .map(|(index, before)| enemy_center(&level.enemies, index).distance(center) - before)
+            .collect()
+    }
+
+    #[test]
+    fn enemy_next_to_a_blast_scatters_and_one_far_away_stays() {
+        let mut simulation = Simulation::new("000000000000000\n000300000030000\n200000000000000");
+        // one tile right of the first skeleton, six tiles left of the second
+        let center = Vec2::new(4.5, 1.5);
+        let moved = scatter_distances(&mut simulation, center);
+        assert!(moved[0] > 0.5, "the close enemy only moved {} tiles away", moved[0]);
+        assert_eq!(moved[1], 0.0);
+    }
+
+    /// an archer's arrow flying at the player facing right along y from the right edge of an open room
+    fn arrow_at_the_player(y: f32) -> Simulation {
+        let mut simulation = Simulation::new("000000000\n000000000\n002000000\n000000000\n000000000");
+        simulation.player.angle = 0.0;
+        simulation.projectiles.add(Vec2::new(8.5, y), Vec2::new(-8.0, 0.0), 1, ProjectileOwner::Enemy);
+        simulation
+    }
+
+    #[test]
+    fn arrow_passing_just_left_of_the_player_whizzes_once_on_the_left() {
+        // 0.4 tiles between the arrow and the player, who faces +x so the left is up
+        let mut simulation = arrow_at_the_player(2.5 - (0.5 + PROJECTILE_RADIUS + 0.4));
+        let (hits, _, near_misses) = fly_projectiles(&mut simulation);
+        assert!(hits.is_empty());
+        assert_eq!(near_misses.len(), 1);
+        assert_eq!(near_misses[0].whizz(simulation.player.pos, simulation.player.angle), Sounds::WhizzLeft);
+    }
+
+    #[test]
+    fn arrow_hitting_the_player_does_not_whizz() {
+        let mut simulation = arrow_at_the_player(2.5);
+        let (hits, _, near_misses) = fly_projectiles(&mut simulation);
+        assert_eq!(hits.len(), 1);
+        assert!(near_misses.is_empty());
+    }
+
+    #[test]
+    fn arrow_far_from_the_player_does_not_whizz() {
+        let mut simulation = arrow_at_the_player(0.5);
+        let (hits, _, near_misses) = fly_projectiles(&mut simulation);
+        assert!(hits.is_empty() && near_misses.is_empty());
+    }
+
+    #[test]
+    fn weapon_wheel_slots_go_clockwise_from_the_top() {
+        let slots: Vec<usize> = [Vec2::new(0.0, -1.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0), Vec2::new(-1.0, 0.0)]
+            .into_iter()
+            .map(|direction| WeaponWheel::slot_at(direction, 4))
+            .collect();
+        assert_eq!(slots, vec![0, 1, 2, 3]);
+        for index in 0..4 {
+            let angle = WeaponWheel::slot_angle(index, 4);
+            assert_eq!(WeaponWheel::slot_at(Vec2::new(angle.cos(), angle.sin()), 4), index);
+        }
+    }
+
+    #[test]
+    fn weapon_wheel_skips_spent_weapons() {
+        let mut weapons = starting_weapons(&ContentRegistry::base()).weapons;
+        assert_eq!(weapons.len(), 4);
+        let spent = &mut weapons[1];
+        spent.current_ammo = 0;
+        spent.reserve_ammo = 0;
+        assert!(spent.is_spent());
+        let mut wheel = WeaponWheel::new(0);
+        wheel.step(1, &weapons);
+        assert_eq!(wheel.highlighted, 2);
+        wheel.step(-1, &weapons);
+        assert_eq!(wheel.highlighted, 0);
+        // a nudge inside the deadzone picks nothing, pointing at the spent weapon keeps the highlight
+        wheel.point(Vec2::new(WEAPON_WHEEL_DEADZONE * 0.5, 0.0), &weapons);
+        assert_eq!(wheel.highlighted, 0);
+        wheel.point(Vec2::new(WEAPON_WHEEL_DEADZONE * 2.0, 0.0), &weapons);
+        assert_eq!(wheel.highlighted, 0);
+        wheel.point(Vec2::new(0.0, WEAPON_WHEEL_DEADZONE * 10.0), &weapons);
+        assert_eq!(wheel.highlighted, 2);
+    }
+
+    #[test]
+    fn weapon_picked_on_the_wheel_fires_only_once_it_is_raised() {
+        let mut weapons = starting_weapons(&ContentRegistry::base());
+        let mut wheel = WeaponWheel::new(weapons.active);
+        wheel.step(1, &weapons.weapons);
+        weapons.request_switch(wheel.highlighted);
+        let mut frames = 1;
+        while !weapon_frame(&mut weapons, true, None) {
+            frames += 1;
+            assert!(frames < 120);
+        }
+        assert_eq!(weapons.active, wheel.highlighted);
+        let switch_frames = ((WEAPON_LOWER_TIME + WEAPON_RAISE_TIME) / PHYSICS_FRAME_TIME).round() as i32;
+        // every phase can round up by a frame
+        assert!(frames >= switch_frames && frames <= switch_frames + 2, "fired after {} frames", frames);
+    }
+
+    #[test]
+    fn weapon_wheel_slow_motion_outlasts_a_hit_stop() {
+        let mut time_scale = TimeScale::new();
+        let mut scheduler = Scheduler::new();
+        time_scale.slow_motion = Some(WEAPON_WHEEL_TIME_SCALE);
+        time_scale.hit_stop(&mut scheduler);
+        assert_eq!(time_scale.current(), HIT_STOP_TIME_SCALE * WEAPON_WHEEL_TIME_SCALE);
+        run_out_hit_stop(&mut time_scale, &mut scheduler);
+        assert_eq!(time_scale.current(), WEAPON_WHEEL_TIME_SCALE);
+        time_scale.slow_motion = None;
+        assert_eq!(time_scale.current(), 1.0);
+    }
+
+    /// the labels of the pickups for a player at (1, 1) of the layout looking along +x
+    fn pickup_labels(rows: &str, pickups: &[(Vec2, LootItem)]) -> Vec<PickupData> {
+        let mut simulation = Simulation::new(rows);
+        simulation.player.angle = 0.0;
+        for &(position, item) in pickups {
+            simulation.level.pickups.add(position, item);
+        }
+        ProximityBasedInteractionSystem::get_pickup_interactions(
+            simulation.player.pos,
+            simulation.player.angle,
+            &simulation.level.pickups,
+            &simulation.level.doors,
+            &simulation.level.world_layout,
+            PICKUP_LABEL_DISTANCE
+        )
+            .into_iter()
+            .map(|event| match event.interaction_type {
+                InteractionType::InspectPickup(pickup) => pickup,
+                other => unreachable!("only pickups are labeled, found {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ammo_pickup_ahead_is_labeled_with_its_amount() {
+        let ammo = LootItem::AmmoPickup(WeaponType::Pistol, 8);
+        let labels = pickup_labels("00000000\n02000000\n00000000", &[(Vec2::new(3.0, 1.0), ammo)]);
+        assert_eq!(labels.len(), 1);
+        assert_eq!((labels[0].handle, labels[0].amount), (PickupHandle(0), 8));
+        assert_eq!(labels[0].weapon.ammo_name(), "pistol rounds");
+    }
+
+    #[test]
+    fn pickups_behind_far_away_hidden_or_not_ammo_are_not_labeled() {
+        let ammo = LootItem::AmmoPickup(WeaponType::Pistol, 8);
+        let rows = "0000000000\n0200100000\n0000000000";
+        let behind = (Vec2::new(0.0, 1.0), ammo);
+        let far_away = (Vec2::new(7.0, 0.0), ammo);
+        let behind_the_wall = (Vec2::new(5.0, 1.0), ammo);
+        let medkit = (Vec2::new(2.0, 1.0), LootItem::HealthPack(1));
+        assert!(pickup_labels(rows, &[behind, far_away, behind_the_wall, medkit]).is_empty());
+    }
+
+    #[test]
+    fn weapon_sounds_resolve_from_their_definitions() {
+        let content = ContentRegistry::base();
+        let weapons = starting_weapons(&content);
+        let sounds = |id: &str| {
+            let weapon = weapons.weapons.iter().find(|weapon| weapon.id == id).expect("base weapon");
+            (weapon.sounds.fire, weapon.sounds.reload, weapon.sounds.empty)
+        };
+        assert_eq!(sounds("pistol"), (Sounds::PistolShoot, Sounds::Reload, Sounds::DryFire));
+        assert_eq!(sounds("shotgun"), (Sounds::ShotgunShoot, Sounds::ShotgunReload, Sounds::DryFire));
+    }
+
+    #[test]
+    fn every_ammo_kind_goes_into_a_base_weapon_and_enemies_drop_it() {
+        let content = ContentRegistry::base();
+        for weapon_type in [WeaponType::Pistol, WeaponType::Shotgun, WeaponType::Chaingun, WeaponType::RocketLauncher] {
+            assert!(content.weapons.contains_key(weapon_type.weapon_id()), "no weapon takes {:?}", weapon_type);
         }
+        let skeleton_loot: Vec<LootItem> = content.enemies["skeleton"].loot.iter().map(|entry| entry.item).collect();
+        assert!(skeleton_loot.contains(&LootItem::AmmoPickup(WeaponType::Shotgun, 2)));
     }
 
-    fn move_player(&mut self, delta: Vec2) {
-        let old_pos = self.player.pos;
+    /// the frost skeleton of the sample mod
+    fn frost_resistances() -> HashMap<DamageType, f32> {
+        HashMap::from([(DamageType::Ballistic, 0.5), (DamageType::Explosive, 2.0)])
+    }
 
-        self.player.pos += delta;
+    fn base_weapon(id: &str) -> Weapon {
+        let mut weapons = starting_weapons(&ContentRegistry::base()).weapons;
+        let index = weapons.iter().position(|weapon| weapon.id == id).expect("base weapon");
+        weapons.swap_remove(index)
+    }
 
-        let old_tile_x = old_pos.x.floor() as usize;
-        let old_tile_y = old_pos.y.floor() as usize;
-        let new_tile_x = self.player.pos.x.floor() as usize;
-        let new_tile_y = self.player.pos.y.floor() as usize;
+    #[test]
+    fn hitscan_damage_is_halved_per_penetrated_enemy_before_the_resistance() {
+        let pistol = base_weapon("pistol");
+        let hit = |depth| WorldEventHandleBased::player_hit_enemy(EnemyHandle(0), depth);
+        let first = DamageSystem::of_hit(&hit(0), &pistol, &frost_resistances());
+        assert_eq!((first.amount, first.multiplier), (0.5, 0.5));
+        let behind = DamageSystem::of_hit(&hit(1), &pistol, &frost_resistances());
+        assert_eq!((behind.amount, behind.multiplier), (0.25, 0.5));
+    }
 
-        if old_tile_x != new_tile_x || old_tile_y != new_tile_y {
-            if self.world_layout[old_tile_y][old_tile_x] == EntityType::Player {
-                self.world_layout[old_tile_y][old_tile_x] = EntityType::None;
-            }
-            self.world_layout[new_tile_y][new_tile_x] = EntityType::Player;
-        }
+    #[test]
+    fn projectile_damage_ignores_the_held_weapon_and_takes_its_own_type() {
+        // the pistol is held, the rocket was fired before the switch
+        let hit = WorldEventHandleBased::projectile_hit_enemy(EnemyHandle(0), 3.0, DamageType::Explosive);
+        let damage = DamageSystem::of_hit(&hit, &base_weapon("pistol"), &frost_resistances());
+        assert_eq!((damage.amount, damage.multiplier), (6.0, 2.0));
     }
-    fn handle_world_event_handle_based(&mut self, event: WorldEventHandleBased) {
-        match event.event_type {
-            WorldEventType::EnemyHitPlayer => {
-                let enemy_pos = self.enemies.positions[event.other_involved as usize];
-
-                self.move_player(self.enemies.velocities[event.other_involved as usize] * 0.5); // move player away
-                self.enemies.velocities[event.other_involved as usize] = (
-                    ( self.player.pos - enemy_pos) * -1.0 // make him move back for one frame
-                 ).normalize(); // make sure enemy doesnt keep his insane speed,
- 
-                if self.player.health == 1 {
-                    self.game_state = GameState::GameOver;
-                }
-                self.player.health -= 1;
-                self.postprocessing = VisualEffect::CameraShake(CameraShake::new(0.4, 20.0));
-            }
-            WorldEventType::PlayerHitEnemy => {
-                let health = self.enemies.healths
-                    .get_mut(event.other_involved as usize)
-                    .expect("Invalid handle in world layout");
-                let e_animation_state =
-                    &mut self.enemies.animation_states[event.other_involved as usize];
-                e_animation_state.add_effect(AnimationState::default_blood_particles(), None);
-                if *health == 0 {
-                    // avoid rescheduling animation callback
-                    return;
-                }
-                if *health <= self.player.weapon.damage {
-                    PlayEnemyAnimation::play_death(
-                        EnemyHandle(event.other_involved),
-                        &mut self.enemies.velocities,
-                        &mut self.enemies.animation_states,
-                        &mut self.enemies.alives
-                    );
-                    return;
-                }
 
-                *health -= self.player.weapon.damage;
-            }
-        }
+    #[test]
+    fn unlisted_damage_types_deal_full_damage_and_negative_multipliers_deal_none() {
+        let melee = DamageSystem::resolve(2.0, DamageType::Melee, &frost_resistances());
+        assert_eq!((melee.amount, melee.multiplier), (2.0, 1.0));
+        let immune = DamageSystem::resolve(2.0, DamageType::Melee, &HashMap::from([(DamageType::Melee, -1.0)]));
+        assert_eq!((immune.amount, immune.multiplier), (0.0, 0.0));
     }
 
-    fn handle_input(&mut self) {
-        if is_key_down(KeyCode::W) {
-            self.player.vel = Vec2::new(self.player.angle.cos(), self.player.angle.sin()) * 2.0;
-        } else if is_key_down(KeyCode::S) {
-            self.player.vel = Vec2::new(-self.player.angle.cos(), -self.player.angle.sin()) * 2.0;
-        } else {
-            self.player.vel = Vec2::new(0.0, 0.0);
-        }
-        if is_key_down(KeyCode::A) {
-            self.player.angle -= 0.9 * get_frame_time();
-            self.player.angle = self.player.angle.rem_euclid(2.0 * PI);
-        }
-        if is_key_down(KeyCode::D) {
-            self.player.angle += 0.9 * get_frame_time();
-            self.player.angle = self.player.angle.rem_euclid(2.0 * PI);
-        }
-        if is_key_pressed(KeyCode::Space) {
-            let shoot_event = self.player.shoot(self.world_layout, &self.enemies);
-            if shoot_event.still_reloading {
-                play_sound(&self.reload_sound, PlaySoundParams {
-                    volume: 0.4,
-                    looped: false,
-                });
-            } else {
-                play_sound(&self.shoot_sound, PlaySoundParams {
-                    volume: 0.4,
-                    looped: false,
-                });
-                self.player.animation_state.add_effect(
-                    AnimationState::default_explosion(),
-                    None
-                );
-                self.postprocessing = VisualEffect::CameraShake(CameraShake::new(0.2, 10.0));
-            }
-            if let Some(event) = shoot_event.world_event {
-                self.handle_world_event_handle_based(event);
+    #[test]
+    fn explosive_splash_falls_off_before_the_resistance_is_applied() {
+        let simulation = Simulation::new("00000\n00300\n20000");
+        let level = &simulation.level;
+        let enemy_center = level.enemies.positions[0] + level.enemies.sizes[0] * 0.5;
+        // halfway to the edge of the blast, so half of the rocket's 3 damage
+        let hits = DamageSystem::blast(
+            enemy_center + Vec2::new(1.0, 0.0),
+            2.0,
+            3,
+            DamageType::Explosive,
+            &level.enemies,
+            &level.doors,
+            &level.world_layout
+        );
+        assert_eq!(hits.len(), 1);
+        let splash = DamageSystem::of_hit(&hits[0], &base_weapon("pistol"), &frost_resistances());
+        assert_eq!((splash.amount, splash.multiplier), (3.0, 2.0));
+        let unresisted = DamageSystem::of_hit(&hits[0], &base_weapon("pistol"), &HashMap::new());
+        assert_eq!(unresisted.amount, 1.5);
+    }
+
+    const SAVE_MAP: &str = "1111111111\n1030303031\n1000000001\n1114111511\n1060080061\n1200000001\n1111111111";
+
+    /// what a save keeps of a level, keyed by SpawnId because handles don't survive kills, spawns and a reload
+    struct SavedLevel {
+        opened_doors: Vec<SpawnId>,
+        enemy_healths: Vec<(SpawnId, f32)>, // the living ones, the rest of the map's enemies are dead
+        pickups: Vec<SpawnId>, // the ones still lying around
+        read_notes: Vec<(usize, usize)>, // by wall tile, notes are only placed by the map file
+    }
+    impl SavedLevel {
+        fn take(level: &Level, notes: &[Note], notes_read: &[bool]) -> Self {
+            SavedLevel {
+                read_notes: notes
+                    .iter()
+                    .zip(notes_read)
+                    .filter(|(_, &read)| read)
+                    .map(|(note, _)| (note.x, note.y))
+                    .collect(),
+                opened_doors: (0..level.doors.positions.len())
+                    .filter(|&door| level.doors.opened[door])
+                    .map(|door| level.doors.spawn_id(DoorHandle(door as u16)))
+                    .collect(),
+                enemy_healths: (0..level.enemies.positions.len())
+                    .map(|enemy| (level.enemies.spawn_id(EnemyHandle(enemy as u16)), level.enemies.healths[enemy]))
+                    .collect(),
+                pickups: level.pickups.spawn_ids.ids.clone(),
             }
         }
-        if is_key_pressed(KeyCode::E) {
-            for interactable in &self.player_interactables {
-                match interactable.interaction_type {
-                    InteractionType::OpenDoor(door_handle) => {
-                        self.doors.open_door(door_handle);
-                    }
-                    InteractionType::CloseDoor(door_handle) => {
-                        self.doors.close_door(door_handle);
-                    }
-                }
+        /// World::notes_read for the notes of the freshly parsed map
+        fn notes_read(&self, notes: &[Note]) -> Vec<bool> {
+            notes.iter().map(|note| self.read_notes.contains(&(note.x, note.y))).collect()
+        }
+        /// onto the level freshly parsed from the saved map
+        fn load(&self, simulation: &mut Simulation) {
+            let level = &mut simulation.level;
+            for &door in &self.opened_doors {
+                level.doors.open_door(level.doors.handle_of(door).expect("saved door is on the map"));
+            }
+            let dead: Vec<u16> = (0..level.enemies.positions.len() as u16)
+                .filter(|&enemy| {
+                    let spawn_id = level.enemies.spawn_id(EnemyHandle(enemy));
+                    !self.enemy_healths.iter().any(|&(saved, _)| saved == spawn_id)
+                })
+                .collect();
+            CallbackHandler::handle_animation_callbacks(
+                kill_callbacks(&dead),
+                &mut level.world_layout,
+                &mut level.enemies,
+                &mut simulation.corpses,
+                &simulation.content,
+                &mut simulation.rng
+            );
+            for &(enemy, health) in &self.enemy_healths {
+                let handle = level.enemies.handle_of(enemy).expect("saved enemy is on the map");
+                level.enemies.healths[handle.0 as usize] = health;
+            }
+            let taken: Vec<SpawnId> = level.pickups.spawn_ids.ids
+                .iter()
+                .filter(|pickup| !self.pickups.contains(pickup))
+                .copied()
+                .collect();
+            for pickup in taken {
+                let handle = level.pickups.spawn_ids.index_of(pickup).expect("taken pickup is on the map");
+                level.pickups.take(PickupHandle(handle), &mut level.world_layout);
             }
         }
     }
 
-    fn update(&mut self) {
-        assert!(self.enemies.positions.len() < 65536);
-        assert!(self.world_layout.len() < 65536 && self.world_layout[0].len() < 65536);
-        assert!(self.walls.len() < 65536);
-        WeaponSystem::update_reload(&mut self.player.weapon);
-        MovementSystem::update_player(
-            &mut self.player,
-            &self.walls,
-            &self.doors,
-            &mut self.world_layout
-        ); // TODO currently chekcing for all walls, which is not necessary, use tilemap
-        MovementSystem::update_enemies(
-            // TODO currently chekcing for all walls, which is not necessary, use tilemap
-            &mut self.enemies,
-            &self.walls,
-            &self.doors,
-            &mut self.world_layout,
-            Duration::from_secs_f32(get_time() as f32)
+    #[test]
+    fn saved_doors_enemies_and_pickups_resolve_after_handles_were_shuffled() {
+        let mut simulation = Simulation::new(SAVE_MAP);
+        let level = &mut simulation.level;
+        level.doors.open_door(DoorHandle(1));
+        level.enemies.healths[2] = 1.5;
+        // the first enemy and pickup die and are taken, the last of each moves into their handles
+        CallbackHandler::handle_animation_callbacks(
+            kill_callbacks(&[0]),
+            &mut level.world_layout,
+            &mut level.enemies,
+            &mut simulation.corpses,
+            &simulation.content,
+            &mut simulation.rng
         );
-        let event = MovingEntityCollisionSystem::check_player_enemy_collisions(
-            &self.player.pos,
-            &self.world_layout,
-            &self.enemies.positions,
-            &self.enemies.sizes,
-            &self.enemies.alives
+        level.pickups.take(PickupHandle(0), &mut level.world_layout);
+        let saved = SavedLevel::take(level, &[], &[]);
+        let (open_door, wounded) = (level.doors.spawn_id(DoorHandle(1)), level.enemies.spawn_id(EnemyHandle(2)));
+        let pickup_positions: Vec<(SpawnId, Vec2)> = level.pickups.spawn_ids.ids
+            .iter()
+            .copied()
+            .zip(level.pickups.positions.iter().copied())
+            .collect();
+
+        // played on after the save, none of it is in there
+        CallbackHandler::handle_animation_callbacks(
+            kill_callbacks(&[1]),
+            &mut level.world_layout,
+            &mut level.enemies,
+            &mut simulation.corpses,
+            &simulation.content,
+            &mut simulation.rng
         );
-        if let Some(event) = event {
-            self.handle_world_event_handle_based(event);
+        let skeleton = &simulation.content.enemies["skeleton"];
+        let spawn = Vec2::new(5.0, 2.0);
+        level.enemies
+            .new_enemy(spawn, Vec2::ZERO, Vec2::new(1.0, 1.0), AnimationState::default_skeleton(), skeleton)
+            .expect("room for one more");
+
+        let mut loaded = Simulation::new(SAVE_MAP);
+        saved.load(&mut loaded);
+        let level = &loaded.level;
+        let opened: Vec<SpawnId> = (0..level.doors.positions.len())
+            .filter(|&door| level.doors.opened[door])
+            .map(|door| level.doors.spawn_id(DoorHandle(door as u16)))
+            .collect();
+        assert_eq!(opened, vec![open_door]);
+        assert_eq!(level.enemies.positions.len(), 3);
+        assert_eq!(level.enemies.handle_of(SpawnId { x: 2, y: 1, ordinal: 0 }), None);
+        let wounded = level.enemies.handle_of(wounded).expect("the wounded enemy was alive");
+        assert_eq!(level.enemies.healths[wounded.0 as usize], 1.5);
+        assert_eq!(level.enemies.positions[wounded.0 as usize], Vec2::new(6.0, 1.0));
+        for (enemy, &position) in level.enemies.positions.iter().enumerate() {
+            let tile = (position.x as usize, position.y as usize);
+            assert_eq!(enemy_tiles(&level.world_layout, EnemyHandle(enemy as u16)), vec![tile]);
         }
-        EnemyAggressionSystem::toggle_enemy_aggressive(
-            self.player.pos,
-            &self.enemies.positions,
-            &mut self.enemies.velocities,
-            &mut self.enemies.aggressive_states,
-            &self.enemies.alives
-        );
-        self.player_interactables.clear();
-        let opt_interactable = ProximityBasedInteractionSystem::get_possible_interactions(
-            &self.player.pos,
-            self.player.angle,
-            &self.world_layout,
-            &self.doors.positions,
-            &self.doors.opened,
-            2.0
-        );
-        if let Some(interactable) = opt_interactable {
-            self.player_interactables.push(interactable);
+        assert_eq!(level.pickups.spawn_ids.index_of(SpawnId { x: 2, y: 4, ordinal: 0 }), None);
+        for (pickup, position) in pickup_positions {
+            let handle = level.pickups.spawn_ids.index_of(pickup).expect("the pickup was still there");
+            assert_eq!(level.pickups.positions[handle as usize], position);
+            let tile = Tile::from_vec2(position);
+            assert_eq!(level.world_layout[tile.y as usize][tile.x as usize], EntityType::Pickup(PickupHandle(handle)));
         }
-        self.doors.update_animation(PHYSICS_FRAME_TIME);
-        // we can rewrite the rendering logic to use this, then put the callbacks into a queue and only update visible enemies animations
-        let mut all_animation_callback_events = Vec::new();
+    }
 
-        all_animation_callback_events.extend(
-            self.player.animation_state.update(PHYSICS_FRAME_TIME)
-        );
+    fn wounded_player(health: u16) -> Player {
+        let mut player = Player::new(Vec2::ZERO, starting_weapons(&ContentRegistry::base()));
+        player.health = health;
+        player
+    }
 
-        let animation_callback_events = UpdateEnemyAnimation::update(
-            self.player.pos,
-            &self.enemies.positions,
-            &self.enemies.aggressive_states,
-            &self.enemies.velocities,
-            &mut self.enemies.animation_states
-        );
-        all_animation_callback_events.extend(animation_callback_events);
-        CallbackHandler::handle_animation_callbacks(
-            all_animation_callback_events,
-            &mut self.world_layout,
-            &mut self.enemies
-        );
+    /// physics frames until the player's health goes up, None if it didn't within `seconds`
+    fn frames_until_healed(player: &mut Player, seconds: f32, in_combat: bool, enabled: bool) -> Option<u32> {
+        let health = player.health;
+        (1..=(seconds / PHYSICS_FRAME_TIME) as u32).find(|_| {
+            player.update_regeneration(in_combat, enabled);
+            player.health > health
+        })
     }
 
-    fn draw(&mut self) {
-        clear_background(LIGHTGRAY);
-        let  player_ray_origin = self.player.pos + Vec2::new(0.5, 0.5);
-        let mut bobbing_offset = 0.0;
-        if self.player.vel.length() > 0.0 {
-            bobbing_offset = (self.player.bobbing_time * self.player.bobbing_speed).sin() * self.player.bobbing_amount;
-        }
-        
-        let start_time: f64 = get_time();
-        let raycast_result = RaycastSystem::raycast(
-            player_ray_origin,
-            self.player.angle,
-            &self.doors,
-            &self.world_layout
-        );
-        let end_time = get_time();
-        let elapsed_time = end_time - start_time;
+    #[test]
+    fn regeneration_heals_a_point_per_interval_after_leaving_combat() {
+        let mut player = wounded_player(1);
+        let frames = frames_until_healed(&mut player, 60.0, false, true).expect("healed within a minute");
+        let seconds = (frames as f32) * PHYSICS_FRAME_TIME;
+        let expected = OUT_OF_COMBAT_TIME + HEALTH_REGEN_INTERVAL;
+        assert!((seconds - expected).abs() < 0.1, "healed after {}s instead of {}s", seconds, expected);
+        assert_eq!(player.health, 2);
+        // HEALTH_REGEN_MAX is reached, the last bar has to be found
+        assert_eq!(frames_until_healed(&mut player, 120.0, false, true), None);
+        assert!(!player.is_regenerating(true));
+    }
 
-        RenderPlayerPOV::render_floor(
-            &self.background_material,
-            self.player.angle,
-            player_ray_origin
-        );
-        let mut z_buffer = [f32::MAX; AMOUNT_OF_RAYS as usize];
-        RenderPlayerPOV::render_walls_and_doors(&raycast_result, &mut z_buffer);
+    #[test]
+    fn combat_restarts_the_regeneration_timers() {
+        let mut player = wounded_player(1);
+        let almost_healed = OUT_OF_COMBAT_TIME + HEALTH_REGEN_INTERVAL - 1.0;
+        assert_eq!(frames_until_healed(&mut player, almost_healed, false, true), None);
+        assert!(player.is_regenerating(true));
+        player.update_regeneration(true, true);
+        assert!(!player.is_regenerating(true));
+        assert_eq!((player.out_of_combat_time, player.regen_elapsed), (0.0, 0.0));
+        // the whole wait starts over
+        assert_eq!(frames_until_healed(&mut player, almost_healed, false, true), None);
+    }
 
-        let mut seen_enemies = Vec::new();
-        for row in 0..self.world_layout.len() {
-            for entity in self.world_layout[row] {
-                match entity {
-                    EntityType::Enemy(enemy_handle) => {
-                        if (enemy_handle.0 as usize) > self.enemies.positions.len() - 1 {
-                            continue;
-                        }
-                        let enemy_pos = self.enemies.positions[enemy_handle.0 as usize];
-                        let angle_to_enemy = (enemy_pos.y - self.player.pos.y).atan2(
-                            enemy_pos.x - self.player.pos.x
-                        );
-                        let normalized_angle_to_enemy =
-                            (angle_to_enemy + 2.0 * std::f32::consts::PI) %
-                            (2.0 * std::f32::consts::PI);
-                        let mut angle_diff = normalized_angle_to_enemy - self.player.angle;
-                        if angle_diff > std::f32::consts::PI {
-                            angle_diff -= 2.0 * std::f32::consts::PI;
-                        } else if angle_diff < -std::f32::consts::PI {
-                            angle_diff += 2.0 * std::f32::consts::PI;
-                        }
-                        if
-                            angle_diff.abs() <= HALF_PLAYER_FOV &&
-                            !seen_enemies.iter().any(|e: &SeenEnemy| e.enemy_handle == enemy_handle)
-                        {
-                            seen_enemies.push(SeenEnemy {
-                                enemy_handle: enemy_handle,
-                                relative_angle: angle_diff,
-                            });
-                        }
-                    }
-                    _ => {}
+    #[test]
+    fn regeneration_is_off_unless_enabled() {
+        let mut player = wounded_player(1);
+        assert_eq!(frames_until_healed(&mut player, 120.0, false, false), None);
+    }
+
+    #[test]
+    fn adrenaline_from_a_kill_at_one_health_speeds_up_for_its_duration() {
+        let mut player = wounded_player(1);
+        assert!(!player.grant_adrenaline(false));
+        assert!(player.grant_adrenaline(true));
+        assert_eq!(player.base_speed(), PLAYER_SPEED * (1.0 + ADRENALINE_SPEED_BONUS));
+        let frames = (1..).find(|_| {
+            player.update_adrenaline();
+            player.base_speed() == PLAYER_SPEED
+        });
+        let seconds = (frames.expect("adrenaline runs out") as f32) * PHYSICS_FRAME_TIME;
+        assert!((seconds - ADRENALINE_DURATION).abs() < 0.05, "ran out after {}s", seconds);
+        assert_eq!(player.adrenaline_remaining, 0.0);
+    }
+
+    #[test]
+    fn kills_above_one_health_grant_no_adrenaline() {
+        let mut player = wounded_player(2);
+        assert!(!player.grant_adrenaline(true));
+        assert_eq!(player.base_speed(), PLAYER_SPEED);
+    }
+
+    #[test]
+    fn player_running_into_a_still_enemy_stops_next_to_it_and_is_hurt_once_per_cooldown() {
+        let mut simulation = Simulation::new("00000000\n20003000\n00000000");
+        let level = &mut simulation.level;
+        level.enemies.velocities[0] = Vec2::ZERO;
+        let enemy_x = level.enemies.positions[0].x;
+        let player = &mut simulation.player;
+        player.angle = 0.0;
+        player.target_vel = Vec2::new(PLAYER_SPEED, 0.0);
+        // enough to survive every hit, so the count isn't cut short
+        player.health = 10;
+        // in the order of World::update, the contact hit of a still enemy has no knockback
+        let (mut hits, mut first_contact) = (0, None);
+        for frame in 0..(4.5 / PHYSICS_FRAME_TIME) as u32 {
+            player.update_hit_cooldown();
+            MovementSystem::update_player(
+                player,
+                &level.walls,
+                &level.doors,
+                &level.enemies,
+                &mut level.world_layout,
+                &simulation.floor_types
+            );
+            let contact = MovingEntityCollisionSystem::check_player_enemy_collisions(
+                &player.pos,
+                &level.world_layout,
+                &level.enemies.positions,
+                &level.enemies.sizes,
+                &level.enemies.alives
+            );
+            if contact.is_some() {
+                first_contact.get_or_insert(frame);
+                if player.can_be_hit() {
+                    player.take_hit(1);
+                    hits += 1;
                 }
             }
+            assert!(player.pos.x < enemy_x, "walked into the enemy at {}", player.pos);
         }
+        let first_contact = first_contact.expect("the player reached the enemy");
+        assert!(player.pos.x > enemy_x - 1.05, "stopped at {} instead of next to the enemy", player.pos);
+        // in contact every frame since, but only hurt at the start of each cooldown
+        let seconds_in_contact = (((4.5 / PHYSICS_FRAME_TIME) as u32 - first_contact) as f32) * PHYSICS_FRAME_TIME;
+        assert!(seconds_in_contact > 2.0 * PLAYER_HIT_COOLDOWN, "only {}s in contact", seconds_in_contact);
+        let expected = (seconds_in_contact / PLAYER_HIT_COOLDOWN).ceil() as u16;
+        assert_eq!(hits, expected);
+        assert_eq!(player.health, 10 - expected);
+    }
 
-        RenderPlayerPOV::render_enemies(
-            &self.enemy_default_material,
-            &z_buffer,
-            self.player.pos,
-            &seen_enemies,
-            &self.enemies.positions,
-            &self.enemies.animation_states,
-            &self.enemies.healths
-        );
+    const NOTE_ROOM: &str = "11111\n12001\n11111";
 
-        match &mut self.postprocessing {
-            VisualEffect::CameraShake(shake) => {
-                gl_use_material(&self.camera_shake_material);
-                let shake_offset = shake.update(get_frame_time());
-                self.camera_shake_material.set_uniform(
-                    "screen_size",
-                    Vec2::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32)
-                );
-                self.camera_shake_material.set_uniform("shake_offset", shake_offset);
-                if shake_offset == Vec2::ZERO {
-                    self.postprocessing = VisualEffect::None;
-                }
-            }
-            VisualEffect::None => {}
+    fn room_notes(lines: &str) -> Vec<Note> {
+        let layout = map::parse_layout(NOTE_ROOM).expect("valid layout");
+        map::parse_notes(lines, &layout).expect("valid notes")
+    }
+
+    #[test]
+    fn reading_an_aimed_note_pauses_until_the_panel_is_closed() {
+        let simulation = Simulation::new(NOTE_ROOM);
+        let notes = room_notes("note 4 1 first page | second page");
+        let level = &simulation.level;
+        let aimed = ProximityBasedInteractionSystem::get_aimed_interaction(
+            simulation.player.pos,
+            0.0,
+            &level.world_layout,
+            &level.doors,
+            &level.walls,
+            &notes,
+            3.0
+        ).expect("the note is aimed at");
+        let InteractionType::ReadNote(note) = aimed.interaction_type else {
+            unreachable!("aimed at {:?} instead of the note", aimed.interaction_type);
+        };
+        assert!(aimed.in_range);
+        let mut notes_read = vec![false];
+        let mut game_state = NoteReader::open(note, &mut notes_read);
+        assert_eq!(notes_read, vec![true]);
+        assert!(!game_state.simulates());
+        let mut timer = timer_at(0);
+        timer.tick(&game_state);
+        assert_eq!(timer.steps, 0);
+        // the pages stop at both ends
+        for (turn, expected_page) in [(1, 1), (1, 1), (-1, 0), (-1, 0)] {
+            NoteReader::input(&mut game_state, &notes, turn, false);
+            assert!(matches!(game_state, GameState::ReadingNote { note: 0, page } if page == expected_page));
         }
-        RenderPlayerPOV::render_weapon(&self.player, bobbing_offset);
-        RenderPlayerPOV::render_health(self.player.health);
-        RenderPlayerPOV::render_possible_interactions(
-            self.player.pos,
-            self.player.angle,
-            &self.player_interactables,
-            &self.doors
+        NoteReader::input(&mut game_state, &notes, 0, true);
+        assert!(game_state.simulates());
+        assert_eq!(notes_read, vec![true], "closing the panel keeps the note read");
+    }
+
+    #[test]
+    fn read_notes_are_saved_by_their_wall() {
+        let simulation = Simulation::new(NOTE_ROOM);
+        let notes = room_notes("note 4 1 east\nnote 0 1 west");
+        let saved = SavedLevel::take(&simulation.level, &notes, &[false, true]);
+        // an edited map lists the same notes the other way round
+        let reordered = room_notes("note 0 1 west\nnote 4 1 east\nnote 2 0 new");
+        assert_eq!(saved.notes_read(&reordered), vec![true, false, false]);
+    }
+
+    /// the enemies a pistol shot along +x hits and the ray that connected
+    fn pistol_shot(rows: &str) -> (Vec<u16>, Option<ShotRay>) {
+        let mut simulation = Simulation::new(rows);
+        let Simulation { level, player, projectiles, rng, .. } = &mut simulation;
+        player.angle = 0.0;
+        assert_eq!(player.weapon().id, "pistol");
+        let event = player.fire(&level.world_layout, &level.enemies, projectiles, rng);
+        let hits = event.world_events.iter().map(|hit| hit.other_involved).collect();
+        (hits, event.hit.map(|hit| hit.ray))
+    }
+
+    #[test]
+    fn near_enemy_in_the_center_takes_the_hit_from_a_far_one_on_the_left_ray() {
+        // the far enemy above the line of fire is only on the left ray, the near one is dead center
+        let (hits, ray) = pistol_shot("00000003000\n20030000000\n00000000000");
+        assert_eq!((hits, ray), (vec![1], Some(ShotRay::Center)));
+    }
+
+    #[test]
+    fn far_enemy_on_the_left_ray_alone_is_still_hit() {
+        let (hits, ray) = pistol_shot("00000003000\n20000000000\n00000000000");
+        assert_eq!((hits, ray), (vec![0], Some(ShotRay::Left)));
+    }
+
+    /// the area the view polygon fans out from the ray origin, facing +x
+    fn view_area(rows: &str) -> (f32, f32, usize) {
+        let simulation = Simulation::new(rows);
+        let origin = simulation.player.pos + Vec2::new(0.5, 0.5);
+        let level = &simulation.level;
+        let rays = RaycastSystem::raycast(origin, 0.0, PLAYER_FOV, &level.doors, &level.world_layout);
+        let fan_area = |points: &[Vec2]| -> f32 {
+            points
+                .windows(2)
+                .map(|edge| 0.5 * (edge[0] - origin).perp_dot(edge[1] - origin).abs())
+                .sum()
+        };
+        let raw: Vec<Vec2> = rays.iter().map(|result| result.intersection_pos).collect();
+        let polygon = RenderMap::view_polygon(&rays);
+        (fan_area(&polygon), fan_area(&raw), polygon.len())
+    }
+
+    #[test]
+    fn view_polygon_covers_an_open_room_but_stays_inside_a_corridor() {
+        let room = "11111111111\n10000000001\n10000000001\n10000000001\n10000000001\n10000200001\n\
+            10000000001\n10000000001\n10000000001\n10000000001\n11111111111";
+        let corridor = "11111111111\n11111111111\n11111111111\n11111111111\n11111111111\n10000200001\n\
+            11111111111\n11111111111\n11111111111\n11111111111\n11111111111";
+        let (room_area, room_raw, room_points) = view_area(room);
+        let (corridor_area, corridor_raw, corridor_points) = view_area(corridor);
+        // the cone reaches the wall 4.5 tiles ahead and spreads 4.5 tiles to each side
+        assert!((room_area - 20.25).abs() < 0.5, "room area {}", room_area);
+        // one tile wide and 4.5 tiles long at most
+        assert!(corridor_area > 0.0 && corridor_area <= 4.5, "corridor area {}", corridor_area);
+        assert!(room_area > 4.0 * corridor_area);
+        // decimation keeps the outline, only the points on straight walls go
+        assert!((room_area - room_raw).abs() < 0.1 && (corridor_area - corridor_raw).abs() < 0.1);
+        assert!(room_points < 10 && corridor_points < 10, "{} and {} points", room_points, corridor_points);
+        assert!(room_points < AMOUNT_OF_RAYS / 10);
+    }
+
+    #[test]
+    fn speed_follows_the_stick_tilt_linearly() {
+        let player = Simulation::new("000\n020\n000").player;
+        let forward = Vec2::new(1.0, 0.0);
+        let full = player.target_velocity(forward).length();
+        assert_eq!(full, PLAYER_SPEED);
+        for tilt in [0.1, 0.25, 0.5, 0.75] {
+            let speed = player.target_velocity(forward * tilt).length();
+            assert!((speed - full * tilt).abs() < 1e-5, "tilt {} moved at {}", tilt, speed);
+        }
+        // a diagonal tilt is as fast as a straight one of the same length
+        let diagonal = player.target_velocity(Vec2::new(0.3, 0.4)).length();
+        assert!((diagonal - full * 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn walk_toggle_survives_the_next_endless_level() {
+        let mut simulation = Simulation::new("000\n020\n000");
+        let player = &mut simulation.player;
+        player.walking = true;
+        let walking_speed = player.target_velocity(Vec2::X).length();
+        assert_eq!(walking_speed, PLAYER_SPEED * WALK_SPEED_FACTOR);
+        for depth in 1..=3 {
+            // the transition World::enter_next_level does, without the textures and sounds
+            let map_file = map::generate::endless_level(7, depth).expect("endless levels generate");
+            let mut level = Level::from_layout(&map_file.layout, &simulation.content).expect("a valid level");
+            level.place_player(player, true);
+            assert_eq!(player.pos, level.player_spawn);
+            assert!(player.walking, "walking was dropped on level {}", depth);
+            assert_eq!(player.target_velocity(Vec2::X).length(), walking_speed);
+        }
+    }
+
+    #[test]
+    fn flat_wall_is_as_tall_at_the_edges_of_the_view_as_in_the_center() {
+        // a wall 4.5 tiles ahead that spans the whole view, the edge rays hit it 45 degrees off
+        let mut rows = vec!["1111111111111"; 15];
+        for row in rows.iter_mut().take(14).skip(1) {
+            *row = "1000000000111";
+        }
+        rows[7] = "1000020000111";
+        let simulation = Simulation::new(&rows.join("\n"));
+        let origin = simulation.player.pos + Vec2::new(0.5, 0.5);
+        let level = &simulation.level;
+        let rays = RaycastSystem::raycast(origin, 0.0, PLAYER_FOV, &level.doors, &level.world_layout);
+        let center = &rays[AMOUNT_OF_RAYS / 2];
+        let edges = [&rays[0], &rays[AMOUNT_OF_RAYS - 1]];
+        assert!(rays.iter().all(|result| matches!(result.entity_type, EntityType::Wall(_))));
+        assert!((center.corrected_distance - 4.5).abs() < 1e-3, "center at {}", center.corrected_distance);
+        let center_height = RenderPlayerPOV::wall_height(center.corrected_distance);
+        assert!(center_height < SCREEN_HEIGHT as f32, "the wall is far enough not to be clamped");
+        for edge in edges {
+            // along the ray the edges are about 6.4 tiles away, they would be drawn a third shorter
+            assert!(edge.intersection_pos.distance(origin) > 6.0);
+            let edge_height = RenderPlayerPOV::wall_height(edge.corrected_distance);
+            assert!((edge_height - center_height).abs() < 0.01 * center_height, "{} vs {}", edge_height, center_height);
+        }
+    }
+
+    /// a room with a skeleton and, behind the door below, an elevator cabin the player stands in
+    const ELEVATOR_MAP: &str = "11111\n10201\n10301\n11411\n11011\n11111";
+    const CABIN: (usize, usize) = (2, 4);
+
+    fn in_the_cabin() -> (Simulation, DoorHandle) {
+        let mut simulation = Simulation::new(ELEVATOR_MAP);
+        let level = &mut simulation.level;
+        let door = ElevatorSystem::door(CABIN, &level.world_layout).expect("the cabin has a door");
+        level.doors.open_door(door);
+        simulation.player.pos = Vec2::new(CABIN.0 as f32, CABIN.1 as f32);
+        (simulation, door)
+    }
+
+    fn ride(simulation: &mut Simulation, riding: bool) -> ElevatorRide {
+        let Simulation { level, player, .. } = simulation;
+        ElevatorSystem::ride(riding, CABIN, player.pos, &level.world_layout, &mut level.doors, &level.enemies)
+    }
+
+    #[test]
+    fn interact_in_the_cabin_closes_the_door_and_again_mid_ride_does_nothing() {
+        let (mut simulation, door) = in_the_cabin();
+        assert_eq!(ride(&mut simulation, false), ElevatorRide::Departing);
+        assert!(!simulation.level.doors.opened[door.0 as usize]);
+        // the door is closed mid-ride, interacting again must not start a second ride or touch it
+        simulation.level.doors.update_animation(0.5);
+        let progress = simulation.level.doors.animation_progress[door.0 as usize];
+        assert_eq!(ride(&mut simulation, true), ElevatorRide::Riding);
+        assert!(!simulation.level.doors.opened[door.0 as usize]);
+        assert_eq!(simulation.level.doors.animation_progress[door.0 as usize], progress);
+    }
+
+    #[test]
+    fn interact_outside_the_cabin_is_left_to_the_other_interactions() {
+        let (mut simulation, door) = in_the_cabin();
+        simulation.player.pos = Vec2::new(2.0, 1.0);
+        assert_eq!(ride(&mut simulation, false), ElevatorRide::Outside);
+        assert!(simulation.level.doors.opened[door.0 as usize]);
+    }
+
+    #[test]
+    fn enemy_in_the_doorway_or_the_cabin_keeps_the_doors_open() {
+        for enemy_tile in [Vec2::new(2.0, 3.0), Vec2::new(2.0, 4.0)] {
+            let (mut simulation, door) = in_the_cabin();
+            simulation.level.enemies.positions[0] = enemy_tile;
+            assert_eq!(ride(&mut simulation, false), ElevatorRide::Blocked);
+            assert!(simulation.level.doors.opened[door.0 as usize], "the door closed on an enemy at {}", enemy_tile);
+            // a dead one is no reason to wait
+            simulation.level.enemies.alives[0] = false;
+            assert_eq!(ride(&mut simulation, false), ElevatorRide::Departing);
+        }
+    }
+
+    /// one frame of the blasts and projectiles like World::update runs them, the hits on the player and the enemies
+    fn step_transients(transients: &mut Transients, simulation: &mut Simulation) -> usize {
+        let level = &mut simulation.level;
+        RepulsionSystem::update(&mut transients.repulsion_fields, &mut level.enemies);
+        let (hits, explosions, _) = ProjectileSystem::update(
+            &mut transients.projectiles,
+            simulation.player.pos,
+            &level.world_layout,
+            &level.doors,
+            &level.enemies
         );
-        gl_use_default_material();
-        RenderMap::render_world_layout(&self.world_layout, &self.doors);
-        RenderMap::render_player_and_enemies_on_map(self.player.pos, &self.enemies);
-        RenderMap::render_rays(player_ray_origin, &raycast_result);
-
-        draw_text(&format!("Raycasting FPS: {}", 1.0 / elapsed_time), 10.0, 30.0, 20.0, RED);
-        draw_text("Controls:", 10.0, 50.0, 20.0, RED);
-        draw_text("W/A", 10.0, 70.0, 20.0, YELLOW);
-        draw_text(" to move", 35.0, 70.0, 20.0, WHITE);
-        draw_text("A/D", 10.0, 90.0, 20.0, YELLOW);
-        draw_text(" to rotate", 35.0, 90.0, 20.0, WHITE);
-        draw_text("Spacebar", 10.0, 110.0, 20.0, YELLOW);
-        draw_text(" to shoot", 80.0, 110.0, 20.0, WHITE);
-        draw_text("E", 10.0, 130.0, 20.0, YELLOW);
-        draw_text(" to interact", 20.0, 130.0, 20.0, WHITE);
+        transients.age_explosions();
+        for explosion in explosions {
+            transients.add_explosion(explosion);
+        }
+        hits.len()
     }
-}
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut elapsed_time = 0.0;
-    let mut world = World::default().await;
-    let bg_music = load_sound("sounds/music.wav").await.expect("Failed to load background music");
-    play_sound(&bg_music, PlaySoundParams {
-        looped: true,
-        volume: 0.3,
-    });
-    loop {
-        elapsed_time += get_frame_time();
-        match world.game_state {
-            GameState::GameGoing => {
-                world.handle_input();
-                if elapsed_time > PHYSICS_FRAME_TIME {
-                    world.update();
-                    elapsed_time = 0.0;
-                }
-                world.draw();
-            }
-            GameState::GameOver => {
-                draw_text(
-                    "You lost!",
-                    HALF_SCREEN_WIDTH - 50.0 * 8.0,
-                    HALF_SCREEN_HEIGHT - 50.0,
-                    50.0,
-                    RED
-                );
-                draw_text(
-                    "Press space to play again or ESC to exit",
-                    HALF_SCREEN_WIDTH - 50.0 * 8.0,
-                    HALF_SCREEN_HEIGHT + 50.0,
-                    50.0,
-                    WHITE
-                );
-                if is_key_down(KeyCode::Escape) {
-                    exit(0);
-                }
-                if is_key_down(KeyCode::Space) {
-                    world = World::default().await;
-                }
+
+    #[test]
+    fn loading_mid_explosion_leaves_no_ghost_effects() {
+        let mut simulation = Simulation::new(SAVE_MAP);
+        let saved = SavedLevel::take(&simulation.level, &[], &[]);
+        let mut transients = Transients::new();
+        let rocket = ProjectileOwner::Player { damage_type: DamageType::Explosive, blast_radius: 1.0 };
+        // one rocket about to hit the wall below the enemies, a slow one still on its way
+        transients.projectiles.add(Vec2::new(7.5, 2.5), Vec2::new(10.0, 0.0), 3, rocket);
+        transients.projectiles.add(Vec2::new(1.5, 2.5), Vec2::new(1.0, 0.0), 3, rocket);
+        // the step of a sequence of the level before, this one has none
+        transients.scheduler.schedule(1.0, ScheduledAction::SequenceStep { sequence: 0, step: 0 });
+        transients.scheduler.schedule(1.0, ScheduledAction::PlaySound { sound: Sounds::Explosion, volume: 1.0 });
+        let mut frames = 0;
+        while transients.explosions.is_empty() {
+            step_transients(&mut transients, &mut simulation);
+            frames += 1;
+            assert!(frames < 60, "the rocket never went off");
+        }
+        // one more frame, so the enemies next to the blast are scattering
+        step_transients(&mut transients, &mut simulation);
+        assert!(simulation.level.enemies.scatter_velocities.iter().any(|&scatter| scatter != Vec2::ZERO));
+        assert_eq!(transients.projectiles.positions.len(), 1);
+        let sequences = Sequences::new(Vec::new());
+        assert!(transients.has_stale_sequence_step(&sequences));
+
+        // what World::reinitialize_layout does with a loaded level
+        let mut loaded = Simulation::new(SAVE_MAP);
+        saved.load(&mut loaded);
+        transients.clear();
+        assert!(!transients.has_stale_sequence_step(&sequences));
+        for _ in 0..120 {
+            assert_eq!(step_transients(&mut transients, &mut loaded), 0);
+            assert!(transients.scheduler.update(PHYSICS_FRAME_TIME).is_empty());
+        }
+        assert!(transients.explosions.is_empty() && transients.repulsion_fields.is_empty());
+        assert!(transients.projectiles.positions.is_empty());
+        assert!(loaded.level.enemies.scatter_velocities.iter().all(|&scatter| scatter == Vec2::ZERO));
+    }
+
+    #[test]
+    fn sequence_steps_run_in_time_order_once() {
+        let layout = map::parse_layout("0200000\n0000000").expect("test map should parse");
+        let text = "sequence ambush enter 3 0 1 2\n\
+            step ambush 1 title Second\n\
+            step ambush 0.5 sound Sting\n\
+            step ambush 1 spawn skeleton 5 1\n\
+            step ambush 0 boss_bar First";
+        let mut sequences = Sequences::new(map::parse_sequences(text, &layout).expect("valid sequences"));
+        let mut scheduler = Scheduler::new();
+        assert!(sequences.start(0, &mut scheduler));
+        // the trigger fires again while the player stays in the zone
+        assert!(!sequences.start(0, &mut scheduler));
+        let mut ran = Vec::new();
+        for frame in 1..=120 {
+            for action in scheduler.update(PHYSICS_FRAME_TIME) {
+                let ScheduledAction::SequenceStep { sequence, step } = action else {
+                    unreachable!("only sequence steps were scheduled");
+                };
+                ran.push((frame, sequences.action(sequence, step).clone()));
             }
         }
-        draw_text(&format!("FPS: {}", 1.0 / get_frame_time()), 10.0, 10.0, 20.0, WHITE);
-        next_frame().await;
+        let frames_at = |seconds: f32| (seconds / PHYSICS_FRAME_TIME).round().max(1.0) as i32;
+        assert_eq!(ran, vec![
+            (frames_at(0.0), SequenceAction::BossBar("First".to_string())),
+            (frames_at(0.5), SequenceAction::Sound("Sting".to_string())),
+            // due together, in the order of the file
+            (frames_at(1.0), SequenceAction::Title("Second".to_string())),
+            (frames_at(1.0), SequenceAction::Spawn { enemy: "skeleton".to_string(), x: 5, y: 1 })
+        ]);
+    }
+
+    /// a skeleton of a sequence spawn step, whether it joined
+    fn reinforce(simulation: &mut Simulation, x: usize, y: usize) -> bool {
+        let Simulation { level, content, .. } = simulation;
+        level.enemies.spawn_reinforcement(&mut level.world_layout, content, "skeleton", x, y).is_some()
+    }
+
+    #[test]
+    fn reinforcements_stop_at_min_alive_enemies() {
+        let mut simulation = Simulation::new("0200000\n0000000");
+        simulation.content.min_alive_enemies = Some(2);
+        assert!(reinforce(&mut simulation, 1, 1));
+        assert!(reinforce(&mut simulation, 2, 1));
+        assert!(!reinforce(&mut simulation, 3, 1), "a third enemy joined two living ones");
+        assert_eq!(simulation.level.enemies.alive_count(), 2);
+        assert_eq!(simulation.level.world_layout[1][3], EntityType::None);
+        // a dying one makes room
+        simulation.level.enemies.alives[0] = false;
+        assert!(reinforce(&mut simulation, 4, 1));
+        assert!(!reinforce(&mut simulation, 5, 1));
+        assert_eq!(simulation.level.enemies.alive_count(), 2);
+
+        // without a threshold only the level capacity limits them
+        simulation.content.min_alive_enemies = None;
+        assert!((2..5).all(|x| reinforce(&mut simulation, x, 0)));
+        assert_eq!(simulation.level.enemies.alive_count(), 5);
     }
 }
+
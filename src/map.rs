@@ -0,0 +1,629 @@
+use std::{ fs, ops::{ Index, IndexMut }, slice::{ Chunks, ChunksMut } };
+use crate::config::config::WORLD_LAYOUT;
+use validate::{ Diagnostic, Severity };
+
+pub mod validate;
+pub mod generate;
+
+pub type Layout = Grid<u8>;
+
+const ZONE_PREFIX: &str = "zone";
+const TELEPORTER_PREFIX: &str = "teleporter";
+const NOTE_PREFIX: &str = "note";
+const WATER_PREFIX: &str = "water";
+const DECORATION_PREFIX: &str = "decoration";
+const PAR_PREFIX: &str = "par";
+const SEQUENCE_PREFIX: &str = "sequence";
+const STEP_PREFIX: &str = "step";
+const NOTE_PAGE_SEPARATOR: &str = "|";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReverbProfile {
+    SmallRoom,
+    Corridor,
+    LargeHall,
+}
+impl ReverbProfile {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "small_room" => Some(ReverbProfile::SmallRoom),
+            "corridor" => Some(ReverbProfile::Corridor),
+            "hall" => Some(ReverbProfile::LargeHall),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FloorType {
+    Stone,
+    Water, // slows down whoever walks through it
+}
+pub type FloorGrid = Grid<FloorType>;
+
+/// A rectangle of tiles as large as the map it was read from, indexed like a nested array with grid[y][x]
+#[derive(Clone, PartialEq, Debug)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>, // row by row
+}
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        assert!(width > 0 && height > 0, "a grid needs at least one tile");
+        Grid { width, height, cells: vec![value; width * height] }
+    }
+    /// a grid of the same size, with every tile set to value
+    pub fn same_size<U>(other: &Grid<U>, value: T) -> Self {
+        Self::new(other.width, other.height, value)
+    }
+}
+impl<T> Grid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.contains(x, y).then(|| &self.cells[y * self.width + x])
+    }
+    pub fn rows(&self) -> Chunks<'_, T> {
+        self.cells.chunks(self.width)
+    }
+    pub fn rows_mut(&mut self) -> ChunksMut<'_, T> {
+        self.cells.chunks_mut(self.width)
+    }
+}
+impl<T> Index<usize> for Grid<T> {
+    type Output = [T];
+    fn index(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
+impl<T> IndexMut<usize> for Grid<T> {
+    fn index_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DecorationPlacement {
+    pub id: String, // content::DecorationDef::id
+    pub x: f32,
+    pub y: f32,
+}
+
+/// rectangle of tiles with its own echo, x and y are the top left tile
+#[derive(Clone, Copy, Debug)]
+pub struct ReverbZone {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub profile: ReverbProfile,
+}
+impl ReverbZone {
+    pub fn contains(&self, tile_x: usize, tile_y: usize) -> bool {
+        tile_x >= self.x && tile_x < self.x + self.width && tile_y >= self.y && tile_y < self.y + self.height
+    }
+}
+
+/// two floor tiles that send whoever steps on one of them next to the other
+#[derive(Clone, Copy, Debug)]
+pub struct TeleporterPair {
+    pub a: (usize, usize),
+    pub b: (usize, usize),
+}
+impl TeleporterPair {
+    /// the other end when `tile` is one of the two ends
+    pub fn partner(&self, tile: (usize, usize)) -> Option<(usize, usize)> {
+        if tile == self.a {
+            Some(self.b)
+        } else if tile == self.b {
+            Some(self.a)
+        } else {
+            None
+        }
+    }
+}
+
+/// what starts a scripted sequence
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SequenceTrigger {
+    Enter {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    }, // the player steps into the rectangle of tiles
+    BossKilled, // the enemy of the boss step dies
+}
+
+/// one thing a step of a scripted sequence does
+#[derive(Clone, PartialEq, Debug)]
+pub enum SequenceAction {
+    LockDoor {
+        x: usize,
+        y: usize,
+    }, // closes the door, the player can't open it until it is unlocked again
+    UnlockDoor {
+        x: usize,
+        y: usize,
+    },
+    Sound(String), // the name of a sound like in the content, e.g. Sting
+    Boss {
+        enemy: String, // content::EnemyKindDef::id, looked up when the step runs
+        x: usize,
+        y: usize,
+    },
+    Spawn {
+        enemy: String, // skipped while content::ContentRegistry::min_alive_enemies are alive
+        x: usize,
+        y: usize,
+    },
+    BossBar(String), // shows the health of the boss under this name
+    Title(String), // large text in the middle of the screen
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SequenceStep {
+    pub at: f32, // seconds after the sequence started
+    pub action: SequenceAction,
+}
+
+/// a set piece of the map, its steps are sorted by their time and run once the trigger fires
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScriptedSequence {
+    pub name: String,
+    pub trigger: SequenceTrigger,
+    pub steps: Vec<SequenceStep>,
+}
+
+/// text pinned to a wall tile that the player can read
+#[derive(Clone, Debug)]
+pub struct Note {
+    pub x: usize,
+    pub y: usize,
+    pub pages: Vec<String>,
+}
+
+pub struct MapFile {
+    pub layout: Layout,
+    pub reverb_zones: Vec<ReverbZone>,
+    pub teleporters: Vec<TeleporterPair>,
+    pub notes: Vec<Note>,
+    pub floor_types: FloorGrid,
+    pub decorations: Vec<DecorationPlacement>,
+    pub par_time: Option<f32>, // seconds
+    pub sequences: Vec<ScriptedSequence>,
+    pub exit: Option<(usize, usize)>, // elevator cabin that leads to the next level, only generated levels have one
+    pub entry: Option<(usize, usize)>, // elevator cabin the player arrives in, its door opens once the level starts
+    pub warnings: Vec<Diagnostic>, // see validate::validate, maps with errors don't load
+}
+
+impl MapFile {
+    /// config::WORLD_LAYOUT without any extras, used when a map can't be loaded
+    pub fn built_in() -> Self {
+        let mut layout = Grid::new(WORLD_LAYOUT[0].len(), WORLD_LAYOUT.len(), 0);
+        for (row, built_in_row) in layout.rows_mut().zip(WORLD_LAYOUT.iter()) {
+            row.copy_from_slice(built_in_row);
+        }
+        MapFile {
+            floor_types: Grid::same_size(&layout, FloorType::Stone),
+            layout,
+            reverb_zones: Vec::new(),
+            teleporters: Vec::new(),
+            notes: Vec::new(),
+            decorations: Vec::new(),
+            par_time: None,
+            sequences: Vec::new(),
+            exit: None,
+            entry: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// lines that start with a word instead of tile digits
+fn is_directive(line: &str) -> bool {
+    [
+        ZONE_PREFIX,
+        TELEPORTER_PREFIX,
+        NOTE_PREFIX,
+        WATER_PREFIX,
+        DECORATION_PREFIX,
+        PAR_PREFIX,
+        SEQUENCE_PREFIX,
+        STEP_PREFIX,
+    ]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// One row of digits per line, the digits mean the same as in config::WORLD_LAYOUT,
+/// other digits are left to the spawn table of the loaded content. The first row sets the width of the map,
+/// every other row has to match it.
+pub fn parse_layout(text: &str) -> Result<Layout, String> {
+    let mut player_count = 0;
+    let rows: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !is_directive(line))
+        .collect();
+    let Some(first_row) = rows.first() else {
+        return Err("the map has no rows".to_string());
+    };
+    let mut layout = Grid::new(first_row.chars().count(), rows.len(), 0);
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != layout.width() {
+            return Err(
+                format!("row {} has {} tiles, expected {}", y + 1, row.chars().count(), layout.width())
+            );
+        }
+        for (x, tile) in row.chars().enumerate() {
+            let value = match tile.to_digit(10) {
+                Some(value) => value as u8,
+                _ => {
+                    return Err(format!("invalid tile '{}' at ({}, {})", tile, x, y));
+                }
+            };
+            if value == 2 {
+                player_count += 1;
+            }
+            layout[y][x] = value;
+        }
+    }
+    if player_count != 1 {
+        return Err(format!("expected exactly one player tile, found {}", player_count));
+    }
+    Ok(layout)
+}
+
+/// Optional lines of the form `zone <small_room|corridor|hall> <x> <y> <width> <height>`,
+/// they may appear anywhere in the map file
+pub fn parse_reverb_zones(text: &str, layout: &Layout) -> Result<Vec<ReverbZone>, String> {
+    let mut zones = Vec::new();
+    for line in text.lines().map(|line| line.trim()) {
+        if !line.starts_with(ZONE_PREFIX) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 6 {
+            return Err(format!("expected 'zone <profile> <x> <y> <width> <height>', found '{}'", line));
+        }
+        let profile = ReverbProfile::parse(parts[1]).ok_or_else(||
+            format!("unknown reverb profile '{}'", parts[1])
+        )?;
+        let [x, y, width, height] = parse_rect(&parts[2..], line, layout)?;
+        zones.push(ReverbZone { x, y, width, height, profile });
+    }
+    Ok(zones)
+}
+
+/// x, y, width and height of a rectangle of tiles that lies inside the map
+fn parse_rect(parts: &[&str], line: &str, layout: &Layout) -> Result<[usize; 4], String> {
+    let mut numbers = [0; 4];
+    for (number, part) in numbers.iter_mut().zip(parts) {
+        *number = part.parse().map_err(|_| format!("invalid number '{}' in '{}'", part, line))?;
+    }
+    let [x, y, width, height] = numbers;
+    if width == 0 || height == 0 || x + width > layout.width() || y + height > layout.height() {
+        return Err(format!("'{}' is empty or outside the map", line));
+    }
+    Ok(numbers)
+}
+
+/// Optional lines of the form `water <x> <y> <width> <height>`, every other tile has a stone floor
+pub fn parse_floor_types(text: &str, layout: &Layout) -> Result<FloorGrid, String> {
+    let mut floor_types = Grid::same_size(layout, FloorType::Stone);
+    for line in text.lines().map(|line| line.trim()) {
+        if !line.starts_with(WATER_PREFIX) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(format!("expected 'water <x> <y> <width> <height>', found '{}'", line));
+        }
+        let [x, y, width, height] = parse_rect(&parts[1..], line, layout)?;
+        for row in floor_types.rows_mut().skip(y).take(height) {
+            row[x..x + width].fill(FloorType::Water);
+        }
+    }
+    Ok(floor_types)
+}
+
+/// Optional lines of the form `teleporter <x1> <y1> <x2> <y2>`, both ends have to be empty floor tiles
+pub fn parse_teleporters(text: &str, layout: &Layout) -> Result<Vec<TeleporterPair>, String> {
+    let mut teleporters: Vec<TeleporterPair> = Vec::new();
+    for line in text.lines().map(|line| line.trim()) {
+        if !line.starts_with(TELEPORTER_PREFIX) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(format!("expected 'teleporter <x1> <y1> <x2> <y2>', found '{}'", line));
+        }
+        let mut numbers = [0; 4];
+        for (number, part) in numbers.iter_mut().zip(&parts[1..]) {
+            *number = part.parse().map_err(|_| format!("invalid number '{}' in '{}'", part, line))?;
+        }
+        let [x1, y1, x2, y2] = numbers;
+        let pair = TeleporterPair { a: (x1, y1), b: (x2, y2) };
+        if pair.a == pair.b {
+            return Err(format!("teleporter '{}' links a tile to itself", line));
+        }
+        for (x, y) in [pair.a, pair.b] {
+            if !layout.contains(x, y) || layout[y][x] != 0 {
+                return Err(format!("teleporter end ({}, {}) is not an empty floor tile", x, y));
+            }
+            if teleporters.iter().any(|other| other.partner((x, y)).is_some()) {
+                return Err(format!("tile ({}, {}) is used by two teleporters", x, y));
+            }
+        }
+        teleporters.push(pair);
+    }
+    Ok(teleporters)
+}
+
+/// Optional lines of the form `note <x> <y> <text>` on a wall tile, `|` in the text starts a new page
+pub fn parse_notes(text: &str, layout: &Layout) -> Result<Vec<Note>, String> {
+    let mut notes: Vec<Note> = Vec::new();
+    for line in text.lines().map(|line| line.trim()) {
+        if !line.starts_with(NOTE_PREFIX) {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(4, char::is_whitespace).collect();
+        if parts.len() != 4 {
+            return Err(format!("expected 'note <x> <y> <text>', found '{}'", line));
+        }
+        let mut numbers = [0; 2];
+        for (number, part) in numbers.iter_mut().zip(&parts[1..3]) {
+            *number = part.parse().map_err(|_| format!("invalid number '{}' in '{}'", part, line))?;
+        }
+        let [x, y] = numbers;
+        if !layout.contains(x, y) || layout[y][x] != 1 {
+            return Err(format!("note at ({}, {}) is not on a wall tile", x, y));
+        }
+        if notes.iter().any(|note| note.x == x && note.y == y) {
+            return Err(format!("wall ({}, {}) has two notes", x, y));
+        }
+        let pages: Vec<String> = parts[3]
+            .split(NOTE_PAGE_SEPARATOR)
+            .map(|page| page.trim().to_string())
+            .filter(|page| !page.is_empty())
+            .collect();
+        if pages.is_empty() {
+            return Err(format!("note at ({}, {}) has no text", x, y));
+        }
+        notes.push(Note { x, y, pages });
+    }
+    Ok(notes)
+}
+
+/// Optional lines of the form `decoration <id> <x> <y>`, x and y are the center of the prop in tiles
+/// and may be fractional, the id is looked up in the content when the level is built
+pub fn parse_decorations(text: &str, layout: &Layout) -> Result<Vec<DecorationPlacement>, String> {
+    let mut decorations = Vec::new();
+    for line in text.lines().map(|line| line.trim()) {
+        if !line.starts_with(DECORATION_PREFIX) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 4 {
+            return Err(format!("expected 'decoration <id> <x> <y>', found '{}'", line));
+        }
+        let mut numbers = [0.0; 2];
+        for (number, part) in numbers.iter_mut().zip(&parts[2..]) {
+            *number = part.parse().map_err(|_| format!("invalid number '{}' in '{}'", part, line))?;
+        }
+        let [x, y] = numbers;
+        let inside = x >= 0.0 && y >= 0.0 && layout.contains(x as usize, y as usize);
+        if !inside || matches!(layout[y as usize][x as usize], 1 | 4 | 5) {
+            return Err(format!("decoration at ({}, {}) is not on a floor tile", x, y));
+        }
+        decorations.push(DecorationPlacement { id: parts[1].to_string(), x, y });
+    }
+    Ok(decorations)
+}
+
+/// An optional line of the form `par <seconds>`, the time the speedrun timer compares against
+pub fn parse_par_time(text: &str) -> Result<Option<f32>, String> {
+    let mut par_time = None;
+    for line in text.lines().map(|line| line.trim()) {
+        if !line.starts_with(PAR_PREFIX) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 2 {
+            return Err(format!("expected 'par <seconds>', found '{}'", line));
+        }
+        if par_time.is_some() {
+            return Err(format!("the par time is set twice, found '{}'", line));
+        }
+        let seconds: f32 = parts[1].parse().map_err(|_| format!("invalid number '{}' in '{}'", parts[1], line))?;
+        if seconds <= 0.0 {
+            return Err(format!("the par time has to be positive, found '{}'", line));
+        }
+        par_time = Some(seconds);
+    }
+    Ok(par_time)
+}
+
+/// Optional lines of the form `sequence <name> enter <x> <y> <width> <height>` or `sequence <name> boss_killed`,
+/// and lines of the form `step <name> <seconds> <action>` for what the sequence does once it started.
+/// The actions are `lock_door <x> <y>`, `unlock_door <x> <y>`, `sound <name>`, `boss <enemy id> <x> <y>`,
+/// `spawn <enemy id> <x> <y>`, `boss_bar <name>` and `title <text>`.
+/// A map has at most one boss, boss_killed waits for it.
+pub fn parse_sequences(text: &str, layout: &Layout) -> Result<Vec<ScriptedSequence>, String> {
+    let lines = || text.lines().map(|line| line.trim());
+    let mut sequences: Vec<ScriptedSequence> = Vec::new();
+    for line in lines().filter(|line| line.starts_with(SEQUENCE_PREFIX)) {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let trigger = match parts.get(2) {
+            Some(&"enter") if parts.len() == 7 => {
+                let [x, y, width, height] = parse_rect(&parts[3..], line, layout)?;
+                SequenceTrigger::Enter { x, y, width, height }
+            }
+            Some(&"boss_killed") if parts.len() == 3 => SequenceTrigger::BossKilled,
+            _ => {
+                return Err(
+                    format!(
+                        "expected 'sequence <name> enter <x> <y> <width> <height>' or 'sequence <name> boss_killed', found '{}'",
+                        line
+                    )
+                );
+            }
+        };
+        if sequences.iter().any(|sequence| sequence.name == parts[1]) {
+            return Err(format!("the sequence '{}' is declared twice", parts[1]));
+        }
+        sequences.push(ScriptedSequence { name: parts[1].to_string(), trigger, steps: Vec::new() });
+    }
+    let mut bosses = 0;
+    for line in lines().filter(|line| line.starts_with(STEP_PREFIX)) {
+        let parts: Vec<&str> = line.splitn(5, char::is_whitespace).collect();
+        if parts.len() < 4 {
+            return Err(format!("expected 'step <sequence> <seconds> <action>', found '{}'", line));
+        }
+        let Some(sequence) = sequences.iter_mut().find(|sequence| sequence.name == parts[1]) else {
+            return Err(format!("step '{}' belongs to the undeclared sequence '{}'", line, parts[1]));
+        };
+        let at: f32 = parts[2].parse().map_err(|_| format!("invalid number '{}' in '{}'", parts[2], line))?;
+        if !at.is_finite() || at < 0.0 {
+            return Err(format!("a step needs a time from the start of its sequence, found '{}'", line));
+        }
+        let arguments: Vec<&str> = parts.get(4).map_or(Vec::new(), |rest| rest.split_whitespace().collect());
+        let tile = |first: usize| -> Result<(usize, usize), String> {
+            let mut numbers = [0; 2];
+            for (number, part) in numbers.iter_mut().zip(&arguments[first..]) {
+                *number = part.parse().map_err(|_| format!("invalid number '{}' in '{}'", part, line))?;
+            }
+            Ok((numbers[0], numbers[1]))
+        };
+        let action = match (parts[3], arguments.len()) {
+            ("lock_door" | "unlock_door", 2) => {
+                let (x, y) = tile(0)?;
+                if !layout.contains(x, y) || !matches!(layout[y][x], 4 | 5) {
+                    return Err(format!("({}, {}) in '{}' is not a door", x, y, line));
+                }
+                if parts[3] == "lock_door" {
+                    SequenceAction::LockDoor { x, y }
+                } else {
+                    SequenceAction::UnlockDoor { x, y }
+                }
+            }
+            ("sound", 1) => SequenceAction::Sound(arguments[0].to_string()),
+            ("boss" | "spawn", 3) => {
+                let (x, y) = tile(1)?;
+                if !layout.contains(x, y) || layout[y][x] != 0 {
+                    return Err(format!("the {} at ({}, {}) is not on an empty floor tile", parts[3], x, y));
+                }
+                let enemy = arguments[0].to_string();
+                if parts[3] == "boss" {
+                    bosses += 1;
+                    SequenceAction::Boss { enemy, x, y }
+                } else {
+                    SequenceAction::Spawn { enemy, x, y }
+                }
+            }
+            ("boss_bar" | "title", _) if !arguments.is_empty() => {
+                // the text keeps its spacing
+                let text = parts[4].trim().to_string();
+                if parts[3] == "boss_bar" {
+                    SequenceAction::BossBar(text)
+                } else {
+                    SequenceAction::Title(text)
+                }
+            }
+            _ => {
+                return Err(format!("unknown action or wrong arguments in '{}'", line));
+            }
+        };
+        sequence.steps.push(SequenceStep { at, action });
+    }
+    if bosses > 1 {
+        return Err(format!("the map spawns {} bosses, only one is supported", bosses));
+    }
+    for sequence in &mut sequences {
+        if sequence.steps.is_empty() {
+            return Err(format!("the sequence '{}' has no steps", sequence.name));
+        }
+        if sequence.trigger == SequenceTrigger::BossKilled && bosses == 0 {
+            return Err(format!("the sequence '{}' waits for a boss that no step spawns", sequence.name));
+        }
+        // stable, steps at the same time keep the order of the file
+        sequence.steps.sort_by(|a, b| a.at.total_cmp(&b.at));
+    }
+    Ok(sequences)
+}
+
+/// spawn_tiles are the digits the content places enemies on, see validate::validate
+pub fn load_map(path: &str, spawn_tiles: &[u8]) -> Result<MapFile, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path, err))?;
+    let layout = parse_layout(&text)?;
+    let teleporters = parse_teleporters(&text, &layout)?;
+    let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) = validate
+        ::validate(&layout, &teleporters, spawn_tiles)
+        .into_iter()
+        .partition(|diagnostic| diagnostic.severity() == Severity::Error);
+    if let Some(error) = errors.first() {
+        return Err(format!("{} ({})", error.message, error.kind.explanation()));
+    }
+    Ok(MapFile {
+        reverb_zones: parse_reverb_zones(&text, &layout)?,
+        teleporters,
+        notes: parse_notes(&text, &layout)?,
+        floor_types: parse_floor_types(&text, &layout)?,
+        decorations: parse_decorations(&text, &layout)?,
+        par_time: parse_par_time(&text)?,
+        sequences: parse_sequences(&text, &layout)?,
+        exit: None,
+        entry: None,
+        warnings,
+        layout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequences(text: &str) -> Result<Vec<ScriptedSequence>, String> {
+        let layout = parse_layout(text).expect("test map should parse");
+        parse_sequences(text, &layout)
+    }
+
+    #[test]
+    fn spawn_steps_are_not_bosses() {
+        let text = "11111\n12001\n11111\n\
+            sequence wave enter 1 1 1 1\n\
+            step wave 0 spawn skeleton 2 1\n\
+            step wave 1 spawn archer 3 1\n\
+            step wave 2 boss skeleton 3 1";
+        let steps: Vec<SequenceAction> = sequences(text)
+            .expect("spawns next to a boss are fine")
+            .remove(0)
+            .steps.into_iter()
+            .map(|step| step.action)
+            .collect();
+        assert_eq!(steps[0], SequenceAction::Spawn { enemy: "skeleton".to_string(), x: 2, y: 1 });
+        assert_eq!(steps[1], SequenceAction::Spawn { enemy: "archer".to_string(), x: 3, y: 1 });
+        assert_eq!(steps[2], SequenceAction::Boss { enemy: "skeleton".to_string(), x: 3, y: 1 });
+    }
+
+    #[test]
+    fn spawn_needs_an_empty_floor_tile() {
+        let text = "11111\n12001\n11111\nsequence wave enter 1 1 1 1\nstep wave 0 spawn skeleton 0 1";
+        assert!(sequences(text).is_err());
+    }
+
+    #[test]
+    fn boss_killed_does_not_wait_for_a_spawn() {
+        let text = "11111\n12001\n11111\nsequence after boss_killed\nstep after 0 spawn skeleton 2 1";
+        assert!(sequences(text).is_err());
+    }
+}
@@ -0,0 +1,451 @@
+use ::rand::{ rngs::StdRng, seq::SliceRandom, Rng, SeedableRng };
+use super::{ validate, FloorType, Grid, Layout, MapFile };
+
+const FLOOR: u8 = 0;
+const WALL: u8 = 1;
+const PLAYER: u8 = 2;
+const ENEMY: u8 = 3; // the skeleton in the base content's spawn table
+const DOOR: u8 = 4;
+const AMMO: u8 = 6;
+const ARCHER: u8 = 7; // the skeleton archer in the base content's spawn table
+const HEALTH: u8 = 8;
+/// the stress timings in the readme were measured on a map of this size
+const WIDTH: usize = 64;
+const HEIGHT: usize = 64;
+const ENDLESS_FIRST_LEVEL_ENEMIES: usize = 8;
+const ENDLESS_ENEMIES_PER_LEVEL: usize = 4;
+const ENDLESS_MAX_ENEMIES: usize = 120;
+const ENDLESS_ARCHERS_PER_LEVEL: f32 = 0.05; // added to the share of archers among the enemies
+const ENDLESS_MAX_ARCHERS: f32 = 0.5;
+const ENDLESS_FIRST_LEVEL_PICKUPS: usize = 10;
+const ENDLESS_MIN_PICKUPS: usize = 2;
+const ROOM_ATTEMPTS: usize = 300;
+const MAX_ROOMS: usize = 16;
+const MIN_ROOM_SIZE: usize = 3;
+const MAX_ROOM_SIZE: usize = 10;
+const MAX_DOORS: usize = 30;
+/// a failed layout is retried with the next seed, so the result stays deterministic
+const MAX_GENERATION_ATTEMPTS: u64 = 16;
+
+#[derive(Clone, Copy, Debug)]
+struct Room {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+impl Room {
+    fn center(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+    /// rooms keep at least one wall tile between each other
+    fn overlaps(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width &&
+            other.x <= self.x + self.width &&
+            self.y <= other.y + other.height &&
+            other.y <= self.y + self.height
+    }
+    fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// what the generator spreads over the rooms
+#[derive(Clone, Copy, Debug)]
+pub struct Population {
+    pub enemies: usize,
+    pub archer_share: f32, // of the enemies, the others are skeletons
+    pub pickups: usize, // every other one is ammo, the rest medkits
+}
+impl Population {
+    /// skeletons only, nothing to pick up
+    pub fn skeletons(enemies: usize) -> Self {
+        Population { enemies, archer_share: 0.0, pickups: 0 }
+    }
+    /// deeper levels hold more enemies, more of them archers, and fewer pickups
+    pub fn endless(depth: u32) -> Self {
+        let levels_down = depth.saturating_sub(1) as usize;
+        Population {
+            enemies: (ENDLESS_FIRST_LEVEL_ENEMIES + levels_down * ENDLESS_ENEMIES_PER_LEVEL).min(
+                ENDLESS_MAX_ENEMIES
+            ),
+            archer_share: ((levels_down as f32) * ENDLESS_ARCHERS_PER_LEVEL).min(ENDLESS_MAX_ARCHERS),
+            pickups: ENDLESS_FIRST_LEVEL_PICKUPS.saturating_sub(levels_down).max(ENDLESS_MIN_PICKUPS),
+        }
+    }
+}
+
+/// a generated layout and its special tiles
+pub struct GeneratedLevel {
+    pub layout: Layout,
+    pub entry: Option<(usize, usize)>, // elevator cabin with the player in it, None without elevators
+    pub exit: (usize, usize), // elevator cabin or middle of the room furthest along the corridors
+}
+
+/// The heavy scene for profiling, see `--stress` in main. The same seed always gives the same map,
+/// so the timings of different builds can be compared.
+pub fn stress_map(seed: u64, enemies: usize) -> Result<MapFile, String> {
+    let generated = rooms_and_corridors(seed, Population::skeletons(enemies), false)?;
+    Ok(MapFile {
+        floor_types: Grid::same_size(&generated.layout, FloorType::Stone),
+        layout: generated.layout,
+        ..MapFile::built_in()
+    })
+}
+
+/// Level `depth` of an endless run, starting at 1. Every level of a run comes from the run seed,
+/// see Population::endless for how it gets harder.
+pub fn endless_level(run_seed: u64, depth: u32) -> Result<MapFile, String> {
+    // spreads the levels of one run apart so they don't share the retry seeds of their neighbours
+    let seed = run_seed ^ (depth as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    let generated = rooms_and_corridors(seed, Population::endless(depth), true)?;
+    Ok(MapFile {
+        floor_types: Grid::same_size(&generated.layout, FloorType::Stone),
+        layout: generated.layout,
+        entry: generated.entry,
+        exit: Some(generated.exit),
+        ..MapFile::built_in()
+    })
+}
+
+/// Rooms joined by one tile wide corridors from left to right, which keeps every room reachable and
+/// gives long sightlines along the corridors. Doors go on corridor tiles that pass between two walls,
+/// enemies and pickups are sprinkled over every room except the player's. The exit is the middle of the room furthest
+/// along the corridors, with `elevators` the player starts in a cabin off the first room and the exit is a
+/// cabin off the last one. The flood fill of validate proves the exit reachable.
+pub fn rooms_and_corridors(
+    seed: u64,
+    population: Population,
+    elevators: bool
+) -> Result<GeneratedLevel, String> {
+    for attempt in 0..MAX_GENERATION_ATTEMPTS {
+        let generated = generate(seed.wrapping_add(attempt), population, elevators);
+        if let Some(generated) = generated {
+            // the same checks a hand made map goes through, any warning counts as a failed attempt
+            if validate::validate(&generated.layout, &[], &[ENEMY, ARCHER]).is_empty() {
+                return Ok(generated);
+            }
+        }
+    }
+    Err(format!("no valid layout after {} attempts from seed {}", MAX_GENERATION_ATTEMPTS, seed))
+}
+
+fn generate(seed: u64, population: Population, elevators: bool) -> Option<GeneratedLevel> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut layout = Grid::new(WIDTH, HEIGHT, WALL);
+    let mut rooms: Vec<Room> = Vec::new();
+    for _ in 0..ROOM_ATTEMPTS {
+        if rooms.len() == MAX_ROOMS {
+            break;
+        }
+        let width = rng.gen_range(MIN_ROOM_SIZE..=MAX_ROOM_SIZE);
+        let height = rng.gen_range(MIN_ROOM_SIZE..=MAX_ROOM_SIZE);
+        let room = Room {
+            x: rng.gen_range(1..WIDTH - width - 1),
+            y: rng.gen_range(1..HEIGHT - height - 1),
+            width,
+            height,
+        };
+        if rooms.iter().any(|other| room.overlaps(other)) {
+            continue;
+        }
+        for row in layout.rows_mut().skip(room.y).take(room.height) {
+            row[room.x..room.x + room.width].fill(FLOOR);
+        }
+        rooms.push(room);
+    }
+    if rooms.len() < 2 {
+        return None;
+    }
+    rooms.sort_by_key(|room| room.center());
+    for pair in rooms.windows(2) {
+        let (from_x, from_y) = pair[0].center();
+        let (to_x, to_y) = pair[1].center();
+        if rng.gen() {
+            carve_horizontal(&mut layout, from_x, to_x, from_y);
+            carve_vertical(&mut layout, from_y, to_y, to_x);
+        } else {
+            carve_vertical(&mut layout, from_y, to_y, from_x);
+            carve_horizontal(&mut layout, from_x, to_x, to_y);
+        }
+    }
+    // before the corridor doors, which then keep their distance to the elevator doors
+    let (entry, exit) = if elevators {
+        let entry = carve_elevator(&mut layout, &rooms[0], &mut rng)?;
+        let exit = carve_elevator(&mut layout, &rooms[rooms.len() - 1], &mut rng)?;
+        (Some(entry), exit)
+    } else {
+        (None, rooms[rooms.len() - 1].center())
+    };
+    place_doors(&mut layout, &rooms, &mut rng);
+
+    let (player_x, player_y) = entry.unwrap_or(rooms[0].center());
+    layout[player_y][player_x] = PLAYER;
+    let mut spawn_tiles: Vec<(usize, usize)> = rooms[1..]
+        .iter()
+        .flat_map(|room| {
+            (room.y..room.y + room.height).flat_map(move |y| (room.x..room.x + room.width).map(move |x| (x, y)))
+        })
+        .filter(|&(x, y)| layout[y][x] == FLOOR && (x, y) != exit)
+        .collect();
+    spawn_tiles.shuffle(&mut rng);
+    let mut spawn_tiles = spawn_tiles.into_iter();
+    for (x, y) in spawn_tiles.by_ref().take(population.enemies) {
+        layout[y][x] = if rng.gen::<f32>() < population.archer_share { ARCHER } else { ENEMY };
+    }
+    for (index, (x, y)) in spawn_tiles.take(population.pickups).enumerate() {
+        layout[y][x] = if index % 2 == 0 { AMMO } else { HEALTH };
+    }
+    Some(GeneratedLevel { layout, entry, exit })
+}
+
+/// A one tile cabin in the wall of `room` behind a door into the room, returns the cabin. None when no
+/// spot along the walls has solid wall around it, corridors leave the room through some of them.
+fn carve_elevator(layout: &mut Layout, room: &Room, rng: &mut StdRng) -> Option<(usize, usize)> {
+    // (door, cabin), the cabin is one further out than the door and needs a wall behind it
+    let mut candidates: Vec<((usize, usize), (usize, usize))> = Vec::new();
+    for x in room.x..room.x + room.width {
+        if room.y >= 3 {
+            candidates.push(((x, room.y - 1), (x, room.y - 2)));
+        }
+        if room.y + room.height + 2 < HEIGHT {
+            candidates.push(((x, room.y + room.height), (x, room.y + room.height + 1)));
+        }
+    }
+    for y in room.y..room.y + room.height {
+        if room.x >= 3 {
+            candidates.push(((room.x - 1, y), (room.x - 2, y)));
+        }
+        if room.x + room.width + 2 < WIDTH {
+            candidates.push(((room.x + room.width, y), (room.x + room.width + 1, y)));
+        }
+    }
+    candidates.shuffle(rng);
+    let offset = |(x, y): (usize, usize), (dx, dy): (isize, isize)| {
+        (((x as isize) + dx) as usize, ((y as isize) + dy) as usize)
+    };
+    let (door, cabin) = candidates.into_iter().find(|&(door, cabin)| {
+        let out = ((cabin.0 as isize) - (door.0 as isize), (cabin.1 as isize) - (door.1 as isize));
+        let side = (out.1, out.0);
+        let back = (-side.0, -side.1);
+        // the door slides into the walls beside it, the cabin is closed everywhere but at the door
+        [
+            door,
+            cabin,
+            offset(door, side),
+            offset(door, back),
+            offset(cabin, side),
+            offset(cabin, back),
+            offset(cabin, out),
+        ]
+            .iter()
+            .all(|&(x, y)| layout[y][x] == WALL)
+    })?;
+    layout[door.1][door.0] = DOOR;
+    layout[cabin.1][cabin.0] = FLOOR;
+    Some(cabin)
+}
+
+fn carve_horizontal(layout: &mut Layout, from_x: usize, to_x: usize, y: usize) {
+    layout[y][from_x.min(to_x)..=from_x.max(to_x)].fill(FLOOR);
+}
+
+fn carve_vertical(layout: &mut Layout, from_y: usize, to_y: usize, x: usize) {
+    let (top, bottom) = (from_y.min(to_y), from_y.max(to_y));
+    for row in layout.rows_mut().skip(top).take(bottom - top + 1) {
+        row[x] = FLOOR;
+    }
+}
+
+/// corridor tiles outside the rooms with walls on two opposite sides and floor on the other two,
+/// the chokepoints where a door can slide into the wall, never next to another door
+fn place_doors(layout: &mut Layout, rooms: &[Room], rng: &mut StdRng) {
+    let mut candidates: Vec<(usize, usize)> = Vec::new();
+    for y in 1..HEIGHT - 1 {
+        for x in 1..WIDTH - 1 {
+            if layout[y][x] != FLOOR || rooms.iter().any(|room| room.contains(x, y)) {
+                continue;
+            }
+            let walls_above_below = layout[y - 1][x] == WALL && layout[y + 1][x] == WALL;
+            let walls_left_right = layout[y][x - 1] == WALL && layout[y][x + 1] == WALL;
+            let open_left_right = layout[y][x - 1] == FLOOR && layout[y][x + 1] == FLOOR;
+            let open_above_below = layout[y - 1][x] == FLOOR && layout[y + 1][x] == FLOOR;
+            if (walls_above_below && open_left_right) || (walls_left_right && open_above_below) {
+                candidates.push((x, y));
+            }
+        }
+    }
+    candidates.shuffle(rng);
+    let mut placed = 0;
+    for (x, y) in candidates {
+        if placed == MAX_DOORS {
+            break;
+        }
+        let next_to_door = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .iter()
+            .any(|&(next_x, next_y)| layout[next_y][next_x] == DOOR);
+        if next_to_door {
+            continue;
+        }
+        layout[y][x] = DOOR;
+        placed += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    const SEEDS: u64 = 50;
+
+    fn stress(seed: u64, elevators: bool) -> GeneratedLevel {
+        rooms_and_corridors(seed, Population::skeletons(200), elevators).expect("every seed generates")
+    }
+
+    /// every tile that isn't a wall, walked to from the player without teleporters
+    fn walkable_from_player(layout: &Layout) -> Grid<bool> {
+        let mut reached = Grid::same_size(layout, false);
+        let start = (0..layout.height())
+            .flat_map(|y| (0..layout.width()).map(move |x| (x, y)))
+            .find(|&(x, y)| layout[y][x] == PLAYER)
+            .expect("generated map has a player");
+        let mut queue = VecDeque::from([start]);
+        reached[start.1][start.0] = true;
+        while let Some((x, y)) = queue.pop_front() {
+            for (next_x, next_y) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if layout[next_y][next_x] != WALL && !reached[next_y][next_x] {
+                    reached[next_y][next_x] = true;
+                    queue.push_back((next_x, next_y));
+                }
+            }
+        }
+        reached
+    }
+
+    fn tiles(layout: &Layout) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..layout.height()).flat_map(move |y| (0..layout.width()).map(move |x| (x, y)))
+    }
+
+    #[test]
+    fn stress_map_is_64_by_64_with_a_closed_border() {
+        let layout = stress(0, false).layout;
+        assert_eq!((layout.width(), layout.height()), (64, 64));
+        for (x, y) in tiles(&layout) {
+            if x == 0 || y == 0 || x == WIDTH - 1 || y == HEIGHT - 1 {
+                assert_eq!(layout[y][x], WALL, "border tile ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn every_open_tile_and_the_exit_are_reachable() {
+        for seed in 0..SEEDS {
+            for elevators in [false, true] {
+                let generated = stress(seed, elevators);
+                let reached = walkable_from_player(&generated.layout);
+                for (x, y) in tiles(&generated.layout) {
+                    assert!(
+                        generated.layout[y][x] == WALL || reached[y][x],
+                        "seed {} leaves ({}, {}) unreachable",
+                        seed,
+                        x,
+                        y
+                    );
+                }
+                assert!(reached[generated.exit.1][generated.exit.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn every_door_is_flanked_by_walls_and_opens_onto_floor() {
+        for seed in 0..SEEDS {
+            let layout = stress(seed, true).layout;
+            let mut doors = 0;
+            for (x, y) in tiles(&layout).filter(|&(x, y)| layout[y][x] == DOOR) {
+                doors += 1;
+                let walls_above_below = layout[y - 1][x] == WALL && layout[y + 1][x] == WALL;
+                let walls_left_right = layout[y][x - 1] == WALL && layout[y][x + 1] == WALL;
+                let open_left_right = layout[y][x - 1] != WALL && layout[y][x + 1] != WALL;
+                let open_above_below = layout[y - 1][x] != WALL && layout[y + 1][x] != WALL;
+                assert!(
+                    (walls_above_below && open_left_right) || (walls_left_right && open_above_below),
+                    "seed {} has a door at ({}, {}) that can't slide or leads into a wall",
+                    seed,
+                    x,
+                    y
+                );
+                let next_to_door = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .iter()
+                    .any(|&(next_x, next_y)| layout[next_y][next_x] == DOOR);
+                assert!(!next_to_door, "seed {} has doors side by side at ({}, {})", seed, x, y);
+            }
+            // the two elevator doors at least
+            assert!((2..=MAX_DOORS + 2).contains(&doors));
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_map() {
+        let first = rooms_and_corridors(7, Population::endless(5), true).expect("seed 7 generates");
+        let second = rooms_and_corridors(7, Population::endless(5), true).expect("seed 7 generates");
+        assert_eq!(first.layout, second.layout);
+        assert_eq!((first.entry, first.exit), (second.entry, second.exit));
+    }
+
+    #[test]
+    fn stress_map_holds_the_requested_enemies() {
+        let layout = stress(0, false).layout;
+        assert_eq!(tiles(&layout).filter(|&(x, y)| layout[y][x] == ENEMY).count(), 200);
+    }
+
+    #[test]
+    fn floor_types_cover_the_generated_map() {
+        let stress = stress_map(0, 200).expect("seed 0 generates");
+        assert_eq!(stress.floor_types.width(), stress.layout.width());
+        assert_eq!(stress.floor_types.height(), stress.layout.height());
+        let level = endless_level(0, 1).expect("seed 0 generates");
+        assert_eq!(level.floor_types.width(), level.layout.width());
+        assert_eq!(level.floor_types.height(), level.layout.height());
+    }
+
+    fn count(layout: &Layout, tile: u8) -> usize {
+        tiles(layout).filter(|&(x, y)| layout[y][x] == tile).count()
+    }
+
+    #[test]
+    fn hundred_endless_levels_are_valid() {
+        for seed in 0..100 {
+            // a few levels deep into a different run each time
+            let depth = 1 + (seed % 20) as u32;
+            let level = endless_level(seed, depth).expect("every endless level generates");
+            let layout = &level.layout;
+            assert!(validate::validate(layout, &[], &[ENEMY, ARCHER]).is_empty(), "seed {} depth {}", seed, depth);
+            assert_eq!(count(layout, PLAYER), 1);
+            let entry = level.entry.expect("endless levels start in an elevator");
+            assert_eq!(layout[entry.1][entry.0], PLAYER);
+            let exit = level.exit.expect("endless levels end in an elevator");
+            assert_eq!(layout[exit.1][exit.0], FLOOR);
+            assert!(walkable_from_player(layout)[exit.1][exit.0]);
+            let population = Population::endless(depth);
+            assert_eq!(count(layout, ENEMY) + count(layout, ARCHER), population.enemies);
+            assert_eq!(count(layout, AMMO) + count(layout, HEALTH), population.pickups);
+            assert!(count(layout, AMMO) >= count(layout, HEALTH));
+        }
+    }
+
+    #[test]
+    fn deeper_levels_are_harder() {
+        let first = Population::endless(1);
+        let deeper = Population::endless(10);
+        assert!(deeper.enemies > first.enemies);
+        assert!(deeper.archer_share > first.archer_share);
+        assert!(deeper.pickups < first.pickups);
+        assert_eq!(first.archer_share, 0.0);
+        let deepest = Population::endless(1000);
+        assert_eq!(deepest.enemies, ENDLESS_MAX_ENEMIES);
+        assert_eq!(deepest.archer_share, ENDLESS_MAX_ARCHERS);
+        assert_eq!(deepest.pickups, ENDLESS_MIN_PICKUPS);
+    }
+}
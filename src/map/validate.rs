@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use super::{ Grid, Layout, TeleporterPair };
+
+const WALL: u8 = 1;
+const PLAYER: u8 = 2;
+/// floor, wall, player, the two doors and the two pickups, every other digit needs a spawn of the content
+const LAYOUT_TILES: [u8; 7] = [0, 1, 2, 4, 5, 6, 8];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error, // the map can't be loaded
+    Warning, // the map loads but probably doesn't play as intended
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DiagnosticKind {
+    UnflankedDoor,
+    UnreachableArea,
+    UnknownTile,
+}
+impl DiagnosticKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            DiagnosticKind::UnflankedDoor => Severity::Error,
+            DiagnosticKind::UnreachableArea => Severity::Warning,
+            DiagnosticKind::UnknownTile => Severity::Error,
+        }
+    }
+    /// what an author should do about it
+    pub fn explanation(self) -> &'static str {
+        match self {
+            DiagnosticKind::UnflankedDoor =>
+                "a door needs walls above and below or left and right so it knows which way to slide",
+            DiagnosticKind::UnreachableArea =>
+                "the player can't walk or teleport there, wall it off or add a door",
+            DiagnosticKind::UnknownTile =>
+                "the digit means nothing to the map format or the spawns of the content, a typo or a missing mod",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub tile: (usize, usize),
+    pub message: String,
+}
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+/// the pair of opposite tiles a door slides into
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DoorWalls {
+    AboveBelow,
+    LeftRight,
+}
+
+/// which walls flank the door at (x, y), None means it has nothing to slide into
+pub fn door_walls(layout: &Layout, x: usize, y: usize) -> Option<DoorWalls> {
+    if y > 0 && y < layout.height() - 1 && layout[y - 1][x] != 0 && layout[y + 1][x] != 0 {
+        Some(DoorWalls::AboveBelow)
+    } else if x > 0 && x < layout.width() - 1 && layout[y][x - 1] != 0 && layout[y][x + 1] != 0 {
+        Some(DoorWalls::LeftRight)
+    } else {
+        None
+    }
+}
+
+fn is_door(tile: u8) -> bool {
+    tile == 4 || tile == 5
+}
+
+/// every problem of a parsed map, errors first, the loader refuses maps with errors.
+/// spawn_tiles are the digits the spawn table of the content places enemies on
+pub fn validate(layout: &Layout, teleporters: &[TeleporterPair], spawn_tiles: &[u8]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for y in 0..layout.height() {
+        for x in 0..layout.width() {
+            let tile = layout[y][x];
+            if !LAYOUT_TILES.contains(&tile) && !spawn_tiles.contains(&tile) {
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::UnknownTile,
+                    tile: (x, y),
+                    message: format!("tile {} at ({}, {}) has no meaning", tile, x, y),
+                });
+            }
+            if is_door(tile) && door_walls(layout, x, y).is_none() {
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::UnflankedDoor,
+                    tile: (x, y),
+                    message: format!("door at ({}, {}) is not between two walls", x, y),
+                });
+            }
+        }
+    }
+    diagnostics.extend(unreachable_areas(layout, teleporters));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.severity() != Severity::Error);
+    diagnostics
+}
+
+/// flood fill from the player through everything that isn't a wall, teleporters included,
+/// one diagnostic per connected area that was never reached
+fn unreachable_areas(layout: &Layout, teleporters: &[TeleporterPair]) -> Vec<Diagnostic> {
+    let mut reached = Grid::same_size(layout, false);
+    let mut areas = Vec::new();
+    let start = (0..layout.height())
+        .flat_map(|y| (0..layout.width()).map(move |x| (x, y)))
+        .find(|&(x, y)| layout[y][x] == PLAYER);
+    let start = match start {
+        Some(start) => start,
+        None => {
+            return areas; // parse_layout already rejects maps without a player
+        }
+    };
+    flood_fill(layout, teleporters, start, &mut reached);
+    for y in 0..layout.height() {
+        for x in 0..layout.width() {
+            if layout[y][x] == WALL || reached[y][x] {
+                continue;
+            }
+            let size = flood_fill(layout, teleporters, (x, y), &mut reached);
+            areas.push(Diagnostic {
+                kind: DiagnosticKind::UnreachableArea,
+                tile: (x, y),
+                message: format!("{} tiles starting at ({}, {}) can't be reached from the player", size, x, y),
+            });
+        }
+    }
+    areas
+}
+
+/// marks every tile connected to `start` and returns how many there were
+fn flood_fill(
+    layout: &Layout,
+    teleporters: &[TeleporterPair],
+    start: (usize, usize),
+    reached: &mut Grid<bool>
+) -> usize {
+    let mut queue = VecDeque::from([start]);
+    reached[start.1][start.0] = true;
+    let mut size = 0;
+    while let Some((x, y)) = queue.pop_front() {
+        size += 1;
+        let mut neighbours = vec![
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1)
+        ];
+        neighbours.extend(teleporters.iter().filter_map(|pair| pair.partner((x, y))));
+        for (next_x, next_y) in neighbours {
+            if
+                !layout.contains(next_x, next_y) ||
+                layout[next_y][next_x] == WALL ||
+                reached[next_y][next_x]
+            {
+                continue;
+            }
+            reached[next_y][next_x] = true;
+            queue.push_back((next_x, next_y));
+        }
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::parse_layout;
+
+    fn kinds(rows: &str, teleporters: &[TeleporterPair]) -> Vec<(DiagnosticKind, (usize, usize))> {
+        let layout = parse_layout(rows).expect("test map should parse");
+        validate(&layout, teleporters, &[3])
+            .into_iter()
+            .map(|diagnostic| (diagnostic.kind, diagnostic.tile))
+            .collect()
+    }
+
+    #[test]
+    fn clean_map_has_no_diagnostics() {
+        assert!(kinds("11111\n12031\n11411\n10001\n11111", &[]).is_empty());
+    }
+
+    #[test]
+    fn door_without_walls_on_opposite_sides_is_unflanked() {
+        assert_eq!(kinds("11111\n12041\n10001\n11111", &[]), vec![(DiagnosticKind::UnflankedDoor, (3, 1))]);
+    }
+
+    #[test]
+    fn walled_off_area_is_unreachable() {
+        assert_eq!(kinds("111111\n120101\n111111", &[]), vec![(DiagnosticKind::UnreachableArea, (4, 1))]);
+    }
+
+    #[test]
+    fn teleporter_reaches_a_walled_off_area() {
+        let teleporters = [TeleporterPair { a: (2, 1), b: (4, 1) }];
+        assert!(kinds("111111\n120101\n111111", &teleporters).is_empty());
+    }
+
+    #[test]
+    fn digit_without_a_meaning_is_an_unknown_tile() {
+        assert_eq!(kinds("11111\n12091\n11111", &[]), vec![(DiagnosticKind::UnknownTile, (3, 1))]);
+    }
+
+    #[test]
+    fn digit_of_the_spawn_table_is_known() {
+        let layout = parse_layout("11111\n12091\n11111").expect("test map should parse");
+        assert!(validate(&layout, &[], &[9]).is_empty());
+    }
+
+    #[test]
+    fn errors_come_before_warnings() {
+        let diagnostics = kinds("1111111\n1201091\n1111111", &[]);
+        assert_eq!(diagnostics, vec![
+            (DiagnosticKind::UnknownTile, (5, 1)),
+            (DiagnosticKind::UnreachableArea, (4, 1)),
+        ]);
+        assert_eq!(diagnostics[0].0.severity(), Severity::Error);
+    }
+}
@@ -0,0 +1,117 @@
+use std::{ collections::BTreeMap, fs };
+use serde::{ Deserialize, Serialize };
+
+pub const RECORDS_PATH: &str = "records.toml";
+
+/// the best results across runs, kept next to the settings
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct Records {
+    pub deepest_endless_level: u32,
+    // physics steps at the end of every level of the best endless run, keyed by the seed since every
+    // seed plays different levels, see SpeedrunTimer::beats_best
+    pub endless_best_splits: BTreeMap<String, Vec<u64>>,
+}
+
+impl Records {
+    /// a missing or unreadable records.toml starts from nothing, it is rewritten on the next record
+    pub fn load() -> Self {
+        Self::load_from(RECORDS_PATH)
+    }
+
+    fn load_from(path: &str) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(RECORDS_PATH)
+    }
+
+    fn save_to(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|err| err.to_string())?;
+        fs::write(path, text).map_err(|err| err.to_string())
+    }
+
+    /// true when no run went this deep before
+    pub fn reach_endless_level(&mut self, depth: u32) -> bool {
+        if depth <= self.deepest_endless_level {
+            return false;
+        }
+        self.deepest_endless_level = depth;
+        true
+    }
+
+    /// empty when no run of the seed finished a level yet
+    pub fn endless_best_splits(&self, seed: u64) -> Vec<u64> {
+        self.endless_best_splits.get(&seed.to_string()).cloned().unwrap_or_default()
+    }
+
+    pub fn set_endless_best_splits(&mut self, seed: u64, splits: Vec<u64>) {
+        self.endless_best_splits.insert(seed.to_string(), splits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("doomr_records_{}_{}.toml", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn only_a_deeper_level_is_a_record() {
+        let mut records = Records::default();
+        assert!(records.reach_endless_level(3));
+        assert!(!records.reach_endless_level(3));
+        assert!(!records.reach_endless_level(2));
+        assert!(records.reach_endless_level(4));
+        assert_eq!(records.deepest_endless_level, 4);
+    }
+
+    #[test]
+    fn records_survive_a_save_and_load() {
+        let path = temp_path("round_trip");
+        let mut records = Records { deepest_endless_level: 7, ..Records::default() };
+        records.set_endless_best_splits(42, vec![2400, 5100]);
+        records.save_to(&path).expect("temp dir is writable");
+        assert_eq!(Records::load_from(&path), records);
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn missing_or_broken_file_starts_from_nothing() {
+        let path = temp_path("broken");
+        assert_eq!(Records::load_from(&path), Records::default());
+        fs::write(&path, "deepest_endless_level = [").expect("temp dir is writable");
+        assert_eq!(Records::load_from(&path), Records::default());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn best_splits_are_kept_per_seed() {
+        let mut records = Records::default();
+        assert!(records.endless_best_splits(1).is_empty());
+        records.set_endless_best_splits(1, vec![600]);
+        records.set_endless_best_splits(2, vec![900, 1800]);
+        records.set_endless_best_splits(1, vec![500]);
+        assert_eq!(records.endless_best_splits(1), vec![500]);
+        assert_eq!(records.endless_best_splits(2), vec![900, 1800]);
+    }
+
+    #[test]
+    fn records_without_splits_still_load() {
+        let path = temp_path("old");
+        fs::write(&path, "deepest_endless_level = 3").expect("temp dir is writable");
+        let records = Records::load_from(&path);
+        assert_eq!(records.deepest_endless_level, 3);
+        assert!(records.endless_best_splits.is_empty());
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,296 @@
+use std::fs;
+use serde::{ Deserialize, Serialize };
+
+pub const SETTINGS_PATH: &str = "settings.toml";
+pub const SETTINGS_VERSION: u32 = 1;
+
+type Migration = fn(&mut toml::Table);
+
+/// each entry upgrades the raw table of a settings file by one version, index 0 goes from 1 to 2,
+/// renamed fields are moved here so they are not silently replaced by their default
+const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fov_degrees: f32, // horizontal, the rays, sprites and prompts all spread over it
+    pub mouse_sensitivity: f32,
+    pub health_regeneration: bool,
+    pub adrenaline: bool,
+    pub turn_step_degrees: f32, // 0 turns smoothly, 45 or 90 snap per key press
+    pub grid_snap: bool,
+    pub hit_stop: bool, // freezes the simulation for a moment when the player is hit or kills up close
+    pub gpu_raycasting: bool, // experimental, walls and floor are raycast in one shader pass instead of cpu stripes
+    pub speedrun_timer: bool, // shows the run time with milliseconds and how far it is from the map's par time
+    pub compass: bool, // the strip of directions across the top of the screen
+    pub bobbing_enabled: bool, // the weapon sways while walking
+    pub bobbing_speed: f32, // radians of the sway per second of walking
+    pub bobbing_amount: f32, // sideways sway as a fraction of the weapon width
+    pub bobbing_amount_y: f32, // vertical sway as a fraction of the weapon height, twice as fast as the sideways one
+    pub hud_theme: String, // classic, high_contrast, colorblind or the name of a file in themes/
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            fov_degrees: 90.0,
+            mouse_sensitivity: 1.0,
+            health_regeneration: false,
+            adrenaline: false,
+            turn_step_degrees: 0.0,
+            grid_snap: false,
+            hit_stop: false,
+            gpu_raycasting: false,
+            speedrun_timer: false,
+            compass: true,
+            bobbing_enabled: true,
+            bobbing_speed: 11.0,
+            bobbing_amount: 0.1,
+            bobbing_amount_y: 0.06,
+            hud_theme: "classic".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// loads settings.toml, migrating and clamping it and writing the corrected file back,
+    /// the returned lines describe everything that was changed so they are only reported once
+    pub fn load() -> (Self, Vec<String>) {
+        Self::load_from(SETTINGS_PATH)
+    }
+
+    fn load_from(path: &str) -> (Self, Vec<String>) {
+        let mut report = Vec::new();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => {
+                let settings = Settings::default();
+                settings.save_to(path, &mut report);
+                return (settings, report);
+            }
+        };
+        let mut settings = match Self::parse(&text, MIGRATIONS, &mut report) {
+            Ok(settings) => settings,
+            Err(err) => {
+                let backup = format!("{}.bak", path);
+                if let Err(rename_err) = fs::rename(path, &backup) {
+                    report.push(format!("Could not back up broken settings: {}", rename_err));
+                }
+                report.push(
+                    format!("Settings were unreadable ({}), moved them to {} and reset to defaults", err, backup)
+                );
+                Settings::default()
+            }
+        };
+        settings.validate(&mut report);
+        if !report.is_empty() {
+            settings.save_to(path, &mut report);
+        }
+        (settings, report)
+    }
+
+    /// the file is on the version after the last migration once they all ran
+    fn parse(text: &str, migrations: &[Migration], report: &mut Vec<String>) -> Result<Self, String> {
+        let latest = (migrations.len() as u32) + 1;
+        let mut table: toml::Table = toml::from_str(text).map_err(|err| err.to_string())?;
+        let version = match table.get("version") {
+            Some(toml::Value::Integer(version)) if *version >= 1 => *version as u32,
+            Some(_) => {
+                return Err("invalid version".to_string());
+            }
+            None => 1,
+        };
+        if version > latest {
+            return Err(format!("version {} is newer than this build", version));
+        }
+        for migration in &migrations[(version as usize) - 1..] {
+            migration(&mut table);
+        }
+        if version < latest {
+            report.push(format!("Settings migrated from version {} to {}", version, latest));
+        }
+        table.insert("version".to_string(), toml::Value::Integer(latest as i64));
+        toml::Value::Table(table).try_into().map_err(|err| err.to_string())
+    }
+
+    fn validate(&mut self, report: &mut Vec<String>) {
+        clamp_setting("master_volume", &mut self.master_volume, 0.0, 1.0, report);
+        clamp_setting("music_volume", &mut self.music_volume, 0.0, 1.0, report);
+        clamp_setting("sfx_volume", &mut self.sfx_volume, 0.0, 1.0, report);
+        clamp_setting("fov_degrees", &mut self.fov_degrees, 50.0, 120.0, report);
+        clamp_setting("mouse_sensitivity", &mut self.mouse_sensitivity, 0.1, 10.0, report);
+        clamp_setting("bobbing_speed", &mut self.bobbing_speed, 0.0, 30.0, report);
+        clamp_setting("bobbing_amount", &mut self.bobbing_amount, 0.0, 0.5, report);
+        clamp_setting("bobbing_amount_y", &mut self.bobbing_amount_y, 0.0, 0.5, report);
+        if ![0.0, 45.0, 90.0].contains(&self.turn_step_degrees) {
+            report.push(
+                format!(
+                    "Setting turn_step_degrees was {}, corrected to 0 (smooth turning)",
+                    self.turn_step_degrees
+                )
+            );
+            self.turn_step_degrees = 0.0;
+        }
+    }
+
+    pub fn save(&self, report: &mut Vec<String>) {
+        self.save_to(SETTINGS_PATH, report);
+    }
+
+    fn save_to(&self, path: &str, report: &mut Vec<String>) {
+        let written = toml
+            ::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()));
+        if let Err(err) = written {
+            report.push(format!("Could not save settings: {}", err));
+        }
+    }
+
+    /// overwrites settings.toml with the defaults
+    pub fn reset_to_defaults(report: &mut Vec<String>) -> Self {
+        let settings = Settings::default();
+        settings.save(report);
+        report.push("Settings reset to defaults".to_string());
+        settings
+    }
+}
+
+fn clamp_setting(name: &str, value: &mut f32, min: f32, max: f32, report: &mut Vec<String>) {
+    let clamped = if value.is_nan() { min } else { value.clamp(min, max) };
+    if clamped != *value {
+        report.push(format!("Setting {} was {}, corrected to {}", name, value, clamped));
+        *value = clamped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("doomr_settings_{}_{}.toml", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn rename(table: &mut toml::Table, from: &str, to: &str) {
+        if let Some(value) = table.remove(from) {
+            table.insert(to.to_string(), value);
+        }
+    }
+
+    // a made up history, version 2 renamed sensitivity and version 3 renamed volume
+    const TEST_MIGRATIONS: &[Migration] = &[
+        |table| rename(table, "sensitivity", "mouse_sensitivity"),
+        |table| rename(table, "volume", "master_volume"),
+    ];
+
+    #[test]
+    fn version_follows_the_migrations() {
+        assert_eq!(SETTINGS_VERSION, (MIGRATIONS.len() as u32) + 1);
+    }
+
+    #[test]
+    fn version_one_file_runs_every_migration() {
+        let mut report = Vec::new();
+        let text = "version = 1\nsensitivity = 2.5\nvolume = 0.4\ncompass = false";
+        let settings = Settings::parse(text, TEST_MIGRATIONS, &mut report).expect("migrates");
+        assert_eq!(settings.version, 3);
+        assert_eq!(settings.mouse_sensitivity, 2.5);
+        assert_eq!(settings.master_volume, 0.4);
+        assert!(!settings.compass);
+        // fields the old file never had get their default
+        assert_eq!(settings.hud_theme, Settings::default().hud_theme);
+        assert_eq!(report, vec!["Settings migrated from version 1 to 3".to_string()]);
+    }
+
+    #[test]
+    fn migrations_start_at_the_file_version() {
+        let mut report = Vec::new();
+        // sensitivity was already renamed in version 2, so a stray one is not picked up
+        let text = "version = 2\nsensitivity = 2.5\nvolume = 0.4";
+        let settings = Settings::parse(text, TEST_MIGRATIONS, &mut report).expect("migrates");
+        assert_eq!(settings.mouse_sensitivity, Settings::default().mouse_sensitivity);
+        assert_eq!(settings.master_volume, 0.4);
+        assert_eq!(report, vec!["Settings migrated from version 2 to 3".to_string()]);
+    }
+
+    #[test]
+    fn missing_version_is_version_one() {
+        let mut report = Vec::new();
+        let settings = Settings::parse("sensitivity = 2.5", TEST_MIGRATIONS, &mut report).expect("migrates");
+        assert_eq!(settings.mouse_sensitivity, 2.5);
+        assert_eq!(settings.version, 3);
+    }
+
+    #[test]
+    fn newer_or_invalid_version_is_rejected() {
+        let mut report = Vec::new();
+        assert!(Settings::parse("version = 4", TEST_MIGRATIONS, &mut report).is_err());
+        assert!(Settings::parse("version = 0", TEST_MIGRATIONS, &mut report).is_err());
+        assert!(Settings::parse("version = \"two\"", TEST_MIGRATIONS, &mut report).is_err());
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped_and_reported() {
+        let mut settings = Settings {
+            master_volume: 1.5,
+            fov_degrees: 30.0,
+            mouse_sensitivity: f32::NAN,
+            turn_step_degrees: 30.0,
+            ..Settings::default()
+        };
+        let mut report = Vec::new();
+        settings.validate(&mut report);
+        assert_eq!(settings.master_volume, 1.0);
+        assert_eq!(settings.fov_degrees, 50.0);
+        assert_eq!(settings.mouse_sensitivity, 0.1);
+        assert_eq!(settings.turn_step_degrees, 0.0);
+        assert_eq!(report.len(), 4);
+        let mut report = Vec::new();
+        Settings::default().validate(&mut report);
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn corrected_file_is_written_back_once() {
+        let path = temp_path("corrected");
+        fs::write(&path, "version = 1\nfov_degrees = 200.0\nsfx_volume = 0.5").expect("temp dir is writable");
+        let (settings, report) = Settings::load_from(&path);
+        assert_eq!(settings.fov_degrees, 120.0);
+        assert_eq!(settings.sfx_volume, 0.5);
+        assert_eq!(report, vec!["Setting fov_degrees was 200, corrected to 120".to_string()]);
+        let (reloaded, report) = Settings::load_from(&path);
+        assert_eq!(reloaded, settings);
+        assert!(report.is_empty());
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn corrupted_file_is_kept_as_bak_and_defaults_are_used() {
+        let path = temp_path("corrupted");
+        let backup = format!("{}.bak", path);
+        let broken = "master_volume = [0.5";
+        fs::write(&path, broken).expect("temp dir is writable");
+        let (settings, report) = Settings::load_from(&path);
+        assert_eq!(settings, Settings::default());
+        assert_eq!(report.len(), 1);
+        assert!(report[0].contains(&backup));
+        assert_eq!(fs::read_to_string(&backup).expect("backup exists"), broken);
+        // the defaults replace the broken file
+        assert_eq!(Settings::load_from(&path), (Settings::default(), Vec::new()));
+        fs::remove_file(&path).ok();
+        fs::remove_file(&backup).ok();
+    }
+}
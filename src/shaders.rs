@@ -23,24 +23,245 @@ uniform vec2 u_player_pos;
 uniform vec2 u_left_ray_dir;
 uniform vec2 u_right_ray_dir;
 uniform float u_half_screen_height;
+uniform float u_eye_offset; // how far the eye is below half a wall, in wall heights
+uniform float u_pitch; // in half screens, positive looks down and moves the horizon up
 uniform sampler2D u_floor_texture;
+uniform sampler2D u_ceiling_texture;
 uniform float u_screen_width;
 uniform float u_screen_height;
 uniform float is_ceiling;
+uniform sampler2D u_floor_types; // one texel per tile, red is water
+uniform vec2 u_world_size;
+uniform float u_time;
 out vec4 FragColor;
 
 void main()
 {
     float row = gl_FragCoord.y;
     float col = gl_FragCoord.x;
-    float row_distance = (u_half_screen_height / (row - u_half_screen_height + 0.01)) * is_ceiling;
-    vec2 ray_dir = mix(u_left_ray_dir, u_right_ray_dir, col / u_screen_width);
+    float row_distance =
+        (u_half_screen_height / (row - u_half_screen_height - u_pitch * u_half_screen_height + 0.01)) * is_ceiling;
+    // a lower eye sees the floor closer and the ceiling further away
+    row_distance *= 1.0 + 2.0 * u_eye_offset * is_ceiling;
+    // row_distance is to the camera plane like the wall distances, the mixed direction is scaled so it
+    // reaches the plane one unit ahead
+    vec2 ray_dir = mix(u_left_ray_dir, u_right_ray_dir, col / u_screen_width) * 2.0 /
+        length(u_left_ray_dir + u_right_ray_dir);
     vec2 floor_pos = u_player_pos + ray_dir * row_distance;
     vec2 tex_coords = fract(floor_pos);
-    vec4 tex_color = texture(u_floor_texture, tex_coords);
+    vec4 tex_color = is_ceiling > 0.0 ? texture(u_ceiling_texture, tex_coords) : texture(u_floor_texture, tex_coords);
     float shade = clamp(1.0 - (row_distance / 15), 0.0, 1.0);
+    vec2 tile_center = (floor(floor_pos) + 0.5) / u_world_size;
+    if (is_ceiling < 0.0 && texture(u_floor_types, tile_center).r > 0.5) {
+        float ripple = 0.5 + 0.5 * sin(dot(floor_pos, vec2(5.0, 3.0)) + u_time * 3.0);
+        tex_color.rgb = mix(tex_color.rgb, vec3(0.1, 0.45, 0.5), 0.6 + 0.15 * ripple);
+    }
     FragColor = vec4(tex_color.rgb * shade, 1.0);
 }
+";
+    /// walls, doors, floor and ceiling in one fullscreen pass, the same dda as RaycastSystem::daa_raycast
+    pub const RAYCAST_FRAGMENT_SHADER: &str =
+        "#version 330 core
+
+uniform vec2 u_player_pos;
+uniform float u_player_angle;
+uniform float u_fov;
+uniform float u_eye_offset; // how far the eye is below half a wall, in wall heights
+uniform float u_pitch; // in half screens, positive looks down and moves the horizon up
+uniform float u_rays;
+uniform vec2 u_left_ray_dir;
+uniform vec2 u_right_ray_dir;
+uniform float u_screen_width;
+uniform float u_screen_height;
+uniform vec2 u_world_size;
+uniform float u_time;
+// r is 1 for walls and 0.5 for doors, g the closed share of a door, b a note on a wall or the DoorDirection of a
+// door in thirds: left, right, up, down
+uniform sampler2D u_tiles;
+uniform sampler2D u_floor_types; // one texel per tile, red is water
+uniform sampler2D u_wall_texture;
+uniform sampler2D u_door_texture;
+uniform sampler2D u_floor_texture;
+uniform sampler2D u_ceiling_texture;
+out vec4 FragColor;
+
+const vec3 WALL_TINT = vec3(0.0, 0.89, 0.19); // GREEN, like the cpu wall stripes
+const float SHADE_DISTANCE = 30.0; // config::SHADE_DISTANCE
+const int MAX_STEPS = 128;
+const float INSIDE_DOOR_TILE = 0.001; // RaycastSystem::raycast_behind_door
+
+// Doors::get_ray_intersection_point, the distance along dir or -1 when the rect is missed
+float ray_rect_distance(vec2 origin, vec2 dir, vec2 rect_min, vec2 rect_max, out bool x_face) {
+    vec2 t1 = (rect_min - origin) / dir;
+    vec2 t2 = (rect_max - origin) / dir;
+    vec2 t_near = min(t1, t2);
+    vec2 t_far = max(t1, t2);
+    x_face = t_near.x > t_near.y;
+    if (t_near.x > t_far.y || t_near.y > t_far.x) {
+        return -1.0;
+    }
+    return max(t_near.x, t_near.y);
+}
+
+// Doors::get_door_hitbox, what is left of a door stays against the wall it slides into
+void door_rect(ivec2 tile, vec4 cell, out vec2 rect_min, out vec2 rect_max) {
+    float closed = cell.g;
+    int direction = int(round(cell.b * 3.0));
+    rect_min = vec2(tile);
+    rect_max = vec2(tile) + 1.0;
+    if (direction == 0) {
+        rect_max.x = rect_min.x + closed;
+    } else if (direction == 1) {
+        rect_min.x = rect_max.x - closed;
+    } else if (direction == 2) {
+        rect_max.y = rect_min.y + closed;
+    } else {
+        rect_min.y = rect_max.y - closed;
+    }
+}
+
+struct Hit {
+    float distance; // along the ray, -1 when nothing was hit
+    float tile_entry; // where the ray entered the tile it hit
+    vec2 pos;
+    bool x_side;
+    bool is_door;
+    bool has_note;
+};
+
+// RaycastSystem::daa_raycast
+Hit cast_ray(vec2 origin, vec2 dir) {
+    Hit hit = Hit(-1.0, -1.0, vec2(0.0), false, false, false);
+    vec2 delta = 1.0 / abs(dir);
+    ivec2 step_dir = ivec2(dir.x > 0.0 ? 1 : -1, dir.y > 0.0 ? 1 : -1);
+    ivec2 tile = ivec2(floor(origin));
+    vec2 side = vec2(
+        dir.x < 0.0 ? (origin.x - float(tile.x)) * delta.x : (float(tile.x) + 1.0 - origin.x) * delta.x,
+        dir.y < 0.0 ? (origin.y - float(tile.y)) * delta.y : (float(tile.y) + 1.0 - origin.y) * delta.y
+    );
+    for (int i = 0; i < MAX_STEPS; i++) {
+        bool x_side = side.x < side.y;
+        if (x_side) {
+            side.x += delta.x;
+            tile.x += step_dir.x;
+        } else {
+            side.y += delta.y;
+            tile.y += step_dir.y;
+        }
+        if (tile.x < 0 || tile.y < 0 || tile.x >= int(u_world_size.x) || tile.y >= int(u_world_size.y)) {
+            break;
+        }
+        vec4 cell = texelFetch(u_tiles, tile, 0);
+        float entry = x_side ? side.x - delta.x : side.y - delta.y;
+        if (cell.r > 0.75) {
+            hit.distance = entry;
+            hit.tile_entry = entry;
+            hit.pos = origin + dir * entry;
+            hit.x_side = x_side;
+            hit.has_note = cell.b > 0.5;
+            return hit;
+        }
+        if (cell.r > 0.25 && cell.g > 0.0) {
+            // a closed door is hit where the ray enters its tile, a sliding one only on its remaining part
+            vec2 entry_pos = origin + dir * entry;
+            vec2 rect_min;
+            vec2 rect_max;
+            door_rect(tile, cell, rect_min, rect_max);
+            bool x_face;
+            float t = ray_rect_distance(entry_pos, dir, rect_min, rect_max, x_face);
+            if (cell.g > 0.99 || t >= 0.0) {
+                // the edge of a sliding door faces the other way than the side of the tile
+                hit.x_side = t > 0.0 ? x_face : x_side;
+                hit.distance = entry + max(t, 0.0);
+                hit.tile_entry = entry;
+                hit.pos = entry_pos + dir * max(t, 0.0);
+                hit.is_door = true;
+                return hit;
+            }
+        }
+    }
+    return hit;
+}
+
+// RenderPlayerPOV::render_stripe, false above and below the stripe, the alpha is the see-through part of a door
+bool stripe_color(Hit hit, float ray_angle, float row_from_top, float horizon, out vec4 color) {
+    // to the camera plane, like RaycastSystem::raycast
+    float distance = hit.distance * cos(ray_angle - u_player_angle);
+    float wall_height = min(u_screen_height / (distance - 0.5 + 0.000001), u_screen_height);
+    // a lower eye moves everything up by the same share of its height
+    float wall_middle = u_screen_height - horizon - u_eye_offset * wall_height;
+    float v = (row_from_top - (wall_middle - wall_height / 2.0)) / wall_height;
+    if (v < 0.0 || v > 1.0) {
+        return false;
+    }
+    float along = hit.x_side ? hit.pos.y : hit.pos.x;
+    float shade = 1.0 - clamp(distance / SHADE_DISTANCE, 0.0, 1.0);
+    vec2 uv = vec2(fract(along), v);
+    color = hit.is_door ? texture(u_door_texture, uv) : vec4(texture(u_wall_texture, uv).rgb * WALL_TINT, 1.0);
+    color.rgb *= hit.x_side ? shade : shade * 0.8;
+    bool on_paper = fract(along) >= 0.35 && fract(along) <= 0.65 &&
+        row_from_top >= wall_middle - wall_height * 0.2 &&
+        row_from_top <= wall_middle + wall_height * 0.05;
+    if (hit.has_note && on_paper) {
+        color.rgb = vec3(0.93, 0.87, 0.7) * shade;
+    }
+    return true;
+}
+
+// the same as FLOOR_FRAGMENT_SHADER
+vec3 floor_and_ceiling(float col, float row, float horizon, float half_height) {
+    float is_ceiling = row > horizon ? 1.0 : -1.0;
+    float row_distance = (half_height / (row - horizon + 0.01)) * is_ceiling;
+    row_distance *= 1.0 + 2.0 * u_eye_offset * is_ceiling;
+    vec2 ray_dir = mix(u_left_ray_dir, u_right_ray_dir, col / u_screen_width) * 2.0 /
+        length(u_left_ray_dir + u_right_ray_dir);
+    vec2 floor_pos = u_player_pos + ray_dir * row_distance;
+    vec4 tex_color = is_ceiling > 0.0 ?
+        texture(u_ceiling_texture, fract(floor_pos)) :
+        texture(u_floor_texture, fract(floor_pos));
+    float shade = clamp(1.0 - (row_distance / 15), 0.0, 1.0);
+    vec2 tile_center = (floor(floor_pos) + 0.5) / u_world_size;
+    if (is_ceiling < 0.0 && texture(u_floor_types, tile_center).r > 0.5) {
+        float ripple = 0.5 + 0.5 * sin(dot(floor_pos, vec2(5.0, 3.0)) + u_time * 3.0);
+        tex_color.rgb = mix(tex_color.rgb, vec3(0.1, 0.45, 0.5), 0.6 + 0.15 * ripple);
+    }
+    return tex_color.rgb * shade;
+}
+
+void main()
+{
+    float col = gl_FragCoord.x;
+    float row = gl_FragCoord.y;
+    float row_from_top = u_screen_height - row;
+    float half_height = u_screen_height / 2.0;
+    float horizon = half_height + u_pitch * half_height; // counted from the bottom like row
+
+    // the ray RaycastSystem::raycast casts for this column
+    float ray = floor(col / u_screen_width * u_rays);
+    float ray_angle = u_player_angle + u_fov / 2.0 - (ray / u_rays) * u_fov;
+    vec2 dir = vec2(cos(ray_angle), sin(ray_angle));
+    vec3 background = floor_and_ceiling(col, row, horizon, half_height);
+    Hit hit = cast_ray(u_player_pos, dir);
+    vec4 color;
+    if (hit.distance > 0.0 && stripe_color(hit, ray_angle, row_from_top, horizon, color)) {
+        if (color.a < 1.0) {
+            // RaycastSystem::raycast_behind_door, what shows through the window, only one door deep
+            vec3 behind_color = background;
+            Hit behind = cast_ray(u_player_pos + dir * (hit.tile_entry + INSIDE_DOOR_TILE), dir);
+            vec4 behind_stripe;
+            if (behind.distance > 0.0) {
+                behind.distance += hit.tile_entry + INSIDE_DOOR_TILE;
+                if (stripe_color(behind, ray_angle, row_from_top, horizon, behind_stripe)) {
+                    behind_color = mix(behind_color, behind_stripe.rgb, behind_stripe.a);
+                }
+            }
+            color.rgb = mix(behind_color, color.rgb, color.a);
+        }
+        FragColor = vec4(color.rgb, 1.0);
+        return;
+    }
+    FragColor = vec4(background, 1.0);
+}
 ";
     pub const CAMERA_SHAKE_VERTEX_SHADER: &'static str =
         "#version 100
@@ -111,6 +332,10 @@ varying vec4 color;
 
 void main() {
     vec4 textureColor = texture2D(Texture, uv);
+    // the background of the sheets, see load_and_convert_texture, and the effects drawn on top share this shader
+    if (textureColor.a < 0.1) {
+        discard;
+    }
     float redIntensity = (1.0 - u_relative_health) * 0.5; 
     float chance = (1.0 - u_relative_health) * 0.5; 
     
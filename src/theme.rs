@@ -0,0 +1,178 @@
+use std::fs;
+use macroquad::color::Color;
+use serde::Deserialize;
+
+const THEMES_DIRECTORY: &str = "themes";
+/// the presets in the order the cycle key goes through them
+const BUILT_IN_THEMES: &[&str] = &["classic", "high_contrast", "colorblind"];
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(transparent)]
+pub struct ThemeColor(pub [u8; 4]);
+impl ThemeColor {
+    pub fn color(self) -> Color {
+        Color::from_rgba(self.0[0], self.0[1], self.0[2], self.0[3])
+    }
+}
+
+/// how enemies are marked on the minimap, a second cue next to the color
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum MarkerShape {
+    Square,
+    Diamond,
+}
+
+/// colors and placement of the HUD, a theme file only needs the fields it changes, the rest is classic
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct HudTheme {
+    pub health_label: ThemeColor,
+    pub health_active: ThemeColor,
+    pub health_inactive: ThemeColor,
+    pub health_outline: ThemeColor,
+    pub health_position: [f32; 2], // left end of the bars as a fraction of the screen size
+    pub stamina_active: ThemeColor, // the stamina bar below the health, inactive segments use health_inactive
+    pub ammo_empty: ThemeColor, // the ammo count once the magazine is empty, otherwise it uses health_label
+    pub controls_heading: ThemeColor,
+    pub controls_key: ThemeColor,
+    pub controls_text: ThemeColor,
+    pub prompt_text: ThemeColor,
+    pub crosshair: ThemeColor,
+    pub crosshair_interactable: ThemeColor,
+    pub crosshair_out_of_range: ThemeColor,
+    pub minimap_background: ThemeColor,
+    pub minimap_wall: ThemeColor,
+    pub minimap_door: ThemeColor,
+    pub minimap_player: ThemeColor,
+    pub minimap_enemy: ThemeColor,
+    pub minimap_enemy_marker: MarkerShape,
+    pub minimap_enemy_health: ThemeColor,
+    pub minimap_ray: ThemeColor,
+    pub minimap_teleporter: ThemeColor,
+    pub minimap_note: ThemeColor,
+    pub minimap_pickup: ThemeColor, // medkits are drawn as a cross, ammo as a square
+    pub compass_background: ThemeColor,
+    pub compass_tick: ThemeColor,
+    pub compass_text: ThemeColor,
+    pub compass_objective: ThemeColor,
+}
+
+impl Default for HudTheme {
+    fn default() -> Self {
+        HudTheme::classic()
+    }
+}
+
+impl HudTheme {
+    pub fn classic() -> Self {
+        HudTheme {
+            health_label: ThemeColor([0, 227, 48, 255]),
+            health_active: ThemeColor([0, 255, 0, 255]),
+            health_inactive: ThemeColor([100, 100, 100, 255]),
+            health_outline: ThemeColor([0, 255, 0, 150]),
+            health_position: [0.45, 0.9],
+            stamina_active: ThemeColor([253, 249, 0, 255]),
+            ammo_empty: ThemeColor([230, 41, 56, 255]),
+            controls_heading: ThemeColor([230, 41, 56, 255]),
+            controls_key: ThemeColor([253, 249, 0, 255]),
+            controls_text: ThemeColor([255, 255, 255, 255]),
+            prompt_text: ThemeColor([255, 255, 255, 255]),
+            crosshair: ThemeColor([255, 255, 255, 180]),
+            crosshair_interactable: ThemeColor([253, 249, 0, 255]),
+            crosshair_out_of_range: ThemeColor([130, 130, 130, 200]),
+            minimap_background: ThemeColor([130, 130, 130, 255]),
+            minimap_wall: ThemeColor([127, 106, 79, 255]),
+            minimap_door: ThemeColor([255, 255, 255, 255]),
+            minimap_player: ThemeColor([0, 120, 242, 255]),
+            minimap_enemy: ThemeColor([230, 41, 56, 255]),
+            minimap_enemy_marker: MarkerShape::Square,
+            minimap_enemy_health: ThemeColor([255, 255, 255, 255]),
+            minimap_ray: ThemeColor([255, 255, 255, 255]),
+            minimap_teleporter: ThemeColor([200, 122, 255, 255]),
+            minimap_note: ThemeColor([238, 222, 178, 255]),
+            minimap_pickup: ThemeColor([0, 228, 48, 255]),
+            compass_background: ThemeColor([0, 0, 0, 120]),
+            compass_tick: ThemeColor([255, 255, 255, 200]),
+            compass_text: ThemeColor([253, 249, 0, 255]),
+            compass_objective: ThemeColor([0, 228, 48, 255]),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        HudTheme {
+            health_label: ThemeColor([255, 255, 255, 255]),
+            health_active: ThemeColor([255, 255, 0, 255]),
+            health_inactive: ThemeColor([60, 60, 60, 255]),
+            health_outline: ThemeColor([255, 255, 255, 255]),
+            stamina_active: ThemeColor([255, 255, 255, 255]),
+            ammo_empty: ThemeColor([255, 0, 255, 255]),
+            controls_heading: ThemeColor([255, 255, 255, 255]),
+            controls_key: ThemeColor([255, 255, 0, 255]),
+            prompt_text: ThemeColor([255, 255, 0, 255]),
+            crosshair: ThemeColor([255, 255, 255, 255]),
+            crosshair_interactable: ThemeColor([255, 255, 0, 255]),
+            minimap_background: ThemeColor([0, 0, 0, 255]),
+            minimap_wall: ThemeColor([255, 255, 255, 255]),
+            minimap_door: ThemeColor([255, 255, 0, 255]),
+            minimap_player: ThemeColor([0, 255, 255, 255]),
+            minimap_enemy: ThemeColor([255, 0, 255, 255]),
+            minimap_ray: ThemeColor([90, 90, 90, 255]),
+            minimap_teleporter: ThemeColor([0, 255, 0, 255]),
+            minimap_note: ThemeColor([255, 128, 0, 255]),
+            minimap_pickup: ThemeColor([0, 255, 0, 255]),
+            compass_background: ThemeColor([0, 0, 0, 255]),
+            compass_tick: ThemeColor([255, 255, 255, 255]),
+            compass_text: ThemeColor([255, 255, 0, 255]),
+            compass_objective: ThemeColor([0, 255, 0, 255]),
+            ..HudTheme::classic()
+        }
+    }
+
+    /// Okabe-Ito colors, enemies are diamonds so they don't rely on red against green
+    pub fn colorblind() -> Self {
+        HudTheme {
+            health_label: ThemeColor([0, 158, 115, 255]),
+            health_active: ThemeColor([0, 158, 115, 255]),
+            health_outline: ThemeColor([0, 158, 115, 150]),
+            stamina_active: ThemeColor([240, 228, 66, 255]),
+            ammo_empty: ThemeColor([213, 94, 0, 255]),
+            controls_heading: ThemeColor([230, 159, 0, 255]),
+            controls_key: ThemeColor([86, 180, 233, 255]),
+            crosshair_interactable: ThemeColor([86, 180, 233, 255]),
+            minimap_wall: ThemeColor([60, 60, 60, 255]),
+            minimap_player: ThemeColor([0, 114, 178, 255]),
+            minimap_enemy: ThemeColor([213, 94, 0, 255]),
+            minimap_enemy_marker: MarkerShape::Diamond,
+            minimap_teleporter: ThemeColor([204, 121, 167, 255]),
+            minimap_note: ThemeColor([240, 228, 66, 255]),
+            minimap_pickup: ThemeColor([86, 180, 233, 255]),
+            compass_text: ThemeColor([86, 180, 233, 255]),
+            compass_objective: ThemeColor([0, 158, 115, 255]),
+            ..HudTheme::classic()
+        }
+    }
+
+    /// a built-in preset by name, otherwise themes/<name>.toml
+    pub fn load(name: &str) -> Result<Self, String> {
+        match name {
+            "classic" => Ok(HudTheme::classic()),
+            "high_contrast" => Ok(HudTheme::high_contrast()),
+            "colorblind" => Ok(HudTheme::colorblind()),
+            _ => {
+                let path = format!("{}/{}.toml", THEMES_DIRECTORY, name);
+                let text = fs
+                    ::read_to_string(&path)
+                    .map_err(|err| format!("could not read {}: {}", path, err))?;
+                toml::from_str(&text).map_err(|err| format!("{} is invalid: {}", path, err))
+            }
+        }
+    }
+
+    /// the built-in preset after `name`, custom themes continue with the first preset
+    pub fn next_built_in(name: &str) -> &'static str {
+        match BUILT_IN_THEMES.iter().position(|theme| *theme == name) {
+            Some(index) => BUILT_IN_THEMES[(index + 1) % BUILT_IN_THEMES.len()],
+            None => BUILT_IN_THEMES[0],
+        }
+    }
+}